@@ -0,0 +1,82 @@
+use crate::filetype::Filetype;
+use crate::keymap::{config_dir, Error};
+use crate::toml;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the set of abbreviations that apply whatever the filetype.
+const ALL: &str = "all";
+
+/// Abbreviations by set, each a map of words to what they expand to. A set
+/// is named after the filetype it applies to, or `all`.
+///
+/// Unlike a snippet, an abbreviation expands by itself, as soon as the word
+/// is followed by a character that can't be part of it, and its text is
+/// inserted as it is, without placeholders.
+#[derive(Clone, Debug, Default)]
+pub struct Abbreviations {
+    sets: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Abbreviations {
+    /// Loads the user's abbreviations, if there are any.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("abbreviations.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the abbreviations in `path`; a missing file just means there
+    /// are none. Each table is a set of words and the text they expand to:
+    ///
+    /// ```toml
+    /// [all]
+    /// teh = "the"
+    ///
+    /// [rust]
+    /// fnmain = "fn main() {\n}"
+    /// ```
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut abbreviations = Self::default();
+        for (name, value) in &table {
+            if name != ALL && !Filetype::ALL.iter().any(|filetype| filetype.name() == name) {
+                return Err(invalid(format!("unknown filetype `{name}`")));
+            }
+            let toml::Value::Table(set) = value else {
+                return Err(invalid(format!(
+                    "`{name}`: expected a table, found {value}"
+                )));
+            };
+            let mut expansions = BTreeMap::new();
+            for (word, expansion) in set {
+                let toml::Value::String(expansion) = expansion else {
+                    return Err(invalid(format!(
+                        "`{name}.{word}`: expected a string, found {expansion}"
+                    )));
+                };
+                expansions.insert(word.clone(), expansion.clone());
+            }
+            abbreviations.sets.insert(name.clone(), expansions);
+        }
+        Ok(abbreviations)
+    }
+
+    /// What `word` expands to in a buffer of `filetype`.
+    pub fn find(&self, filetype: Filetype, word: &str) -> Option<&str> {
+        [filetype.name(), ALL]
+            .into_iter()
+            .find_map(|set| self.sets.get(set)?.get(word))
+            .map(String::as_str)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::history;
+use crate::scripts::Hook;
+use crate::workspace::Workspace;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the timer checks whether a buffer is due to be autosaved.
+pub const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where the recovery copy of the file at `path` is autosaved.
+pub fn recovery_path(path: &Path) -> Option<PathBuf> {
+    history::cache_file(path, "recovery", "txt")
+}
+
+/// Autosaves every buffer with changes that's due: once no key has been
+/// pressed for the idle time the settings give, or once it's been edited
+/// the number of times they give since it was last saved or autosaved.
+///
+/// Only buffers with a file are autosaved, to the file itself or to a
+/// recovery copy, as the settings say. Failures are left for the next
+/// save to report.
+pub fn tick(workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let settings = ws.settings().clone();
+    let idle =
+        settings.autosave_idle > 0 && ws.idle_time() >= Duration::from_secs(settings.autosave_idle);
+    let mut saved = Vec::new();
+    for ed in ws.editors_mut() {
+        let edits = ed.edits_since_autosave();
+        let Some(path) = ed.path().map(Path::to_path_buf) else {
+            continue;
+        };
+        let busy = settings.autosave_edits > 0 && edits >= settings.autosave_edits;
+        if !ed.is_modified() || edits == 0 || !(idle || busy) {
+            continue;
+        }
+        let result = if settings.autosave_to_file {
+            ed.save().map(|()| saved.push(path))
+        } else if ed.is_large() {
+            // A recovery copy of a large file would cost as much as saving it.
+            continue;
+        } else {
+            write_recovery(&path, &ed.buffer().to_string())
+        };
+        if result.is_ok() {
+            ed.mark_autosaved();
+        }
+    }
+    for path in saved {
+        ws.run_hook(Hook::Save, &path);
+    }
+}
+
+/// Keeps `text` as the recovery copy of the file at `path`.
+fn write_recovery(path: &Path, text: &str) -> io::Result<()> {
+    let recovery = recovery_path(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = recovery.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(recovery, text)
+}
@@ -0,0 +1,230 @@
+use crate::plugins::Plugins;
+use crate::scripts::Scripts;
+use crate::transform::{Registry, Transform};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// What `--batch` was asked to do.
+struct Job {
+    /// The transforms to run, one after the other.
+    transforms: Vec<Arc<dyn Transform>>,
+    /// The lines to rewrite, numbered from 1, the last one included; all of
+    /// them if not given.
+    lines: Option<(usize, Option<usize>)>,
+    /// The file to read, or standard input.
+    path: Option<PathBuf>,
+    /// Whether to write the result back to the file rather than print it.
+    in_place: bool,
+}
+
+/// Runs the transforms `args` name over a file, or standard input, without
+/// starting the editor: the text the transform menu would give back is
+/// printed, or written back to the file with `--in-place`.
+///
+/// ```text
+/// wasdev --batch --transform upper --range 10:20 notes.txt
+/// sort-me | wasdev --batch -t sort-lines -t remove-duplicate-lines
+/// ```
+///
+/// A transform is named as in the menu, lowercased with hyphens between
+/// its words, or by the start of that name if no other one starts the
+/// same way. The user's own transforms in `scripts.toml` are there too, and
+/// the plugins'.
+pub fn run(args: Vec<OsString>) -> Result<(), String> {
+    let mut registry = Registry::default();
+    Scripts::load()
+        .map_err(|err| err.to_string())?
+        .register_transforms(&mut registry);
+    Plugins::load()
+        .map_err(|err| err.to_string())?
+        .register_transforms(&mut registry);
+    let job = parse(args, &registry)?;
+
+    let text = match &job.path {
+        Some(path) => fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?,
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|err| format!("Could not read standard input: {err}"))?;
+            bytes
+        }
+    };
+    let text = String::from_utf8(text).map_err(|_| "The text isn't UTF-8".to_string())?;
+    let range = match job.lines {
+        Some(lines) => line_range(&text, lines)?,
+        None => 0..text.len(),
+    };
+    let mut part = text[range.clone()].to_string();
+    for transform in &job.transforms {
+        part = transform
+            .apply(&part)
+            .map_err(|failure| format!("{}: {}", transform.name(), failure.message))?;
+    }
+    let result = [&text[..range.start], &part, &text[range.end..]].concat();
+
+    match &job.path {
+        Some(path) if job.in_place => {
+            fs::write(path, result).map_err(|err| format!("{}: {err}", path.display()))
+        }
+        _ => io::stdout()
+            .write_all(result.as_bytes())
+            .map_err(|err| format!("Could not write the result: {err}")),
+    }
+}
+
+/// Reads the arguments after `--batch`.
+fn parse(args: Vec<OsString>, registry: &Registry) -> Result<Job, String> {
+    let mut job = Job {
+        transforms: Vec::new(),
+        lines: None,
+        path: None,
+        in_place: false,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .and_then(|value| value.into_string().ok())
+                .ok_or_else(|| format!("{} needs a value", arg.to_string_lossy()))
+        };
+        match arg.to_str() {
+            Some("--transform" | "-t") => job.transforms.push(find(registry, &value()?)?),
+            Some("--range" | "-r") => job.lines = Some(parse_lines(&value()?)?),
+            Some("--in-place" | "-i") => job.in_place = true,
+            Some("-") if job.path.is_none() => {}
+            Some(flag) if flag.starts_with('-') => return Err(format!("Unknown option {flag}")),
+            _ if job.path.is_none() => job.path = Some(PathBuf::from(arg)),
+            _ => return Err("Give one file at most".to_string()),
+        }
+    }
+    if job.transforms.is_empty() {
+        return Err("Nothing to do: name a transform with --transform".to_string());
+    }
+    if job.in_place && job.path.is_none() {
+        return Err("--in-place needs a file".to_string());
+    }
+    Ok(job)
+}
+
+/// How a transform is named on the command line: its name lowercased, with
+/// hyphens in place of spaces and punctuation.
+fn flag_name(name: &str) -> String {
+    name.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The transform `name` names, in full or by the start of its name.
+fn find(registry: &Registry, name: &str) -> Result<Arc<dyn Transform>, String> {
+    let wanted = flag_name(name);
+    let mut found: Vec<_> = registry
+        .iter()
+        .filter(|transform| flag_name(transform.name()).starts_with(&wanted))
+        .collect();
+    if let Some(exact) = found
+        .iter()
+        .find(|transform| flag_name(transform.name()) == wanted)
+    {
+        return Ok(Arc::clone(exact));
+    }
+    match found.len() {
+        1 => Ok(Arc::clone(found.remove(0))),
+        0 => {
+            let names: Vec<_> = registry.iter().map(|t| flag_name(t.name())).collect();
+            Err(format!(
+                "No transform is called {name}; there are {}",
+                names.join(", ")
+            ))
+        }
+        _ => {
+            let names: Vec<_> = found.iter().map(|t| flag_name(t.name())).collect();
+            Err(format!("{name} could be {}", names.join(" or ")))
+        }
+    }
+}
+
+/// Reads a range of lines: `10:20`, `10:` to the end, `:20` from the
+/// start, or just `10`.
+fn parse_lines(range: &str) -> Result<(usize, Option<usize>), String> {
+    let invalid = || format!("Not a range of lines: {range}");
+    let number = |n: &str| {
+        n.parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(invalid)
+    };
+    let (first, last) = range.split_once(':').unwrap_or((range, range));
+    let first = match first {
+        "" => 1,
+        first => number(first)?,
+    };
+    let last = (!last.is_empty()).then(|| number(last)).transpose()?;
+    if last.is_some_and(|last| last < first) {
+        return Err(invalid());
+    }
+    Ok((first, last))
+}
+
+/// The bytes of `text` that `lines` cover, with the newline ending the last.
+fn line_range(text: &str, (first, last): (usize, Option<usize>)) -> Result<Range<usize>, String> {
+    // The offset each line, counted from 0, starts at.
+    let start_of = |line: usize| match line {
+        0 => Some(0),
+        _ => text.match_indices('\n').nth(line - 1).map(|(i, _)| i + 1),
+    };
+    let start = start_of(first - 1)
+        .filter(|&start| start < text.len() || first == 1)
+        .ok_or_else(|| format!("There is no line {first}"))?;
+    let end = last.and_then(start_of).unwrap_or(text.len());
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_are_named_by_their_menu_names() {
+        assert_eq!(flag_name("Sort lines by number"), "sort-lines-by-number");
+        assert_eq!(flag_name("Pretty-print JSON"), "pretty-print-json");
+        assert_eq!(flag_name("snake_case"), "snake-case");
+    }
+
+    #[test]
+    fn the_start_of_a_name_finds_the_transform() {
+        let registry = Registry::default();
+        assert_eq!(find(&registry, "upper").unwrap().name(), "Uppercase");
+        // A whole name wins over the longer ones it starts.
+        assert_eq!(find(&registry, "reverse").unwrap().name(), "Reverse");
+        assert_eq!(find(&registry, "Sort lines").unwrap().name(), "Sort lines");
+        assert!(find(&registry, "base64").is_err());
+        assert!(find(&registry, "nothing").is_err());
+    }
+
+    #[test]
+    fn ranges_of_lines() {
+        assert_eq!(parse_lines("10:20"), Ok((10, Some(20))));
+        assert_eq!(parse_lines("3:"), Ok((3, None)));
+        assert_eq!(parse_lines(":4"), Ok((1, Some(4))));
+        assert_eq!(parse_lines("7"), Ok((7, Some(7))));
+        assert!(parse_lines("0:2").is_err());
+        assert!(parse_lines("5:2").is_err());
+    }
+
+    #[test]
+    fn a_range_covers_whole_lines() {
+        let text = "one\ntwo\nthree\n";
+        assert_eq!(line_range(text, (2, Some(2))), Ok(4..8));
+        assert_eq!(line_range(text, (2, None)), Ok(4..14));
+        assert_eq!(line_range(text, (1, Some(9))), Ok(0..14));
+        assert!(line_range(text, (4, None)).is_err());
+        assert_eq!(line_range("", (1, None)), Ok(0..0));
+    }
+}
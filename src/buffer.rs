@@ -0,0 +1,725 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Leaves are split once they grow past this many bytes.
+const MAX_LEAF: usize = 1024;
+
+/// Bytes taken from a reader at a time by `Buffer::read_utf8`.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Rope-backed text storage.
+///
+/// Text lives in a binary tree whose leaves hold short strings. Every
+/// branch caches the byte length and newline count of its subtree, so
+/// mapping between byte offsets and line numbers only walks one path from
+/// the root instead of scanning the whole text. All offsets are byte
+/// offsets and must fall on `char` boundaries.
+#[derive(Clone)]
+pub struct Buffer {
+    root: Node,
+}
+
+#[derive(Clone)]
+enum Node {
+    Leaf {
+        text: String,
+        newlines: usize,
+    },
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        len: usize,
+        newlines: usize,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn leaf(text: String) -> Self {
+        let newlines = count_newlines(&text);
+        Node::Leaf { text, newlines }
+    }
+
+    fn branch(left: Node, right: Node) -> Self {
+        Node::Branch {
+            len: left.len() + right.len(),
+            newlines: left.newlines() + right.newlines(),
+            height: 1 + left.height().max(right.height()),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Builds a balanced tree over `text`, split into leaf-sized pieces.
+    fn from_str(text: &str) -> Self {
+        let mut leaves = Vec::new();
+        Node::split_into(text, &mut leaves);
+        Node::build(leaves)
+    }
+
+    /// Appends `text` to `leaves`, split into leaf-sized pieces.
+    fn split_into(text: &str, leaves: &mut Vec<Node>) {
+        let mut rest = text;
+        while rest.len() > MAX_LEAF {
+            let mut split = MAX_LEAF;
+            while !rest.is_char_boundary(split) {
+                split -= 1;
+            }
+            leaves.push(Node::leaf(rest[..split].to_string()));
+            rest = &rest[split..];
+        }
+        if !rest.is_empty() || leaves.is_empty() {
+            leaves.push(Node::leaf(rest.to_string()));
+        }
+    }
+
+    /// Builds a balanced tree out of an in-order list of leaves.
+    fn build(mut leaves: Vec<Node>) -> Self {
+        match leaves.len() {
+            0 => Node::leaf(String::new()),
+            1 => leaves.pop().unwrap(),
+            n => {
+                let right = leaves.split_off(n / 2);
+                Node::branch(Node::build(leaves), Node::build(right))
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf { text, .. } => text.len(),
+            Node::Branch { len, .. } => *len,
+        }
+    }
+
+    fn newlines(&self) -> usize {
+        match self {
+            Node::Leaf { newlines, .. } | Node::Branch { newlines, .. } => *newlines,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    /// Refreshes the cached totals of a branch after one of its children changed.
+    fn recompute(&mut self) {
+        if let Node::Branch {
+            left,
+            right,
+            len,
+            newlines,
+            height,
+        } = self
+        {
+            *len = left.len() + right.len();
+            *newlines = left.newlines() + right.newlines();
+            *height = 1 + left.height().max(right.height());
+        }
+    }
+
+    fn insert(&mut self, offset: usize, insertion: &str) {
+        match self {
+            Node::Leaf { text, newlines } => {
+                text.insert_str(offset, insertion);
+                *newlines += count_newlines(insertion);
+                if text.len() > MAX_LEAF {
+                    *self = Node::from_str(text);
+                }
+            }
+            Node::Branch { left, right, .. } => {
+                let left_len = left.len();
+                if offset <= left_len {
+                    left.insert(offset, insertion);
+                } else {
+                    right.insert(offset - left_len, insertion);
+                }
+                self.recompute();
+            }
+        }
+    }
+
+    fn remove(&mut self, start: usize, end: usize) {
+        match self {
+            Node::Leaf { text, newlines } => {
+                *newlines -= count_newlines(&text[start..end]);
+                text.replace_range(start..end, "");
+            }
+            Node::Branch { left, right, .. } => {
+                let left_len = left.len();
+                if start < left_len {
+                    left.remove(start, end.min(left_len));
+                }
+                if end > left_len {
+                    right.remove(start.saturating_sub(left_len), end - left_len);
+                }
+                // Collapse branches whose child became empty.
+                if left.len() == 0 {
+                    *self = std::mem::replace(right.as_mut(), Node::leaf(String::new()));
+                } else if right.len() == 0 {
+                    *self = std::mem::replace(left.as_mut(), Node::leaf(String::new()));
+                } else {
+                    self.recompute();
+                }
+            }
+        }
+    }
+
+    /// Byte offset of the `n`-th newline (zero-based) in this subtree.
+    fn newline_offset(&self, n: usize) -> usize {
+        match self {
+            Node::Leaf { text, .. } => text.match_indices('\n').nth(n).map(|(i, _)| i).unwrap(),
+            Node::Branch { left, right, .. } => {
+                if n < left.newlines() {
+                    left.newline_offset(n)
+                } else {
+                    left.len() + right.newline_offset(n - left.newlines())
+                }
+            }
+        }
+    }
+
+    /// Number of newlines strictly before `offset` in this subtree.
+    fn newlines_before(&self, offset: usize) -> usize {
+        match self {
+            Node::Leaf { text, .. } => count_newlines(&text[..offset]),
+            Node::Branch { left, right, .. } => {
+                if offset <= left.len() {
+                    left.newlines_before(offset)
+                } else {
+                    left.newlines() + right.newlines_before(offset - left.len())
+                }
+            }
+        }
+    }
+
+    fn is_char_boundary(&self, offset: usize) -> bool {
+        match self {
+            Node::Leaf { text, .. } => text.is_char_boundary(offset),
+            Node::Branch { left, right, .. } => {
+                if offset <= left.len() {
+                    left.is_char_boundary(offset)
+                } else {
+                    right.is_char_boundary(offset - left.len())
+                }
+            }
+        }
+    }
+
+    fn collect_leaves(self, out: &mut Vec<String>) {
+        match self {
+            Node::Leaf { text, .. } => out.push(text),
+            Node::Branch { left, right, .. } => {
+                left.collect_leaves(out);
+                right.collect_leaves(out);
+            }
+        }
+    }
+}
+
+impl Buffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Self {
+            root: Node::leaf(String::new()),
+        }
+    }
+
+    /// Reads UTF-8 text from `reader` a piece at a time, straight into the
+    /// leaves, so the whole text is never held in one string. With `crlf`,
+    /// each `\r\n` is read as `\n`.
+    pub fn read_utf8(mut reader: impl Read, crlf: bool) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Not UTF-8");
+        let mut leaves = Vec::new();
+        let mut pending = Vec::new();
+        let mut chunk = vec![0; READ_CHUNK];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            let done = read == 0;
+            pending.extend_from_slice(&chunk[..read]);
+            // A character, or a `\r\n`, split between reads waits for the rest.
+            let mut end = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(err) if err.error_len().is_none() && !done => err.valid_up_to(),
+                Err(_) => return Err(invalid()),
+            };
+            if crlf && !done && pending[..end].ends_with(b"\r") {
+                end -= 1;
+            }
+            let text = std::str::from_utf8(&pending[..end]).map_err(|_| invalid())?;
+            if crlf {
+                Node::split_into(&text.replace("\r\n", "\n"), &mut leaves);
+            } else {
+                Node::split_into(text, &mut leaves);
+            }
+            pending.drain(..end);
+            if done {
+                break;
+            }
+        }
+        Ok(Self {
+            root: Node::build(leaves),
+        })
+    }
+
+    /// Length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    /// Returns `true` if there is no text at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of lines; a buffer always has at least one (possibly empty) line.
+    pub fn line_count(&self) -> usize {
+        self.root.newlines() + 1
+    }
+
+    /// Returns `true` if `offset` falls on a `char` boundary (or the end of the text).
+    pub fn is_char_boundary(&self, offset: usize) -> bool {
+        offset <= self.len() && self.root.is_char_boundary(offset)
+    }
+
+    /// Inserts `text` at byte `offset`.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.root.insert(offset, text);
+        self.rebalance();
+    }
+
+    /// Removes the text in `range`.
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.root.remove(range.start, range.end);
+        self.rebalance();
+    }
+
+    /// Replaces the text in `range` with `text`.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.remove(range);
+        self.insert(start, text);
+    }
+
+    /// Copies the text in `range` out of the buffer.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let mut out = String::with_capacity(range.len());
+        for (start, chunk) in self.chunks_at(range.start) {
+            if start >= range.end {
+                break;
+            }
+            let take = (range.end - start).min(chunk.len());
+            out.push_str(&chunk[..take]);
+        }
+        out
+    }
+
+    /// Zero-based line containing byte `offset`.
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.root.newlines_before(offset.min(self.len()))
+    }
+
+    /// Byte offset where `line` starts; lines past the end map to the end of the text.
+    pub fn line_start(&self, line: usize) -> usize {
+        if line == 0 {
+            0
+        } else if line > self.root.newlines() {
+            self.len()
+        } else {
+            self.root.newline_offset(line - 1) + 1
+        }
+    }
+
+    /// Byte offset where `line` ends, excluding its newline.
+    pub fn line_end(&self, line: usize) -> usize {
+        if line < self.root.newlines() {
+            self.root.newline_offset(line)
+        } else {
+            self.len()
+        }
+    }
+
+    /// The text of `line`, without its newline.
+    pub fn line(&self, line: usize) -> String {
+        self.slice(self.line_start(line)..self.line_end(line))
+    }
+
+    /// Byte offset of the first occurrence of `pattern` starting at or after `from`.
+    pub fn find(&self, pattern: &str, from: usize) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+        // Matches may straddle chunks, so keep the tail of what was scanned.
+        let mut window = String::new();
+        let mut window_start = from;
+        for (_, chunk) in self.chunks_at(from) {
+            window.push_str(chunk);
+            if let Some(pos) = window.find(pattern) {
+                return Some(window_start + pos);
+            }
+            let mut keep_from = window.len().saturating_sub(pattern.len() - 1);
+            while !window.is_char_boundary(keep_from) {
+                keep_from -= 1;
+            }
+            window.drain(..keep_from);
+            window_start += keep_from;
+        }
+        None
+    }
+
+    /// Byte offset of the last occurrence of `pattern` ending at or before `before`.
+    pub fn rfind(&self, pattern: &str, before: usize) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+        let mut window = String::new();
+        for (start, chunk) in self.chunks_before(before) {
+            window.insert_str(0, chunk);
+            if let Some(pos) = window.rfind(pattern) {
+                return Some(start + pos);
+            }
+            let mut keep = (pattern.len() - 1).min(window.len());
+            while !window.is_char_boundary(keep) {
+                keep += 1;
+            }
+            window.truncate(keep);
+        }
+        None
+    }
+
+    /// The `char` starting at `offset`, if any.
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        self.chars_at(offset).next().map(|(_, ch)| ch)
+    }
+
+    /// Where the grapheme cluster at `offset` ends, if there is one: what
+    /// reads as a single character, such as a letter with combining accents
+    /// or an emoji with modifiers.
+    pub fn next_grapheme(&self, offset: usize) -> Option<usize> {
+        self.graphemes(self.line_of(offset))
+            .into_iter()
+            .map(|grapheme| grapheme.end)
+            .find(|&end| end > offset)
+    }
+
+    /// Where the grapheme cluster ending at `offset` starts, if there is one.
+    pub fn previous_grapheme(&self, offset: usize) -> Option<usize> {
+        let mut line = self.line_of(offset);
+        if offset == self.line_start(line) {
+            line = line.checked_sub(1)?;
+        }
+        self.graphemes(line)
+            .into_iter()
+            .map(|grapheme| grapheme.start)
+            .take_while(|&start| start < offset)
+            .last()
+    }
+
+    /// Where the grapheme clusters of `line` are, its line break included.
+    /// Clusters never span lines, short of `\r\n`.
+    fn graphemes(&self, line: usize) -> Vec<Range<usize>> {
+        let start = self.line_start(line);
+        let text = self.slice(start..self.line_start(line + 1));
+        text.grapheme_indices(true)
+            .map(|(i, grapheme)| start + i..start + i + grapheme.len())
+            .collect()
+    }
+
+    /// Iterates over `(offset, char)` pairs from `offset` to the end of the text.
+    pub fn chars_at(&self, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        self.chunks_at(offset)
+            .flat_map(|(start, chunk)| chunk.char_indices().map(move |(i, ch)| (start + i, ch)))
+    }
+
+    /// Iterates backwards over `(offset, char)` pairs before `offset`.
+    pub fn chars_before(&self, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        self.chunks_before(offset).flat_map(|(start, chunk)| {
+            chunk
+                .char_indices()
+                .rev()
+                .map(move |(i, ch)| (start + i, ch))
+        })
+    }
+
+    /// Iterates over the stored chunks from `offset` onwards, each paired with
+    /// the byte offset it starts at. The first chunk is cut to begin at `offset`.
+    pub fn chunks_at(&self, offset: usize) -> impl Iterator<Item = (usize, &str)> + '_ {
+        let mut stack = Vec::new();
+        let mut node = &self.root;
+        let mut base = 0;
+        let mut skip = offset.min(self.len());
+        loop {
+            match node {
+                Node::Leaf { .. } => break,
+                Node::Branch { left, right, .. } => {
+                    if skip < left.len() {
+                        stack.push((right.as_ref(), base + left.len()));
+                        node = left;
+                    } else {
+                        skip -= left.len();
+                        base += left.len();
+                        node = right;
+                    }
+                }
+            }
+        }
+        let first = match node {
+            Node::Leaf { text, .. } => Some((base + skip, &text[skip..])),
+            Node::Branch { .. } => None,
+        };
+
+        first
+            .into_iter()
+            .chain(std::iter::from_fn(move || {
+                // Walk down the left spine of the next pending subtree.
+                let (mut node, base) = stack.pop()?;
+                loop {
+                    match node {
+                        Node::Leaf { text, .. } => return Some((base, text.as_str())),
+                        Node::Branch { left, right, .. } => {
+                            stack.push((right.as_ref(), base + left.len()));
+                            node = left;
+                        }
+                    }
+                }
+            }))
+            .filter(|(_, chunk)| !chunk.is_empty())
+    }
+
+    /// Iterates backwards over the stored chunks before `offset`, each paired
+    /// with the byte offset it starts at. The first chunk is cut to end at `offset`.
+    pub fn chunks_before(&self, offset: usize) -> impl Iterator<Item = (usize, &str)> + '_ {
+        let mut stack = Vec::new();
+        let mut node = &self.root;
+        let mut base = 0;
+        let mut keep = offset.min(self.len());
+        loop {
+            match node {
+                Node::Leaf { .. } => break,
+                Node::Branch { left, right, .. } => {
+                    if keep <= left.len() {
+                        node = left;
+                    } else {
+                        stack.push((left.as_ref(), base));
+                        keep -= left.len();
+                        base += left.len();
+                        node = right;
+                    }
+                }
+            }
+        }
+        let first = match node {
+            Node::Leaf { text, .. } => Some((base, &text[..keep])),
+            Node::Branch { .. } => None,
+        };
+
+        first
+            .into_iter()
+            .chain(std::iter::from_fn(move || {
+                // Walk down the right spine of the next pending subtree.
+                let (mut node, mut base) = stack.pop()?;
+                loop {
+                    match node {
+                        Node::Leaf { text, .. } => return Some((base, text.as_str())),
+                        Node::Branch { left, right, .. } => {
+                            stack.push((left.as_ref(), base));
+                            base += left.len();
+                            node = right;
+                        }
+                    }
+                }
+            }))
+            .filter(|(_, chunk)| !chunk.is_empty())
+    }
+
+    /// Rebuilds the tree when edits have made it noticeably deeper than a
+    /// balanced tree over the same text would be.
+    fn rebalance(&mut self) {
+        let leaves = self.len() / (MAX_LEAF / 2) + 1;
+        let balanced_height = usize::BITS - leaves.leading_zeros();
+        if self.root.height() <= 2 * balanced_height as usize + 4 {
+            return;
+        }
+
+        let root = std::mem::replace(&mut self.root, Node::leaf(String::new()));
+        let mut pieces = Vec::new();
+        root.collect_leaves(&mut pieces);
+
+        // Glue small neighbouring leaves back together while rebuilding.
+        let mut leaves = Vec::new();
+        let mut current = String::new();
+        for piece in pieces {
+            if !current.is_empty() && current.len() + piece.len() > MAX_LEAF {
+                leaves.push(Node::leaf(std::mem::take(&mut current)));
+            }
+            current.push_str(&piece);
+        }
+        leaves.push(Node::leaf(current));
+        self.root = Node::build(leaves);
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        Self {
+            root: Node::from_str(text),
+        }
+    }
+}
+
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (_, chunk) in self.chunks_at(0) {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn count_newlines(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A rope of several leaves, with lines numbered so every leaf differs.
+    fn numbered(lines: usize) -> (Buffer, String) {
+        let text: String = (0..lines).map(|i| format!("line {i:04} é\n")).collect();
+        (Buffer::from(text.as_str()), text)
+    }
+
+    #[test]
+    fn edits_across_leaf_boundaries() {
+        let (mut buffer, mut text) = numbered(300);
+        assert!(buffer.root.height() > 0);
+        for offset in [MAX_LEAF - 2, MAX_LEAF, 3 * MAX_LEAF + 1] {
+            // Line starts are boundaries, and nowhere near the `é`s.
+            let offset = text[offset..].find('\n').unwrap() + offset + 1;
+            buffer.remove(offset - 5..offset + 5);
+            text.replace_range(offset - 5..offset + 5, "");
+            buffer.insert(offset - 5, "«joined»\n");
+            text.insert_str(offset - 5, "«joined»\n");
+        }
+        assert_eq!(buffer.to_string(), text);
+        assert_eq!(buffer.len(), text.len());
+        assert_eq!(buffer.line_count(), text.matches('\n').count() + 1);
+    }
+
+    #[test]
+    fn a_growing_leaf_splits() {
+        let mut buffer = Buffer::from("ab");
+        let long = "é".repeat(MAX_LEAF);
+        buffer.insert(1, &long);
+        assert!(buffer.root.height() > 0);
+        assert_eq!(buffer.to_string(), format!("a{long}b"));
+        assert!(buffer.is_char_boundary(1 + long.len()));
+        assert!(!buffer.is_char_boundary(2));
+    }
+
+    #[test]
+    fn many_small_edits_keep_the_tree_shallow() {
+        let mut buffer = Buffer::new();
+        let mut text = String::new();
+        for i in 0..5000 {
+            let piece = format!("{i}\n");
+            // Always typing at the same spot builds a lopsided tree.
+            let offset = text.len() / 2;
+            buffer.insert(offset, &piece);
+            text.insert_str(offset, &piece);
+        }
+        let leaves = buffer.len() / (MAX_LEAF / 2) + 1;
+        let balanced = (usize::BITS - leaves.leading_zeros()) as usize;
+        assert!(buffer.root.height() <= 2 * balanced + 4);
+        assert_eq!(buffer.to_string(), text);
+
+        buffer.remove(10..buffer.len() - 10);
+        text.replace_range(10..text.len() - 10, "");
+        assert_eq!(buffer.to_string(), text);
+        buffer.remove(0..buffer.len());
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.line_count(), 1);
+    }
+
+    #[test]
+    fn line_edges() {
+        let buffer = Buffer::new();
+        assert_eq!(buffer.line_count(), 1);
+        assert_eq!((buffer.line_start(0), buffer.line_end(0)), (0, 0));
+
+        let buffer = Buffer::from("ab\n\ncd\n");
+        assert_eq!(buffer.line_count(), 4);
+        assert_eq!((buffer.line_start(1), buffer.line_end(1)), (3, 3));
+        assert_eq!((buffer.line_start(2), buffer.line_end(2)), (4, 6));
+        // The empty line after the final newline.
+        assert_eq!((buffer.line_start(3), buffer.line_end(3)), (7, 7));
+        assert_eq!(buffer.line_start(9), buffer.len());
+        assert_eq!(buffer.line_end(9), buffer.len());
+        assert_eq!(buffer.line_of(2), 0);
+        assert_eq!(buffer.line_of(3), 1);
+        assert_eq!(buffer.line_of(100), 3);
+
+        let (buffer, text) = numbered(300);
+        for (line, expected) in text.split('\n').enumerate() {
+            assert_eq!(buffer.line(line), expected);
+            assert_eq!(buffer.line_of(buffer.line_start(line)), line);
+        }
+    }
+
+    #[test]
+    fn slices_over_multibyte_text() {
+        let text = "aé☕".repeat(MAX_LEAF / 3);
+        let buffer = Buffer::from(text.as_str());
+        let start = text.char_indices().nth(300).unwrap().0;
+        let end = text.char_indices().nth(900).unwrap().0;
+        assert_eq!(buffer.slice(start..end), text[start..end]);
+        assert_eq!(buffer.slice(start..start), "");
+        assert_eq!(buffer.slice(0..buffer.len()), text);
+        assert_eq!(buffer.char_at(1), Some('é'));
+        assert_eq!(buffer.char_at(buffer.len()), None);
+    }
+
+    #[test]
+    fn find_and_rfind() {
+        let (buffer, text) = numbered(300);
+        // Found across a leaf boundary, wherever it falls.
+        for needle in ["line 0077 é\nline 0078", "0150", "é\nline 0299"] {
+            let at = text.find(needle).unwrap();
+            assert_eq!(buffer.find(needle, 0), Some(at), "{needle}");
+            assert_eq!(buffer.find(needle, at), Some(at));
+            let next = at + needle.chars().next().unwrap().len_utf8();
+            assert_eq!(buffer.find(needle, next), None);
+            assert_eq!(buffer.rfind(needle, buffer.len()), Some(at));
+            assert_eq!(buffer.rfind(needle, at + needle.len()), Some(at));
+            assert_eq!(buffer.rfind(needle, at + needle.len() - 1), None);
+        }
+        assert_eq!(
+            buffer.find("line", 1),
+            text[1..].find("line").map(|i| i + 1)
+        );
+        assert_eq!(buffer.find("", 0), None);
+        assert_eq!(buffer.rfind("missing", buffer.len()), None);
+    }
+}
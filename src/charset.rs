@@ -0,0 +1,129 @@
+/// How the text of a file is stored as bytes.
+///
+/// Buffers always hold UTF-8; a file's own encoding is used again when it's
+/// saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    /// UTF-8 after a byte order mark.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Charset {
+    /// Every encoding, in the order the picker lists them.
+    pub const ALL: &'static [Charset] = &[
+        Charset::Utf8,
+        Charset::Utf8Bom,
+        Charset::Utf16Le,
+        Charset::Utf16Be,
+        Charset::Latin1,
+    ];
+
+    /// Guesses how `bytes` are encoded: by their byte order mark if they
+    /// have one, as UTF-8 if they're valid, as UTF-16 if every other byte
+    /// is mostly zero, and as Latin-1 otherwise, which anything decodes as.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return Charset::Utf8Bom;
+        }
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return Charset::Utf16Le;
+        }
+        if bytes.starts_with(&[0xfe, 0xff]) {
+            return Charset::Utf16Be;
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Charset::Utf8;
+        }
+        if bytes.len().is_multiple_of(2) {
+            let zeros = |parity| {
+                bytes
+                    .iter()
+                    .skip(parity)
+                    .step_by(2)
+                    .filter(|&&byte| byte == 0)
+                    .count()
+            };
+            let half = bytes.len() / 2;
+            if zeros(1) * 2 > half {
+                return Charset::Utf16Le;
+            }
+            if zeros(0) * 2 > half {
+                return Charset::Utf16Be;
+            }
+        }
+        Charset::Latin1
+    }
+
+    /// The name shown in the status bar and the picker.
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "UTF-8",
+            Charset::Utf8Bom => "UTF-8 BOM",
+            Charset::Utf16Le => "UTF-16 LE",
+            Charset::Utf16Be => "UTF-16 BE",
+            Charset::Latin1 => "Latin-1",
+        }
+    }
+
+    /// The byte order mark files in this encoding start with, if any. UTF-16
+    /// is always written with one.
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Charset::Utf8 | Charset::Latin1 => &[],
+            Charset::Utf8Bom => &[0xef, 0xbb, 0xbf],
+            Charset::Utf16Le => &[0xff, 0xfe],
+            Charset::Utf16Be => &[0xfe, 0xff],
+        }
+    }
+
+    /// Decodes the contents of a file, byte order mark and all.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, String> {
+        let bytes = bytes.strip_prefix(self.bom()).unwrap_or(bytes);
+        match self {
+            Charset::Utf8 | Charset::Utf8Bom => {
+                String::from_utf8(bytes.to_vec()).map_err(|err| format!("Not UTF-8: {err}"))
+            }
+            Charset::Utf16Le | Charset::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err("Not UTF-16: the length is odd".to_string());
+                }
+                let units = bytes.chunks(2).map(|pair| {
+                    let pair = [pair[0], pair[1]];
+                    if self == Charset::Utf16Le {
+                        u16::from_le_bytes(pair)
+                    } else {
+                        u16::from_be_bytes(pair)
+                    }
+                });
+                char::decode_utf16(units)
+                    .collect::<Result<String, _>>()
+                    .map_err(|err| format!("Not UTF-16: {err}"))
+            }
+            Charset::Latin1 => Ok(bytes.iter().map(|&byte| byte as char).collect()),
+        }
+    }
+
+    /// Encodes `text` to be written to a file, starting with the byte order
+    /// mark. Fails on characters Latin-1 has no byte for.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Charset::Utf8 | Charset::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Charset::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Charset::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+            Charset::Latin1 => {
+                for ch in text.chars() {
+                    let byte = u8::try_from(ch)
+                        .map_err(|_| format!("`{ch}` can't be written in Latin-1"))?;
+                    bytes.push(byte);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
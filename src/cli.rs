@@ -0,0 +1,225 @@
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// What `--help` prints.
+pub const USAGE: &str = "\
+Usage: wasdev [options] [+line[:column]] [file[:line[:column]]]...
+       wasdev --batch --transform <name> [--range <lines>] [--in-place] [file]
+
+Options:
+  -R, --readonly     open every file read-only
+  -                  edit the text piped to standard input, printing it on quitting
+  +line[:column]     open the file after it at that line
+  --config <dir>     read the configuration files in <dir>
+  --batch            run transforms over a file without starting the editor
+  -V, --version      print the version
+  -h, --help         print this help";
+
+/// What the command line asks for.
+pub enum Action {
+    /// Start the editor.
+    Edit(Options),
+    /// Run transforms without starting the editor, with the arguments that
+    /// say which.
+    Batch(Vec<OsString>),
+    Version,
+    Help,
+}
+
+/// How the editor was asked to start.
+#[derive(Default)]
+pub struct Options {
+    /// The buffers to open, in order.
+    pub files: Vec<Open>,
+    /// Whether every file opens read-only, for reading like a pager.
+    pub read_only: bool,
+}
+
+/// A buffer to open from the command line.
+pub struct Open {
+    /// The file, or `None` for standard input.
+    pub path: Option<PathBuf>,
+    /// Where to put the cursor, as a line and a column counted from 0,
+    /// instead of where it was left.
+    pub position: Option<(usize, usize)>,
+}
+
+/// Reads the command line, less the program's name. The configuration
+/// directory `--config` gives applies to the editor and `--batch` alike,
+/// so it's returned either way.
+pub fn parse(args: Vec<OsString>) -> Result<(Action, Option<PathBuf>), String> {
+    let mut config = None;
+    let mut batch = None::<Vec<OsString>>;
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--version" | "-V") if batch.is_none() => return Ok((Action::Version, config)),
+            Some("--help" | "-h") if batch.is_none() => return Ok((Action::Help, config)),
+            Some("--config") => match args.next() {
+                Some(dir) => config = Some(PathBuf::from(dir)),
+                None => return Err("--config needs a directory".to_string()),
+            },
+            Some("--batch") => batch = Some(std::mem::take(&mut rest)),
+            Some("--") => {
+                rest.push(arg);
+                rest.extend(args.by_ref());
+            }
+            _ => rest.push(arg),
+        }
+    }
+    if let Some(mut before) = batch {
+        before.extend(rest);
+        return Ok((Action::Batch(before), config));
+    }
+    Ok((Action::Edit(options(rest)?), config))
+}
+
+/// Reads the arguments that say what to edit.
+fn options(args: Vec<OsString>) -> Result<Options, String> {
+    let mut options = Options::default();
+    // The position given before the next file, if any.
+    let mut position = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let text = arg.to_str();
+        match text {
+            Some("-R" | "--readonly") => options.read_only = true,
+            Some("-") => options.files.push(Open {
+                path: None,
+                position: position.take(),
+            }),
+            Some("--") => {
+                for arg in args.by_ref() {
+                    options.files.push(file(arg, position.take()));
+                }
+            }
+            Some(text) if text.starts_with('+') => {
+                position = Some(
+                    parse_position(&text[1..])
+                        .ok_or_else(|| format!("{text} isn't a line to open a file at"))?,
+                );
+            }
+            Some(text) if text.starts_with('-') => {
+                return Err(format!("Unknown option {text}; see --help"))
+            }
+            _ => options.files.push(file(arg, position.take())),
+        }
+    }
+    if position.is_some() {
+        return Err("A +line needs a file after it".to_string());
+    }
+    Ok(options)
+}
+
+/// The file `arg` names, at `position` or at the line and column it ends
+/// with, as compilers print them: `src/main.rs:42:7`. A name that is a file
+/// of its own, colons and all, is taken as it is.
+fn file(arg: OsString, position: Option<(usize, usize)>) -> Open {
+    let whole = PathBuf::from(&arg);
+    if position.is_none() && !whole.exists() {
+        if let Some((path, at)) = split_position(&arg) {
+            return Open {
+                path: Some(PathBuf::from(path)),
+                position: Some(at),
+            };
+        }
+    }
+    Open {
+        path: Some(whole),
+        position,
+    }
+}
+
+/// Splits `file:line` or `file:line:column` into the file and the
+/// position.
+fn split_position(arg: &OsStr) -> Option<(&str, (usize, usize))> {
+    let arg = arg.to_str()?;
+    // A column comes after the line, so try taking both first.
+    let (path, at) = arg
+        .rmatch_indices(':')
+        .map(|(i, _)| (&arg[..i], &arg[i + 1..]))
+        .take(2)
+        .filter_map(|(path, at)| Some((path, parse_position(at)?)))
+        .last()?;
+    (!path.is_empty()).then_some((path, at))
+}
+
+/// Reads `line` or `line:column`, both counted from 1, into a position
+/// counted from 0.
+fn parse_position(text: &str) -> Option<(usize, usize)> {
+    let number = |n: &str| n.parse::<usize>().ok().filter(|&n| n > 0);
+    let (line, column) = match text.split_once(':') {
+        Some((line, column)) => (number(line)?, number(column)?),
+        None => (number(text)?, 1),
+    };
+    Some((line - 1, column - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(args: &[&str]) -> Options {
+        match parse(args.iter().map(OsString::from).collect()) {
+            Ok((Action::Edit(options), _)) => options,
+            _ => panic!("{args:?} doesn't start the editor"),
+        }
+    }
+
+    #[test]
+    fn positions_before_and_after_files() {
+        let options = edit(&["+42", "a.txt", "b.rs:3:7", "c.md:9", "d"]);
+        let files: Vec<_> = options
+            .files
+            .iter()
+            .map(|open| (open.path.clone().unwrap(), open.position))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (PathBuf::from("a.txt"), Some((41, 0))),
+                (PathBuf::from("b.rs"), Some((2, 6))),
+                (PathBuf::from("c.md"), Some((8, 0))),
+                (PathBuf::from("d"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_that_only_look_like_positions() {
+        assert_eq!(split_position(OsStr::new("notes:draft")), None);
+        assert_eq!(split_position(OsStr::new(":12")), None);
+        assert_eq!(split_position(OsStr::new("a:b:4")), Some(("a:b", (3, 0))));
+    }
+
+    #[test]
+    fn options_anywhere() {
+        let options = edit(&["a", "--readonly", "-", "--", "-R"]);
+        assert!(options.read_only);
+        assert_eq!(options.files.len(), 3);
+        assert!(options.files[1].path.is_none());
+        assert_eq!(options.files[2].path, Some(PathBuf::from("-R")));
+    }
+
+    #[test]
+    fn mistakes() {
+        let parse = |args: &[&str]| parse(args.iter().map(OsString::from).collect()).err();
+        assert!(parse(&["--frobnicate"]).is_some());
+        assert!(parse(&["a", "+3"]).is_some());
+        assert!(parse(&["+x", "a"]).is_some());
+        assert!(parse(&["--config"]).is_some());
+    }
+
+    #[test]
+    fn batch_takes_the_rest() {
+        let args = ["-t", "upper", "--batch", "--config", "/c", "f", "-V"];
+        match parse(args.iter().map(OsString::from).collect()) {
+            Ok((Action::Batch(rest), config)) => {
+                assert_eq!(rest, ["-t", "upper", "f", "-V"]);
+                assert_eq!(config, Some(PathBuf::from("/c")));
+            }
+            _ => panic!("--batch doesn't run transforms"),
+        }
+    }
+}
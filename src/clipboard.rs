@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Commands used to talk to the system clipboard. The first word of each
+/// command is the program to run, the rest are its arguments.
+#[derive(Clone, Copy)]
+struct Provider {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+const WAYLAND: Provider = Provider {
+    copy: &["wl-copy"],
+    paste: &["wl-paste", "--no-newline"],
+};
+const XCLIP: Provider = Provider {
+    copy: &["xclip", "-selection", "clipboard", "-in"],
+    paste: &["xclip", "-selection", "clipboard", "-out"],
+};
+const XSEL: Provider = Provider {
+    copy: &["xsel", "--clipboard", "--input"],
+    paste: &["xsel", "--clipboard", "--output"],
+};
+const MACOS: Provider = Provider {
+    copy: &["pbcopy"],
+    paste: &["pbpaste"],
+};
+const WINDOWS: Provider = Provider {
+    copy: &["clip.exe"],
+    paste: &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+};
+
+/// Most pieces of text the clipboard remembers having held.
+const RING: usize = 20;
+
+/// Clipboard shared by copy, cut and paste.
+///
+/// Text is handed to the OS clipboard through whichever helper program is
+/// available (`wl-copy`, `xclip`, `xsel`, `pbcopy`, `clip.exe`). The last
+/// copied text is always kept in-process as well, so copy and paste keep
+/// working inside the editor when there is no display server or no helper,
+/// along with the text copied before it, so any of it can be pasted again.
+#[derive(Clone, Default)]
+pub struct Clipboard {
+    provider: Option<Provider>,
+    fallback: String,
+    /// What was copied or cut, newest first, without repeats.
+    ring: VecDeque<String>,
+}
+
+impl Clipboard {
+    /// Creates a clipboard, detecting the system provider once up front.
+    pub fn new() -> Self {
+        Self {
+            provider: detect_provider(),
+            fallback: String::new(),
+            ring: VecDeque::new(),
+        }
+    }
+
+    /// Stores `text` in the clipboard.
+    pub fn set(&mut self, text: &str) {
+        self.fallback = text.to_string();
+        self.ring.retain(|held| held != text);
+        self.ring.push_front(text.to_string());
+        self.ring.truncate(RING);
+        if let Some(provider) = self.provider {
+            let child = Command::new(provider.copy[0])
+                .args(&provider.copy[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            if let Ok(mut child) = child {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(text.as_bytes());
+                }
+                let _ = child.wait();
+            }
+        }
+    }
+
+    /// Returns the clipboard contents, preferring the system clipboard.
+    pub fn get(&self) -> String {
+        self.system_contents()
+            .unwrap_or_else(|| self.fallback.clone())
+    }
+
+    /// The text the clipboard has held, newest first.
+    pub fn ring(&self) -> impl Iterator<Item = &str> {
+        self.ring.iter().map(String::as_str)
+    }
+
+    /// Reads the system clipboard, if a provider is available and succeeds.
+    fn system_contents(&self) -> Option<String> {
+        let provider = self.provider?;
+        let output = Command::new(provider.paste[0])
+            .args(&provider.paste[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let mut text = String::from_utf8(output.stdout).ok()?;
+        // PowerShell terminates its output with a line break of its own.
+        if cfg!(windows) && text.ends_with("\r\n") {
+            text.truncate(text.len() - 2);
+        }
+        Some(text)
+    }
+}
+
+/// Picks the clipboard helper matching the current platform and session.
+fn detect_provider() -> Option<Provider> {
+    let candidates: &[Provider] = if cfg!(target_os = "macos") {
+        &[MACOS]
+    } else if cfg!(windows) {
+        &[WINDOWS]
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        &[WAYLAND, XCLIP, XSEL]
+    } else if env::var_os("DISPLAY").is_some() {
+        &[XCLIP, XSEL]
+    } else {
+        &[]
+    };
+    candidates
+        .iter()
+        .copied()
+        .find(|provider| in_path(provider.copy[0]) && in_path(provider.paste[0]))
+}
+
+/// Returns `true` if `program` can be found in one of the `PATH` directories.
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file()))
+        .unwrap_or(false)
+}
@@ -0,0 +1,1566 @@
+use crate::charset::Charset;
+use crate::scripts::Hook;
+use crate::transform::Transform;
+use crate::workspace::Workspace;
+use crate::{
+    compare, complete, export, finder, format, git, hex, insert, jobs, line_ending, macros,
+    markdown, palette, plugins, project, prompt, quickfix, recent, registers, remap, rename,
+    replace, shell, spell, stats, swap, tags, tree, undo_tree, unicode, Editor, LineOp,
+};
+use cursive::direction::Orientation;
+use cursive::event::{Event, Key};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+
+/// Most bytes of output `read-command` inserts; a command printing more
+/// is stopped and nothing is inserted.
+const MAX_READ: usize = 1 << 20;
+
+/// Every action that can be bound to a key, by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    MoveRight,
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveWordRight,
+    MoveWordLeft,
+    MoveBigWordRight,
+    MoveBigWordLeft,
+    MoveParagraphDown,
+    MoveParagraphUp,
+    MoveLineStart,
+    MoveLineEnd,
+    DeleteBackward,
+    DeleteForward,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    CenterView,
+    GotoStart,
+    GotoEnd,
+    JumpToMatch,
+    ExpandSelection,
+    ToggleSelection,
+    ShrinkSelection,
+    Copy,
+    Cut,
+    Paste,
+    PasteFromHistory,
+    CopyToRegister,
+    CutToRegister,
+    PasteFromRegister,
+    ShowRegisters,
+    Quit,
+    Search,
+    SearchNext,
+    SearchPrevious,
+    Replace,
+    SwitchBuffer,
+    OpenFile,
+    Save,
+    SplitBelow,
+    SplitBeside,
+    NextPane,
+    ClosePane,
+    OpenTransformMenu,
+    OpenInsertMenu,
+    InsertCharacter,
+    DescribeCharacter,
+    Uppercase,
+    Lowercase,
+    Capitalize,
+    Reverse,
+    SortLines,
+    SortLinesDescending,
+    SortLinesNumeric,
+    ReverseLines,
+    RemoveDuplicateLines,
+    SnakeCase,
+    CamelCase,
+    KebabCase,
+    TitleCase,
+    Pipe,
+    OpenLineMenu,
+    JoinLines,
+    Reflow,
+    ToggleComment,
+    DuplicateLines,
+    MoveLinesUp,
+    MoveLinesDown,
+    DeleteLines,
+    OpenPalette,
+    Undo,
+    UndoTree,
+    Redo,
+    RecordMacro,
+    PlayMacro,
+    SaveMacro,
+    RunMacro,
+    RunPlugin,
+    AddCursorBelow,
+    AddCursorsAtMatches,
+    SelectNextOccurrence,
+    NextOccurrence,
+    PreviousOccurrence,
+    GotoDefinition,
+    RenameSymbol,
+    GotoTag,
+    PopTag,
+    Hover,
+    Complete,
+    ExpandSnippet,
+    GotoLine,
+    SetMark,
+    GotoMark,
+    JumpBack,
+    JumpForward,
+    Count,
+    Increment,
+    Decrement,
+    Surround,
+    ChangeSurround,
+    DeleteSurround,
+    RestoreSelection,
+    RestoreSelectionForward,
+    SelectLines,
+    SelectBlock,
+    ToggleLineEnding,
+    ReopenWithEncoding,
+    ToggleReadOnly,
+    ToggleHex,
+    DiffWithDisk,
+    NextHunk,
+    PreviousHunk,
+    Blame,
+    SearchProject,
+    Build,
+    ShowQuickfix,
+    NextError,
+    PreviousError,
+    ToggleFileTree,
+    FindFile,
+    OpenRecent,
+    ReadShell,
+    ToggleSpellCheck,
+    ToggleAbbreviations,
+    InsertLiteral,
+    SpellSuggest,
+    DocumentStatistics,
+    ToggleMarkdownPreview,
+    ExportHtml,
+    ToggleSelectMode,
+    RemapKeys,
+    ToggleLineNumbers,
+    ToggleWhitespace,
+    ToggleWrap,
+    FormatBuffer,
+    CancelJob,
+}
+
+impl Command {
+    /// All commands, in the order menus list them.
+    pub const ALL: &'static [Command] = &[
+        Command::MoveRight,
+        Command::MoveLeft,
+        Command::MoveDown,
+        Command::MoveUp,
+        Command::MoveWordRight,
+        Command::MoveWordLeft,
+        Command::MoveBigWordRight,
+        Command::MoveBigWordLeft,
+        Command::MoveParagraphDown,
+        Command::MoveParagraphUp,
+        Command::MoveLineStart,
+        Command::MoveLineEnd,
+        Command::DeleteBackward,
+        Command::DeleteForward,
+        Command::PageDown,
+        Command::PageUp,
+        Command::HalfPageDown,
+        Command::HalfPageUp,
+        Command::CenterView,
+        Command::GotoStart,
+        Command::GotoEnd,
+        Command::JumpToMatch,
+        Command::ExpandSelection,
+        Command::ToggleSelection,
+        Command::ShrinkSelection,
+        Command::Copy,
+        Command::Cut,
+        Command::Paste,
+        Command::PasteFromHistory,
+        Command::CopyToRegister,
+        Command::CutToRegister,
+        Command::PasteFromRegister,
+        Command::ShowRegisters,
+        Command::Quit,
+        Command::Search,
+        Command::SearchNext,
+        Command::SearchPrevious,
+        Command::Replace,
+        Command::SwitchBuffer,
+        Command::OpenFile,
+        Command::Save,
+        Command::SplitBelow,
+        Command::SplitBeside,
+        Command::NextPane,
+        Command::ClosePane,
+        Command::OpenTransformMenu,
+        Command::OpenInsertMenu,
+        Command::InsertCharacter,
+        Command::DescribeCharacter,
+        Command::Uppercase,
+        Command::Lowercase,
+        Command::Capitalize,
+        Command::Reverse,
+        Command::SortLines,
+        Command::SortLinesDescending,
+        Command::SortLinesNumeric,
+        Command::ReverseLines,
+        Command::RemoveDuplicateLines,
+        Command::SnakeCase,
+        Command::CamelCase,
+        Command::KebabCase,
+        Command::TitleCase,
+        Command::Pipe,
+        Command::OpenLineMenu,
+        Command::JoinLines,
+        Command::Reflow,
+        Command::ToggleComment,
+        Command::DuplicateLines,
+        Command::MoveLinesUp,
+        Command::MoveLinesDown,
+        Command::DeleteLines,
+        Command::OpenPalette,
+        Command::Undo,
+        Command::UndoTree,
+        Command::Redo,
+        Command::RecordMacro,
+        Command::PlayMacro,
+        Command::SaveMacro,
+        Command::RunMacro,
+        Command::RunPlugin,
+        Command::AddCursorBelow,
+        Command::AddCursorsAtMatches,
+        Command::SelectNextOccurrence,
+        Command::NextOccurrence,
+        Command::PreviousOccurrence,
+        Command::GotoDefinition,
+        Command::RenameSymbol,
+        Command::GotoTag,
+        Command::PopTag,
+        Command::Hover,
+        Command::Complete,
+        Command::ExpandSnippet,
+        Command::GotoLine,
+        Command::SetMark,
+        Command::GotoMark,
+        Command::JumpBack,
+        Command::JumpForward,
+        Command::Count,
+        Command::Increment,
+        Command::Decrement,
+        Command::Surround,
+        Command::ChangeSurround,
+        Command::DeleteSurround,
+        Command::RestoreSelection,
+        Command::RestoreSelectionForward,
+        Command::SelectLines,
+        Command::SelectBlock,
+        Command::ToggleLineEnding,
+        Command::ReopenWithEncoding,
+        Command::ToggleReadOnly,
+        Command::ToggleHex,
+        Command::DiffWithDisk,
+        Command::NextHunk,
+        Command::PreviousHunk,
+        Command::Blame,
+        Command::SearchProject,
+        Command::Build,
+        Command::ShowQuickfix,
+        Command::NextError,
+        Command::PreviousError,
+        Command::ToggleFileTree,
+        Command::FindFile,
+        Command::OpenRecent,
+        Command::ReadShell,
+        Command::ToggleSpellCheck,
+        Command::ToggleAbbreviations,
+        Command::InsertLiteral,
+        Command::SpellSuggest,
+        Command::DocumentStatistics,
+        Command::ToggleMarkdownPreview,
+        Command::ExportHtml,
+        Command::ToggleSelectMode,
+        Command::RemapKeys,
+        Command::ToggleLineNumbers,
+        Command::ToggleWhitespace,
+        Command::ToggleWrap,
+        Command::FormatBuffer,
+        Command::CancelJob,
+    ];
+
+    /// The name used for the command in configuration files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::MoveRight => "move-right",
+            Command::MoveLeft => "move-left",
+            Command::MoveDown => "move-down",
+            Command::MoveUp => "move-up",
+            Command::MoveWordRight => "move-word-right",
+            Command::MoveWordLeft => "move-word-left",
+            Command::MoveBigWordRight => "move-big-word-right",
+            Command::MoveBigWordLeft => "move-big-word-left",
+            Command::MoveParagraphDown => "move-paragraph-down",
+            Command::MoveParagraphUp => "move-paragraph-up",
+            Command::MoveLineStart => "move-line-start",
+            Command::MoveLineEnd => "move-line-end",
+            Command::DeleteBackward => "delete-backward",
+            Command::DeleteForward => "delete-forward",
+            Command::PageDown => "page-down",
+            Command::PageUp => "page-up",
+            Command::HalfPageDown => "half-page-down",
+            Command::HalfPageUp => "half-page-up",
+            Command::CenterView => "center-view",
+            Command::GotoStart => "goto-start",
+            Command::GotoEnd => "goto-end",
+            Command::JumpToMatch => "jump-to-match",
+            Command::ExpandSelection => "expand-selection",
+            Command::ToggleSelection => "toggle-selection",
+            Command::ShrinkSelection => "shrink-selection",
+            Command::Copy => "copy",
+            Command::Cut => "cut",
+            Command::Paste => "paste",
+            Command::PasteFromHistory => "paste-from-history",
+            Command::CopyToRegister => "copy-to-register",
+            Command::CutToRegister => "cut-to-register",
+            Command::PasteFromRegister => "paste-from-register",
+            Command::ShowRegisters => "show-registers",
+            Command::Quit => "quit",
+            Command::Search => "search",
+            Command::SearchNext => "search-next",
+            Command::SearchPrevious => "search-previous",
+            Command::Replace => "replace",
+            Command::SwitchBuffer => "switch-buffer",
+            Command::OpenFile => "open-file",
+            Command::Save => "save",
+            Command::SplitBelow => "split-below",
+            Command::SplitBeside => "split-beside",
+            Command::NextPane => "next-pane",
+            Command::ClosePane => "close-pane",
+            Command::OpenTransformMenu => "open-transform-menu",
+            Command::OpenInsertMenu => "open-insert-menu",
+            Command::InsertCharacter => "insert-character",
+            Command::DescribeCharacter => "describe-character",
+            Command::Uppercase => "uppercase",
+            Command::Lowercase => "lowercase",
+            Command::Capitalize => "capitalize",
+            Command::Reverse => "reverse",
+            Command::SortLines => "sort-lines",
+            Command::SortLinesDescending => "sort-lines-descending",
+            Command::SortLinesNumeric => "sort-lines-numeric",
+            Command::ReverseLines => "reverse-lines",
+            Command::RemoveDuplicateLines => "remove-duplicate-lines",
+            Command::SnakeCase => "snake-case",
+            Command::CamelCase => "camel-case",
+            Command::KebabCase => "kebab-case",
+            Command::TitleCase => "title-case",
+            Command::Pipe => "pipe",
+            Command::OpenLineMenu => "open-line-menu",
+            Command::JoinLines => "join-lines",
+            Command::Reflow => "reflow",
+            Command::ToggleComment => "toggle-comment",
+            Command::DuplicateLines => "duplicate-lines",
+            Command::MoveLinesUp => "move-lines-up",
+            Command::MoveLinesDown => "move-lines-down",
+            Command::DeleteLines => "delete-lines",
+            Command::OpenPalette => "command-palette",
+            Command::Undo => "undo",
+            Command::UndoTree => "undo-tree",
+            Command::Redo => "redo",
+            Command::RecordMacro => "record-macro",
+            Command::PlayMacro => "play-macro",
+            Command::SaveMacro => "save-macro",
+            Command::RunMacro => "run-macro",
+            Command::RunPlugin => "run-plugin",
+            Command::AddCursorBelow => "add-cursor-below",
+            Command::AddCursorsAtMatches => "add-cursors-at-matches",
+            Command::SelectNextOccurrence => "select-next-occurrence",
+            Command::NextOccurrence => "next-occurrence",
+            Command::PreviousOccurrence => "previous-occurrence",
+            Command::GotoDefinition => "goto-definition",
+            Command::RenameSymbol => "rename-symbol",
+            Command::GotoTag => "goto-tag",
+            Command::PopTag => "pop-tag",
+            Command::Hover => "hover",
+            Command::Complete => "complete",
+            Command::ExpandSnippet => "expand-snippet",
+            Command::GotoLine => "goto-line",
+            Command::SetMark => "set-mark",
+            Command::GotoMark => "goto-mark",
+            Command::JumpBack => "jump-back",
+            Command::JumpForward => "jump-forward",
+            Command::Count => "count",
+            Command::Increment => "increment",
+            Command::Decrement => "decrement",
+            Command::Surround => "surround",
+            Command::ChangeSurround => "change-surround",
+            Command::DeleteSurround => "delete-surround",
+            Command::RestoreSelection => "restore-selection",
+            Command::RestoreSelectionForward => "restore-selection-forward",
+            Command::SelectLines => "select-lines",
+            Command::SelectBlock => "select-block",
+            Command::ToggleLineEnding => "toggle-line-ending",
+            Command::ReopenWithEncoding => "reopen-with-encoding",
+            Command::ToggleReadOnly => "toggle-read-only",
+            Command::ToggleHex => "toggle-hex",
+            Command::DiffWithDisk => "diff-with-disk",
+            Command::NextHunk => "next-hunk",
+            Command::PreviousHunk => "previous-hunk",
+            Command::Blame => "blame-line",
+            Command::SearchProject => "search-project",
+            Command::Build => "build",
+            Command::ShowQuickfix => "quickfix-list",
+            Command::NextError => "next-error",
+            Command::PreviousError => "previous-error",
+            Command::ToggleFileTree => "toggle-file-tree",
+            Command::FindFile => "find-file",
+            Command::OpenRecent => "open-recent",
+            Command::ReadShell => "read-command",
+            Command::ToggleSpellCheck => "toggle-spell-check",
+            Command::ToggleAbbreviations => "toggle-abbreviations",
+            Command::InsertLiteral => "insert-literal",
+            Command::SpellSuggest => "spell-suggest",
+            Command::DocumentStatistics => "document-statistics",
+            Command::ToggleMarkdownPreview => "toggle-markdown-preview",
+            Command::ExportHtml => "export-html",
+            Command::ToggleSelectMode => "toggle-select-mode",
+            Command::RemapKeys => "remap-keys",
+            Command::ToggleLineNumbers => "toggle-line-numbers",
+            Command::ToggleWhitespace => "toggle-whitespace",
+            Command::ToggleWrap => "toggle-wrap",
+            Command::FormatBuffer => "format-buffer",
+            Command::CancelJob => "cancel-job",
+        }
+    }
+
+    /// Whether the command changes the text, which read-only buffers don't
+    /// allow.
+    pub fn edits(self) -> bool {
+        matches!(
+            self,
+            Command::DeleteBackward
+                | Command::DeleteForward
+                | Command::Cut
+                | Command::Paste
+                | Command::PasteFromHistory
+                | Command::CutToRegister
+                | Command::PasteFromRegister
+                | Command::Replace
+                | Command::OpenTransformMenu
+                | Command::OpenInsertMenu
+                | Command::InsertCharacter
+                | Command::Uppercase
+                | Command::Lowercase
+                | Command::Capitalize
+                | Command::Reverse
+                | Command::SortLines
+                | Command::SortLinesDescending
+                | Command::SortLinesNumeric
+                | Command::ReverseLines
+                | Command::RemoveDuplicateLines
+                | Command::SnakeCase
+                | Command::CamelCase
+                | Command::KebabCase
+                | Command::TitleCase
+                | Command::Pipe
+                | Command::ReadShell
+                | Command::SpellSuggest
+                | Command::OpenLineMenu
+                | Command::JoinLines
+                | Command::Reflow
+                | Command::ToggleComment
+                | Command::DuplicateLines
+                | Command::MoveLinesUp
+                | Command::MoveLinesDown
+                | Command::DeleteLines
+                | Command::Undo
+                | Command::UndoTree
+                | Command::Redo
+                | Command::Complete
+                | Command::ExpandSnippet
+                | Command::Increment
+                | Command::Decrement
+                | Command::Surround
+                | Command::ChangeSurround
+                | Command::DeleteSurround
+                | Command::InsertLiteral
+                | Command::ToggleLineEnding
+                | Command::FormatBuffer
+        )
+    }
+
+    /// Carries out a motion through the bytes of the hex dump the buffer
+    /// is shown as, returning `false` if the command isn't one.
+    fn move_in_hex(self, workspace: &Arc<Mutex<Workspace>>) -> bool {
+        let mut ws = workspace.lock().unwrap();
+        let row = hex::ROW as isize;
+        let page = ws.panes()[ws.focus()].height.max(1) as isize * row;
+        let Some(hex) = ws.editor_mut().hex_mut() else {
+            return false;
+        };
+        match self {
+            Command::MoveRight => hex.move_by(1),
+            Command::MoveLeft => hex.move_by(-1),
+            Command::MoveDown => hex.move_by(row),
+            Command::MoveUp => hex.move_by(-row),
+            Command::MoveLineStart => hex.move_row_start(),
+            Command::MoveLineEnd => hex.move_row_end(),
+            Command::PageDown => hex.move_by(page),
+            Command::PageUp => hex.move_by(-page),
+            Command::HalfPageDown => hex.move_by(page / 2),
+            Command::HalfPageUp => hex.move_by(-page / 2),
+            Command::GotoStart => hex.move_to(0),
+            Command::GotoEnd => hex.move_to(usize::MAX),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Carries out a deletion of bytes of the hex dump the buffer is shown
+    /// as, returning `false` if the command isn't one.
+    fn edit_in_hex(self, workspace: &Arc<Mutex<Workspace>>) -> bool {
+        let change: fn(&mut hex::Hex) -> bool = match self {
+            Command::DeleteBackward => hex::Hex::backspace,
+            Command::DeleteForward => hex::Hex::delete,
+            _ => return false,
+        };
+        workspace.lock().unwrap().editor_mut().edit_hex(change);
+        true
+    }
+
+    /// Looks a command up by its configuration name.
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    /// Carries out the command, with the count typed before it, if any.
+    /// Commands that edit are refused in a read-only buffer, and in a hex
+    /// dump, where motions and deletions work on the bytes instead.
+    pub fn run(self, s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+        let count = workspace.lock().unwrap().take_count();
+        if self.edits() && workspace.lock().unwrap().editor().is_read_only() {
+            workspace.lock().unwrap().notify("The buffer is read-only");
+            return;
+        }
+        if workspace.lock().unwrap().editor().hex().is_some() {
+            if self.move_in_hex(workspace) || self.edit_in_hex(workspace) {
+                return;
+            }
+            if self.edits() {
+                workspace
+                    .lock()
+                    .unwrap()
+                    .notify("Leave the hex dump to edit the text");
+                return;
+            }
+        }
+        match self {
+            Command::MoveRight => each_cursor(workspace, |ed| ed.move_right()),
+            Command::MoveLeft => each_cursor(workspace, |ed| ed.move_left()),
+            Command::MoveDown => workspace.lock().unwrap().move_rows(1),
+            Command::MoveUp => workspace.lock().unwrap().move_rows(-1),
+            Command::MoveWordRight => each_cursor(workspace, |ed| ed.move_word_right(false)),
+            Command::MoveWordLeft => each_cursor(workspace, |ed| ed.move_word_left(false)),
+            Command::MoveBigWordRight => each_cursor(workspace, |ed| ed.move_word_right(true)),
+            Command::MoveBigWordLeft => each_cursor(workspace, |ed| ed.move_word_left(true)),
+            Command::MoveParagraphDown => each_cursor(workspace, |ed| ed.move_paragraph_down()),
+            Command::MoveParagraphUp => each_cursor(workspace, |ed| ed.move_paragraph_up()),
+            Command::MoveLineStart => each_cursor(workspace, |ed| ed.move_line_start()),
+            Command::MoveLineEnd => each_cursor(workspace, |ed| ed.move_line_end()),
+            Command::DeleteBackward => each_cursor(workspace, Editor::backspace),
+            Command::DeleteForward => each_cursor(workspace, Editor::delete),
+            Command::PageDown => scroll(workspace, 2),
+            Command::PageUp => scroll(workspace, -2),
+            Command::HalfPageDown => scroll(workspace, 1),
+            Command::HalfPageUp => scroll(workspace, -1),
+            Command::CenterView => workspace.lock().unwrap().center_view(),
+            Command::GotoStart => workspace.lock().unwrap().editor_mut().goto_start(),
+            Command::GotoEnd => workspace.lock().unwrap().editor_mut().goto_end(),
+            Command::JumpToMatch => each_cursor(workspace, Editor::jump_to_match),
+            Command::ExpandSelection => each_cursor(workspace, Editor::expand_selection),
+            Command::ToggleSelection => each_cursor(workspace, Editor::toggle_selection),
+            Command::ShrinkSelection => workspace.lock().unwrap().editor_mut().shrink_selection(),
+            Command::Copy => workspace.lock().unwrap().copy(),
+            Command::Cut => workspace.lock().unwrap().cut(),
+            Command::Paste => workspace.lock().unwrap().paste(),
+            Command::PasteFromHistory => paste_menu(s, workspace),
+            Command::CopyToRegister => {
+                register(s, workspace, "Copy to register", Workspace::copy_to)
+            }
+            Command::CutToRegister => register(s, workspace, "Cut to register", Workspace::cut_to),
+            Command::PasteFromRegister => {
+                register(s, workspace, "Paste from register", |ws, name| {
+                    ws.paste_from(name);
+                })
+            }
+            Command::ShowRegisters => registers_menu(s, workspace),
+            Command::Quit => quit(s, workspace),
+            Command::Search => search(s, workspace),
+            Command::SearchNext => {
+                workspace.lock().unwrap().search_next();
+            }
+            Command::SearchPrevious => {
+                workspace.lock().unwrap().search_previous();
+            }
+            Command::Replace => replace::start(s, workspace.clone()),
+            Command::SwitchBuffer => switch_buffer(s, workspace),
+            Command::OpenFile => open_file(s, workspace),
+            Command::Save => save(s, workspace),
+            Command::SplitBelow => workspace.lock().unwrap().split(Orientation::Vertical),
+            Command::SplitBeside => workspace.lock().unwrap().split(Orientation::Horizontal),
+            Command::NextPane => workspace.lock().unwrap().focus_next(),
+            Command::ClosePane => {
+                workspace.lock().unwrap().close_pane();
+            }
+            Command::OpenTransformMenu => transform_menu(s, workspace),
+            Command::OpenInsertMenu => insert::open(s, workspace),
+            Command::InsertCharacter => unicode::open(s, workspace),
+            Command::DescribeCharacter => unicode::describe_at_cursor(s, workspace),
+            Command::Uppercase => transform(s, workspace, "Uppercase"),
+            Command::Lowercase => transform(s, workspace, "Lowercase"),
+            Command::Capitalize => transform(s, workspace, "Capitalized"),
+            Command::Reverse => transform(s, workspace, "Reverse"),
+            Command::SortLines => transform(s, workspace, "Sort lines"),
+            Command::SortLinesDescending => transform(s, workspace, "Sort lines descending"),
+            Command::SortLinesNumeric => transform(s, workspace, "Sort lines by number"),
+            Command::ReverseLines => transform(s, workspace, "Reverse lines"),
+            Command::RemoveDuplicateLines => transform(s, workspace, "Remove duplicate lines"),
+            Command::SnakeCase => transform(s, workspace, "snake_case"),
+            Command::CamelCase => transform(s, workspace, "camelCase"),
+            Command::KebabCase => transform(s, workspace, "kebab-case"),
+            Command::TitleCase => transform(s, workspace, "Title Case"),
+            Command::Pipe => pipe(s, workspace),
+            Command::OpenLineMenu => line_menu(s, workspace),
+            Command::JoinLines => line_op(workspace, LineOp::Join),
+            Command::Reflow => line_op(workspace, LineOp::Reflow),
+            Command::ToggleComment => toggle_comment(s, workspace),
+            Command::DuplicateLines => line_op(workspace, LineOp::Duplicate),
+            Command::MoveLinesUp => line_op(workspace, LineOp::MoveUp),
+            Command::MoveLinesDown => line_op(workspace, LineOp::MoveDown),
+            Command::DeleteLines => line_op(workspace, LineOp::Delete),
+            Command::OpenPalette => palette::open(s, workspace),
+            Command::Undo => {
+                workspace.lock().unwrap().editor_mut().undo();
+            }
+            Command::Redo => {
+                workspace.lock().unwrap().editor_mut().redo();
+            }
+            Command::UndoTree => undo_tree::open(s, workspace),
+            Command::RecordMacro => workspace.lock().unwrap().recorder_mut().toggle(),
+            Command::PlayMacro => macros::play(s, workspace),
+            Command::SaveMacro => macros::save(s, workspace),
+            Command::RunMacro => macros::open(s, workspace),
+            Command::RunPlugin => plugins::open(s, workspace),
+            Command::AddCursorBelow => workspace.lock().unwrap().editor_mut().add_caret_below(),
+            Command::AddCursorsAtMatches => {
+                workspace.lock().unwrap().carets_at_matches();
+            }
+            Command::NextOccurrence => workspace
+                .lock()
+                .unwrap()
+                .editor_mut()
+                .jump_to_occurrence(true),
+            Command::PreviousOccurrence => workspace
+                .lock()
+                .unwrap()
+                .editor_mut()
+                .jump_to_occurrence(false),
+            Command::SelectNextOccurrence => {
+                workspace
+                    .lock()
+                    .unwrap()
+                    .editor_mut()
+                    .select_next_occurrence();
+            }
+            Command::GotoDefinition => {
+                if !workspace.lock().unwrap().goto_definition() {
+                    s.add_layer(Dialog::info("No language server for this buffer"));
+                }
+            }
+            Command::RenameSymbol => rename::rename(s, workspace),
+            Command::GotoTag => tags::goto(s, workspace),
+            Command::PopTag => tags::pop(s, workspace),
+            Command::Hover => {
+                if !workspace.lock().unwrap().hover() {
+                    s.add_layer(Dialog::info("No language server for this buffer"));
+                }
+            }
+            Command::Complete => complete::open(s, workspace),
+            Command::ExpandSnippet => {
+                workspace.lock().unwrap().expand_snippet();
+            }
+            Command::GotoLine => goto_line(s, workspace),
+            Command::SetMark => set_mark(s, workspace),
+            Command::GotoMark => goto_mark(s, workspace),
+            Command::JumpBack => {
+                workspace.lock().unwrap().editor_mut().jump_back();
+            }
+            Command::JumpForward => {
+                workspace.lock().unwrap().editor_mut().jump_forward();
+            }
+            Command::Count => workspace.lock().unwrap().start_count(),
+            Command::Increment => increment(workspace, count.unwrap_or(1) as i64),
+            Command::Decrement => increment(workspace, -(count.unwrap_or(1) as i64)),
+            Command::Surround => surround(s, workspace),
+            Command::ChangeSurround => change_surround(s, workspace),
+            Command::DeleteSurround => delete_surround(s, workspace),
+            Command::SelectLines => each_cursor(workspace, Editor::select_lines),
+            Command::SelectBlock => each_cursor(workspace, Editor::select_block),
+            Command::ToggleLineEnding => {
+                workspace.lock().unwrap().editor_mut().toggle_line_ending()
+            }
+            Command::ReopenWithEncoding => reopen_with_encoding(s, workspace),
+            Command::ToggleReadOnly => workspace.lock().unwrap().editor_mut().toggle_read_only(),
+            Command::DiffWithDisk => compare::show(s, workspace),
+            Command::Blame => blame(s, workspace),
+            Command::SearchProject => project::search(s, workspace),
+            Command::Build => quickfix::build(s, workspace),
+            Command::ShowQuickfix => quickfix::show(s, workspace),
+            Command::NextError => quickfix::step(s, workspace, true),
+            Command::PreviousError => quickfix::step(s, workspace, false),
+            Command::ToggleFileTree => tree::toggle(s, workspace),
+            Command::FindFile => finder::open(s, workspace),
+            Command::OpenRecent => recent::open(s, workspace),
+            Command::ReadShell => read_command(s, workspace),
+            Command::ToggleSpellCheck => spell::toggle(s, workspace),
+            Command::ToggleAbbreviations => workspace
+                .lock()
+                .unwrap()
+                .editor_mut()
+                .toggle_abbreviations(),
+            Command::InsertLiteral => insert_literal(s, workspace),
+            Command::SpellSuggest => spell::suggest(s, workspace),
+            Command::DocumentStatistics => stats::show(s, workspace),
+            Command::ToggleMarkdownPreview => markdown::toggle(s, workspace),
+            Command::ExportHtml => export::export(s, workspace),
+            Command::ToggleSelectMode => workspace.lock().unwrap().grammar_mut().toggle(),
+            Command::RemapKeys => remap::open(s, workspace),
+            Command::ToggleLineNumbers => workspace.lock().unwrap().cycle_line_numbers(),
+            Command::ToggleWhitespace => workspace.lock().unwrap().toggle_whitespace(),
+            Command::ToggleWrap => workspace.lock().unwrap().toggle_wrap(),
+            Command::FormatBuffer => format::format_current(s, workspace),
+            Command::NextHunk => {
+                workspace.lock().unwrap().editor_mut().next_hunk();
+            }
+            Command::PreviousHunk => {
+                workspace.lock().unwrap().editor_mut().previous_hunk();
+            }
+            Command::ToggleHex => {
+                let result = workspace.lock().unwrap().editor_mut().toggle_hex();
+                if let Err(message) = result {
+                    s.add_layer(Dialog::info(message));
+                }
+            }
+            Command::CancelJob => {
+                workspace.lock().unwrap().jobs_mut().cancel_latest();
+            }
+            Command::RestoreSelection => {
+                workspace.lock().unwrap().editor_mut().restore_selection();
+            }
+            Command::RestoreSelectionForward => workspace
+                .lock()
+                .unwrap()
+                .editor_mut()
+                .restore_selection_forward(),
+        }
+    }
+}
+
+/// Runs `op` at every cursor of the current buffer.
+fn each_cursor(workspace: &Arc<Mutex<Workspace>>, op: fn(&mut Editor)) {
+    workspace.lock().unwrap().editor_mut().for_each_cursor(op);
+}
+
+/// Adds `delta` to the number at or after every cursor.
+fn increment(workspace: &Arc<Mutex<Workspace>>, delta: i64) {
+    workspace
+        .lock()
+        .unwrap()
+        .editor_mut()
+        .for_each_cursor(|ed| ed.increment(delta));
+}
+
+/// Asks for a delimiter and wraps every selection in it.
+fn surround(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    if workspace.lock().unwrap().editor().selected_text().is_none() {
+        return;
+    }
+    let workspace = workspace.clone();
+    prompt::read_char(s, "Surround with: ", move |_, ch| {
+        let mut ws = workspace.lock().unwrap();
+        ws.editor_mut().for_each_cursor(|ed| ed.surround(ch));
+    });
+}
+
+/// Waits for a character and types it as it is, without expanding the
+/// abbreviation before it.
+fn insert_literal(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::read_char(s, "Insert literally: ", move |_, ch| {
+        workspace.lock().unwrap().type_char(ch, true);
+    });
+}
+
+/// Asks for the delimiters around each cursor to change, then for what to
+/// change them to.
+fn change_surround(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::read_char(s, "Change surrounding: ", move |s, old| {
+        let workspace = workspace.clone();
+        prompt::read_char(
+            s,
+            &format!("Change surrounding {old} to: "),
+            move |_, new| {
+                let mut ws = workspace.lock().unwrap();
+                ws.editor_mut()
+                    .for_each_cursor(|ed| ed.change_surround(old, Some(new)));
+            },
+        );
+    });
+}
+
+/// Asks for the delimiters around each cursor to delete.
+fn delete_surround(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::read_char(s, "Delete surrounding: ", move |_, ch| {
+        let mut ws = workspace.lock().unwrap();
+        ws.editor_mut()
+            .for_each_cursor(|ed| ed.change_surround(ch, None));
+    });
+}
+
+/// Scrolls the focused pane by `halves` half pages, down if positive, the
+/// cursor moving along.
+fn scroll(workspace: &Arc<Mutex<Workspace>>, halves: isize) {
+    let mut ws = workspace.lock().unwrap();
+    let half = (ws.page_height() / 2).max(1) as isize;
+    ws.scroll_lines(half * halves);
+}
+
+/// Opens the incremental search prompt.
+fn search(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    // Searching starts over from where the cursor was when the prompt opened.
+    let origin = workspace.lock().unwrap().editor().cursor();
+    let on_edit = {
+        let workspace = workspace.clone();
+        move |_: &mut Cursive, text: &str| {
+            let mut ws = workspace.lock().unwrap();
+            ws.set_search(text);
+            ws.editor_mut().set_cursor(origin);
+            ws.search_from(origin);
+        }
+    };
+    let on_cancel = {
+        let workspace = workspace.clone();
+        move |_: &mut Cursive| {
+            let mut ws = workspace.lock().unwrap();
+            ws.set_search("");
+            ws.editor_mut().set_cursor(origin);
+        }
+    };
+    // The jump list gets the place the search started from, not each match on the way.
+    let on_submit = {
+        let workspace = workspace.clone();
+        move |_: &mut Cursive, _: &str| {
+            workspace.lock().unwrap().editor_mut().record_jump(origin);
+        }
+    };
+    prompt::open(s, "Search: ", on_edit, on_submit, on_cancel);
+}
+
+/// Prompts for a `line` or `line:col`, both counted from 1, and moves the
+/// cursor there.
+fn goto_line(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Go to line: ",
+        |_, _| {},
+        move |s, text| {
+            let (line, col) = text.trim().split_once(':').unwrap_or((text.trim(), "1"));
+            match (line.parse::<usize>(), col.parse::<usize>()) {
+                (Ok(line), Ok(col)) if line > 0 && col > 0 => {
+                    let mut ws = workspace.lock().unwrap();
+                    ws.editor_mut().goto_line(line - 1, col - 1);
+                }
+                _ => s.add_layer(Dialog::info(format!("Not a line number: {text}"))),
+            }
+        },
+        |_| {},
+    );
+}
+
+/// Prompts for a name, or a number, to mark the cursor position with.
+fn set_mark(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Mark name: ",
+        |_, _| {},
+        move |_, name| {
+            let name = name.trim();
+            if !name.is_empty() {
+                workspace.lock().unwrap().editor_mut().set_mark(name);
+            }
+        },
+        |_| {},
+    );
+}
+
+/// Lists the marks of the current buffer, with the line each is on, so one
+/// can be jumped to.
+fn goto_mark(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let mut sv: SelectView<String> = SelectView::new();
+    for (name, offset) in ed.marks().named() {
+        let line = ed.buffer().line_of(offset);
+        let text = ed.buffer().line(line);
+        sv.add_item(
+            format!("{name}  {}: {}", line + 1, text.trim()),
+            name.to_string(),
+        );
+    }
+    drop(ws);
+    if sv.is_empty() {
+        s.add_layer(Dialog::info("No marks in this buffer"));
+        return;
+    }
+
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, name: &String| {
+        value.lock().unwrap().editor_mut().goto_mark(name);
+        s.pop_layer();
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Lists the encodings the focused buffer's file can be read again as.
+/// Unsaved changes would be lost, so they have to be dealt with first.
+fn reopen_with_encoding(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    if ws.editor().path().is_none() {
+        s.add_layer(Dialog::info("This buffer has no file to reopen"));
+        return;
+    }
+    if ws.editor().is_modified() {
+        s.add_layer(Dialog::info("Save or undo the changes before reopening"));
+        return;
+    }
+    let mut sv: SelectView<Charset> = SelectView::new();
+    for &charset in Charset::ALL {
+        sv.add_item(charset.name(), charset);
+    }
+    if let Some(index) = Charset::ALL
+        .iter()
+        .position(|&c| c == ws.editor().charset())
+    {
+        sv.set_selection(index);
+    }
+    drop(ws);
+
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, &charset| {
+        s.pop_layer();
+        if let Err(err) = value.lock().unwrap().reopen(charset) {
+            s.add_layer(Dialog::info(format!("Could not reopen: {err}")));
+        }
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Lists the open buffers so one can be picked for the focused pane.
+fn switch_buffer(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let mut sv: SelectView<usize> = SelectView::new();
+    for (index, ed) in ws.editors().iter().enumerate() {
+        let flag = if ed.is_modified() { " [+]" } else { "" };
+        sv.add_item(format!("{}{}", ed.name(), flag), index);
+    }
+    sv.set_selection(ws.current());
+    drop(ws);
+
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, index| {
+        value.lock().unwrap().switch_to(*index);
+        s.pop_layer();
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Quits, first asking whether to save the buffers with unsaved changes, if
+/// there are any.
+fn quit(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let names: Vec<String> = workspace
+        .lock()
+        .unwrap()
+        .editors()
+        .iter()
+        .filter(|ed| ed.has_unsaved_changes())
+        .map(Editor::name)
+        .collect();
+    if names.is_empty() {
+        s.quit();
+        return;
+    }
+    let message = format!("Unsaved changes in {}", names.join(", "));
+    let value = workspace.clone();
+    s.add_layer(
+        Dialog::text(message)
+            .title("Quit")
+            .button("Save", move |s| {
+                s.pop_layer();
+                match save_all(&value) {
+                    Ok(()) => s.quit(),
+                    Err(err) => s.add_layer(Dialog::info(err)),
+                }
+            })
+            .button("Discard", |s| s.quit())
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// Saves every buffer with unsaved changes. Buffers without a file can't
+/// be, and stop the rest from being saved.
+fn save_all(workspace: &Arc<Mutex<Workspace>>) -> Result<(), String> {
+    let mut ws = workspace.lock().unwrap();
+    if let Some(ed) = ws
+        .editors()
+        .iter()
+        .find(|ed| ed.has_unsaved_changes() && ed.path().is_none())
+    {
+        return Err(format!("{} has no file name; save it first", ed.name()));
+    }
+    let mut saved = Vec::new();
+    let mut result = Ok(());
+    for ed in ws
+        .editors_mut()
+        .iter_mut()
+        .filter(|ed| ed.has_unsaved_changes())
+    {
+        if let Err(err) = ed.save() {
+            result = Err(format!("Could not save {}: {err}", ed.name()));
+            break;
+        }
+        saved.extend(ed.path().map(Path::to_path_buf));
+    }
+    for path in saved {
+        ws.run_hook(Hook::Save, &path);
+    }
+    result
+}
+
+/// Prompts for a file to open in the focused pane.
+fn open_file(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Open: ",
+        |_, _| {},
+        move |s, path| {
+            let existing = workspace.lock().unwrap().position_of(Path::new(path));
+            if let Some(index) = existing {
+                workspace.lock().unwrap().switch_to(index);
+                return;
+            }
+            // Reading a large file takes a while; the screen stays usable.
+            let path = path.to_string();
+            let label = format!("Opening {path}");
+            let reading = path.clone();
+            let opened = workspace.clone();
+            jobs::spawn(
+                s,
+                &workspace,
+                label,
+                move |progress| Editor::open_with_progress(Path::new(&reading), None, progress),
+                move |s, result| match result {
+                    Ok(editor) => {
+                        opened.lock().unwrap().add(editor);
+                        swap::offer(s, &opened);
+                    }
+                    Err(err) => s.add_layer(Dialog::info(format!("Could not open {path}: {err}"))),
+                },
+            );
+        },
+        |_| {},
+    );
+}
+
+/// Saves the current buffer, asking for a file name first if it has none,
+/// and formatting it first if the settings say to.
+fn save(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    if ws.editor().path().is_none() {
+        drop(ws);
+        let workspace = workspace.clone();
+        prompt::open(
+            s,
+            "Save as: ",
+            |_, _| {},
+            move |s, path| {
+                workspace
+                    .lock()
+                    .unwrap()
+                    .editor_mut()
+                    .set_path(PathBuf::from(path));
+                save(s, &workspace);
+            },
+            |_| {},
+        );
+        return;
+    }
+    let index = ws.current();
+    let formats = ws.settings().format_on_save && format::formatter(&ws, ws.editor()).is_some();
+    drop(ws);
+    if formats {
+        let saved = workspace.clone();
+        format::format(s, workspace, index, move |s| write(s, &saved, index));
+    } else {
+        write(s, workspace, index);
+    }
+}
+
+/// Writes buffer `index` to its file in the background, then runs the
+/// save hooks.
+fn write(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, index: usize) {
+    let ws = workspace.lock().unwrap();
+    let Some(ed) = ws.editors().get(index) else {
+        return;
+    };
+    let name = ed.name();
+    let snapshot = match ed.snapshot() {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            drop(ws);
+            s.add_layer(Dialog::info(format!("Could not save {name}: {err}")));
+            return;
+        }
+    };
+    drop(ws);
+    // Written in the background, so a large file doesn't hold up editing.
+    let saved = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        format!("Saving {name}"),
+        move |progress| snapshot.write(progress).map(|()| snapshot),
+        move |s, result| match result {
+            Ok(snapshot) => {
+                let mut ws = saved.lock().unwrap();
+                if let Some(ed) = ws.editors_mut().get_mut(index) {
+                    ed.saved(&snapshot);
+                }
+                ws.run_hook(Hook::Save, &snapshot.path);
+            }
+            Err(err) => s.add_layer(Dialog::info(format!("Could not save {name}: {err}"))),
+        },
+    );
+}
+
+/// An entry of the transform menu.
+#[derive(Clone)]
+enum Entry {
+    Transform(Arc<dyn Transform>),
+    /// A submenu of the transforms listed in it.
+    Submenu(String),
+    /// Piping through a shell command asked for next.
+    Pipe,
+}
+
+/// Opens the transform menu, listing every registered transform; the choice
+/// applies to the selection, or the whole buffer.
+fn transform_menu(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    open_transforms(s, workspace, None);
+}
+
+/// Lists the transforms in `menu`, or those in no submenu along with an
+/// entry for each submenu.
+fn open_transforms(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, menu: Option<&str>) {
+    let mut sv: SelectView<Entry> = SelectView::new();
+    let mut submenus = Vec::new();
+    for transform in workspace.lock().unwrap().transforms().iter() {
+        match transform.menu() {
+            found if found == menu => {
+                sv.add_item(transform.name(), Entry::Transform(transform.clone()))
+            }
+            Some(submenu) if menu.is_none() && !submenus.contains(&submenu.to_string()) => {
+                submenus.push(submenu.to_string())
+            }
+            _ => {}
+        }
+    }
+    if menu.is_none() {
+        for submenu in submenus {
+            sv.add_item(format!("{submenu}..."), Entry::Submenu(submenu));
+        }
+        sv.add_item("Pipe through command...", Entry::Pipe);
+    }
+
+    // On submit, transform the selection (or everything if nothing is selected).
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, entry: &Entry| {
+        s.pop_layer();
+        match entry {
+            Entry::Transform(transform) => apply_transform(s, &value, transform.as_ref()),
+            Entry::Submenu(submenu) => open_transforms(s, &value, Some(submenu)),
+            Entry::Pipe => pipe(s, &value),
+        }
+    });
+    s.add_layer(sv);
+}
+
+/// Applies the transform named `name` to the selection, or the whole buffer.
+fn transform(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, name: &str) {
+    let transform = workspace.lock().unwrap().transforms().find(name);
+    if let Some(transform) = transform {
+        apply_transform(s, workspace, transform.as_ref());
+    }
+}
+
+/// Applies `transform` to the selection, or the whole buffer, showing why
+/// if it fails.
+fn apply_transform(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, transform: &dyn Transform) {
+    let result = workspace
+        .lock()
+        .unwrap()
+        .editor_mut()
+        .try_transform_selections(|text| transform.apply(text));
+    if let Err(err) = result {
+        s.add_layer(Dialog::info(err).title(transform.name()));
+    }
+}
+
+/// Opens the line operations menu.
+fn line_menu(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut sv: SelectView<LineOp> = SelectView::new();
+    sv.add_item("Join lines", LineOp::Join);
+    sv.add_item("Reflow", LineOp::Reflow);
+    sv.add_item("Duplicate", LineOp::Duplicate);
+    sv.add_item("Move up", LineOp::MoveUp);
+    sv.add_item("Move down", LineOp::MoveDown);
+    sv.add_item("Delete", LineOp::Delete);
+
+    // On submit, work on the selected lines (or the cursor's line).
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, item| {
+        line_op(&value, *item);
+        s.pop_layer();
+    });
+    s.add_layer(sv);
+}
+
+/// Lists the text copied or cut before, newest first, and pastes the one
+/// picked.
+fn paste_menu(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ring = workspace.lock().unwrap().clipboard_ring();
+    if ring.is_empty() {
+        s.add_layer(Dialog::info("Nothing has been copied yet"));
+        return;
+    }
+    let mut sv: SelectView<String> = SelectView::new();
+    for text in ring {
+        sv.add_item(preview(&text), text);
+    }
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, text: &String| {
+        value.lock().unwrap().paste_again(text);
+        s.pop_layer();
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// How a piece of text is shown in a list: its first line, cut short, and
+/// how many lines follow it.
+fn preview(text: &str) -> String {
+    let mut lines = text.lines();
+    let first: String = lines.next().unwrap_or_default().chars().take(48).collect();
+    match lines.count() {
+        0 => first,
+        more => format!("{first}  (+{more} lines)"),
+    }
+}
+
+/// Asks for the name of a register, shown `title`, and runs `op` with it.
+fn register(
+    s: &mut Cursive,
+    workspace: &Arc<Mutex<Workspace>>,
+    title: &str,
+    op: fn(&mut Workspace, char),
+) {
+    let workspace = workspace.clone();
+    let capture = remap::KeyCapture::new(
+        "Press a letter, a digit for the clipboard, or Esc.",
+        move |s, event| {
+            s.pop_layer();
+            match event {
+                Event::Char(name) if registers::is_register(name) => {
+                    op(&mut workspace.lock().unwrap(), name)
+                }
+                _ => s.add_layer(Dialog::info("Registers are named by letters and digits")),
+            }
+        },
+    );
+    s.add_layer(Dialog::around(capture).title(title));
+}
+
+/// Lists the registers holding something, the letters' then the
+/// clipboard's, and pastes from the one picked.
+fn registers_menu(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let mut sv: SelectView<char> = SelectView::new();
+    for (name, text) in ws.registers().named() {
+        sv.add_item(format!("\"{name}  {}", preview(text)), name);
+    }
+    for (back, text) in ws.clipboard_ring().iter().take(10).enumerate() {
+        let name = char::from_digit(back as u32, 10).unwrap_or('0');
+        sv.add_item(format!("\"{name}  {}", preview(text)), name);
+    }
+    drop(ws);
+    if sv.is_empty() {
+        s.add_layer(Dialog::info("Every register is empty"));
+        return;
+    }
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, &name: &char| {
+        value.lock().unwrap().paste_from(name);
+        s.pop_layer();
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Applies a line operation to the selected lines, or the cursor's line.
+fn line_op(workspace: &Arc<Mutex<Workspace>>, op: LineOp) {
+    let mut ws = workspace.lock().unwrap();
+    let width = ws
+        .filetype_options()
+        .wrap_column
+        .unwrap_or(ws.settings().reflow_width);
+    let ed = ws.editor_mut();
+    match op {
+        LineOp::Join => ed.join_lines(),
+        LineOp::Reflow => ed.reflow(width),
+        LineOp::Duplicate => ed.duplicate_lines(),
+        LineOp::MoveUp => ed.move_lines_up(),
+        LineOp::MoveDown => ed.move_lines_down(),
+        LineOp::Delete => ed.delete_lines(),
+    }
+}
+
+/// Comments out the selected lines, or the cursor's line, with the comment
+/// leader of the buffer's filetype, or uncomments them.
+fn toggle_comment(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let Some(leader) = ws.filetype_options().comment else {
+        let filetype = ws.editor().highlighter().filetype();
+        drop(ws);
+        s.add_layer(Dialog::info(format!(
+            "{} files have no comment leader",
+            filetype.name()
+        )));
+        return;
+    };
+    ws.editor_mut().toggle_comment(&leader);
+}
+
+/// Prompts for a shell command and replaces the selection (or the whole
+/// buffer) with what the command prints when given it on its input.
+/// Anything the command writes to stderr is shown; if it fails, the text
+/// is left alone.
+fn pipe(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Pipe: ",
+        |_, _| {},
+        move |s, command| pipe_through(s, &workspace, command),
+        |_| {},
+    );
+}
+
+/// Replaces the selection (or the whole buffer) with what `command` prints
+/// when given it on its input. The command runs in the background; if the
+/// buffer or its selection changes meanwhile, its output is dropped.
+fn pipe_through(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, command: &str) {
+    let ws = workspace.lock().unwrap();
+    let index = ws.current();
+    let ed = ws.editor();
+    let input = ed
+        .selected_text()
+        .unwrap_or_else(|| ed.buffer().to_string());
+    let state = (ed.revision(), ed.carets());
+    drop(ws);
+    let command = command.to_string();
+    let label = format!("Running {command}");
+    let piped = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        label,
+        move |progress| {
+            let output = shell::filter(&command, &input, &|| progress.is_cancelled());
+            (command, output)
+        },
+        move |s, (command, output)| {
+            let mut ws = piped.lock().unwrap();
+            let Some(ed) = ws.editors_mut().get_mut(index) else {
+                return;
+            };
+            if (ed.revision(), ed.carets()) != state {
+                drop(ws);
+                s.add_layer(Dialog::info(format!(
+                    "The buffer changed while {command} ran, so its output was dropped"
+                )));
+                return;
+            }
+            show_output(s, ed, &command, output);
+        },
+    );
+}
+
+/// Prompts for a shell command and inserts what it prints at the cursor,
+/// in place of the selection if there is one. Like `pipe_through`, it runs
+/// in the background and its output is dropped if the buffer changes
+/// meanwhile; a command printing more than `MAX_READ` bytes is stopped.
+fn read_command(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Read !",
+        |_, _| {},
+        move |s, command| {
+            let ws = workspace.lock().unwrap();
+            let index = ws.current();
+            let ed = ws.editor();
+            let state = (ed.revision(), ed.carets());
+            drop(ws);
+            let command = command.to_string();
+            let label = format!("Running {command}");
+            let read = workspace.clone();
+            jobs::spawn(
+                s,
+                &workspace,
+                label,
+                move |progress| {
+                    let output = shell::read(&command, MAX_READ, &|| progress.is_cancelled());
+                    (command, output)
+                },
+                move |s, (command, output)| {
+                    let mut ws = read.lock().unwrap();
+                    let Some(ed) = ws.editors_mut().get_mut(index) else {
+                        return;
+                    };
+                    if (ed.revision(), ed.carets()) != state {
+                        drop(ws);
+                        s.add_layer(Dialog::info(format!(
+                            "The buffer changed while {command} ran, so its output was dropped"
+                        )));
+                        return;
+                    }
+                    show_read(s, ed, &command, output);
+                },
+            );
+        },
+        |_| {},
+    );
+}
+
+/// Inserts what the read `command` printed at the cursor, and shows
+/// anything it complained about. A command that fails inserts nothing.
+fn show_read(s: &mut Cursive, ed: &mut Editor, command: &str, output: io::Result<Output>) {
+    match output {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if output.status.success() && !stdout.is_empty() {
+                ed.insert_str(&line_ending::normalize(&stdout));
+            }
+            if !stderr.trim().is_empty() {
+                s.add_layer(Dialog::info(stderr.trim_end()).title(command));
+            } else if !output.status.success() {
+                s.add_layer(Dialog::info(format!("{command}: {}", output.status)));
+            }
+        }
+        Err(err) => s.add_layer(Dialog::info(format!("Could not run {command}: {err}"))),
+    }
+}
+
+/// Looks up the last commit to change the cursor's line in the background,
+/// then shows it at the end of the line.
+fn blame(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let index = ws.current();
+    let ed = ws.editor();
+    let Some(path) = ed.path().map(Path::to_path_buf) else {
+        drop(ws);
+        s.add_layer(Dialog::info("The buffer has no file to blame"));
+        return;
+    };
+    let text = ed.buffer().to_string();
+    let line = ed.buffer().line_of(ed.cursor());
+    let revision = ed.revision();
+    let label = format!("Blaming {}", ed.name());
+    drop(ws);
+    let blamed = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        label,
+        move |_| git::blame(&path, &text, line),
+        move |s, result| match result {
+            Ok(blame) => {
+                let mut ws = blamed.lock().unwrap();
+                if let Some(ed) = ws.editors_mut().get_mut(index) {
+                    ed.set_blame(line, revision, blame);
+                }
+            }
+            Err(message) => s.add_layer(Dialog::info(message)),
+        },
+    );
+}
+
+/// Puts what the piped `command` printed in place of the selection, or the
+/// whole buffer, and shows anything it complained about.
+fn show_output(s: &mut Cursive, ed: &mut Editor, command: &str, output: io::Result<Output>) {
+    match output {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            if output.status.success() {
+                ed.apply_transformation(|_| stdout.clone());
+            }
+            if !stderr.trim().is_empty() {
+                s.add_layer(Dialog::info(stderr.trim_end()).title(command));
+            } else if !output.status.success() {
+                s.add_layer(Dialog::info(format!("{command}: {}", output.status)));
+            }
+        }
+        Err(err) => s.add_layer(Dialog::info(format!("Could not run {command}: {err}"))),
+    }
+}
@@ -0,0 +1,220 @@
+use crate::diff::{self, Hunk, Line};
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::event::{Event, Key};
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{Dialog, LinearLayout, OnEventView, ScrollView, TextView};
+use cursive::Cursive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Name of the scrolling text showing the diff.
+const DIFF: &str = "compare_diff";
+
+/// Kept lines shown around each hunk.
+const CONTEXT: usize = 3;
+
+/// What the diff view is of, and which of its hunks the keys act on.
+struct Session {
+    path: PathBuf,
+    current: usize,
+}
+
+/// What a key pressed in the diff view does.
+#[derive(Clone, Copy)]
+enum Action {
+    Next,
+    Previous,
+    Revert,
+}
+
+/// Shows how the focused buffer differs from its file on disk, one hunk
+/// after another. `n` and `p` move between the hunks, and `r` reverts the
+/// current one, putting back in the buffer what the file has there.
+pub fn show(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let Some(path) = ed.path().map(Path::to_path_buf) else {
+        drop(ws);
+        s.add_layer(Dialog::info("The buffer has no file to compare with"));
+        return;
+    };
+    let title = format!("{}: - buffer, + disk", ed.name());
+    drop(ws);
+    let session = Arc::new(Mutex::new(Session { path, current: 0 }));
+
+    let content = LinearLayout::vertical()
+        .child(TextView::new("").scrollable().with_name(DIFF).full_screen())
+        .child(TextView::new("[n]ext  [p]revious  [r]evert  [q]uit"));
+    let mut view = OnEventView::new(Dialog::around(content).title(title));
+    let actions = [
+        (Event::Char('n'), Action::Next),
+        (Event::Char('p'), Action::Previous),
+        (Event::Char('r'), Action::Revert),
+    ];
+    for (event, action) in actions {
+        let workspace = workspace.clone();
+        let session = session.clone();
+        view.set_on_event(event, move |s| act(s, &workspace, &session, action));
+    }
+    for event in [Event::Char('q'), Event::Key(Key::Esc)] {
+        view.set_on_event(event, |s| {
+            s.pop_layer();
+        });
+    }
+    s.add_layer(view);
+    refresh(s, workspace, &session);
+}
+
+/// Carries out `action` on the current hunk, then shows the diff again.
+fn act(
+    s: &mut Cursive,
+    workspace: &Arc<Mutex<Workspace>>,
+    session: &Arc<Mutex<Session>>,
+    action: Action,
+) {
+    let mut sess = session.lock().unwrap();
+    match action {
+        Action::Next => sess.current += 1,
+        Action::Previous => sess.current = sess.current.saturating_sub(1),
+        Action::Revert => {
+            let mut ws = workspace.lock().unwrap();
+            let Some(ed) = ws
+                .editors_mut()
+                .iter_mut()
+                .find(|ed| ed.path() == Some(&sess.path))
+            else {
+                return;
+            };
+            if ed.is_read_only() || ed.hex().is_some() {
+                drop(ws);
+                drop(sess);
+                s.add_layer(Dialog::info("The buffer can't be changed just now"));
+                return;
+            }
+            if let Err(message) = revert(ed, sess.current) {
+                drop(ws);
+                drop(sess);
+                s.add_layer(Dialog::info(message));
+                return;
+            }
+        }
+    }
+    drop(sess);
+    refresh(s, workspace, session);
+}
+
+/// Replaces the lines of hunk `index` in the buffer with the file's, as
+/// one step that can be undone.
+fn revert(ed: &mut Editor, index: usize) -> Result<(), String> {
+    let disk = ed.read_from_disk().map_err(|err| err.to_string())?;
+    let text = ed.buffer().to_string();
+    let diff = diff::lines(&text, &disk);
+    let Some(hunk) = diff::hunks(&diff).into_iter().nth(index) else {
+        return Ok(());
+    };
+    let range = line_offset(&text, hunk.old.start)..line_offset(&text, hunk.old.end);
+    let mut with =
+        disk[line_offset(&disk, hunk.new.start)..line_offset(&disk, hunk.new.end)].to_string();
+    // Lines added after a last line with no line break need one first.
+    if range.start == text.len() && !text.is_empty() && !text.ends_with('\n') {
+        with.insert(0, '\n');
+        if with.ends_with('\n') {
+            with.pop();
+        }
+    }
+    ed.replace_ranges(vec![(range, with)]);
+    Ok(())
+}
+
+/// Where line `line` of `text` starts, or the end of `text` for the
+/// lines past its last.
+fn line_offset(text: &str, line: usize) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    text.match_indices('\n')
+        .nth(line - 1)
+        .map_or(text.len(), |(i, _)| i + 1)
+}
+
+/// Shows the diff as it is now, scrolled to the current hunk.
+fn refresh(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, session: &Arc<Mutex<Session>>) {
+    let mut sess = session.lock().unwrap();
+    let ws = workspace.lock().unwrap();
+    let Some(ed) = ws.editors().iter().find(|ed| ed.path() == Some(&sess.path)) else {
+        return;
+    };
+    let (text, row) = match ed.read_from_disk() {
+        Ok(disk) => {
+            let buffer = ed.buffer().to_string();
+            let diff = diff::lines(&buffer, &disk);
+            let hunks = diff::hunks(&diff);
+            sess.current = sess.current.min(hunks.len().saturating_sub(1));
+            if hunks.is_empty() {
+                let text = "The file on disk holds the same text as the buffer.";
+                (StyledString::plain(text), 0)
+            } else {
+                render(&diff, &hunks, sess.current)
+            }
+        }
+        Err(err) => (
+            StyledString::plain(format!("Could not read {}: {err}", sess.path.display())),
+            0,
+        ),
+    };
+    drop(ws);
+    drop(sess);
+    s.call_on_name(DIFF, |view: &mut ScrollView<TextView>| {
+        view.get_inner_mut().set_content(text);
+        view.set_offset((0, row));
+    });
+}
+
+/// The hunks of `diff` with the kept lines around them, the one at
+/// `current` marked, and the row its header is on.
+fn render(diff: &[Line], hunks: &[Hunk], current: usize) -> (StyledString, usize) {
+    let mut text = StyledString::new();
+    let mut rows = 0;
+    let mut row = 0;
+    let mut shown = 0;
+    for (i, hunk) in hunks.iter().enumerate() {
+        let start = hunk.lines.start.saturating_sub(CONTEXT).max(shown);
+        let end = (hunk.lines.end + CONTEXT).min(diff.len());
+        let end = hunks
+            .get(i + 1)
+            .map_or(end, |next| end.min(next.lines.start));
+        let header = format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old.start + 1,
+            hunk.old.len(),
+            hunk.new.start + 1,
+            hunk.new.len()
+        );
+        let style = Style::from(Color::Dark(BaseColor::Cyan));
+        if i == current {
+            row = rows;
+            text.append_styled(header, style.combine(Effect::Reverse));
+        } else {
+            text.append_styled(header, style);
+        }
+        rows += 1;
+        for line in &diff[start..end] {
+            let (prefix, line, color) = match line {
+                Line::Same(line) => (' ', line, None),
+                Line::Removed(line) => ('-', line, Some(BaseColor::Red)),
+                Line::Added(line) => ('+', line, Some(BaseColor::Green)),
+            };
+            let line = format!("{prefix} {line}\n");
+            match color {
+                Some(color) => text.append_styled(line, Color::Light(color)),
+                None => text.append_plain(line),
+            }
+            rows += 1;
+        }
+        shown = end;
+    }
+    (text, row)
+}
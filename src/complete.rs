@@ -0,0 +1,87 @@
+use crate::view::EditorView;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::traits::Resizable;
+use cursive::view::{Position, Scrollable};
+use cursive::views::stack_view::LayerAt;
+use cursive::views::{OnEventView, SelectView};
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+
+/// Most words offered at once.
+const MAX_CANDIDATES: usize = 50;
+
+/// Offers the words of the buffer that complete the one before the cursor,
+/// closest first, in a list just below the cursor. Enter inserts the rest
+/// of the chosen word; Esc closes the list.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let (prefix, words) = {
+        let ws = workspace.lock().unwrap();
+        let ed = ws.editor();
+        let prefix = ed.word_before_cursor();
+        let words = candidates(&ed.buffer().to_string(), &prefix, ed.cursor());
+        (prefix, words)
+    };
+    if prefix.is_empty() || words.is_empty() {
+        return;
+    }
+
+    let mut list: SelectView<String> = SelectView::new();
+    for word in words {
+        list.add_item(word.clone(), word);
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, word: &String| {
+        s.pop_layer();
+        let mut ws = workspace.lock().unwrap();
+        let ed = ws.editor_mut();
+        ed.clear_selection();
+        ed.insert_str(&word[prefix.len()..]);
+    });
+    let list = OnEventView::new(list.scrollable().max_height(10)).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    });
+
+    let cursor = s
+        .call_on_name("main", |view: &mut EditorView| view.cursor_position())
+        .flatten()
+        .unwrap_or_default();
+    s.screen_mut()
+        .add_layer(LayerAt(Position::absolute(cursor + (0, 1)), list));
+}
+
+fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// The distinct words of `text` that are longer than `prefix` and start with
+/// it, ordered by how close they are to `cursor`.
+fn candidates(text: &str, prefix: &str, cursor: usize) -> Vec<String> {
+    let mut found: Vec<(usize, &str)> = Vec::new();
+    let mut start = None;
+    for (pos, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, is_word(ch)) {
+            (None, true) => start = Some(pos),
+            (Some(from), false) => {
+                start = None;
+                let word = &text[from..pos];
+                // The word being typed doesn't complete itself.
+                if pos == cursor || word.len() <= prefix.len() || !word.starts_with(prefix) {
+                    continue;
+                }
+                let distance = from.abs_diff(cursor);
+                match found.iter_mut().find(|(_, seen)| *seen == word) {
+                    Some(entry) => entry.0 = entry.0.min(distance),
+                    None => found.push((distance, word)),
+                }
+            }
+            _ => {}
+        }
+    }
+    found.sort_by_key(|&(distance, _)| distance);
+    found
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(_, word)| word.to_string())
+        .collect()
+}
@@ -0,0 +1,522 @@
+use crate::buffer::Buffer;
+use crate::motion;
+use crate::selection::Selection;
+use crate::structure::{self, Structure};
+use crate::width::display_width;
+use crate::wrap;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The text being edited, with the cursor and selection in it: all that
+/// moving around and selecting needs, apart from any way of showing it.
+///
+/// The fields are open to the frontend, which edits the text and keeps
+/// its undo history, marks and other cursors alongside: operations on the
+/// text only work out the [`Change`](crate::edits::Change) they make, for
+/// the frontend to make it. While a selection is active its head always
+/// sits on the cursor, so moving the cursor adjusts the selection.
+#[derive(Clone, Default)]
+pub struct EditorCore {
+    pub buffer: Buffer,
+    pub cursor: usize,
+    pub selection: Option<Selection>,
+    /// The column up and down keep to, while the cursor is where the last
+    /// of them left it; edits clear it.
+    pub goal: Option<Goal>,
+}
+
+/// The column a run of vertical moves aims for, so that passing through a
+/// short line doesn't lose it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Goal {
+    /// Where the move that last kept to it left the cursor.
+    pub offset: usize,
+    /// In characters for moves by line, in screen columns along the row
+    /// for moves by row.
+    pub column: usize,
+    /// The width rows were wrapped to, for moves by row.
+    pub width: Option<usize>,
+}
+
+impl EditorCore {
+    /// Starts at the beginning of `buffer`, with nothing selected.
+    pub fn new(buffer: Buffer) -> Self {
+        Self {
+            buffer,
+            cursor: 0,
+            selection: None,
+            goal: None,
+        }
+    }
+
+    /// The text being edited.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Byte offset of the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves the cursor to `offset`, dragging the selection head along with
+    /// it, and forgets the column vertical moves were keeping to.
+    pub fn set_cursor(&mut self, offset: usize) {
+        self.cursor = offset;
+        self.goal = None;
+        if let Some(sel) = self.selection.as_mut() {
+            sel.head = offset;
+        }
+    }
+
+    /// Selects from `anchor` to `head`, with the cursor on `head`, as the
+    /// mouse drags; unlike `update_selection` each step isn't remembered
+    /// as a selection of its own.
+    pub fn drag_selection(&mut self, anchor: usize, head: usize) {
+        self.selection = (anchor != head).then(|| Selection::new(anchor, head));
+        self.cursor = head;
+    }
+
+    /// The selected text, if anything is selected.
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection.map(|sel| self.buffer.slice(sel.range()))
+    }
+
+    /// The lines the selection covers, if it covers more than one. A
+    /// selection ending at the start of a line leaves that line out.
+    pub fn selected_lines(&self) -> Option<Range<usize>> {
+        let sel = self.selection.filter(|sel| !sel.is_empty())?;
+        let lines = motion::lines_of(&self.buffer, sel.range());
+        (lines.len() > 1).then_some(lines)
+    }
+
+    /// The word that ends at the cursor, or an empty string.
+    pub fn word_before_cursor(&self) -> String {
+        let start = self
+            .buffer
+            .chars_before(self.cursor)
+            .take_while(|&(_, ch)| motion::is_word(ch))
+            .last()
+            .map_or(self.cursor, |(pos, _)| pos);
+        self.buffer.slice(start..self.cursor)
+    }
+
+    /// The word the cursor is in or just after, while nothing is selected.
+    pub fn word_at_cursor(&self) -> Option<Range<usize>> {
+        if self.selection.is_some_and(|sel| !sel.is_empty()) {
+            return None;
+        }
+        motion::word_around(&self.buffer, self.cursor..self.cursor).filter(|word| !word.is_empty())
+    }
+
+    /// Column of the cursor on its line, counted in characters as they
+    /// read, that is grapheme clusters.
+    pub fn cursor_column(&self) -> usize {
+        let line_start = self.buffer.line_start(self.buffer.line_of(self.cursor));
+        self.buffer
+            .slice(line_start..self.cursor)
+            .graphemes(true)
+            .count()
+    }
+
+    /// Byte offset of character column `col` on `line`, clamped to the line's length.
+    pub fn offset_at_column(&self, line: usize, col: usize) -> usize {
+        let line_start = self.buffer.line_start(line);
+        let line_end = self.buffer.line_end(line);
+        self.buffer
+            .slice(line_start..line_end)
+            .grapheme_indices(true)
+            .nth(col)
+            .map_or(line_end, |(i, _)| line_start + i)
+    }
+
+    /// What expanding the selection (or the cursor position) grows it to:
+    /// the next structure around it, whichever is smallest of the word, the
+    /// expression, statement and function as `structure` tells them apart,
+    /// the line, sentence or paragraph, the inside of the surrounding
+    /// brackets, the brackets themselves, and so on out to the whole
+    /// buffer. There's nothing bigger once the whole buffer is selected.
+    pub fn expansion(&self, structure: Structure) -> Option<Range<usize>> {
+        let range = self
+            .selection
+            .map_or(self.cursor..self.cursor, |sel| sel.range());
+        let buffer = &self.buffer;
+        let brackets = motion::brackets_around(buffer, range.clone());
+        let candidates = [
+            motion::word_around(buffer, range.clone()),
+            structure::expression_around(buffer, range.clone(), structure),
+            structure::statement_around(buffer, range.clone(), structure),
+            structure::function_around(buffer, range.clone(), structure),
+            Some(motion::lines_around(buffer, range.clone())),
+            Some(motion::whole_lines(
+                buffer,
+                motion::lines_of(buffer, range.clone()),
+            )),
+            motion::sentence_around(buffer, range.clone()),
+            motion::paragraph_around(buffer, range.clone()),
+            brackets.clone().map(|(inside, _)| inside),
+            brackets.map(|(_, pair)| pair),
+            Some(0..buffer.len()),
+        ];
+        // The smallest candidate that still grows the selection is the next level up.
+        candidates
+            .into_iter()
+            .flatten()
+            .filter(|c| c.start <= range.start && c.end >= range.end && c.len() > range.len())
+            .min_by_key(|c| c.len())
+    }
+
+    /// Moves the cursor one character to the right.
+    pub fn move_right(&mut self) {
+        if let Some(next) = self.buffer.next_grapheme(self.cursor) {
+            self.set_cursor(next);
+        }
+    }
+
+    /// Moves the cursor one character to the left.
+    pub fn move_left(&mut self) {
+        if let Some(prev) = self.buffer.previous_grapheme(self.cursor) {
+            self.set_cursor(prev);
+        }
+    }
+
+    /// The column vertical moves over rows `width` wide, or over lines
+    /// without one, keep to: the one the last of them did, if the cursor
+    /// is still where it left it, or else `column`, the cursor's own.
+    fn goal_column(&self, width: Option<usize>, column: impl FnOnce() -> usize) -> usize {
+        match self.goal {
+            Some(goal) if goal.offset == self.cursor && goal.width == width => goal.column,
+            _ => column(),
+        }
+    }
+
+    /// Moves the cursor to `offset` by a vertical move keeping to `column`.
+    fn set_cursor_keeping(&mut self, offset: usize, column: usize, width: Option<usize>) {
+        self.set_cursor(offset);
+        self.goal = Some(Goal {
+            offset,
+            column,
+            width,
+        });
+    }
+
+    /// Moves the cursor to the same column on the next line, clamped to its
+    /// length; a run of moves keeps to the column it started from.
+    pub fn move_down(&mut self) {
+        let line = self.buffer.line_of(self.cursor);
+        if line + 1 < self.buffer.line_count() {
+            let col = self.goal_column(None, || self.cursor_column());
+            self.set_cursor_keeping(self.offset_at_column(line + 1, col), col, None);
+        }
+    }
+
+    /// Moves the cursor to the same column on the previous line, clamped
+    /// to its length; a run of moves keeps to the column it started from.
+    pub fn move_up(&mut self) {
+        let line = self.buffer.line_of(self.cursor);
+        if line > 0 {
+            let col = self.goal_column(None, || self.cursor_column());
+            self.set_cursor_keeping(self.offset_at_column(line - 1, col), col, None);
+        }
+    }
+
+    /// Moves the cursor `count` lines down (or up, if negative) at the same
+    /// column, stopping at the first or last line.
+    pub fn move_lines(&mut self, count: isize) {
+        let line = self.buffer.line_of(self.cursor);
+        let last = self.buffer.line_count().saturating_sub(1);
+        let target = line.saturating_add_signed(count).min(last);
+        let col = self.goal_column(None, || self.cursor_column());
+        self.set_cursor_keeping(self.offset_at_column(target, col), col, None);
+    }
+
+    /// Moves the cursor `count` rows down (or up, if negative) as the lines
+    /// show wrapped to `width` columns, keeping its column on screen.
+    pub fn move_rows(&mut self, count: isize, width: usize) {
+        let mut line = self.buffer.line_of(self.cursor);
+        let mut text = self.buffer.line(line);
+        let mut rows = wrap::rows(&text, width);
+        let index = self.cursor - self.buffer.line_start(line);
+        let mut row = wrap::row_of(&rows, index);
+        let col = self.goal_column(Some(width), || display_width(&text[rows[row].start..index]));
+        for _ in 0..count.unsigned_abs() {
+            if count > 0 && row + 1 < rows.len() {
+                row += 1;
+            } else if count > 0 && line + 1 < self.buffer.line_count() {
+                line += 1;
+                text = self.buffer.line(line);
+                rows = wrap::rows(&text, width);
+                row = 0;
+            } else if count < 0 && row > 0 {
+                row -= 1;
+            } else if count < 0 && line > 0 {
+                line -= 1;
+                text = self.buffer.line(line);
+                rows = wrap::rows(&text, width);
+                row = rows.len() - 1;
+            } else {
+                break;
+            }
+        }
+        let offset = self.buffer.line_start(line) + wrap::index_at(&text, rows[row].clone(), col);
+        self.set_cursor_keeping(offset, col, Some(width));
+    }
+
+    /// Moves the cursor to the start of the next word; with `big`, words are
+    /// only broken up by whitespace.
+    pub fn move_word_right(&mut self, big: bool) {
+        self.set_cursor(motion::next_word(&self.buffer, self.cursor, big));
+    }
+
+    /// Moves the cursor to the start of the previous word; with `big`, words
+    /// are only broken up by whitespace.
+    pub fn move_word_left(&mut self, big: bool) {
+        self.set_cursor(motion::previous_word(&self.buffer, self.cursor, big));
+    }
+
+    /// Moves the cursor to the blank line below the current paragraph.
+    pub fn move_paragraph_down(&mut self) {
+        self.set_cursor(motion::next_paragraph(&self.buffer, self.cursor));
+    }
+
+    /// Moves the cursor to the blank line above the current paragraph.
+    pub fn move_paragraph_up(&mut self) {
+        self.set_cursor(motion::previous_paragraph(&self.buffer, self.cursor));
+    }
+
+    /// Moves the cursor to the start of its line.
+    pub fn move_line_start(&mut self) {
+        let line = self.buffer.line_of(self.cursor);
+        self.set_cursor(self.buffer.line_start(line));
+    }
+
+    /// Moves the cursor to the end of its line.
+    pub fn move_line_end(&mut self) {
+        let line = self.buffer.line_of(self.cursor);
+        self.set_cursor(self.buffer.line_end(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str, cursor: usize) -> EditorCore {
+        let mut core = EditorCore::new(Buffer::from(text));
+        core.cursor = cursor;
+        core
+    }
+
+    #[test]
+    fn moves_over_whole_graphemes() {
+        // "e" followed by a combining acute accent, then "x".
+        let mut core = at("e\u{301}x", 0);
+        core.move_right();
+        assert_eq!(core.cursor(), 3);
+        core.move_right();
+        assert_eq!(core.cursor(), 4);
+        core.move_right();
+        assert_eq!(core.cursor(), 4);
+        core.move_left();
+        core.move_left();
+        assert_eq!(core.cursor(), 0);
+        core.move_left();
+        assert_eq!(core.cursor(), 0);
+    }
+
+    #[test]
+    fn vertical_moves_keep_the_column_clamped_to_the_line() {
+        let mut core = at("hello\nhi\nworld", 4);
+        core.move_down();
+        assert_eq!(core.cursor(), 8);
+        core.move_down();
+        assert_eq!(
+            (core.buffer().line_of(core.cursor()), core.cursor_column()),
+            (2, 4)
+        );
+        core.move_down();
+        assert_eq!(core.cursor(), 13);
+        core.move_up();
+        core.move_up();
+        assert_eq!(core.cursor(), 4);
+        core.move_up();
+        assert_eq!(core.cursor(), 4);
+    }
+
+    #[test]
+    fn vertical_moves_keep_to_the_column_they_started_from() {
+        let mut core = at("hello\nhi\n\nworld", 4);
+        core.move_down();
+        core.move_down();
+        assert_eq!(core.cursor(), 9);
+        core.move_lines(1);
+        assert_eq!(core.cursor(), 14);
+        core.move_up();
+        core.move_left();
+        core.move_up();
+        assert_eq!(core.cursor(), 2);
+        // An edit that leaves the cursor where it is forgets the column too.
+        let mut core = at("hello\nhi\nworld", 4);
+        core.move_down();
+        core.goal = None;
+        core.move_down();
+        assert_eq!(core.cursor(), 11);
+        // Wrapped to 6 columns, "aaaa bbbb" shows as "aaaa " and "bbbb".
+        let mut core = at("aaaa bbbb\nc\naaaa bbbb", 4);
+        core.move_rows(2, 6);
+        assert_eq!(core.cursor(), 11);
+        core.move_rows(1, 6);
+        assert_eq!(core.cursor(), 16);
+    }
+
+    #[test]
+    fn columns_count_graphemes() {
+        let core = at("e\u{301}e\u{301}\nab", 6);
+        assert_eq!(core.cursor_column(), 2);
+        assert_eq!(core.offset_at_column(0, 1), 3);
+        assert_eq!(core.offset_at_column(1, 5), 9);
+    }
+
+    #[test]
+    fn move_lines_stops_at_the_first_and_last_line() {
+        let mut core = at("one\ntwo\nthree\nfour", 1);
+        core.move_lines(2);
+        assert_eq!(core.cursor(), 9);
+        core.move_lines(10);
+        assert_eq!(core.cursor(), 15);
+        core.move_lines(-10);
+        assert_eq!(core.cursor(), 1);
+    }
+
+    #[test]
+    fn move_rows_steps_through_wrapped_rows() {
+        // Wrapped to 6 columns the first line shows as "aaaa " and "bbbb".
+        let mut core = at("aaaa bbbb\ncc", 1);
+        core.move_rows(1, 6);
+        assert_eq!(core.cursor(), 6);
+        core.move_rows(1, 6);
+        assert_eq!(core.cursor(), 11);
+        core.move_rows(-2, 6);
+        assert_eq!(core.cursor(), 1);
+        core.move_rows(-1, 6);
+        assert_eq!(core.cursor(), 1);
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut core = at("foo.bar baz", 0);
+        core.move_word_right(false);
+        assert_eq!(core.cursor(), 3);
+        core.move_word_right(false);
+        assert_eq!(core.cursor(), 4);
+        core.move_word_right(false);
+        assert_eq!(core.cursor(), 8);
+        core.move_word_left(true);
+        assert_eq!(core.cursor(), 0);
+        core.move_word_right(true);
+        assert_eq!(core.cursor(), 8);
+    }
+
+    #[test]
+    fn paragraph_motions_stop_on_blank_lines() {
+        let mut core = at("a\nb\n\nc\n\nd", 0);
+        core.move_paragraph_down();
+        assert_eq!(core.cursor(), 4);
+        core.move_paragraph_down();
+        assert_eq!(core.cursor(), 7);
+        core.move_paragraph_down();
+        assert_eq!(core.cursor(), 9);
+        // The blank line just above is the one the paragraph starts after.
+        core.move_paragraph_up();
+        assert_eq!(core.cursor(), 4);
+        core.move_paragraph_up();
+        assert_eq!(core.cursor(), 0);
+    }
+
+    #[test]
+    fn line_start_and_end() {
+        let mut core = at("one\ntwo", 5);
+        core.move_line_end();
+        assert_eq!(core.cursor(), 7);
+        core.move_line_start();
+        assert_eq!(core.cursor(), 4);
+    }
+
+    #[test]
+    fn moving_drags_the_selection_head() {
+        let mut core = at("hello world", 2);
+        core.selection = Some(Selection::new(2, 2));
+        core.move_word_right(false);
+        assert_eq!(core.selection, Some(Selection::new(2, 6)));
+        assert_eq!(core.selected_text().as_deref(), Some("llo "));
+        core.set_cursor(0);
+        assert_eq!(core.selected_text().as_deref(), Some("he"));
+    }
+
+    #[test]
+    fn dragging_to_the_anchor_selects_nothing() {
+        let mut core = at("hello", 0);
+        core.drag_selection(1, 4);
+        assert_eq!(
+            (core.selection, core.cursor()),
+            (Some(Selection::new(1, 4)), 4)
+        );
+        core.drag_selection(1, 1);
+        assert_eq!((core.selection, core.cursor()), (None, 1));
+    }
+
+    #[test]
+    fn selected_lines_leave_out_a_line_the_selection_only_touches() {
+        let mut core = at("a\nb\nc", 0);
+        core.selection = Some(Selection::new(0, 2));
+        assert_eq!(core.selected_lines(), None);
+        core.selection = Some(Selection::new(0, 4));
+        assert_eq!(core.selected_lines(), Some(0..2));
+        core.selection = Some(Selection::new(5, 0));
+        assert_eq!(core.selected_lines(), Some(0..3));
+    }
+
+    #[test]
+    fn words_around_the_cursor() {
+        let core = at("let foo_bar = 1", 7);
+        assert_eq!(core.word_before_cursor(), "foo");
+        assert_eq!(core.word_at_cursor(), Some(4..11));
+        let core = at("a = b", 2);
+        assert_eq!(core.word_before_cursor(), "");
+        assert_eq!(core.word_at_cursor(), None);
+    }
+
+    #[test]
+    fn expansion_grows_to_the_next_structure_out() {
+        let mut core = at("f(a + b);\nend", 2);
+        let mut levels = Vec::new();
+        while let Some(next) = core.expansion(Structure::Plain) {
+            levels.push(next.clone());
+            core.drag_selection(next.start, next.end);
+        }
+        assert_eq!(levels, vec![2..3, 2..7, 1..8, 0..9, 0..10, 0..13]);
+
+        let mut core = at("fn f() {\n    g(1);\n    h(2, 3);\n}\n", 25);
+        let mut levels = Vec::new();
+        while let Some(next) = core.expansion(Structure::Braces) {
+            levels.push(next.clone());
+            core.drag_selection(next.start, next.end);
+        }
+        // The call, the statement, the function body and the function.
+        assert_eq!(
+            levels,
+            vec![
+                25..26,
+                25..29,
+                24..30,
+                23..30,
+                23..31,
+                19..32,
+                8..32,
+                7..33,
+                0..33,
+                0..34
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::ops::Range;
+
+/// Most pairs of lines compared between the parts that differ; past this
+/// they're shown as removed and added wholesale.
+const MAX_CELLS: usize = 4_000_000;
+
+/// One line of a diff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of `old` and `new`, each kept, removed or added, so that as
+/// many as possible are kept.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut diff: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    if a.len() * b.len() > MAX_CELLS {
+        diff.extend(a.iter().map(|line| Line::Removed(line)));
+        diff.extend(b.iter().map(|line| Line::Added(line)));
+    } else {
+        // kept[i][j]: the most lines kept between a[i..] and b[j..].
+        let mut kept = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                kept[i][j] = if a[i] == b[j] {
+                    kept[i + 1][j + 1] + 1
+                } else {
+                    kept[i + 1][j].max(kept[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                diff.push(Line::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && kept[i + 1][j] >= kept[i][j + 1]) {
+                diff.push(Line::Removed(a[i]));
+                i += 1;
+            } else {
+                diff.push(Line::Added(b[j]));
+                j += 1;
+            }
+        }
+    }
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+    diff
+}
+
+/// A run of changed lines in a diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// Where its lines are in the diff.
+    pub lines: Range<usize>,
+    /// The lines of the old text it removes, by number from 0.
+    pub old: Range<usize>,
+    /// The lines of the new text it adds in their place.
+    pub new: Range<usize>,
+}
+
+/// The runs of removed and added lines in `diff`, in order.
+pub fn hunks(diff: &[Line]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut old, mut new) = (0, 0);
+    for (i, line) in diff.iter().enumerate() {
+        let (removed, added) = match line {
+            Line::Same(_) => {
+                old += 1;
+                new += 1;
+                continue;
+            }
+            Line::Removed(_) => (1, 0),
+            Line::Added(_) => (0, 1),
+        };
+        match hunks.last_mut() {
+            Some(hunk) if hunk.lines.end == i => hunk.lines.end += 1,
+            _ => hunks.push(Hunk {
+                lines: i..i + 1,
+                old: old..old,
+                new: new..new,
+            }),
+        }
+        old += removed;
+        new += added;
+        let hunk = hunks.last_mut().unwrap();
+        hunk.old.end = old;
+        hunk.new.end = new;
+    }
+    hunks
+}
+
+/// `old` against `new` as text, with `-` before removed lines, `+` before
+/// added ones, and only `context` kept lines around each change.
+pub fn unified(old: &str, new: &str, context: usize) -> String {
+    let diff = lines(old, new);
+    let changed: Vec<bool> = diff
+        .iter()
+        .map(|line| !matches!(line, Line::Same(_)))
+        .collect();
+    let near = |i: usize| {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(diff.len());
+        changed[start..end].iter().any(|&changed| changed)
+    };
+    let mut text = String::new();
+    let mut skipped = false;
+    for (i, line) in diff.iter().enumerate() {
+        if !near(i) {
+            skipped = true;
+            continue;
+        }
+        if skipped && !text.is_empty() {
+            text.push_str("...\n");
+        }
+        skipped = false;
+        let (sign, line) = match line {
+            Line::Same(line) => (' ', line),
+            Line::Removed(line) => ('-', line),
+            Line::Added(line) => ('+', line),
+        };
+        text.push(sign);
+        text.push(' ');
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_as_many_lines_as_it_can() {
+        let diff = lines("a\nb\nc\nd", "a\nc\nx\nd");
+        assert_eq!(
+            diff,
+            vec![
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Same("c"),
+                Line::Added("x"),
+                Line::Same("d"),
+            ]
+        );
+        assert_eq!(
+            hunks(&diff),
+            vec![
+                Hunk {
+                    lines: 1..2,
+                    old: 1..2,
+                    new: 1..1,
+                },
+                Hunk {
+                    lines: 3..4,
+                    old: 3..3,
+                    new: 2..3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unified_keeps_only_the_context_asked_for() {
+        let old = "1\n2\n3\n4\n5\n6\n7";
+        let new = "1\n2\n3\n4\n5\n6\nseven";
+        assert_eq!(unified(old, new, 1), "  6\n- 7\n+ seven\n");
+        assert_eq!(
+            unified("a\nb\nc", "x\nb\ny", 0),
+            "- a\n+ x\n...\n- c\n+ y\n"
+        );
+        assert_eq!(unified(old, old, 3), "");
+    }
+}
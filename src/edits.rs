@@ -0,0 +1,332 @@
+use crate::cursor::EditorCore;
+use crate::filetype::Options;
+use crate::motion;
+use crate::number;
+use crate::selection::{Caret, Selection};
+use std::ops::Range;
+
+/// The edits an operation on the text works out, to be made one after the
+/// other as a single undo step, with where the cursor and selection end up
+/// once they're made.
+///
+/// The core only works them out: making them is the frontend's, which
+/// keeps the undo history, marks and other cursors that follow edits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// Ranges of the text as it is when their edit is made, each replaced
+    /// with the string alongside it.
+    pub edits: Vec<(Range<usize>, String)>,
+    pub caret: Caret,
+}
+
+impl Change {
+    /// No edits yet, leaving the cursor and selection as `caret` has them.
+    fn new(caret: Caret) -> Self {
+        Self {
+            edits: Vec::new(),
+            caret,
+        }
+    }
+
+    /// Adds replacing `range` with `text`, moving the caret along with it.
+    fn push(&mut self, range: Range<usize>, text: impl Into<String>) {
+        let text = text.into();
+        self.caret.adjust(range.start, range.len(), text.len());
+        self.edits.push((range, text));
+    }
+}
+
+impl EditorCore {
+    /// The cursor with its selection.
+    fn caret(&self) -> Caret {
+        Caret {
+            cursor: self.cursor,
+            selection: self.selection,
+        }
+    }
+
+    /// The lines line operations work on: those selected, or the cursor's.
+    pub fn current_lines(&self) -> Range<usize> {
+        let line = self.buffer.line_of(self.cursor);
+        self.selected_lines().unwrap_or(line..line + 1)
+    }
+
+    /// Adds a level of indentation, as `options` has levels, at the start
+    /// of each of the current lines but blank ones, or with `dedent` takes
+    /// one off each of them.
+    pub fn reindent(&self, dedent: bool, options: &Options) -> Change {
+        let lines = self.current_lines();
+        let unit = lines
+            .clone()
+            .map(|line| self.buffer.line(line))
+            .find(|text| !text.trim().is_empty())
+            .unwrap_or_default();
+        let unit = options.indent_unit(&unit);
+        let mut change = Change::new(self.caret());
+        // Bottom up, so the starts of the lines left to do stay put.
+        for line in lines.rev() {
+            let start = self.buffer.line_start(line);
+            let text = self.buffer.line(line);
+            if dedent {
+                let spaces = text.len() - text.trim_start_matches(' ').len();
+                let removed = if text.starts_with('\t') {
+                    1
+                } else {
+                    spaces.min(options.tab_width)
+                };
+                if removed > 0 {
+                    change.push(start..start + removed, "");
+                }
+            } else if !text.trim().is_empty() {
+                change.push(start..start, unit.as_str());
+            }
+        }
+        change
+    }
+
+    /// Comments out the current lines by putting `leader` and a space at
+    /// the indentation they share, or takes the leader off again if every
+    /// one of them starts with it. Blank lines are left alone, and with
+    /// nothing but them there's nothing to do.
+    pub fn toggle_comment(&self, leader: &str) -> Option<Change> {
+        let texts: Vec<(usize, String)> = self
+            .current_lines()
+            .map(|line| (line, self.buffer.line(line)))
+            .filter(|(_, text)| !text.trim().is_empty())
+            .collect();
+        if texts.is_empty() {
+            return None;
+        }
+        let commented = texts
+            .iter()
+            .all(|(_, text)| text.trim_start().starts_with(leader));
+        let column = texts
+            .iter()
+            .map(|(_, text)| text.len() - text.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let mut change = Change::new(self.caret());
+        // Bottom up, so the starts of the lines left to do stay put.
+        for (line, text) in texts.into_iter().rev() {
+            let start = self.buffer.line_start(line);
+            if commented {
+                let indent = start + text.len() - text.trim_start().len();
+                let after = &text[indent - start + leader.len()..];
+                let removed = leader.len() + usize::from(after.starts_with(' '));
+                change.push(indent..indent + removed, "");
+            } else {
+                change.push(start + column..start + column, format!("{leader} "));
+            }
+        }
+        Some(change)
+    }
+
+    /// Joins the selected lines, or the cursor's line and the next, into
+    /// one. The whitespace around each line break becomes a single space,
+    /// or nothing next to a blank line. Without a selection the cursor ends
+    /// up where the first two lines were joined; on the last line there's
+    /// nothing to join.
+    pub fn join_lines(&self) -> Option<Change> {
+        let mut lines = self.current_lines();
+        if lines.len() < 2 {
+            if lines.end >= self.buffer.line_count() {
+                return None;
+            }
+            lines.end += 1;
+        }
+        let mut change = Change::new(self.caret());
+        let mut joint = self.cursor;
+        for line in (lines.start..lines.end - 1).rev() {
+            let text = self.buffer.line(line);
+            let next = self.buffer.line(line + 1);
+            let start = self.buffer.line_start(line) + text.trim_end().len();
+            let end = self.buffer.line_start(line + 1) + next.len() - next.trim_start().len();
+            let space = if text.trim().is_empty() || next.trim().is_empty() {
+                ""
+            } else {
+                " "
+            };
+            change.push(start..end, space);
+            joint = start;
+        }
+        if change.caret.selection.is_none() {
+            change.caret.cursor = joint;
+        }
+        Some(change)
+    }
+
+    /// Adds `delta` to the number under or after the cursor on its line,
+    /// leaving the cursor on its last digit and nothing selected. There's
+    /// nothing to do without a number, or if the sum doesn't fit.
+    pub fn increment(&self, delta: i64) -> Option<Change> {
+        let line = self.buffer.line_of(self.cursor);
+        let line_start = self.buffer.line_start(line);
+        let text = self.buffer.line(line);
+        let range = number::find(&text, self.cursor - line_start)?;
+        let number = number::add(&text[range.clone()], delta)?;
+        let start = line_start + range.start;
+        let mut change = Change::new(Caret {
+            cursor: start + number.len() - 1,
+            selection: None,
+        });
+        change.edits.push((start..line_start + range.end, number));
+        Some(change)
+    }
+
+    /// Wraps the selection in `ch` and the delimiter it pairs with, keeping
+    /// the wrapped text selected. There's nothing to wrap without a
+    /// selection.
+    pub fn surround(&self, ch: char) -> Option<Change> {
+        let sel = self.selection?;
+        let (open, close) = motion::pair(ch);
+        let range = sel.range();
+        let shift = |offset| offset + open.len_utf8();
+        let mut change = Change::new(Caret {
+            cursor: shift(sel.head),
+            selection: Some(Selection::new(shift(sel.anchor), shift(sel.head))),
+        });
+        change.edits = vec![
+            (range.end..range.end, close.to_string()),
+            (range.start..range.start, open.to_string()),
+        ];
+        Some(change)
+    }
+
+    /// Replaces the innermost pair of `ch` delimiters around the selection
+    /// or cursor with the pair of `with`, or deletes them when there's
+    /// nothing to replace them with. There's nothing to do outside a pair.
+    pub fn change_surround(&self, ch: char, with: Option<char>) -> Option<Change> {
+        let (open, close) = motion::pair(ch);
+        let range = self
+            .selection
+            .map_or(self.cursor..self.cursor, |sel| sel.range());
+        let (start, end) = motion::pair_around(&self.buffer, range, open, close)?;
+        let (new_open, new_close) = match with.map(motion::pair) {
+            Some((open, close)) => (open.to_string(), close.to_string()),
+            None => (String::new(), String::new()),
+        };
+        let mut change = Change::new(self.caret());
+        change.push(end..end + close.len_utf8(), new_close);
+        change.push(start..start + open.len_utf8(), new_open);
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    fn at(text: &str, cursor: usize, selection: Option<(usize, usize)>) -> EditorCore {
+        let mut core = EditorCore::new(Buffer::from(text));
+        core.cursor = cursor;
+        core.selection = selection.map(|(anchor, head)| Selection::new(anchor, head));
+        core
+    }
+
+    /// Makes `change` to `core`'s text, returning it and the caret after.
+    fn make(core: &EditorCore, change: Change) -> (String, Caret) {
+        let mut buffer = core.buffer.clone();
+        for (range, text) in &change.edits {
+            buffer.replace(range.clone(), text);
+        }
+        (buffer.to_string(), change.caret)
+    }
+
+    fn caret(cursor: usize, selection: Option<(usize, usize)>) -> Caret {
+        Caret {
+            cursor,
+            selection: selection.map(|(anchor, head)| Selection::new(anchor, head)),
+        }
+    }
+
+    fn options() -> Options {
+        Options {
+            tab_width: 4,
+            expand_tab: true,
+            comment: None,
+            wrap_column: None,
+        }
+    }
+
+    #[test]
+    fn reindent_skips_blank_lines_and_follows_the_selection() {
+        let core = at("a\n\nb\n", 4, Some((0, 4)));
+        let change = core.reindent(false, &options());
+        assert_eq!(
+            make(&core, change),
+            ("    a\n\n    b\n".to_string(), caret(12, Some((0, 12))))
+        );
+
+        let core = at("    a\n\tb\n  c", 12, Some((0, 12)));
+        let change = core.reindent(true, &options());
+        assert_eq!(
+            make(&core, change),
+            ("a\nb\nc".to_string(), caret(5, Some((0, 5))))
+        );
+    }
+
+    #[test]
+    fn toggle_comment_uses_the_shared_indentation() {
+        let core = at("  a\n    b\n\n  c", 0, Some((0, 14)));
+        let change = core.toggle_comment("//").unwrap();
+        let (text, _) = make(&core, change);
+        assert_eq!(text, "  // a\n  //   b\n\n  // c");
+
+        let core = at(&text, 0, Some((0, text.len())));
+        let change = core.toggle_comment("//").unwrap();
+        assert_eq!(make(&core, change).0, "  a\n    b\n\n  c");
+
+        assert_eq!(at("\n", 0, None).toggle_comment("#"), None);
+    }
+
+    #[test]
+    fn join_lines_squeezes_the_break_to_a_space() {
+        let core = at("one  \n   two\nthree", 1, None);
+        let change = core.join_lines().unwrap();
+        assert_eq!(
+            make(&core, change),
+            ("one two\nthree".to_string(), caret(3, None))
+        );
+
+        let core = at("a\n\nb", 4, Some((0, 4)));
+        let change = core.join_lines().unwrap();
+        assert_eq!(
+            make(&core, change),
+            ("ab".to_string(), caret(2, Some((0, 2))))
+        );
+
+        assert_eq!(at("last", 0, None).join_lines(), None);
+    }
+
+    #[test]
+    fn increment_rewrites_the_next_number() {
+        let core = at("x = 9;", 0, Some((0, 1)));
+        let change = core.increment(1).unwrap();
+        assert_eq!(make(&core, change), ("x = 10;".to_string(), caret(5, None)));
+        assert_eq!(at("none", 0, None).increment(1), None);
+    }
+
+    #[test]
+    fn surround_keeps_the_text_selected() {
+        let core = at("a word", 6, Some((2, 6)));
+        let change = core.surround(')').unwrap();
+        assert_eq!(
+            make(&core, change),
+            ("a (word)".to_string(), caret(7, Some((3, 7))))
+        );
+        assert_eq!(at("a word", 0, None).surround('('), None);
+    }
+
+    #[test]
+    fn change_surround_replaces_or_drops_the_pair() {
+        let core = at("f(x)", 2, None);
+        let change = core.change_surround('(', Some('[')).unwrap();
+        assert_eq!(make(&core, change), ("f[x]".to_string(), caret(2, None)));
+
+        let change = core.change_surround('(', None).unwrap();
+        assert_eq!(make(&core, change), ("fx".to_string(), caret(1, None)));
+
+        assert_eq!(core.change_surround('{', None), None);
+    }
+}
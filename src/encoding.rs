@@ -0,0 +1,100 @@
+/// The base64 alphabet, standard flavour.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `text` as padded base64.
+pub fn base64_encode(text: &str) -> String {
+    let mut out = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                out.push(BASE64[index as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes base64 back into text, ignoring whitespace. Fails on anything
+/// that isn't base64 or doesn't decode to UTF-8.
+pub fn base64_decode(text: &str) -> Result<String, String> {
+    let digits: Vec<u8> = text
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    let data = digits
+        .strip_suffix(b"==")
+        .or_else(|| digits.strip_suffix(b"="));
+    let data = data.unwrap_or(&digits);
+    if !digits.len().is_multiple_of(4) || data.len() % 4 == 1 {
+        return Err("Not base64: the length is off".to_string());
+    }
+    let mut bytes = Vec::new();
+    for chunk in data.chunks(4) {
+        let mut group = 0;
+        for (i, &digit) in chunk.iter().enumerate() {
+            let value = BASE64
+                .iter()
+                .position(|&d| d == digit)
+                .ok_or_else(|| format!("Not base64: unexpected `{}`", digit as char))?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        let group = group.to_be_bytes();
+        bytes.extend_from_slice(&group[1..chunk.len()]);
+    }
+    String::from_utf8(bytes).map_err(|_| "The decoded bytes aren't UTF-8 text".to_string())
+}
+
+/// Percent-encodes everything in `text` but letters, digits and `-_.~`.
+pub fn percent_encode(text: &str) -> String {
+    let mut out = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+/// Decodes the `%XX` escapes in `text`. Fails on a malformed escape, or if
+/// the result isn't UTF-8.
+pub fn percent_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let escape = after
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or("Not percent-encoded: `%` must be followed by two hex digits")?;
+            bytes.push(escape);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| "The decoded bytes aren't UTF-8 text".to_string())
+}
+
+/// Rotates ASCII letters 13 places; doing it twice gives back the text.
+pub fn rot13(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            'a'..='z' => ((ch as u8 - b'a' + 13) % 26 + b'a') as char,
+            'A'..='Z' => ((ch as u8 - b'A' + 13) % 26 + b'A') as char,
+            _ => ch,
+        })
+        .collect()
+}
@@ -0,0 +1,157 @@
+use crate::prompt;
+use crate::view::token_color;
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::theme::{BaseColor, Color, Palette, PaletteColor};
+use cursive::views::Dialog;
+use cursive::Cursive;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Asks where to, then writes the selection, or the whole buffer, to an
+/// HTML file colored the way it's shown, with the styles inline so the
+/// markup can be pasted anywhere. Left empty, the file is the buffer's
+/// with `.html` added.
+pub fn export(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let default = {
+        let ws = workspace.lock().unwrap();
+        let ed = ws.editor();
+        let name = ed
+            .path()
+            .map_or(PathBuf::from(ed.name()), Path::to_path_buf);
+        PathBuf::from(format!("{}.html", name.display()))
+    };
+    let label = format!("Export HTML to ({}): ", default.display());
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        &label,
+        |_, _| {},
+        move |s, path| {
+            let path = if path.is_empty() {
+                default.clone()
+            } else {
+                PathBuf::from(path)
+            };
+            let palette = s.current_theme().palette.clone();
+            let mut ws = workspace.lock().unwrap();
+            let html = html(ws.editor_mut(), &palette);
+            drop(ws);
+            let message = match fs::write(&path, html) {
+                Ok(()) => format!("Exported to {}", path.display()),
+                Err(err) => format!("Could not write {}: {err}", path.display()),
+            };
+            s.add_layer(Dialog::info(message));
+        },
+        |_| {},
+    );
+}
+
+/// The selection of `ed`, or all of its text, as an HTML page holding a
+/// `pre` block colored like the panes are with `palette`.
+fn html(ed: &mut Editor, palette: &Palette) -> String {
+    let buffer = ed.buffer();
+    let range = ed
+        .carets()
+        .first()
+        .and_then(|caret| caret.selection)
+        .map_or(0..buffer.len(), |selection| selection.range());
+    let first = buffer.line_of(range.start);
+    let last = buffer.line_of(range.end);
+    ed.highlight(last + 1);
+
+    let buffer = ed.buffer();
+    let mut code = String::new();
+    for line in first..=last {
+        let start = buffer.line_start(line);
+        let text = buffer.line(line);
+        // Only the part of the line that's selected.
+        let from = range.start.saturating_sub(start).min(text.len());
+        let to = (range.end - start.min(range.end)).min(text.len());
+        let mut at = from;
+        for (span, token) in ed.highlighter().spans(line) {
+            let span = span.start.max(from)..span.end.min(to);
+            if span.start >= span.end {
+                continue;
+            }
+            code += &escape(&text[at..span.start]);
+            let color = css(token_color(*token)).unwrap_or_default();
+            code += &format!(
+                "<span style=\"color: {color}\">{}</span>",
+                escape(&text[span.clone()])
+            );
+            at = span.end;
+        }
+        code += &escape(&text[at.min(to)..to]);
+        if line < last {
+            code.push('\n');
+        }
+    }
+
+    // The panes show text in the view color on the secondary one.
+    let background = css(palette[PaletteColor::Secondary]);
+    let foreground = css(palette[PaletteColor::View]);
+    let mut style = String::new();
+    if let Some(background) = background {
+        style += &format!("background-color: {background}; ");
+    }
+    if let Some(foreground) = foreground {
+        style += &format!("color: {foreground}; ");
+    }
+    style += "padding: 1em; tab-size: 4;";
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n\
+         <body>\n<pre style=\"{style}\">{code}</pre>\n</body>\n</html>\n",
+        escape(&ed.name())
+    )
+}
+
+/// `text` with the characters HTML gives meaning to spelled out.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// `color` as CSS, with the terminal's sixteen colors as xterm shows them,
+/// or nothing for the terminal's own default.
+fn css(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::TerminalDefault => return None,
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::RgbLowRes(r, g, b) => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + 40 * value };
+            (level(r), level(g), level(b))
+        }
+        Color::Dark(base) => match base {
+            BaseColor::Black => (0, 0, 0),
+            BaseColor::Red => (205, 0, 0),
+            BaseColor::Green => (0, 205, 0),
+            BaseColor::Yellow => (205, 205, 0),
+            BaseColor::Blue => (0, 0, 238),
+            BaseColor::Magenta => (205, 0, 205),
+            BaseColor::Cyan => (0, 205, 205),
+            BaseColor::White => (229, 229, 229),
+        },
+        Color::Light(base) => match base {
+            BaseColor::Black => (127, 127, 127),
+            BaseColor::Red => (255, 0, 0),
+            BaseColor::Green => (0, 255, 0),
+            BaseColor::Yellow => (255, 255, 0),
+            BaseColor::Blue => (92, 92, 255),
+            BaseColor::Magenta => (255, 0, 255),
+            BaseColor::Cyan => (0, 255, 255),
+            BaseColor::White => (255, 255, 255),
+        },
+    };
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
+}
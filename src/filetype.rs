@@ -0,0 +1,197 @@
+use crate::structure::Structure;
+use std::path::Path;
+
+/// The languages the editor knows, told apart by file extension or, for
+/// scripts without one, by the interpreter their `#!` line names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Filetype {
+    Plain,
+    Rust,
+    C,
+    Python,
+    JavaScript,
+    Shell,
+    Toml,
+    Json,
+}
+
+impl Filetype {
+    /// Every language with a syntax of its own.
+    pub const ALL: &'static [Filetype] = &[
+        Filetype::Rust,
+        Filetype::C,
+        Filetype::Python,
+        Filetype::JavaScript,
+        Filetype::Shell,
+        Filetype::Toml,
+        Filetype::Json,
+    ];
+
+    /// Guesses the language of the file at `path`, whose first line is
+    /// `first_line`, from its extension or else its `#!` line.
+    pub fn detect(path: Option<&Path>, first_line: &str) -> Self {
+        let extension = path
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+        match extension {
+            "rs" => Filetype::Rust,
+            "c" | "h" | "cc" | "cpp" | "hpp" => Filetype::C,
+            "py" => Filetype::Python,
+            "js" | "mjs" | "ts" => Filetype::JavaScript,
+            "sh" | "bash" => Filetype::Shell,
+            "toml" => Filetype::Toml,
+            "json" => Filetype::Json,
+            _ => Self::from_shebang(first_line),
+        }
+    }
+
+    /// The language of a script starting with `line`, if it's a `#!` line
+    /// naming an interpreter the editor knows, directly or through `env`.
+    fn from_shebang(line: &str) -> Self {
+        let Some(command) = line.strip_prefix("#!") else {
+            return Filetype::Plain;
+        };
+        let mut words = command.split_whitespace();
+        let mut program = words.next().unwrap_or("");
+        if program.rsplit('/').next() == Some("env") {
+            // `env -S` and the like come before the program.
+            program = words.find(|word| !word.starts_with('-')).unwrap_or("");
+        }
+        let name = program.rsplit('/').next().unwrap_or("");
+        // Versions such as `python3.12` name the same language.
+        match name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" | "pypy" => Filetype::Python,
+            "node" | "deno" | "bun" => Filetype::JavaScript,
+            "sh" | "bash" | "dash" | "zsh" | "ksh" => Filetype::Shell,
+            _ => Filetype::Plain,
+        }
+    }
+
+    /// The name shown for the language, and used for it in settings.
+    pub fn name(self) -> &'static str {
+        match self {
+            Filetype::Plain => "text",
+            Filetype::Rust => "rust",
+            Filetype::C => "c",
+            Filetype::Python => "python",
+            Filetype::JavaScript => "javascript",
+            Filetype::Shell => "shell",
+            Filetype::Toml => "toml",
+            Filetype::Json => "json",
+        }
+    }
+
+    /// The language named `name`, as `name` gives it, plain text included.
+    pub fn named(name: &str) -> Option<Self> {
+        std::iter::once(&Filetype::Plain)
+            .chain(Filetype::ALL)
+            .copied()
+            .find(|filetype| filetype.name() == name)
+    }
+
+    /// How the language's code is put together, for growing selections.
+    pub fn structure(self) -> Structure {
+        match self {
+            Filetype::Rust | Filetype::C | Filetype::JavaScript | Filetype::Json => {
+                Structure::Braces
+            }
+            Filetype::Python => Structure::Indented,
+            Filetype::Plain | Filetype::Shell | Filetype::Toml => Structure::Plain,
+        }
+    }
+
+    /// How buffers of the language are edited unless the settings say
+    /// otherwise.
+    pub fn options(self) -> Options {
+        let (tab_width, comment) = match self {
+            Filetype::Plain => (4, None),
+            Filetype::Rust | Filetype::C => (4, Some("//")),
+            Filetype::Python | Filetype::Shell | Filetype::Toml => (4, Some("#")),
+            Filetype::JavaScript => (2, Some("//")),
+            Filetype::Json => (2, None),
+        };
+        Options {
+            tab_width,
+            expand_tab: true,
+            comment: comment.map(String::from),
+            wrap_column: None,
+        }
+    }
+}
+
+/// How buffers of a filetype are indented, commented and reflowed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    /// Columns in a level of indentation, and between the stops the Tab
+    /// key types spaces up to.
+    pub tab_width: usize,
+    /// Whether indentation is made of spaces rather than tabs, in a file
+    /// that isn't already indented with one or the other.
+    pub expand_tab: bool,
+    /// What commenting out a line puts before it, if the language has line
+    /// comments.
+    pub comment: Option<String>,
+    /// The column reflowing wraps paragraphs at, rather than the one set
+    /// for every filetype.
+    pub wrap_column: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Filetype::Plain.options()
+    }
+}
+
+impl Options {
+    /// One level of indentation for `line`: a tab if it's indented with
+    /// tabs, spaces if with spaces, and as these options say if it isn't
+    /// indented.
+    pub fn indent_unit(&self, line: &str) -> String {
+        match line.chars().next() {
+            Some('\t') => "\t".to_string(),
+            Some(' ') => " ".repeat(self.tab_width),
+            _ if self.expand_tab => " ".repeat(self.tab_width),
+            _ => "\t".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_filetypes() {
+        let path = |name| Some(Path::new(name));
+        assert_eq!(Filetype::detect(path("src/main.rs"), ""), Filetype::Rust);
+        assert_eq!(
+            Filetype::detect(path("a.py"), "#!/bin/sh"),
+            Filetype::Python
+        );
+        for (line, filetype) in [
+            ("#!/bin/sh", Filetype::Shell),
+            ("#!/usr/bin/env python3.12", Filetype::Python),
+            ("#!/usr/bin/env -S node --no-warnings", Filetype::JavaScript),
+            ("#! /usr/local/bin/bash -e", Filetype::Shell),
+            ("#!/usr/bin/perl", Filetype::Plain),
+            ("# sh", Filetype::Plain),
+        ] {
+            assert_eq!(Filetype::detect(path("script"), line), filetype, "{line}");
+        }
+        assert_eq!(Filetype::detect(None, "#!/bin/bash"), Filetype::Shell);
+        assert_eq!(Filetype::named("text"), Some(Filetype::Plain));
+        assert_eq!(Filetype::named("perl"), None);
+    }
+
+    #[test]
+    fn indent_units() {
+        let mut options = Filetype::JavaScript.options();
+        assert_eq!(options.indent_unit(""), "  ");
+        assert_eq!(options.indent_unit("\t"), "\t");
+        options.expand_tab = false;
+        assert_eq!(options.indent_unit(""), "\t");
+        assert_eq!(options.indent_unit("    x"), "  ");
+        assert_eq!(options.indent_unit("x"), "\t");
+    }
+}
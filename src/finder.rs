@@ -0,0 +1,123 @@
+use crate::fuzzy;
+use crate::jobs;
+use crate::project;
+use crate::swap;
+use crate::workspace::Workspace;
+use cursive::event::{EventResult, Key};
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{Dialog, EditView, LinearLayout, OnEventView, SelectView};
+use cursive::Cursive;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Name of the list of matching files.
+const LIST: &str = "finder_list";
+
+/// Most files listed at once; typing more narrows the list down.
+const MAX_SHOWN: usize = 200;
+
+/// Lists the project's files in the background, then opens the finder:
+/// typing narrows the list by a fuzzy match on their paths, Up/Down pick
+/// one and Enter opens it.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let listed = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        "Listing files".to_string(),
+        |progress| {
+            project::files(progress)
+                .into_iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        },
+        move |s, files| show(s, &listed, Arc::new(files)),
+    );
+}
+
+/// Shows the finder over `files`.
+fn show(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, files: Arc<Vec<String>>) {
+    let mut list: SelectView<String> = SelectView::new();
+    fill(&mut list, &files, "");
+    let list = {
+        let workspace = workspace.clone();
+        list.on_submit(move |s, path: &String| {
+            s.pop_layer();
+            open_file(s, &workspace, path);
+        })
+    };
+
+    let input = {
+        let on_submit = workspace.clone();
+        EditView::new()
+            .on_edit(move |s, text, _| {
+                s.call_on_name(LIST, |list: &mut SelectView<String>| {
+                    fill(list, &files, text);
+                });
+            })
+            .on_submit(move |s, _| {
+                let chosen = s
+                    .call_on_name(LIST, |list: &mut SelectView<String>| list.selection())
+                    .flatten();
+                if let Some(path) = chosen {
+                    s.pop_layer();
+                    open_file(s, &on_submit, &path);
+                }
+            })
+    };
+
+    // The input keeps focus; Up and Down move through the list underneath it.
+    let input = OnEventView::new(input)
+        .on_pre_event_inner(Key::Up, |_, _| Some(move_selection(true)))
+        .on_pre_event_inner(Key::Down, |_, _| Some(move_selection(false)));
+
+    let content = LinearLayout::vertical()
+        .child(input)
+        .child(list.with_name(LIST).scrollable().max_height(12));
+    let finder = OnEventView::new(Dialog::around(content).title("Files").min_width(50)).on_event(
+        Key::Esc,
+        |s| {
+            s.pop_layer();
+        },
+    );
+    s.add_layer(finder);
+}
+
+/// Opens the file at `path` in a buffer, or switches to it if it's open.
+fn open_file(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, path: &str) {
+    let result = workspace.lock().unwrap().open(Path::new(path));
+    match result {
+        Ok(()) => swap::offer(s, workspace),
+        Err(err) => s.add_layer(Dialog::info(format!("Could not open {path}: {err}"))),
+    }
+}
+
+/// Moves the list's selection one entry up or down.
+fn move_selection(up: bool) -> EventResult {
+    EventResult::with_cb(move |s| {
+        s.call_on_name(LIST, |list: &mut SelectView<String>| {
+            // Nothing listens for selection changes, so the callback can be dropped.
+            let _ = if up {
+                list.select_up(1)
+            } else {
+                list.select_down(1)
+            };
+        });
+    })
+}
+
+/// Lists the files matching `pattern`, best match first.
+fn fill(list: &mut SelectView<String>, files: &[String], pattern: &str) {
+    let mut matches: Vec<(i64, &String)> = files
+        .iter()
+        .filter_map(|path| Some((fuzzy::score(pattern, path)?, path)))
+        .collect();
+    // Stable, so equal scores keep the files sorted by path.
+    if !pattern.is_empty() {
+        matches.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    }
+    list.clear();
+    for (_, path) in matches.into_iter().take(MAX_SHOWN) {
+        list.add_item(path.clone(), path.clone());
+    }
+}
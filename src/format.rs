@@ -0,0 +1,227 @@
+use crate::diff::{self, Hunk, Line};
+use crate::jobs;
+use crate::line_ending;
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use txt_editor::shell;
+
+/// The command that formats `ed`, if its filetype has one and it's a
+/// buffer formatting can change.
+pub fn formatter(ws: &Workspace, ed: &Editor) -> Option<String> {
+    if ed.is_read_only() || ed.is_large() || ed.hex().is_some() {
+        return None;
+    }
+    let filetype = ed.highlighter().filetype();
+    ws.settings().formatters.get(&filetype).cloned()
+}
+
+/// Formats the buffer being edited, or says why it can't be.
+pub fn format_current(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    if formatter(&ws, ed).is_none() {
+        let message = format!(
+            "No formatter for {} files",
+            ed.highlighter().filetype().name()
+        );
+        drop(ws);
+        s.add_layer(Dialog::info(message));
+        return;
+    }
+    let index = ws.current();
+    drop(ws);
+    format(s, workspace, index, |_| {});
+}
+
+/// Pipes buffer `index` through its formatter in the background, then
+/// changes the lines the formatter changed, keeping the cursor on the
+/// same line of text, and calls `then`. A formatter that fails, or a
+/// buffer edited meanwhile, is left as it is, though `then` still runs.
+pub fn format<F>(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, index: usize, then: F)
+where
+    F: FnOnce(&mut Cursive) + Send + 'static,
+{
+    let ws = workspace.lock().unwrap();
+    let Some(ed) = ws.editors().get(index) else {
+        return;
+    };
+    let Some(command) = formatter(&ws, ed) else {
+        drop(ws);
+        then(s);
+        return;
+    };
+    let text = ed.buffer().to_string();
+    let revision = ed.revision();
+    let path = ed.path().map(|path| path.to_path_buf()).unwrap_or_default();
+    let label = format!("Formatting {}", ed.name());
+    drop(ws);
+    let formatted = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        label,
+        move |progress| {
+            let output = shell::filter_file(&command, &text, &path, &|| progress.is_cancelled());
+            (command, text, output)
+        },
+        move |s, (command, text, output)| {
+            let output = match output {
+                Ok(output) if output.status.success() => output,
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let message = match stderr.trim_end() {
+                        "" => format!("{command}: {}", output.status),
+                        stderr => stderr.to_string(),
+                    };
+                    s.add_layer(
+                        Dialog::around(TextView::new(message).scrollable())
+                            .title(command)
+                            .dismiss_button("Ok")
+                            .max_height(20),
+                    );
+                    then(s);
+                    return;
+                }
+                Err(err) => {
+                    s.add_layer(Dialog::info(format!("Could not run {command}: {err}")));
+                    then(s);
+                    return;
+                }
+            };
+            let new = line_ending::normalize(&String::from_utf8_lossy(&output.stdout));
+            let mut ws = formatted.lock().unwrap();
+            match ws.editors_mut().get_mut(index) {
+                Some(ed) if ed.revision() == revision => apply(ed, &text, &new),
+                Some(_) => ws.notify("The buffer changed while it was formatted, so it was left"),
+                None => {}
+            }
+            drop(ws);
+            then(s);
+        },
+    );
+}
+
+/// Turns `ed`, whose text is `old`, into `new` by changing only the lines
+/// that differ, as one step to undo. The cursor stays on its line, and in
+/// a changed run of lines on the one as far into it, at the same column
+/// where there's room.
+fn apply(ed: &mut Editor, old: &str, new: &str) {
+    let (edits, hunks) = changes(old, new);
+    if edits.is_empty() {
+        return;
+    }
+    let line = line_after(ed.buffer().line_of(ed.cursor()), &hunks);
+    let column = ed.cursor_column();
+    ed.replace_ranges(edits);
+    let offset = ed.offset_at_column(line, column);
+    ed.set_cursor(offset);
+}
+
+/// The edits that turn `old` into `new`, one for each run of changed
+/// lines, and the runs.
+fn changes(old: &str, new: &str) -> (Vec<(Range<usize>, String)>, Vec<Hunk>) {
+    let diff = diff::lines(old, new);
+    let (old_starts, new_starts) = (line_starts(old), line_starts(new));
+    let mut edits = Vec::new();
+    let hunks = diff::hunks(&diff);
+    for hunk in &hunks {
+        let range = old_starts[hunk.old.start]..old_starts[hunk.old.end];
+        let mut text = new[new_starts[hunk.new.start]..new_starts[hunk.new.end]].to_string();
+        // Lines added after a last line without a newline need one first.
+        if range.start == old.len() && !old.is_empty() && !old.ends_with('\n') {
+            text.insert(0, '\n');
+        }
+        edits.push((range, text));
+    }
+    // Comparing lines misses a last newline put in or taken out.
+    let kept_last = diff
+        .last()
+        .is_some_and(|line| matches!(line, Line::Same(_)));
+    if kept_last && old.ends_with('\n') != new.ends_with('\n') {
+        if new.ends_with('\n') {
+            edits.push((old.len()..old.len(), "\n".to_string()));
+        } else {
+            edits.push((old.len() - 1..old.len(), String::new()));
+        }
+    }
+    (edits, hunks)
+}
+
+/// Where each line of `text` starts, and where the text ends.
+fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    if starts.last() != Some(&text.len()) {
+        starts.push(text.len());
+    }
+    starts
+}
+
+/// The line old line `line` is after the changed runs `hunks`.
+fn line_after(line: usize, hunks: &[Hunk]) -> usize {
+    let mut shift = 0isize;
+    for Hunk { old, new, .. } in hunks {
+        if line < old.start {
+            break;
+        }
+        if line < old.end {
+            let into = (line - old.start).min(new.len().saturating_sub(1));
+            return new.start + into;
+        }
+        shift += new.len() as isize - old.len() as isize;
+    }
+    line.saturating_add_signed(shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(old: &str, new: &str) -> String {
+        let (edits, _) = changes(old, new);
+        let mut text = old.to_string();
+        for (range, new) in edits.into_iter().rev() {
+            text.replace_range(range, &new);
+        }
+        text
+    }
+
+    #[test]
+    fn only_changed_lines() {
+        let old = "fn f(){\nlet x=1;\n}\n";
+        let new = "fn f() {\n    let x = 1;\n}\n";
+        let (edits, hunks) = changes(old, new);
+        assert_eq!(edits, [(0..17, "fn f() {\n    let x = 1;\n".to_string())]);
+        assert_eq!((hunks[0].old.clone(), hunks[0].new.clone()), (0..2, 0..2));
+        assert_eq!(changed(old, new), new);
+        for (old, new) in [
+            ("a\nb", "a\nb\n"),
+            ("a\nb\n", "a\nb"),
+            ("", "x\n"),
+            ("x", ""),
+            ("a\nb", "b\na\n"),
+        ] {
+            assert_eq!(changed(old, new), new);
+        }
+    }
+
+    #[test]
+    fn cursor_lines() {
+        let hunk = |old, new| Hunk {
+            lines: 0..0,
+            old,
+            new,
+        };
+        let hunks = [hunk(1..3, 1..2), hunk(5..6, 4..7)];
+        assert_eq!(line_after(0, &hunks), 0);
+        assert_eq!(line_after(2, &hunks), 1);
+        assert_eq!(line_after(3, &hunks), 2);
+        assert_eq!(line_after(5, &hunks), 4);
+        assert_eq!(line_after(9, &hunks), 10);
+    }
+}
@@ -0,0 +1,32 @@
+/// Scores how well `pattern` matches `candidate`, or `None` if it doesn't.
+///
+/// Every character of the pattern has to appear in the candidate, in order and
+/// ignoring case. Higher scores are better: runs of consecutive characters and
+/// matches at the start of a word (after `-`, `_`, `/`, `.` or a space) count
+/// extra, and shorter candidates win ties.
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut chars = candidate.chars().enumerate();
+    let mut last_char: Option<char> = None;
+
+    for wanted in pattern.chars().flat_map(char::to_lowercase) {
+        loop {
+            let (index, ch) = chars.next()?;
+            let before = last_char.replace(ch);
+            if !ch.to_lowercase().eq(std::iter::once(wanted)) {
+                continue;
+            }
+            score += 1;
+            if previous.is_some_and(|p| p + 1 == index) {
+                score += 5;
+            }
+            if before.is_none_or(|b| matches!(b, '-' | '_' | '/' | '.' | ' ')) {
+                score += 3;
+            }
+            previous = Some(index);
+            break;
+        }
+    }
+    Some(score * 100 - candidate.len() as i64)
+}
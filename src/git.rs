@@ -0,0 +1,214 @@
+use crate::buffer::Buffer;
+use crate::diff;
+use crate::line_ending;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How a line of the buffer differs from the last commit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sign {
+    Added,
+    Modified,
+    /// Lines were removed just above this one.
+    Removed,
+}
+
+/// The file at `path` as of the last commit, if it's tracked in a git
+/// repository, with its lines ending the way the buffer's do. Asked of
+/// `git` itself, so there's nothing to find when it isn't installed.
+fn head_text(path: &Path) -> Option<String> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let name = path.file_name()?.to_str()?;
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    let output = command
+        .arg("show")
+        .arg(format!("HEAD:./{name}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(line_ending::normalize(&text))
+}
+
+/// The lines of a buffer that changed since the last commit, for the
+/// gutter to mark. They're worked out again whenever the text changes.
+#[derive(Clone, Debug)]
+pub struct Changes {
+    base: String,
+    /// The revision of the text `signs` are for.
+    revision: Option<u64>,
+    signs: Vec<Option<Sign>>,
+    /// The first line of each run of changed lines.
+    starts: Vec<usize>,
+}
+
+impl Changes {
+    /// The changes to the file at `path`, if git tracks it.
+    pub fn load(path: &Path) -> Option<Self> {
+        Some(Self {
+            base: head_text(path)?,
+            revision: None,
+            signs: Vec::new(),
+            starts: Vec::new(),
+        })
+    }
+
+    /// Compares the text of `buffer`, at `revision`, with the last commit,
+    /// unless it already was.
+    pub fn update(&mut self, buffer: &Buffer, revision: u64) {
+        if self.revision == Some(revision) {
+            return;
+        }
+        self.revision = Some(revision);
+        let text = &buffer.to_string();
+        let lines = text.split('\n').count();
+        self.signs = vec![None; lines];
+        self.starts.clear();
+        let diff = diff::lines(&self.base, text);
+        for hunk in diff::hunks(&diff) {
+            let start = hunk.new.start.min(lines - 1);
+            self.starts.push(start);
+            if hunk.new.is_empty() {
+                self.signs[start] = Some(Sign::Removed);
+                continue;
+            }
+            let sign = if hunk.old.is_empty() {
+                Sign::Added
+            } else {
+                Sign::Modified
+            };
+            for line in hunk.new {
+                self.signs[line] = Some(sign);
+            }
+        }
+    }
+
+    /// How `line` differs from the last commit, if it does.
+    pub fn sign(&self, line: usize) -> Option<Sign> {
+        self.signs.get(line).copied().flatten()
+    }
+
+    /// The first line of the next run of changes after `line`.
+    pub fn next(&self, line: usize) -> Option<usize> {
+        self.starts.iter().copied().find(|&start| start > line)
+    }
+
+    /// The first line of the last run of changes starting before `line`.
+    pub fn previous(&self, line: usize) -> Option<usize> {
+        self.starts
+            .iter()
+            .copied()
+            .rev()
+            .find(|&start| start < line)
+    }
+}
+
+/// Who last changed line `line` (from 0) of the file at `path`, when, and
+/// with what commit, as `git blame` tells it for the buffer's `text`, so
+/// lines edited since are known as not committed yet.
+pub fn blame(path: &Path, text: &str, line: usize) -> Result<String, String> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let name = path.file_name().ok_or("The buffer has no file name")?;
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    let mut child = command
+        .args(["blame", "--porcelain", "--contents", "-", "-L"])
+        .arg(format!("{0},{0}", line + 1))
+        .arg("--")
+        .arg(name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // git reads all of the text before saying anything.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let commit = lines
+        .next()
+        .and_then(|header| header.split(' ').next())
+        .ok_or("git blame said nothing")?;
+    if commit.bytes().all(|digit| digit == b'0') {
+        return Ok("Not committed yet".to_string());
+    }
+    let (mut author, mut time, mut summary) = ("", None, "");
+    for line in lines {
+        if let Some(name) = line.strip_prefix("author ") {
+            author = name;
+        } else if let Some(seconds) = line.strip_prefix("author-time ") {
+            time = seconds.parse::<i64>().ok();
+        } else if let Some(text) = line.strip_prefix("summary ") {
+            summary = text;
+        }
+    }
+    let date = time.map(date).unwrap_or_default();
+    Ok(format!("{:.8} {author}, {date}: {summary}", commit))
+}
+
+/// The branch checked out in the repository `dir` is in, or the commit
+/// when none is.
+pub fn branch(dir: &Path) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().to_string());
+    }
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch != "HEAD" {
+        return Ok(branch);
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--short", "HEAD"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The day `seconds` after the Unix epoch falls on, as `YYYY-MM-DD` in UTC.
+fn date(seconds: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, with eras of 400 years.
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,322 @@
+use crate::commands::Command;
+use crate::registers;
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::event::{Event, EventResult, Key};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How keys typed into a buffer are taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Keys type, as they always have.
+    #[default]
+    Off,
+    /// Keys select text, and act on what's selected.
+    Normal,
+    /// Keys type, until Esc goes back to `Normal`.
+    Insert,
+}
+
+impl Mode {
+    /// What the status bar shows for the mode, if anything.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Mode::Off => None,
+            Mode::Normal => Some("NORMAL"),
+            Mode::Insert => Some("INSERT"),
+        }
+    }
+}
+
+/// The selection-first grammar: in its normal mode every motion selects,
+/// replacing the selection or, typed in upper case, extending it, and the
+/// verbs that follow act on whatever is selected. A register named after
+/// `"` is what the next yank, delete, change or paste uses instead of the
+/// clipboard.
+#[derive(Debug, Default)]
+pub struct Grammar {
+    mode: Mode,
+    /// The first key of a two-key command (`g`, `G`, `r` or `"`), once typed.
+    pending: Option<char>,
+    /// The register named for the next verb.
+    register: Option<char>,
+}
+
+impl Grammar {
+    /// The mode keys are taken in.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Switches the grammar on, in normal mode, or off again.
+    pub fn toggle(&mut self) {
+        self.mode = match self.mode {
+            Mode::Off => Mode::Normal,
+            _ => Mode::Off,
+        };
+        self.pending = None;
+        self.register = None;
+    }
+
+    /// Goes back to normal mode from insert mode.
+    pub fn leave_insert(&mut self) {
+        if self.mode == Mode::Insert {
+            self.mode = Mode::Normal;
+        }
+    }
+}
+
+/// Takes `event` as a normal-mode key. Keys the grammar has no use for go
+/// on to the keymap; other characters are swallowed, so they never type.
+pub fn on_event(
+    mut ws: MutexGuard<Workspace>,
+    workspace: &Arc<Mutex<Workspace>>,
+    event: Event,
+) -> EventResult {
+    let ch = match event {
+        Event::Char(ch) => ch,
+        Event::Key(Key::Left) => 'h',
+        Event::Key(Key::Down) => 'j',
+        Event::Key(Key::Up) => 'k',
+        Event::Key(Key::Right) => 'l',
+        Event::Shift(Key::Left) => 'H',
+        Event::Shift(Key::Down) => 'J',
+        Event::Shift(Key::Up) => 'K',
+        Event::Shift(Key::Right) => 'L',
+        Event::Key(Key::Esc) => ';',
+        _ => return EventResult::Ignored,
+    };
+    if let Some(first) = ws.grammar_mut().pending.take() {
+        let count = ws.take_count().unwrap_or(1);
+        let ed = ws.editor_mut();
+        match first {
+            'r' => replace_with(ed, ch),
+            '"' => {
+                ws.grammar_mut().register = registers::is_register(ch).then_some(ch);
+            }
+            _ => {
+                let motion: fn(&mut Editor) = match ch {
+                    'h' => |ed| ed.move_line_start(),
+                    'l' => |ed| ed.move_line_end(),
+                    'g' | 'k' => Editor::goto_start,
+                    'j' | 'e' => Editor::goto_end,
+                    _ => return EventResult::Consumed(None),
+                };
+                select(ed, first == 'G', count, motion);
+            }
+        }
+        return EventResult::Consumed(None);
+    }
+    // Digits make up a count for the motion or command that follows.
+    if let Some(digit) = ch.to_digit(10) {
+        if ws.count().is_none() {
+            if digit == 0 {
+                return EventResult::Consumed(None);
+            }
+            ws.start_count();
+        }
+        ws.type_count_digit(digit);
+        return EventResult::Consumed(None);
+    }
+    let motion: Option<fn(&mut Editor)> = match ch.to_ascii_lowercase() {
+        'h' => Some(|ed| ed.move_left()),
+        'j' => Some(|ed| ed.move_down()),
+        'k' => Some(|ed| ed.move_up()),
+        'l' => Some(|ed| ed.move_right()),
+        'w' => Some(|ed| ed.move_word_right(false)),
+        'b' => Some(|ed| ed.move_word_left(false)),
+        'm' => Some(Editor::jump_to_match),
+        _ => None,
+    };
+    if let Some(motion) = motion {
+        let count = ws.take_count().unwrap_or(1);
+        select(ws.editor_mut(), ch.is_ascii_uppercase(), count, motion);
+        return EventResult::Consumed(None);
+    }
+
+    let read_only = ws.editor().is_read_only();
+    // Anything but a motion uses up the register named, if only to drop it.
+    let register = ws.grammar_mut().register.take();
+    let command = match ch {
+        'g' | 'G' | 'r' | '"' => {
+            ws.grammar_mut().pending = Some(ch);
+            ws.grammar_mut().register = register;
+            None
+        }
+        'x' => {
+            ws.editor_mut().for_each_cursor(Editor::select_lines);
+            None
+        }
+        '%' => {
+            let ed = ws.editor_mut();
+            ed.clear_carets();
+            ed.update_selection(0, ed.buffer().len());
+            None
+        }
+        ';' => {
+            ws.editor_mut().for_each_cursor(|ed| collapse(ed, false));
+            None
+        }
+        ',' => {
+            ws.editor_mut().clear_carets();
+            None
+        }
+        'C' => Some(Command::AddCursorBelow),
+        'd' | 'c' => {
+            ws.editor_mut().for_each_cursor(select_if_empty);
+            if ch == 'c' && !read_only {
+                ws.grammar_mut().mode = Mode::Insert;
+            }
+            match register {
+                Some(name) if !read_only => {
+                    ws.cut_to(name);
+                    None
+                }
+                Some(_) => None,
+                None => Some(Command::Cut),
+            }
+        }
+        'y' => match register {
+            Some(name) => {
+                ws.copy_to(name);
+                None
+            }
+            None => Some(Command::Copy),
+        },
+        'p' | 'P' | 'R' => {
+            if ch != 'R' {
+                ws.editor_mut()
+                    .for_each_cursor(|ed| collapse(ed, ch == 'p'));
+            }
+            match register {
+                Some(name) if !read_only => {
+                    ws.paste_from(name);
+                    None
+                }
+                Some(_) => None,
+                None => Some(Command::Paste),
+            }
+        }
+        'u' => Some(Command::Undo),
+        'U' => Some(Command::Redo),
+        '`' | '~' => {
+            ws.editor_mut().for_each_cursor(select_if_empty);
+            Some(if ch == '~' {
+                Command::Uppercase
+            } else {
+                Command::Lowercase
+            })
+        }
+        '>' | '<' if !read_only => {
+            let dedent = ch == '<';
+            let options = ws.filetype_options();
+            ws.editor_mut()
+                .for_each_cursor(|ed| ed.reindent(dedent, &options));
+            None
+        }
+        '|' => Some(Command::Pipe),
+        '!' => Some(Command::ReadShell),
+        '/' => Some(Command::Search),
+        'n' => Some(Command::SearchNext),
+        'N' => Some(Command::SearchPrevious),
+        'i' | 'a' | 'I' | 'A' | 'o' | 'O' if !read_only => {
+            let options = ws.filetype_options();
+            let ed = ws.editor_mut();
+            match ch {
+                'i' => ed.for_each_cursor(|ed| collapse(ed, false)),
+                'a' => ed.for_each_cursor(|ed| collapse(ed, true)),
+                'I' => ed.for_each_cursor(|ed| {
+                    ed.clear_selection();
+                    ed.move_line_start();
+                }),
+                'A' => ed.for_each_cursor(|ed| {
+                    ed.clear_selection();
+                    ed.move_line_end();
+                }),
+                'o' => ed.for_each_cursor(|ed| {
+                    ed.clear_selection();
+                    ed.move_line_end();
+                    ed.newline(&options);
+                }),
+                _ => ed.for_each_cursor(|ed| {
+                    ed.clear_selection();
+                    let line = ed.buffer().line_of(ed.cursor());
+                    let start = ed.buffer().line_start(line);
+                    ed.set_cursor(start);
+                    ed.insert_str("\n");
+                    ed.move_left();
+                }),
+            }
+            ws.grammar_mut().mode = Mode::Insert;
+            None
+        }
+        _ => None,
+    };
+    match command {
+        Some(command) => {
+            let workspace = workspace.clone();
+            EventResult::with_cb(move |s| command.run(s, &workspace))
+        }
+        None => {
+            ws.take_count();
+            EventResult::Consumed(None)
+        }
+    }
+}
+
+/// Runs `motion` `count` times at every cursor, selecting what it moves
+/// over: from where the cursor was, or when `extend`ing from the anchor of
+/// the selection already there.
+fn select(ed: &mut Editor, extend: bool, count: usize, motion: fn(&mut Editor)) {
+    ed.for_each_cursor(|ed| {
+        let anchor = match ed.selection {
+            Some(sel) if extend => sel.anchor,
+            _ => ed.cursor(),
+        };
+        ed.clear_selection();
+        for _ in 0..count {
+            motion(ed);
+        }
+        ed.update_selection(anchor, ed.cursor());
+    });
+}
+
+/// Drops the selection, leaving the cursor at its end or its start.
+fn collapse(ed: &mut Editor, to_end: bool) {
+    if let Some(sel) = ed.selection {
+        ed.clear_selection();
+        ed.set_cursor(if to_end { sel.end() } else { sel.start() });
+    }
+}
+
+/// Selects the character under the cursor when nothing is, so that verbs
+/// always have something to act on.
+fn select_if_empty(ed: &mut Editor) {
+    if ed.selection.is_none_or(|sel| sel.is_empty()) {
+        if let Some(end) = ed.buffer().next_grapheme(ed.cursor()) {
+            ed.update_selection(ed.cursor(), end);
+        }
+    }
+}
+
+/// Replaces every character selected, at every cursor, with `ch`, keeping
+/// the line breaks and the selections.
+fn replace_with(ed: &mut Editor, ch: char) {
+    if ed.is_read_only() {
+        return;
+    }
+    ed.for_each_cursor(|ed| {
+        select_if_empty(ed);
+        let Some(sel) = ed.selection else {
+            return;
+        };
+        let replaced: String = ed
+            .buffer()
+            .slice(sel.range())
+            .chars()
+            .map(|c| if c == '\n' { c } else { ch })
+            .collect();
+        ed.insert_str(&replaced);
+        ed.update_selection(sel.start(), sel.start() + replaced.len());
+    });
+}
@@ -0,0 +1,228 @@
+use cursive::theme::PaletteStyle;
+use cursive::{Printer, Vec2};
+
+/// Bytes shown on each row of the dump.
+pub const ROW: usize = 16;
+
+/// Column the text side of the dump starts in.
+const TEXT_X: usize = 10 + 3 * ROW + 2;
+
+/// The side of the dump typing goes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Hex,
+    Text,
+}
+
+/// A buffer's bytes, shown and edited as a classic hex dump: the offset,
+/// sixteen bytes in hex, then the same bytes as text.
+///
+/// The cursor is on one byte, or just past the last, where typing appends.
+/// Typing overwrites: two hex digits make a byte on the hex side, and a
+/// printable ASCII character does on the text side.
+#[derive(Clone, Debug)]
+pub struct Hex {
+    bytes: Vec<u8>,
+    cursor: usize,
+    side: Side,
+    /// Whether the next hex digit typed is the low half of the byte.
+    low: bool,
+    /// Whether any byte changed since the dump was made.
+    changed: bool,
+}
+
+impl Hex {
+    /// A dump of `bytes`, with the cursor on the first.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            cursor: 0,
+            side: Side::Hex,
+            low: false,
+            changed: false,
+        }
+    }
+
+    /// The bytes, as edited so far.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Whether any byte changed since the dump was made.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Offset of the byte the cursor is on.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves the cursor to byte `offset`, or just past the last.
+    pub fn move_to(&mut self, offset: usize) {
+        self.cursor = offset.min(self.bytes.len());
+        self.low = false;
+    }
+
+    /// Moves the cursor `delta` bytes along, stopping at either end.
+    pub fn move_by(&mut self, delta: isize) {
+        self.move_to(self.cursor.saturating_add_signed(delta));
+    }
+
+    /// Moves the cursor to the start of its row.
+    pub fn move_row_start(&mut self) {
+        self.move_to(self.cursor - self.cursor % ROW);
+    }
+
+    /// Moves the cursor to the last byte of its row.
+    pub fn move_row_end(&mut self) {
+        self.move_to(self.cursor - self.cursor % ROW + ROW - 1);
+    }
+
+    /// Switches typing between the hex and text sides.
+    pub fn switch_side(&mut self) {
+        self.side = match self.side {
+            Side::Hex => Side::Text,
+            Side::Text => Side::Hex,
+        };
+        self.low = false;
+    }
+
+    /// Types `ch` over the byte under the cursor, returning `false` if it
+    /// isn't something the side the cursor is on takes.
+    pub fn type_char(&mut self, ch: char) -> bool {
+        match self.side {
+            Side::Hex => {
+                let Some(digit) = ch.to_digit(16) else {
+                    return false;
+                };
+                let digit = digit as u8;
+                if self.cursor == self.bytes.len() {
+                    self.bytes.push(0);
+                }
+                let byte = &mut self.bytes[self.cursor];
+                if self.low {
+                    *byte = (*byte & 0xf0) | digit;
+                    self.cursor += 1;
+                } else {
+                    *byte = (digit << 4) | (*byte & 0x0f);
+                }
+                self.low = !self.low;
+            }
+            Side::Text => {
+                if !(ch.is_ascii_graphic() || ch == ' ') {
+                    return false;
+                }
+                if self.cursor == self.bytes.len() {
+                    self.bytes.push(0);
+                }
+                self.bytes[self.cursor] = ch as u8;
+                self.cursor += 1;
+            }
+        }
+        self.changed = true;
+        true
+    }
+
+    /// Inserts a zero byte at the cursor.
+    pub fn insert(&mut self) -> bool {
+        self.bytes.insert(self.cursor, 0);
+        self.low = false;
+        self.changed = true;
+        true
+    }
+
+    /// Deletes the byte under the cursor, returning `false` past the end.
+    pub fn delete(&mut self) -> bool {
+        if self.cursor == self.bytes.len() {
+            return false;
+        }
+        self.bytes.remove(self.cursor);
+        self.low = false;
+        self.changed = true;
+        true
+    }
+
+    /// Deletes the byte before the cursor, returning `false` at the start.
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.move_by(-1);
+        self.delete()
+    }
+
+    /// Where the cursor is drawn, as a (column, row) from the top of the
+    /// dump, on the side typing goes to.
+    pub fn cursor_cell(&self) -> (usize, usize) {
+        let column = self.cursor % ROW;
+        let x = match self.side {
+            Side::Hex => hex_x(column) + usize::from(self.low),
+            Side::Text => TEXT_X + 1 + column,
+        };
+        (x, self.cursor / ROW)
+    }
+
+    /// Draws the dump scrolled to `offset`, a (column, row) like the
+    /// cursor's cell. The byte under the cursor is marked on both sides,
+    /// most strongly on the one typing goes to.
+    pub fn draw(&self, printer: &Printer, offset: Vec2) {
+        printer.with_style(PaletteStyle::EditableText, |printer| {
+            for y in 0..printer.size.y {
+                printer.print_hline((0, y), printer.size.x, " ");
+            }
+        });
+        let printer = &printer.content_offset((offset.x, 0));
+        let top = offset.y;
+        // The row just past the last byte still shows, to append to.
+        let rows = self.bytes.len() / ROW + 1;
+        for y in 0..printer.size.y.min(rows.saturating_sub(top)) {
+            let row = top + y;
+            let start = row * ROW;
+            printer.with_style(PaletteStyle::Secondary, |printer| {
+                printer.print((0, y), &format!("{start:08x}"));
+            });
+            printer.print((TEXT_X, y), "|");
+            let end = (start + ROW).min(self.bytes.len());
+            for (column, offset) in (start..start + ROW).enumerate() {
+                let byte = self.bytes.get(offset).copied();
+                let style = |side| {
+                    if !printer.focused || offset != self.cursor {
+                        PaletteStyle::EditableText
+                    } else if side == self.side {
+                        PaletteStyle::EditableTextCursor
+                    } else {
+                        PaletteStyle::Highlight
+                    }
+                };
+                let hex = byte.map_or("  ".to_string(), |byte| format!("{byte:02x}"));
+                let text = byte.map_or(" ".to_string(), |byte| printable(byte).to_string());
+                if offset <= end {
+                    printer.with_style(style(Side::Hex), |printer| {
+                        printer.print((hex_x(column), y), &hex);
+                    });
+                    printer.with_style(style(Side::Text), |printer| {
+                        printer.print((TEXT_X + 1 + column, y), &text);
+                    });
+                }
+            }
+            printer.print((TEXT_X + 1 + (end - start), y), "|");
+        }
+    }
+}
+
+/// Column the hex digits of the byte in `column` of a row start in; the
+/// second eight bytes are set a little apart from the first.
+fn hex_x(column: usize) -> usize {
+    10 + 3 * column + usize::from(column >= ROW / 2)
+}
+
+/// How `byte` shows on the text side: itself if it's printable ASCII, a
+/// dot otherwise.
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
@@ -0,0 +1,376 @@
+use crate::buffer::Buffer;
+use crate::filetype::Filetype;
+use std::ops::Range;
+
+/// The kinds of text that get a color of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+}
+
+/// How `filetype` is colored, if it is.
+fn syntax(filetype: Filetype) -> Option<&'static Syntax> {
+    match filetype {
+        Filetype::Plain => None,
+        Filetype::Rust => Some(&RUST),
+        Filetype::C => Some(&C),
+        Filetype::Python => Some(&PYTHON),
+        Filetype::JavaScript => Some(&JAVASCRIPT),
+        Filetype::Shell => Some(&SHELL),
+        Filetype::Toml => Some(&TOML),
+        Filetype::Json => Some(&JSON),
+    }
+}
+
+/// What a language looks like, as far as coloring it goes.
+struct Syntax {
+    line_comment: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Whether a string left open at the end of a line carries on to the next.
+    multiline_strings: bool,
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    /// Characters that, ending a line, open a block indented one level deeper.
+    indent_after: &'static [char],
+}
+
+const RUST: Syntax = Syntax {
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    // A `'` is as likely to start a lifetime as a character literal.
+    quotes: &['"'],
+    multiline_strings: true,
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64", "String", "Vec", "Option", "Result", "Box", "Some",
+        "None", "Ok", "Err",
+    ],
+    indent_after: &['{', '[', '('],
+};
+
+const C: Syntax = Syntax {
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    multiline_strings: false,
+    keywords: &[
+        "break",
+        "case",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "extern",
+        "for",
+        "goto",
+        "if",
+        "inline",
+        "namespace",
+        "new",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "typedef",
+        "union",
+        "using",
+        "volatile",
+        "while",
+        "#include",
+        "#define",
+        "#if",
+        "#ifdef",
+        "#ifndef",
+        "#endif",
+        "#else",
+    ],
+    types: &[
+        "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "bool",
+        "size_t",
+    ],
+    indent_after: &['{', '[', '('],
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    multiline_strings: false,
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ],
+    types: &[
+        "bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple",
+    ],
+    indent_after: &[':', '{', '[', '('],
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    line_comment: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    multiline_strings: false,
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "let",
+        "new",
+        "null",
+        "return",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typeof",
+        "undefined",
+        "var",
+        "while",
+        "yield",
+    ],
+    types: &[
+        "Array", "Boolean", "Map", "Number", "Object", "Promise", "Set", "String",
+    ],
+    indent_after: &['{', '[', '('],
+};
+
+const SHELL: Syntax = Syntax {
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    multiline_strings: true,
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "until", "while",
+    ],
+    types: &[],
+    indent_after: &['{', '('],
+};
+
+const TOML: Syntax = Syntax {
+    line_comment: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    multiline_strings: false,
+    keywords: &["true", "false"],
+    types: &[],
+    indent_after: &['[', '{'],
+};
+
+const JSON: Syntax = Syntax {
+    line_comment: &[],
+    block_comment: None,
+    quotes: &['"'],
+    multiline_strings: false,
+    keywords: &["true", "false", "null"],
+    types: &[],
+    indent_after: &['{', '['],
+};
+
+/// What a line starts inside of, carried over from the line before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Normal,
+    BlockComment,
+    String(char),
+}
+
+/// The colored spans of one line, as byte ranges within the line.
+#[derive(Clone, Debug)]
+struct Line {
+    end: State,
+    spans: Vec<(Range<usize>, Token)>,
+}
+
+/// Colors a buffer line by line, remembering the result.
+///
+/// Lines are only highlighted when asked for, top down, since a line's
+/// colors depend on the ones above it. An edit forgets the lines from the
+/// one it touched on, so only those get highlighted again.
+#[derive(Clone, Debug)]
+pub struct Highlighter {
+    filetype: Filetype,
+    lines: Vec<Line>,
+}
+
+impl Highlighter {
+    /// Creates a highlighter for text in the language `filetype`.
+    pub fn new(filetype: Filetype) -> Self {
+        Self {
+            filetype,
+            lines: Vec::new(),
+        }
+    }
+
+    /// The language being highlighted.
+    pub fn filetype(&self) -> Filetype {
+        self.filetype
+    }
+
+    /// Whether a line ending in `ch` opens a block whose lines get indented
+    /// a level deeper.
+    pub fn indents_after(&self, ch: char) -> bool {
+        syntax(self.filetype).is_some_and(|syntax| syntax.indent_after.contains(&ch))
+    }
+
+    /// Forgets the colors of line `line` and everything after it, after an
+    /// edit there.
+    pub fn invalidate(&mut self, line: usize) {
+        self.lines.truncate(line);
+    }
+
+    /// Makes sure the first `count` lines of `buffer` are highlighted.
+    pub fn update(&mut self, buffer: &Buffer, count: usize) {
+        let Some(syntax) = syntax(self.filetype) else {
+            return;
+        };
+        let count = count.min(buffer.line_count());
+        while self.lines.len() < count {
+            let start = self.lines.last().map_or(State::Normal, |line| line.end);
+            let text = buffer.line(self.lines.len());
+            self.lines.push(highlight_line(syntax, &text, start));
+        }
+    }
+
+    /// The colored spans of line `line`, or none if it isn't highlighted.
+    pub fn spans(&self, line: usize) -> &[(Range<usize>, Token)] {
+        self.lines.get(line).map_or(&[], |line| &line.spans)
+    }
+}
+
+/// Splits `text` into colored spans, starting in state `state`.
+fn highlight_line(syntax: &Syntax, text: &str, mut state: State) -> Line {
+    let mut spans = Vec::new();
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    // Where the comment or string being scanned started.
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        match state {
+            State::BlockComment => {
+                let (_, close) = syntax.block_comment.unwrap_or_default();
+                match rest.find(close) {
+                    Some(pos) => {
+                        i += pos + close.len();
+                        spans.push((start..i, Token::Comment));
+                        state = State::Normal;
+                    }
+                    None => i = text.len(),
+                }
+            }
+            State::String(quote) => {
+                let mut chars = rest.char_indices();
+                let mut end = None;
+                while let Some((pos, ch)) = chars.next() {
+                    if ch == '\\' {
+                        chars.next();
+                    } else if ch == quote {
+                        end = Some(pos + ch.len_utf8());
+                        break;
+                    }
+                }
+                match end {
+                    Some(len) => {
+                        i += len;
+                        spans.push((start..i, Token::String));
+                        state = State::Normal;
+                    }
+                    None => i = text.len(),
+                }
+            }
+            State::Normal => {
+                let ch = rest.chars().next().unwrap_or_default();
+                let after_word = text[..i].chars().next_back().is_some_and(is_word);
+                if syntax.line_comment.iter().any(|c| rest.starts_with(c)) {
+                    spans.push((i..text.len(), Token::Comment));
+                    return Line { end: state, spans };
+                } else if let Some((open, _)) = syntax
+                    .block_comment
+                    .filter(|(open, _)| rest.starts_with(open))
+                {
+                    start = i;
+                    i += open.len();
+                    state = State::BlockComment;
+                } else if syntax.quotes.contains(&ch) {
+                    start = i;
+                    i += ch.len_utf8();
+                    state = State::String(ch);
+                } else if ch.is_ascii_digit() && !after_word {
+                    let len = rest
+                        .find(|ch: char| !is_word(ch) && ch != '.')
+                        .unwrap_or(rest.len());
+                    spans.push((i..i + len, Token::Number));
+                    i += len;
+                } else if (is_word(ch) || ch == '#') && !after_word {
+                    let len = rest[ch.len_utf8()..]
+                        .find(|ch: char| !is_word(ch))
+                        .map_or(rest.len(), |len| len + ch.len_utf8());
+                    let word = &rest[..len];
+                    if syntax.keywords.contains(&word) {
+                        spans.push((i..i + len, Token::Keyword));
+                    } else if syntax.types.contains(&word) {
+                        spans.push((i..i + len, Token::Type));
+                    }
+                    i += len;
+                } else {
+                    i += ch.len_utf8();
+                }
+            }
+        }
+    }
+
+    // Whatever is still open runs to the end of the line.
+    match state {
+        State::BlockComment => spans.push((start..text.len(), Token::Comment)),
+        State::String(_) => {
+            spans.push((start..text.len(), Token::String));
+            if !syntax.multiline_strings {
+                state = State::Normal;
+            }
+        }
+        State::Normal => {}
+    }
+    Line { end: state, spans }
+}
@@ -0,0 +1,132 @@
+use crate::keymap::{self, Lookup};
+use crate::macros;
+use crate::workspace::Workspace;
+use cursive::event::{Event, EventResult};
+use cursive::view::Nameable;
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Name of the popup listing the keys that may come next.
+const NAME: &str = "key_hints";
+
+/// How long a sequence waits for its next key before it's given up.
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The start of a key sequence, pressed and waiting for the rest.
+#[derive(Debug, Default)]
+pub struct Pending {
+    keys: Vec<Event>,
+    /// Counts the sequences started, so a timeout knows if its own is
+    /// still the one waiting.
+    serial: u64,
+}
+
+impl Pending {
+    /// The keys pressed so far; none when no sequence has been started.
+    pub fn keys(&self) -> &[Event] {
+        &self.keys
+    }
+}
+
+/// Takes `event` as the next key of a sequence: runs the command the keys
+/// make up, or waits for more while showing what may follow. Returns `None`
+/// for a key that neither starts a sequence nor is bound at all; one that
+/// breaks off a sequence is dropped along with it.
+pub fn press(workspace: &Arc<Mutex<Workspace>>, event: &Event) -> Option<EventResult> {
+    let mut ws = workspace.lock().unwrap();
+    let mut keys = ws.pending().keys.clone();
+    let started = !keys.is_empty();
+    keys.push(event.clone());
+    if let Some(events) = ws.macros().bound_to(&keys).map(<[Event]>::to_vec) {
+        ws.pending_mut().keys.clear();
+        let workspace = workspace.clone();
+        return Some(EventResult::with_cb(move |s| {
+            close(s);
+            macros::play_saved(s, &workspace, &events);
+        }));
+    }
+    let lookup = match ws.keymap().lookup(&keys) {
+        Lookup::None if !ws.macros().continuations(&keys).is_empty() => Lookup::Prefix,
+        lookup => lookup,
+    };
+    let pending = ws.pending_mut();
+    match lookup {
+        Lookup::Command(command) => {
+            pending.keys.clear();
+            let workspace = workspace.clone();
+            Some(EventResult::with_cb(move |s| {
+                close(s);
+                command.run(s, &workspace);
+            }))
+        }
+        Lookup::Prefix => {
+            pending.keys = keys;
+            pending.serial += 1;
+            let serial = pending.serial;
+            let workspace = workspace.clone();
+            Some(EventResult::with_cb(move |s| {
+                show(s, &workspace);
+                expire(s, &workspace, serial);
+            }))
+        }
+        Lookup::None if started => {
+            pending.keys.clear();
+            Some(EventResult::with_cb(close))
+        }
+        Lookup::None => None,
+    }
+}
+
+/// Shows the keys that may follow those pressed, with what each runs.
+fn show(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let keys = ws.pending().keys();
+    let title = keymap::format_sequence(keys).unwrap_or_default();
+    let mut next: Vec<(String, String)> = ws
+        .keymap()
+        .continuations(keys)
+        .into_iter()
+        .map(|(key, command)| (key, command.name().to_string()))
+        .chain(ws.macros().continuations(keys))
+        .collect();
+    drop(ws);
+    next.sort_by(|a, b| a.0.cmp(&b.0));
+    let width = next.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    let text = next
+        .iter()
+        .map(|(key, name)| format!("{key:<width$}  {name}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    close(s);
+    s.add_layer(Dialog::around(TextView::new(text).with_name(NAME)).title(title));
+}
+
+/// Gives the sequence `serial` up, unless it's been finished or broken off
+/// by then, once `TIMEOUT` has passed.
+fn expire(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, serial: u64) {
+    let sink = s.cb_sink().clone();
+    let workspace = workspace.clone();
+    thread::spawn(move || {
+        thread::sleep(TIMEOUT);
+        let _ = sink.send(Box::new(move |s| {
+            let mut ws = workspace.lock().unwrap();
+            let pending = ws.pending_mut();
+            if pending.serial == serial && !pending.keys.is_empty() {
+                pending.keys.clear();
+                drop(ws);
+                close(s);
+            }
+        }));
+    });
+}
+
+/// Takes the popup down, if it's up.
+fn close(s: &mut Cursive) {
+    let screen = s.screen_mut();
+    if let Some(layer) = screen.find_layer_from_name(NAME) {
+        screen.remove_layer(layer);
+    }
+}
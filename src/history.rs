@@ -0,0 +1,570 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Most undo steps kept in a stored log; older ones are dropped.
+const STORED_STEPS: usize = 1000;
+
+/// Most characters of an edit shown when summing up a step.
+const SUMMARY: usize = 24;
+
+/// One change to the text: `removed` was replaced by `inserted` at `offset`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+/// Edits undone and redone together, with the cursor to restore on undo.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub cursor: usize,
+    pub edits: Vec<Edit>,
+}
+
+impl Step {
+    /// The edits undoing the step makes, in order: each range of the text
+    /// replaced with what was there before.
+    pub fn undone(&self) -> impl Iterator<Item = (Range<usize>, &str)> {
+        self.edits.iter().rev().map(|edit| {
+            let end = edit.offset + edit.inserted.len();
+            (edit.offset..end, edit.removed.as_str())
+        })
+    }
+
+    /// The edits redoing the step makes, in order: each range of the text
+    /// replaced with what the step put there.
+    pub fn redone(&self) -> impl Iterator<Item = (Range<usize>, &str)> {
+        self.edits.iter().map(|edit| {
+            let end = edit.offset + edit.removed.len();
+            (edit.offset..end, edit.inserted.as_str())
+        })
+    }
+
+    /// What the step did: the text its first edit put in, or else took out,
+    /// and how many other edits came with it. Characters typed one after
+    /// another count as a single edit.
+    pub fn summary(&self) -> String {
+        let Some(edit) = self.edits.first() else {
+            return String::new();
+        };
+        let (sign, mut text) = if edit.inserted.is_empty() {
+            ('-', edit.removed.clone())
+        } else {
+            ('+', edit.inserted.clone())
+        };
+        let mut end = edit.offset + edit.inserted.len();
+        let mut typed = 1;
+        if sign == '+' {
+            for edit in &self.edits[1..] {
+                if !edit.removed.is_empty() || edit.offset != end {
+                    break;
+                }
+                text += &edit.inserted;
+                end += edit.inserted.len();
+                typed += 1;
+            }
+        }
+        let mut shown: String = text.chars().take(SUMMARY).collect();
+        if shown.len() < text.len() {
+            shown.push('…');
+        }
+        let shown = shown.escape_debug();
+        match self.edits.len() - typed {
+            0 => format!("{sign}\"{shown}\""),
+            more => format!("{sign}\"{shown}\" and {more} more"),
+        }
+    }
+}
+
+/// Every state one buffer's text has been in, as a tree: undoing and then
+/// editing starts a new branch rather than throwing the undone steps away.
+///
+/// Edits made between `begin` and `end` form a single step, as do runs of
+/// typed characters; anything else is a step of its own.
+#[derive(Clone, Debug)]
+pub struct History {
+    /// The states, in the order they were first reached; the first is the
+    /// text as loaded.
+    nodes: Vec<Node>,
+    /// The state the text is in.
+    current: usize,
+    /// How many `begin`s are still waiting for their `end`.
+    depth: usize,
+    /// Edits recorded since the outermost `begin`.
+    grouped: usize,
+    /// Whether the next edit has to start a new step.
+    sealed: bool,
+}
+
+/// A state of the text, and how it was reached from the one before.
+#[derive(Clone, Debug)]
+struct Node {
+    /// The state `step` was made in; the first state is its own.
+    parent: usize,
+    /// What changed the parent's text into this state's.
+    step: Step,
+    /// The states reached from this one, oldest first.
+    children: Vec<usize>,
+    /// The child redoing goes to: the one last left by undoing, or made.
+    next: Option<usize>,
+}
+
+impl Node {
+    fn new(parent: usize, step: Step) -> Self {
+        Self {
+            parent,
+            step,
+            children: Vec::new(),
+            next: None,
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    /// Creates a history holding only the text as it is.
+    pub fn new() -> Self {
+        let root = Step {
+            cursor: 0,
+            edits: Vec::new(),
+        };
+        Self {
+            nodes: vec![Node::new(0, root)],
+            current: 0,
+            depth: 0,
+            grouped: 0,
+            sealed: true,
+        }
+    }
+
+    /// Starts grouping edits into one step, until the matching `end`.
+    pub fn begin(&mut self) {
+        if self.depth == 0 {
+            self.grouped = 0;
+        }
+        self.depth += 1;
+    }
+
+    /// Ends a group started by `begin`. A group of one edit is treated like
+    /// that edit alone, so typed characters still run together.
+    pub fn end(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && self.grouped > 1 {
+            self.sealed = true;
+        }
+    }
+
+    /// Records an edit made with the cursor at `cursor`, as a step to a new
+    /// state or as part of the last one. The steps that were undone stay
+    /// in the tree, on a branch of their own.
+    pub fn record(&mut self, cursor: usize, edit: Edit) {
+        let typing =
+            edit.removed.is_empty() && edit.inserted.chars().count() == 1 && edit.inserted != "\n";
+
+        let node = &self.nodes[self.current];
+        // Only the newest state may grow; one with states after it is where
+        // a branch starts.
+        let merge = self.current > 0
+            && node.children.is_empty()
+            && if self.depth > 0 && self.grouped > 0 {
+                true
+            } else {
+                !self.sealed
+                    && typing
+                    && node.step.edits.last().is_some_and(|last| {
+                        last.removed.is_empty() && last.offset + last.inserted.len() == edit.offset
+                    })
+            };
+        if merge {
+            self.nodes[self.current].step.edits.push(edit);
+        } else {
+            let child = self.nodes.len();
+            let step = Step {
+                cursor,
+                edits: vec![edit],
+            };
+            self.nodes.push(Node::new(self.current, step));
+            let parent = &mut self.nodes[self.current];
+            parent.children.push(child);
+            parent.next = Some(child);
+            self.current = child;
+        }
+        if self.depth > 0 {
+            self.grouped += 1;
+        }
+        // Only typing continues a step; `end` seals a group of several edits.
+        self.sealed = !typing;
+    }
+
+    /// Takes the step that led to the current state, going back to the
+    /// state before it.
+    pub fn undo(&mut self) -> Option<Step> {
+        if self.current == 0 {
+            return None;
+        }
+        let child = self.current;
+        self.current = self.nodes[child].parent;
+        self.nodes[self.current].next = Some(child);
+        self.sealed = true;
+        Some(self.nodes[child].step.clone())
+    }
+
+    /// Takes the step to the state last undone from the current one, or
+    /// made from it, going on to that state.
+    pub fn redo(&mut self) -> Option<Step> {
+        let child = self.nodes[self.current].next?;
+        self.current = child;
+        self.sealed = true;
+        Some(self.nodes[child].step.clone())
+    }
+
+    /// The state the text is in, as an index into the states.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Every state, depth first from the text as loaded, each with how many
+    /// branches in from the oldest it is: a state's first child carries on
+    /// its branch, and each later one starts a branch further in.
+    pub fn states(&self) -> Vec<(usize, usize)> {
+        let mut states = Vec::new();
+        let mut pending = vec![(0, 0)];
+        while let Some((node, branch)) = pending.pop() {
+            states.push((node, branch));
+            let children = &self.nodes[node].children;
+            for (k, &child) in children.iter().enumerate().rev() {
+                pending.push((child, branch + usize::from(k > 0)));
+            }
+        }
+        states
+    }
+
+    /// The step that reached `state`; the text as loaded has none.
+    pub fn step(&self, state: usize) -> Option<&Step> {
+        (state > 0).then(|| &self.nodes[state].step)
+    }
+
+    /// The way from the current state to `state`: how many steps to undo,
+    /// then the states to redo to one after another. It goes through the
+    /// last state both have come from.
+    pub fn route(&self, state: usize) -> (usize, Vec<usize>) {
+        let ancestors = |mut node: usize| {
+            let mut path = vec![node];
+            while node != 0 {
+                node = self.nodes[node].parent;
+                path.push(node);
+            }
+            path
+        };
+        let from = ancestors(self.current);
+        let mut to = ancestors(state);
+        let common = to
+            .iter()
+            .position(|node| from.contains(node))
+            .unwrap_or(to.len() - 1);
+        let up = from
+            .iter()
+            .position(|&node| node == to[common])
+            .unwrap_or(0);
+        to.truncate(common);
+        to.reverse();
+        (up, to)
+    }
+
+    /// Makes `state` the one redoing goes to from its parent.
+    pub fn choose(&mut self, state: usize) {
+        let parent = self.nodes[state].parent;
+        if state > 0 {
+            self.nodes[parent].next = Some(state);
+        }
+    }
+
+    /// Loads the log stored for the file at `path`, if it was written for
+    /// exactly `content`; otherwise starts a fresh history.
+    pub fn load(path: &Path, content: &str) -> Self {
+        let log = log_path(path)
+            .and_then(|log| fs::read_to_string(log).ok())
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+        let Some(log) = log else {
+            return Self::new();
+        };
+        if log["content"].as_str() != Some(&content_hash(content)) {
+            return Self::new();
+        }
+        from_json(&log).unwrap_or_default()
+    }
+
+    /// Stores the log for the file at `path`, which now holds `content`.
+    /// A tree grown past the steps kept is stored as just its current
+    /// branch, without the oldest of them.
+    pub fn store(&self, path: &Path, content: &str) -> io::Result<()> {
+        let Some(log) = log_path(path) else {
+            return Ok(());
+        };
+        let trimmed;
+        let history = if self.nodes.len() > STORED_STEPS + 1 {
+            trimmed = self.branch();
+            &trimmed
+        } else {
+            self
+        };
+        let nodes: Vec<Value> = history
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "parent": node.parent,
+                    "next": node.next,
+                    "step": step_to_json(&node.step),
+                })
+            })
+            .collect();
+        let json = json!({
+            "path": path.to_string_lossy(),
+            "content": content_hash(content),
+            "states": nodes,
+            "current": history.current,
+        });
+        if let Some(dir) = log.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(log, json.to_string())
+    }
+
+    /// The current branch alone: the last `STORED_STEPS` steps reaching the
+    /// current state, and those redoing would go on to, with the state the
+    /// first of them starts from as the text loaded.
+    fn branch(&self) -> Self {
+        let mut undo = Vec::new();
+        let mut node = self.current;
+        while node != 0 && undo.len() < STORED_STEPS {
+            undo.push(self.nodes[node].step.clone());
+            node = self.nodes[node].parent;
+        }
+        undo.reverse();
+        let mut redo = Vec::new();
+        let mut node = self.current;
+        while let Some(next) = self.nodes[node]
+            .next
+            .filter(|_| undo.len() + redo.len() < STORED_STEPS)
+        {
+            redo.push(self.nodes[next].step.clone());
+            node = next;
+        }
+        let mut branch = Self::new();
+        let current = undo.len();
+        for step in undo.into_iter().chain(redo) {
+            let child = branch.nodes.len();
+            let parent = child - 1;
+            branch.nodes.push(Node::new(parent, step));
+            branch.nodes[parent].children.push(child);
+            branch.nodes[parent].next = Some(child);
+        }
+        branch.current = current;
+        branch
+    }
+}
+
+fn step_to_json(step: &Step) -> Value {
+    let edits: Vec<Value> = step
+        .edits
+        .iter()
+        .map(|edit| json!([edit.offset, edit.removed, edit.inserted]))
+        .collect();
+    json!({ "cursor": step.cursor, "edits": edits })
+}
+
+fn step_from_json(step: &Value) -> Option<Step> {
+    let edits = step["edits"]
+        .as_array()?
+        .iter()
+        .map(|edit| {
+            Some(Edit {
+                offset: edit[0].as_u64()? as usize,
+                removed: edit[1].as_str()?.to_string(),
+                inserted: edit[2].as_str()?.to_string(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Step {
+        cursor: step["cursor"].as_u64()? as usize,
+        edits,
+    })
+}
+
+/// The history a stored log holds, unless it's been tampered with: every
+/// state but the first has to come from one stored before it.
+fn from_json(log: &Value) -> Option<History> {
+    let mut nodes: Vec<Node> = Vec::new();
+    for (index, node) in log["states"].as_array()?.iter().enumerate() {
+        let parent = node["parent"].as_u64()? as usize;
+        if index > 0 && parent >= index || index == 0 && parent != 0 {
+            return None;
+        }
+        let mut state = Node::new(parent, step_from_json(&node["step"])?);
+        state.next = node["next"].as_u64().map(|next| next as usize);
+        if index > 0 {
+            nodes[parent].children.push(index);
+        }
+        nodes.push(state);
+    }
+    let current = log["current"].as_u64()? as usize;
+    let valid = current < nodes.len()
+        && nodes.iter().enumerate().all(|(index, node)| {
+            node.next.is_none_or(|next| {
+                nodes
+                    .get(next)
+                    .is_some_and(|child| child.parent == index && next > 0)
+            })
+        });
+    valid.then(|| History {
+        nodes,
+        current,
+        ..History::new()
+    })
+}
+
+/// Where the undo log for `path` lives.
+fn log_path(path: &Path) -> Option<PathBuf> {
+    cache_file(path, "undo", "json")
+}
+
+/// Where something of `kind` kept for the file at `path` lives: a file in
+/// the `kind` directory of the cache, named after a hash of the file's
+/// absolute path.
+pub fn cache_file(path: &Path, kind: &str, extension: &str) -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    let absolute = path
+        .canonicalize()
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .ok()?;
+    let hash = fnv1a(absolute.to_string_lossy().as_bytes());
+    let name = format!("{hash:016x}.{extension}");
+    Some(cache.join("wasdev").join(kind).join(name))
+}
+
+/// Fingerprint of a file's content, to tell whether a stored log still applies.
+pub fn content_hash(content: &str) -> String {
+    format!("{:016x}-{}", fnv1a(content.as_bytes()), content.len())
+}
+
+/// 64-bit FNV-1a: stable across runs and Rust versions, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(offset: usize, text: &str) -> Edit {
+        Edit {
+            offset,
+            removed: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn typing_runs_together_until_something_else() {
+        let mut history = History::new();
+        history.record(0, insert(0, "a"));
+        history.record(1, insert(1, "b"));
+        history.record(2, insert(2, "\n"));
+        history.record(3, insert(3, "c"));
+        assert_eq!(history.states().len(), 4);
+        assert_eq!(history.undo().unwrap().edits, vec![insert(3, "c")]);
+        history.undo();
+        let step = history.undo().unwrap();
+        assert_eq!(step.edits, vec![insert(0, "a"), insert(1, "b")]);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn edits_between_begin_and_end_are_one_step() {
+        let mut history = History::new();
+        history.begin();
+        history.record(0, insert(4, ")"));
+        history.record(0, insert(0, "("));
+        history.end();
+        history.record(0, insert(0, "x"));
+        let step = history.undo().unwrap();
+        assert_eq!(step.edits, vec![insert(0, "x")]);
+        let step = history.undo().unwrap();
+        let undone: Vec<_> = step.undone().collect();
+        assert_eq!(undone, vec![(0..1, ""), (4..5, "")]);
+        let redone: Vec<_> = step.redone().collect();
+        assert_eq!(redone, vec![(4..4, ")"), (0..0, "(")]);
+    }
+
+    #[test]
+    fn editing_after_undoing_starts_a_branch() {
+        let mut history = History::new();
+        history.record(0, insert(0, "\n"));
+        history.record(1, insert(1, "\n"));
+        history.undo();
+        history.record(1, insert(1, "x"));
+        assert_eq!(history.states(), vec![(0, 0), (1, 0), (2, 0), (3, 1)]);
+        assert_eq!(history.current(), 3);
+        assert_eq!(history.route(2), (1, vec![2]));
+        assert_eq!(history.route(0), (2, vec![]));
+        history.undo();
+        history.choose(2);
+        assert_eq!(history.redo().unwrap().edits, vec![insert(1, "\n")]);
+    }
+
+    #[test]
+    fn summary_names_the_first_edit() {
+        let mut history = History::new();
+        for (i, ch) in "hello".chars().enumerate() {
+            history.record(i, insert(i, &ch.to_string()));
+        }
+        assert_eq!(history.step(1).unwrap().summary(), "+\"hello\"");
+        let step = Step {
+            cursor: 0,
+            edits: vec![
+                Edit {
+                    offset: 0,
+                    removed: "gone\n".to_string(),
+                    inserted: String::new(),
+                },
+                insert(9, "x"),
+            ],
+        };
+        assert_eq!(step.summary(), "-\"gone\\n\" and 1 more");
+        assert_eq!(history.step(0).map(Step::summary), None);
+    }
+
+    #[test]
+    fn stored_logs_only_load_for_the_same_content() {
+        let dir = std::env::temp_dir().join(format!("wasdev-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        let mut history = History::new();
+        history.record(0, insert(0, "\n"));
+        history.record(1, insert(1, "\n"));
+        history.undo();
+        std::env::set_var("XDG_CACHE_HOME", &dir);
+        history.store(&path, "\n").unwrap();
+        let loaded = History::load(&path, "\n");
+        let fresh = History::load(&path, "other");
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.current(), 1);
+        assert_eq!(loaded.states(), history.states());
+        assert_eq!(fresh.states(), vec![(0, 0)]);
+    }
+}
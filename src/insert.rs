@@ -0,0 +1,101 @@
+use crate::git;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::views::{OnEventView, SelectView};
+use cursive::Cursive;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Opens the insert menu: the date and time in each of the formats the
+/// settings list, a fresh UUID, the file's name and path and the git
+/// branch, each shown as it would be typed. Picking one types it at every
+/// cursor, in place of the selection.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut sv: SelectView<String> = SelectView::new();
+    {
+        let ws = workspace.lock().unwrap();
+        for format in &ws.settings().date_formats {
+            if let Some(date) = date(format) {
+                sv.add_item(entry("Date", &date), date);
+            }
+        }
+        let id = uuid();
+        sv.add_item(entry("UUID", &id), id);
+        let path = ws.editor().path();
+        if let Some(name) = path.and_then(Path::file_name) {
+            let name = name.to_string_lossy().into_owned();
+            sv.add_item(entry("File name", &name), name);
+        }
+        if let Some(path) = path {
+            let path = path.display().to_string();
+            sv.add_item(entry("File path", &path), path);
+        }
+        let dir = path
+            .and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        if let Ok(branch) = git::branch(dir) {
+            sv.add_item(entry("Git branch", &branch), branch);
+        }
+    }
+
+    let value = workspace.clone();
+    sv.set_on_submit(move |s, text: &String| {
+        s.pop_layer();
+        value
+            .lock()
+            .unwrap()
+            .editor_mut()
+            .for_each_cursor(|ed| ed.insert_str(text));
+    });
+    s.add_layer(OnEventView::new(sv).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// How an item is listed: what it is, then what it types.
+fn entry(label: &str, text: &str) -> String {
+    format!("{label:<12}{text}")
+}
+
+/// The date and time now, in the local time zone, as `date` formats them
+/// with `format`; `None` if it can't be run or rejects the format.
+fn date(format: &str) -> Option<String> {
+    let output = Command::new("date")
+        .arg(format!("+{format}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    output
+        .status
+        .success()
+        .then(|| text.trim_end_matches('\n').to_string())
+}
+
+/// A random (version 4) UUID, in its usual hyphenated form.
+fn uuid() -> String {
+    let mut bytes = [0u8; 16];
+    for (i, half) in bytes.chunks_mut(8).enumerate() {
+        // Each `RandomState` is seeded differently, from the operating
+        // system's randomness.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(i);
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,152 @@
+use crate::workspace::Workspace;
+use cursive::Cursive;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// How a job's work reports how far along it is, and learns that it was
+/// cancelled. One that isn't part of a job goes unread.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    done: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Progress {
+    /// Sets how much work there is in all, in whatever unit `advance` uses.
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Counts `amount` more of the work as done.
+    pub fn advance(&self, amount: u64) {
+        self.done.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    /// Whether the job was cancelled, so the work can stop early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// How much of the work is done, in percent, once the total is known.
+    fn percent(&self) -> Option<u64> {
+        let total = self.total.load(Ordering::Relaxed);
+        (total > 0).then(|| self.done.load(Ordering::Relaxed).min(total) * 100 / total)
+    }
+}
+
+/// A reader that counts what's read from it as progress, and fails once
+/// the job is cancelled.
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    /// Wraps `inner`, reporting to `progress`.
+    pub fn new(inner: R, progress: &'a Progress) -> Self {
+        Self { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.progress.is_cancelled() {
+            return Err(io::Error::other("cancelled"));
+        }
+        let read = self.inner.read(buf)?;
+        self.progress.advance(read as u64);
+        Ok(read)
+    }
+}
+
+/// A job in progress.
+struct Job {
+    id: u64,
+    label: String,
+    progress: Progress,
+}
+
+/// The jobs running on worker threads, the latest last.
+#[derive(Default)]
+pub struct Jobs {
+    next_id: u64,
+    running: Vec<Job>,
+}
+
+impl Jobs {
+    /// Registers a job described by `label`, returning its id and where
+    /// its work reports to.
+    fn start(&mut self, label: String) -> (u64, Progress) {
+        self.next_id += 1;
+        let progress = Progress::default();
+        self.running.push(Job {
+            id: self.next_id,
+            label,
+            progress: progress.clone(),
+        });
+        (self.next_id, progress)
+    }
+
+    /// Takes the job `id` off the list, returning `false` if it was
+    /// cancelled meanwhile.
+    fn finish(&mut self, id: u64) -> bool {
+        let before = self.running.len();
+        self.running.retain(|job| job.id != id);
+        self.running.len() < before
+    }
+
+    /// Cancels the latest job, returning `false` if none is running. Its
+    /// work is told to stop, and whatever it comes up with is dropped.
+    pub fn cancel_latest(&mut self) -> bool {
+        let Some(job) = self.running.pop() else {
+            return false;
+        };
+        job.progress.cancelled.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// What the status bar shows while jobs run: the latest one, how far
+    /// along it is, and how many others there are.
+    pub fn describe(&self) -> Option<String> {
+        let job = self.running.last()?;
+        let mut text = job.label.clone();
+        if let Some(percent) = job.progress.percent() {
+            text += &format!(" {percent}%");
+        }
+        if self.running.len() > 1 {
+            text += &format!(" (+{})", self.running.len() - 1);
+        }
+        Some(text)
+    }
+}
+
+/// Runs `work` as a job described by `label` on a thread of its own, so
+/// the screen keeps responding, then hands what it returns to `done` back
+/// on the event loop, unless the job was cancelled first.
+pub fn spawn<T, W, D>(
+    s: &mut Cursive,
+    workspace: &Arc<Mutex<Workspace>>,
+    label: String,
+    work: W,
+    done: D,
+) where
+    T: Send + 'static,
+    W: FnOnce(&Progress) -> T + Send + 'static,
+    D: FnOnce(&mut Cursive, T) + Send + 'static,
+{
+    let (id, progress) = workspace.lock().unwrap().jobs_mut().start(label);
+    let sink = s.cb_sink().clone();
+    let workspace = workspace.clone();
+    thread::spawn(move || {
+        let result = work(&progress);
+        let _ = sink.send(Box::new(move |s| {
+            let finished = workspace.lock().unwrap().jobs_mut().finish(id);
+            if finished {
+                done(s, result);
+            }
+        }));
+    });
+}
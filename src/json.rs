@@ -0,0 +1,108 @@
+use std::fmt;
+
+/// Where some JSON stopped making sense, and why.
+#[derive(Debug)]
+pub struct SyntaxError {
+    message: String,
+    /// 1-based, as in the editor's status line.
+    pub line: usize,
+    pub column: usize,
+    /// The byte offset in the text.
+    pub offset: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Lays out the JSON document in `text` again: one member per line with
+/// `indent` spaces per level of nesting, or all on one line without any
+/// whitespace when there is no indent. Keys keep their order, and strings
+/// and numbers are copied as they are. A trailing newline is kept.
+pub fn reformat(text: &str, indent: Option<usize>) -> Result<String, SyntaxError> {
+    if let Err(err) = serde_json::from_str::<serde_json::Value>(text) {
+        return Err(syntax_error(text, &err));
+    }
+    let newline = |out: &mut String, depth: usize| {
+        if let Some(indent) = indent {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent * depth));
+        }
+    };
+
+    let mut out = String::new();
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => {
+                out.push(ch);
+                while let Some(ch) = chars.next() {
+                    out.push(ch);
+                    match ch {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' | '[' => {
+                out.push(ch);
+                while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+                // Empty objects and arrays stay on one line.
+                if let Some(close) = chars.next_if(|&close| close == '}' || close == ']') {
+                    out.push(close);
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(ch);
+            }
+            ',' => {
+                out.push(ch);
+                newline(&mut out, depth);
+            }
+            ':' => {
+                out.push(ch);
+                if indent.is_some() {
+                    out.push(' ');
+                }
+            }
+            ch if ch.is_whitespace() => {}
+            ch => out.push(ch),
+        }
+    }
+    if text.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Turns the line and column serde_json reports into an offset in `text`.
+fn syntax_error(text: &str, err: &serde_json::Error) -> SyntaxError {
+    let mut message = err.to_string();
+    if let Some(at) = message.rfind(" at line ") {
+        message.truncate(at);
+    }
+    let line_start = text
+        .split_inclusive('\n')
+        .take(err.line().saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let mut offset = (line_start + err.column().saturating_sub(1)).min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    SyntaxError {
+        message,
+        line: err.line(),
+        column: err.column(),
+        offset,
+    }
+}
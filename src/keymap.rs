@@ -0,0 +1,557 @@
+use crate::commands::Command;
+use crate::toml::{self, Value};
+use cursive::event::{Event, Key};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Bindings used when no configuration overrides them: Ctrl+WASD to move,
+/// plus the rest of the Ctrl-letter scheme.
+const DEFAULT_BINDINGS: &[(Command, &[&str])] = &[
+    (Command::MoveRight, &["right", "ctrl-d"]),
+    (Command::MoveLeft, &["left", "ctrl-a"]),
+    (Command::MoveDown, &["down", "ctrl-s"]),
+    (Command::MoveUp, &["up", "ctrl-w"]),
+    (Command::MoveWordRight, &["ctrl-right"]),
+    (Command::MoveWordLeft, &["ctrl-left"]),
+    (Command::MoveBigWordRight, &["ctrl-shift-right"]),
+    (Command::MoveBigWordLeft, &["ctrl-shift-left"]),
+    (Command::MoveParagraphDown, &["ctrl-down"]),
+    (Command::MoveParagraphUp, &["ctrl-up"]),
+    (Command::MoveLineStart, &["home"]),
+    (Command::MoveLineEnd, &["end"]),
+    (Command::DeleteBackward, &["backspace"]),
+    (Command::DeleteForward, &["del"]),
+    (Command::PageDown, &["pagedown"]),
+    (Command::PageUp, &["pageup"]),
+    (Command::HalfPageDown, &["shift-pagedown"]),
+    (Command::HalfPageUp, &["shift-pageup"]),
+    (Command::GotoStart, &["ctrl-home"]),
+    (Command::GotoEnd, &["ctrl-end"]),
+    (Command::JumpToMatch, &["alt-5"]),
+    (Command::ExpandSelection, &["ctrl-p"]),
+    (Command::ToggleSelection, &["ctrl-space"]),
+    (Command::ShrinkSelection, &["ctrl-n"]),
+    (Command::Copy, &["ctrl-c"]),
+    (Command::Cut, &["ctrl-x"]),
+    (Command::Paste, &["ctrl-v"]),
+    (Command::PasteFromHistory, &["alt-y"]),
+    (Command::Quit, &["ctrl-q"]),
+    (Command::Search, &["ctrl-f"]),
+    (Command::SearchNext, &["alt-n", "f3"]),
+    (Command::SearchPrevious, &["alt-N", "shift-f3"]),
+    (Command::Replace, &["ctrl-r"]),
+    (Command::SwitchBuffer, &["ctrl-b"]),
+    (Command::OpenFile, &["ctrl-o"]),
+    (Command::Save, &["alt-s"]),
+    (Command::SplitBelow, &["alt-2"]),
+    (Command::SplitBeside, &["alt-3"]),
+    (Command::NextPane, &["alt-o"]),
+    (Command::ClosePane, &["alt-0"]),
+    (Command::OpenTransformMenu, &["ctrl-u"]),
+    (Command::OpenInsertMenu, &["alt-a"]),
+    (Command::Pipe, &["alt-|"]),
+    (Command::OpenLineMenu, &["alt-l"]),
+    (Command::JoinLines, &["ctrl-l"]),
+    (Command::DuplicateLines, &["alt-shift-down"]),
+    (Command::MoveLinesUp, &["alt-up"]),
+    (Command::MoveLinesDown, &["alt-down"]),
+    (Command::DeleteLines, &["alt-k"]),
+    (Command::OpenPalette, &["ctrl-k"]),
+    (Command::Undo, &["ctrl-z"]),
+    (Command::Redo, &["ctrl-y"]),
+    (Command::RecordMacro, &["alt-m"]),
+    (Command::PlayMacro, &["alt-p"]),
+    (Command::InsertLiteral, &["alt-`"]),
+    (Command::AddCursorBelow, &["ctrl-j"]),
+    (Command::AddCursorsAtMatches, &["alt-j"]),
+    (Command::SelectNextOccurrence, &["alt-d"]),
+    (Command::NextOccurrence, &["alt-*"]),
+    (Command::PreviousOccurrence, &["alt-#"]),
+    (Command::GotoDefinition, &["f12", "alt-g"]),
+    (Command::RenameSymbol, &["f2"]),
+    (Command::GotoTag, &["alt-]", "shift-f12"]),
+    (Command::PopTag, &["alt-}"]),
+    (Command::Hover, &["alt-i"]),
+    (Command::Complete, &["ctrl-tab", "alt-/"]),
+    (Command::ExpandSnippet, &["ctrl-e"]),
+    (Command::GotoLine, &["ctrl-g"]),
+    (Command::SetMark, &["alt-b"]),
+    (Command::GotoMark, &["alt-'"]),
+    (Command::JumpBack, &["alt-left"]),
+    (Command::JumpForward, &["alt-right"]),
+    (Command::Count, &["alt-c"]),
+    (Command::Increment, &["alt-="]),
+    (Command::Decrement, &["alt--"]),
+    (Command::Surround, &["alt-w"]),
+    (Command::ChangeSurround, &["alt-r"]),
+    (Command::DeleteSurround, &["alt-x"]),
+    (Command::RestoreSelection, &["alt-v"]),
+    (Command::SelectLines, &["alt-e"]),
+    (Command::SelectBlock, &["alt-h"]),
+    (Command::NextHunk, &["alt-."]),
+    (Command::PreviousHunk, &["alt-,"]),
+    (Command::SearchProject, &["alt-f"]),
+    (Command::Build, &["f5"]),
+    (Command::ShowQuickfix, &["shift-f5"]),
+    (Command::NextError, &["f4"]),
+    (Command::PreviousError, &["shift-f4"]),
+    (Command::ToggleFileTree, &["alt-t"]),
+    (Command::FindFile, &["ctrl-t"]),
+    (Command::OpenRecent, &["alt-u"]),
+    (Command::ReadShell, &["alt-!"]),
+    (Command::SpellSuggest, &["alt-;"]),
+    (Command::ToggleComment, &["alt-\\"]),
+    (Command::ToggleSelectMode, &["alt-q"]),
+    (Command::CancelJob, &["alt-z"]),
+];
+
+/// Which events trigger which commands.
+///
+/// Loaded from `keys.toml`, where each line binds a command to a key or a list
+/// of keys, replacing its default bindings:
+///
+/// ```toml
+/// move-up = "alt-w"
+/// search-next = ["alt-n", "f3"]
+/// save = "alt-x s"
+/// ```
+///
+/// Keys separated by spaces make a sequence, pressed one after the other,
+/// after whose first key the keys that may follow are shown. A key can't
+/// both run a command and start a sequence, which it would run before the
+/// rest was pressed: a sequence takes the key from the command bound to it
+/// by default, and the file binding both is an error.
+///
+/// Rebinding a key from the `remap-keys` screen rewrites the file.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    name: String,
+    bindings: Vec<(Vec<Event>, Command)>,
+}
+
+/// What the keys pressed so far amount to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// They're bound to this command.
+    Command(Command),
+    /// They start one or more longer sequences.
+    Prefix,
+    /// Nothing is bound to them.
+    None,
+}
+
+/// Why a configuration file couldn't be loaded.
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Invalid(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .flat_map(|&(command, keys)| {
+                keys.iter().map(move |key| {
+                    let keys = parse_sequence(key).expect("default bindings are valid");
+                    (keys, command)
+                })
+            })
+            .collect();
+        Self {
+            name: "WASD".to_string(),
+            bindings,
+        }
+    }
+}
+
+impl Keymap {
+    /// Loads the user's key configuration, or the defaults if there is none.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("keys.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the bindings in `path` over the defaults; a missing file just
+    /// means the defaults.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut keymap = Self {
+            name: "custom".to_string(),
+            ..Self::default()
+        };
+        let mut configured: Vec<(Vec<Event>, Command)> = Vec::new();
+        for (name, value) in &table {
+            let command = Command::from_name(name)
+                .ok_or_else(|| invalid(format!("unknown command `{name}`")))?;
+            let keys = match value {
+                Value::String(key) => vec![key.as_str()],
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(key) => Ok(key.as_str()),
+                        other => Err(invalid(format!("{name}: expected a key, found {other}"))),
+                    })
+                    .collect::<Result<_, _>>()?,
+                other => {
+                    return Err(invalid(format!(
+                        "{name}: expected a key or a list of keys, found {other}"
+                    )))
+                }
+            };
+
+            keymap.bindings.retain(|&(_, bound)| bound != command);
+            for key in keys {
+                let keys = parse_sequence(key)
+                    .ok_or_else(|| invalid(format!("{name}: unknown key `{key}`")))?;
+                if let Some((clash, other)) = configured
+                    .iter()
+                    .find(|(bound, _)| *bound != keys && overlap(bound, &keys))
+                {
+                    let clash = format_sequence(clash).unwrap_or_default();
+                    return Err(invalid(format!(
+                        "{name}: `{key}` and `{clash}`, bound to {}, can't both be bound, \
+                         since one starts the other",
+                        other.name()
+                    )));
+                }
+                // A key triggers a single command: the configured one wins,
+                // as a sequence does over a default key it starts with.
+                keymap.bindings.retain(|(bound, _)| !overlap(bound, &keys));
+                keymap.bindings.push((keys.clone(), command));
+                configured.push((keys, command));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Name shown in the status bar: the defaults' name, or `custom`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The command `keys` are bound to, if any.
+    pub fn command_for(&self, keys: &[Event]) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == keys)
+            .map(|&(_, command)| command)
+    }
+
+    /// Whether `keys` run a command, start a longer sequence, or neither.
+    pub fn lookup(&self, keys: &[Event]) -> Lookup {
+        if let Some(command) = self.command_for(keys) {
+            Lookup::Command(command)
+        } else if self.continuations(keys).is_empty() {
+            Lookup::None
+        } else {
+            Lookup::Prefix
+        }
+    }
+
+    /// The sequences that start with `keys`, as the rest of each with the
+    /// command it runs.
+    pub fn continuations(&self, keys: &[Event]) -> Vec<(String, Command)> {
+        self.bindings
+            .iter()
+            .filter(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys))
+            .filter_map(|(bound, command)| Some((format_sequence(&bound[keys.len()..])?, *command)))
+            .collect()
+    }
+
+    /// Binds `event` to `command` in place of the keys it had, taking it
+    /// from whichever command it triggered before and unbinding the
+    /// sequences that started with it.
+    pub fn bind(&mut self, command: Command, event: Event) {
+        self.bindings
+            .retain(|(bound, to)| *to != command && bound.first() != Some(&event));
+        self.bindings.push((vec![event], command));
+        self.name = "custom".to_string();
+    }
+
+    /// Writes the bindings that differ from the defaults to `keys.toml`,
+    /// replacing what it held, and returns where.
+    pub fn save(&self) -> Result<PathBuf, Error> {
+        let dir = config_dir().ok_or_else(|| {
+            Error::Invalid(PathBuf::from("keys.toml"), "no home directory".to_string())
+        })?;
+        let path = dir.join("keys.toml");
+        let defaults = Self::default();
+        let mut text = String::from("# Each command's keys replace its default ones.\n");
+        for &command in Command::ALL {
+            let mut keys = self.keys_for(command);
+            let mut default = defaults.keys_for(command);
+            keys.sort();
+            default.sort();
+            if keys != default {
+                let keys = Value::Array(keys.into_iter().map(Value::String).collect());
+                text += &format!("{} = {keys}\n", command.name());
+            }
+        }
+        fs::create_dir_all(&dir)
+            .and_then(|()| fs::write(&path, text))
+            .map_err(|err| Error::Io(path.clone(), err))?;
+        Ok(path)
+    }
+
+    /// The keys bound to `command`, as `parse_sequence` accepts them.
+    pub fn keys_for(&self, command: Command) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == command)
+            .filter_map(|(keys, _)| format_sequence(keys))
+            .collect()
+    }
+}
+
+/// Whether `a` and `b` are the same keys, or one starts the other.
+fn overlap(a: &[Event], b: &[Event]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// The directory `--config` gives, read instead of the usual one.
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Reads the configuration files in `dir` from now on, rather than in the
+/// usual directory. Only the first call counts.
+pub fn set_config_dir(dir: PathBuf) {
+    let _ = CONFIG_DIR.set(dir);
+}
+
+/// Directory holding the configuration files: the one set with
+/// `set_config_dir`, or `$XDG_CONFIG_HOME/wasdev`, or `~/.config/wasdev`.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = CONFIG_DIR.get() {
+        return Some(dir.clone());
+    }
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("wasdev"))
+}
+
+/// Parses keys separated by spaces, such as `ctrl-x s`, into the events
+/// they produce, one after the other.
+pub fn parse_sequence(spec: &str) -> Option<Vec<Event>> {
+    let keys: Vec<Event> = spec
+        .split_whitespace()
+        .map(parse_event)
+        .collect::<Option<_>>()?;
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// Describes `keys` the way `parse_sequence` reads them.
+pub fn format_sequence(keys: &[Event]) -> Option<String> {
+    let keys: Vec<String> = keys.iter().map(format_event).collect::<Option<_>>()?;
+    Some(keys.join(" "))
+}
+
+/// Parses a key description such as `ctrl-w`, `alt-N`, `shift-f3`, `esc` or
+/// `x` into the event it produces.
+pub fn parse_event(spec: &str) -> Option<Event> {
+    let (modifiers, key) = match spec.rfind('-') {
+        // A trailing `-` is the key itself, as in `ctrl--`.
+        Some(pos) if pos + 1 == spec.len() && pos > 0 => (&spec[..pos - 1], "-"),
+        Some(pos) if pos > 0 => (&spec[..pos], &spec[pos + 1..]),
+        _ => ("", spec),
+    };
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
+    for modifier in modifiers.split('-').filter(|m| !m.is_empty()) {
+        match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "c" => ctrl = true,
+            "alt" | "meta" | "m" => alt = true,
+            "shift" | "s" => shift = true,
+            _ => return None,
+        }
+    }
+
+    let mut chars = key.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return match (ctrl, alt, shift) {
+            (false, false, false) => Some(Event::Char(ch)),
+            (true, false, false) => Some(Event::CtrlChar(ch)),
+            (false, true, false) => Some(Event::AltChar(ch)),
+            _ => None,
+        };
+    }
+    if key.eq_ignore_ascii_case("space") && !alt && !shift {
+        return Some(if ctrl {
+            Event::CtrlChar(' ')
+        } else {
+            Event::Char(' ')
+        });
+    }
+
+    let key = parse_key(&key.to_ascii_lowercase())?;
+    Some(match (ctrl, alt, shift) {
+        (false, false, false) => Event::Key(key),
+        (true, false, false) => Event::Ctrl(key),
+        (false, true, false) => Event::Alt(key),
+        (false, false, true) => Event::Shift(key),
+        (true, true, false) => Event::CtrlAlt(key),
+        (true, false, true) => Event::CtrlShift(key),
+        (false, true, true) => Event::AltShift(key),
+        (true, true, true) => return None,
+    })
+}
+
+/// Describes `event` the way `parse_event` reads it, if it's a key press.
+pub fn format_event(event: &Event) -> Option<String> {
+    let text = match event {
+        Event::Char(' ') => "space".to_string(),
+        Event::CtrlChar(' ') => "ctrl-space".to_string(),
+        Event::Char(ch) => ch.to_string(),
+        Event::CtrlChar(ch) => format!("ctrl-{ch}"),
+        Event::AltChar(ch) => format!("alt-{ch}"),
+        Event::Key(key) => key_name(*key)?,
+        Event::Ctrl(key) => format!("ctrl-{}", key_name(*key)?),
+        Event::Alt(key) => format!("alt-{}", key_name(*key)?),
+        Event::Shift(key) => format!("shift-{}", key_name(*key)?),
+        Event::CtrlAlt(key) => format!("ctrl-alt-{}", key_name(*key)?),
+        Event::CtrlShift(key) => format!("ctrl-shift-{}", key_name(*key)?),
+        Event::AltShift(key) => format!("alt-shift-{}", key_name(*key)?),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// The name `parse_key` reads for `key`.
+fn key_name(key: Key) -> Option<String> {
+    let name = match key {
+        Key::Enter => "enter",
+        Key::Tab => "tab",
+        Key::Backspace => "backspace",
+        Key::Esc => "esc",
+        Key::Left => "left",
+        Key::Right => "right",
+        Key::Up => "up",
+        Key::Down => "down",
+        Key::Ins => "ins",
+        Key::Del => "del",
+        Key::Home => "home",
+        Key::End => "end",
+        Key::PageUp => "pageup",
+        Key::PageDown => "pagedown",
+        Key::F1 => "f1",
+        Key::F2 => "f2",
+        Key::F3 => "f3",
+        Key::F4 => "f4",
+        Key::F5 => "f5",
+        Key::F6 => "f6",
+        Key::F7 => "f7",
+        Key::F8 => "f8",
+        Key::F9 => "f9",
+        Key::F10 => "f10",
+        Key::F11 => "f11",
+        Key::F12 => "f12",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Parses the name of a non-character key.
+fn parse_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "enter" | "return" => Key::Enter,
+        "tab" => Key::Tab,
+        "backspace" => Key::Backspace,
+        "esc" | "escape" => Key::Esc,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "ins" | "insert" => Key::Ins,
+        "del" | "delete" => Key::Del,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => {
+            let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+            if !(1..=12).contains(&n) {
+                return None;
+            }
+            Key::from_f(n)
+        }
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads `text` as the contents of a `keys.toml`.
+    fn load(name: &str, text: &str) -> Result<Keymap, Error> {
+        let path = std::env::temp_dir().join(format!("wasdev-{}-{name}.toml", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let keymap = Keymap::load_from(&path);
+        fs::remove_file(&path).unwrap();
+        keymap
+    }
+
+    fn keys(spec: &str) -> Vec<Event> {
+        parse_sequence(spec).unwrap()
+    }
+
+    #[test]
+    fn a_sequence_takes_its_first_key_from_the_defaults() {
+        assert_eq!(
+            Keymap::default().lookup(&keys("ctrl-x")),
+            Lookup::Command(Command::Cut)
+        );
+        let keymap = load("sequence", "save = \"ctrl-x s\"\n").unwrap();
+        assert_eq!(keymap.lookup(&keys("ctrl-x")), Lookup::Prefix);
+        assert_eq!(
+            keymap.lookup(&keys("ctrl-x s")),
+            Lookup::Command(Command::Save)
+        );
+        assert_eq!(keymap.keys_for(Command::Cut), Vec::<String>::new());
+        assert_eq!(keymap.keys_for(Command::Save), ["ctrl-x s"]);
+    }
+
+    #[test]
+    fn a_key_and_a_sequence_it_starts_clash() {
+        let err = load("clash", "cut = \"ctrl-x\"\nsave = \"ctrl-x s\"\n").unwrap_err();
+        assert!(matches!(err, Error::Invalid(..)), "{err}");
+        let err = load("clash-one", "save = [\"alt-x s\", \"alt-x\"]\n").unwrap_err();
+        assert!(matches!(err, Error::Invalid(..)), "{err}");
+    }
+
+    #[test]
+    fn binding_a_key_unbinds_the_sequences_it_started() {
+        let mut keymap = load("rebind", "save = \"alt-x s\"\nquit = \"alt-x q\"\n").unwrap();
+        assert_eq!(keymap.continuations(&keys("alt-x")).len(), 2);
+        keymap.bind(Command::Copy, keys("alt-x")[0].clone());
+        assert_eq!(
+            keymap.lookup(&keys("alt-x")),
+            Lookup::Command(Command::Copy)
+        );
+        assert!(keymap.continuations(&keys("alt-x")).is_empty());
+        assert_eq!(keymap.command_for(&keys("ctrl-c")), None);
+    }
+}
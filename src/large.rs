@@ -0,0 +1,68 @@
+use crate::buffer::Buffer;
+use crate::charset::Charset;
+use crate::jobs::{Progress, ProgressReader};
+use crate::line_ending::LineEnding;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Files bigger than this many bytes open in large-file mode.
+pub const THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Whether the file at `path` is big enough for large-file mode.
+pub fn is_large(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.len() > THRESHOLD)
+}
+
+/// Reads a large file into a buffer a piece at a time, rather than into
+/// one string first. Only UTF-8 is read this way; the line endings are
+/// guessed from the start of the file.
+pub fn read(path: &Path, progress: &Progress) -> io::Result<(Buffer, Charset, LineEnding)> {
+    let file = File::open(path)?;
+    progress.set_total(file.metadata()?.len());
+    let mut reader = BufReader::new(ProgressReader::new(file, progress));
+    let start = reader.fill_buf()?;
+    let charset = match Charset::detect(start) {
+        Charset::Utf8Bom => Charset::Utf8Bom,
+        // The start of a UTF-8 file may end partway through a character,
+        // which looks like Latin-1; anything else is caught while reading.
+        Charset::Utf8 | Charset::Latin1 => Charset::Utf8,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Large files can only be read as UTF-8",
+            ))
+        }
+    };
+    let line_ending = LineEnding::detect(&String::from_utf8_lossy(start));
+    if charset == Charset::Utf8Bom {
+        reader.consume(3);
+    }
+    let buffer = Buffer::read_utf8(reader, line_ending == LineEnding::Crlf)?;
+    Ok((buffer, charset, line_ending))
+}
+
+/// Writes `buffer` to the file at `path` a piece at a time, encoded as
+/// `charset` and with its lines ending as `line_ending` says. Once started
+/// it isn't cancelled, which would leave the file half written.
+pub fn write(
+    path: &Path,
+    buffer: &Buffer,
+    charset: Charset,
+    line_ending: LineEnding,
+    progress: &Progress,
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    progress.set_total(buffer.len() as u64);
+    for (i, (_, chunk)) in buffer.chunks_at(0).enumerate() {
+        progress.advance(chunk.len() as u64);
+        let chunk = line_ending.apply(chunk);
+        let bytes = charset
+            .encode(&chunk)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        // Only the first piece keeps its byte order mark.
+        let skip = if i == 0 { 0 } else { charset.bom().len() };
+        file.write_all(&bytes[skip..])?;
+    }
+    file.flush()
+}
@@ -0,0 +1,27 @@
+//! The editing core of WasDev, kept apart from the terminal it's drawn in:
+//! the text and the cursor moving through it, selections, the edits line
+//! and text operations make, the undo history and marks that follow them,
+//! searching, diffs, and the ways text can be rewritten. The `txt_editor`
+//! binary puts a Cursive frontend over it.
+
+pub mod buffer;
+pub mod cursor;
+pub mod diff;
+pub mod edits;
+pub mod encoding;
+pub mod filetype;
+pub mod history;
+pub mod json;
+pub mod marks;
+pub mod motion;
+pub mod number;
+pub mod reflow;
+pub mod search;
+pub mod selection;
+pub mod shell;
+pub mod structure;
+pub mod toml;
+pub mod transform;
+pub mod wasm;
+pub mod width;
+pub mod wrap;
@@ -0,0 +1,52 @@
+/// How lines end in a file.
+///
+/// Buffers always hold `\n` alone; a file's own line endings are put back
+/// when it's saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The line ending most lines of `text` have; LF when there's a tie.
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// The name shown in the status bar.
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    /// The other line ending.
+    pub fn toggled(self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        }
+    }
+
+    /// `text`, from a buffer, with its lines ending this way.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// `text` with every `\r\n` turned into `\n`, as buffers hold it.
+pub fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
@@ -0,0 +1,801 @@
+use crate::buffer::Buffer;
+use crate::filetype::Filetype;
+use crate::keymap::{config_dir, Error};
+use crate::markdown;
+use crate::rename;
+use crate::toml;
+use crate::view::EditorView;
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::event::Key;
+use cursive::view::{Position, Resizable, Scrollable};
+use cursive::views::stack_view::LayerAt;
+use cursive::views::{Dialog, OnEventView, Panel, TextView};
+use cursive::Cursive;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The servers started for each filetype unless `lsp.toml` says otherwise.
+const DEFAULT_SERVERS: &[(Filetype, &[&str])] = &[
+    (Filetype::Rust, &["rust-analyzer"]),
+    (Filetype::C, &["clangd"]),
+    (Filetype::Python, &["pylsp"]),
+    (
+        Filetype::JavaScript,
+        &["typescript-language-server", "--stdio"],
+    ),
+];
+
+/// Widest a hover popup gets, in columns.
+const HOVER_WIDTH: usize = 72;
+
+/// Most rows of a hover popup shown at once; the rest scroll.
+const HOVER_HEIGHT: usize = 15;
+
+/// Called from a server's reader thread whenever a message arrives, so the
+/// UI can pick it up.
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+/// How serious a diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+/// A problem a server reported on one line of a file.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// An answer from a server that the UI has to act on.
+enum Reply {
+    /// Where the symbol asked about is defined: a file, line and UTF-16 column.
+    Definition(PathBuf, usize, usize),
+    /// What the server had to say about the symbol asked about.
+    Hover(String),
+    /// The changes that rename the symbol asked about, file by file.
+    Rename(Vec<FileEdit>),
+    /// The server refused the request, saying why.
+    Refused(String),
+    /// The server had nothing to say about the symbol asked about.
+    Nothing,
+}
+
+/// Changes a server asked for in one file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    /// The version of the file the changes were worked out for, if the
+    /// server says.
+    pub version: Option<u64>,
+    pub edits: Vec<TextEdit>,
+}
+
+/// Text a server asked to put in place of a range, whose ends are lines
+/// and UTF-16 columns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+/// What a request still waiting for its response asked for.
+#[derive(Clone, Copy, Debug)]
+enum Pending {
+    Initialize,
+    Definition,
+    Hover,
+    Rename,
+}
+
+/// A running language server, spoken to over its stdin and stdout.
+struct Client {
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+    /// Whether the server answered `initialize`; nothing else is sent before.
+    ready: bool,
+    /// The version last sent of each open document, by URI.
+    documents: HashMap<String, u64>,
+}
+
+impl Client {
+    /// Starts `command` and asks it to initialize for the current directory.
+    fn spawn(command: &[String], wake: Option<Waker>) -> io::Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || read_messages(BufReader::new(stdout), sender, wake));
+
+        let mut client = Self {
+            stdin,
+            messages,
+            next_id: 0,
+            pending: HashMap::new(),
+            ready: false,
+            documents: HashMap::new(),
+        };
+        let root = std::env::current_dir().map(|dir| uri(&dir)).ok();
+        client.request(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root,
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "dynamicRegistration": false },
+                        "hover": { "contentFormat": ["plaintext", "markdown"] },
+                        "definition": { "linkSupport": true },
+                        "rename": { "prepareSupport": false },
+                        "publishDiagnostics": {},
+                    },
+                    "workspace": {
+                        "workspaceEdit": { "documentChanges": true },
+                    },
+                },
+            }),
+            Pending::Initialize,
+        )?;
+        Ok(client)
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.stdin.flush()
+    }
+
+    fn request(&mut self, method: &str, params: Value, pending: Pending) -> io::Result<()> {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, pending);
+        self.send(
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }),
+        )
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Brings the server's copy of `ed` up to date, opening it there first
+    /// if need be. The whole text is sent on every change.
+    fn sync(&mut self, ed: &Editor, uri: String) -> io::Result<()> {
+        let version = ed.revision();
+        match self.documents.get(&uri) {
+            Some(&sent) if sent == version => return Ok(()),
+            Some(_) => self.notify(
+                "textDocument/didChange",
+                json!({
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": ed.buffer().to_string() }],
+                }),
+            )?,
+            None => self.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": ed.highlighter().filetype().name(),
+                        "version": version,
+                        "text": ed.buffer().to_string(),
+                    },
+                }),
+            )?,
+        }
+        self.documents.insert(uri, version);
+        Ok(())
+    }
+}
+
+/// The language servers for the open buffers, and what they reported.
+///
+/// A server is started the first time a file of its filetype is open, and
+/// is sent the whole text of every such buffer as it changes. Its answers
+/// arrive on a thread of their own and are handled by `poll`.
+#[derive(Default)]
+pub struct Lsp {
+    servers: HashMap<Filetype, Vec<String>>,
+    clients: HashMap<Filetype, Client>,
+    /// Filetypes whose server couldn't be started, so it isn't tried again.
+    failed: Vec<Filetype>,
+    diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
+    wake: Option<Waker>,
+}
+
+impl Lsp {
+    /// Loads the user's server configuration, or the defaults if there is none.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("lsp.toml")),
+            None => Ok(Self::with_defaults()),
+        }
+    }
+
+    /// Loads the servers in `path` over the defaults; a missing file just
+    /// means the defaults. Each line names the command for a filetype, as a
+    /// string or an array of arguments; an empty one turns the server off:
+    ///
+    /// ```toml
+    /// rust = "rust-analyzer"
+    /// javascript = ["typescript-language-server", "--stdio"]
+    /// python = ""
+    /// ```
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::with_defaults()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut lsp = Self::with_defaults();
+        for (name, value) in &table {
+            let filetype = Filetype::ALL
+                .iter()
+                .copied()
+                .find(|filetype| filetype.name() == name)
+                .ok_or_else(|| invalid(format!("unknown filetype `{name}`")))?;
+            let command = match value {
+                toml::Value::String(command) => {
+                    command.split_whitespace().map(str::to_string).collect()
+                }
+                toml::Value::Array(args) => args
+                    .iter()
+                    .map(|arg| match arg {
+                        toml::Value::String(arg) => Ok(arg.clone()),
+                        other => Err(invalid(format!(
+                            "`{name}`: expected a string, found {other}"
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                other => {
+                    return Err(invalid(format!(
+                        "`{name}`: expected a command, found {other}"
+                    )))
+                }
+            };
+            if command.is_empty() {
+                lsp.servers.remove(&filetype);
+            } else {
+                lsp.servers.insert(filetype, command);
+            }
+        }
+        Ok(lsp)
+    }
+
+    fn with_defaults() -> Self {
+        let servers = DEFAULT_SERVERS
+            .iter()
+            .map(|&(filetype, command)| {
+                (
+                    filetype,
+                    command.iter().map(|arg| arg.to_string()).collect(),
+                )
+            })
+            .collect();
+        Self {
+            servers,
+            ..Self::default()
+        }
+    }
+
+    /// Sets what to call when a server sends something.
+    pub fn set_waker(&mut self, wake: Waker) {
+        self.wake = Some(wake);
+    }
+
+    /// The running server for `filetype`, started now if it isn't yet.
+    fn client(&mut self, filetype: Filetype) -> Option<&mut Client> {
+        if !self.clients.contains_key(&filetype) {
+            let command = self.servers.get(&filetype)?;
+            if self.failed.contains(&filetype) {
+                return None;
+            }
+            match Client::spawn(command, self.wake.clone()) {
+                Ok(client) => {
+                    self.clients.insert(filetype, client);
+                }
+                Err(_) => {
+                    self.failed.push(filetype);
+                    return None;
+                }
+            }
+        }
+        self.clients.get_mut(&filetype)
+    }
+
+    /// The server that has `ed` open, if it's ready to be asked about it.
+    fn attached_client(&mut self, ed: &Editor) -> Option<(&mut Client, String)> {
+        let uri = uri(ed.path()?);
+        let client = self.clients.get_mut(&ed.highlighter().filetype())?;
+        client.documents.contains_key(&uri).then_some((client, uri))
+    }
+
+    /// Whether a server has `ed` open, so its diagnostics are shown.
+    pub fn is_attached(&self, ed: &Editor) -> bool {
+        let Some(path) = ed.path() else {
+            return false;
+        };
+        self.clients
+            .get(&ed.highlighter().filetype())
+            .is_some_and(|client| client.documents.contains_key(&uri(path)))
+    }
+
+    /// Tells the servers about every change to `editors` since the last call,
+    /// starting any that are needed.
+    pub fn sync(&mut self, editors: &[Editor]) {
+        for ed in editors {
+            let Some(path) = ed.path() else {
+                continue;
+            };
+            let Some(client) = self.client(ed.highlighter().filetype()) else {
+                continue;
+            };
+            if client.ready {
+                // A server that went away just stops getting updates.
+                let _ = client.sync(ed, uri(path));
+            }
+        }
+    }
+
+    /// Asks where the symbol under the cursor of `ed` is defined. Returns
+    /// `false` if no server has the buffer open.
+    pub fn request_definition(&mut self, ed: &Editor) -> bool {
+        self.request_at_cursor(ed, "textDocument/definition", Pending::Definition)
+    }
+
+    /// Asks about the symbol under the cursor of `ed`. Returns `false` if no
+    /// server has the buffer open.
+    pub fn request_hover(&mut self, ed: &Editor) -> bool {
+        self.request_at_cursor(ed, "textDocument/hover", Pending::Hover)
+    }
+
+    /// Asks for the changes that rename the symbol under the cursor of `ed`
+    /// to `name`. Returns `false` if no server has the buffer open.
+    pub fn request_rename(&mut self, ed: &Editor, name: &str) -> bool {
+        let Some((client, uri)) = self.attached_client(ed) else {
+            return false;
+        };
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": position(ed.buffer(), ed.cursor()),
+            "newName": name,
+        });
+        client
+            .request("textDocument/rename", params, Pending::Rename)
+            .is_ok()
+    }
+
+    fn request_at_cursor(&mut self, ed: &Editor, method: &str, pending: Pending) -> bool {
+        let Some((client, uri)) = self.attached_client(ed) else {
+            return false;
+        };
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": position(ed.buffer(), ed.cursor()),
+        });
+        client.request(method, params, pending).is_ok()
+    }
+
+    /// The diagnostics last reported for the file at `path`.
+    pub fn diagnostics(&self, path: &Path) -> &[Diagnostic] {
+        self.diagnostics
+            .get(&absolute(path))
+            .map_or(&[], |diagnostics| diagnostics.as_slice())
+    }
+
+    /// Handles everything the servers sent since the last call, returning
+    /// the answers the UI has to show.
+    fn poll(&mut self) -> Vec<Reply> {
+        let mut replies = Vec::new();
+        for client in self.clients.values_mut() {
+            while let Ok(message) = client.messages.try_recv() {
+                match (message["method"].as_str(), message.get("id")) {
+                    // Requests from the server get an empty answer, which
+                    // is enough to keep it going.
+                    (Some(_), Some(id)) => {
+                        let _ = client.send(json!({ "jsonrpc": "2.0", "id": id, "result": null }));
+                    }
+                    (Some("textDocument/publishDiagnostics"), None) => {
+                        let params = &message["params"];
+                        if let Some(path) = params["uri"].as_str().and_then(path_from_uri) {
+                            self.diagnostics
+                                .insert(path, diagnostics_from_json(&params["diagnostics"]));
+                        }
+                    }
+                    (Some(_), None) => {}
+                    (None, Some(id)) => {
+                        let pending = id.as_u64().and_then(|id| client.pending.remove(&id));
+                        let result = &message["result"];
+                        match pending {
+                            Some(Pending::Initialize) => {
+                                client.ready = true;
+                                let _ = client.notify("initialized", json!({}));
+                            }
+                            Some(Pending::Definition) => {
+                                replies.push(definition_from_json(result).unwrap_or(Reply::Nothing))
+                            }
+                            Some(Pending::Hover) => {
+                                replies.push(hover_from_json(result).unwrap_or(Reply::Nothing))
+                            }
+                            Some(Pending::Rename) => {
+                                let reply = match message["error"]["message"].as_str() {
+                                    Some(error) => Reply::Refused(error.to_string()),
+                                    None => workspace_edit_from_json(result)
+                                        .filter(|files| !files.is_empty())
+                                        .map_or(Reply::Nothing, Reply::Rename),
+                                };
+                                replies.push(reply);
+                            }
+                            None => {}
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        replies
+    }
+}
+
+/// Handles what the language servers sent: jumps to a definition, shows
+/// hover text, previews renames, and brings the servers up to date with
+/// the buffers.
+pub fn poll(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let replies = ws.lsp_mut().poll();
+    ws.sync_lsp();
+    let mut renames = Vec::new();
+    let mut hovers = Vec::new();
+    for reply in replies {
+        match reply {
+            Reply::Definition(path, line, character) => {
+                if let Err(err) = ws.open(&path) {
+                    s.add_layer(Dialog::info(format!(
+                        "Could not open {}: {err}",
+                        path.display()
+                    )));
+                    continue;
+                }
+                let ed = ws.editor_mut();
+                let target = offset(ed.buffer(), line, character);
+                ed.jump_to(target);
+            }
+            Reply::Hover(text) => hovers.push(text),
+            Reply::Rename(files) => renames.push(files),
+            Reply::Refused(message) => {
+                s.add_layer(Dialog::info(message));
+            }
+            Reply::Nothing => {
+                s.add_layer(Dialog::info("Nothing found"));
+            }
+        }
+    }
+    drop(ws);
+    for files in renames {
+        rename::preview(s, workspace, files);
+    }
+    for text in hovers {
+        show_hover(s, &text);
+    }
+}
+
+/// Shows what a server said about a symbol, rendered as Markdown, in a
+/// popup just below the cursor, or above it if there's no room below.
+/// Esc closes it.
+fn show_hover(s: &mut Cursive, text: &str) {
+    let cursor = s
+        .call_on_name("main", |view: &mut EditorView| view.cursor_position())
+        .flatten()
+        .unwrap_or_default();
+    let screen = s.screen_size();
+    // Room for the border and a column either side.
+    let width = HOVER_WIDTH.min(screen.x.saturating_sub(4)).max(1);
+    let content = markdown::styled(text, width);
+    let height = content.source().lines().count().min(HOVER_HEIGHT) + 2;
+    let y = if cursor.y + 1 + height <= screen.y || cursor.y < height {
+        cursor.y + 1
+    } else {
+        cursor.y - height
+    };
+    let popup = Panel::new(TextView::new(content).scrollable())
+        .title("Hover")
+        .max_height(height);
+    let popup = OnEventView::new(popup).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    });
+    s.screen_mut()
+        .add_layer(LayerAt(Position::absolute((cursor.x, y)), popup));
+}
+
+/// Reads the messages a server writes to `reader`, sending each on.
+fn read_messages(mut reader: impl BufRead, sender: Sender<Value>, wake: Option<Waker>) {
+    loop {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let Ok(message) = serde_json::from_slice(&body) else {
+            continue;
+        };
+        if sender.send(message).is_err() {
+            return;
+        }
+        if let Some(wake) = &wake {
+            wake();
+        }
+    }
+}
+
+fn diagnostics_from_json(value: &Value) -> Vec<Diagnostic> {
+    let Some(diagnostics) = value.as_array() else {
+        return Vec::new();
+    };
+    diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let severity = match diagnostic["severity"].as_u64() {
+                Some(2) => Severity::Warning,
+                Some(3) => Severity::Information,
+                Some(4) => Severity::Hint,
+                _ => Severity::Error,
+            };
+            Some(Diagnostic {
+                line: diagnostic["range"]["start"]["line"].as_u64()? as usize,
+                severity,
+                message: diagnostic["message"].as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The first place of a definition answer, which may be a single location,
+/// a list of them, or a list of links.
+fn definition_from_json(value: &Value) -> Option<Reply> {
+    let location = match value {
+        Value::Array(locations) => locations.first()?,
+        location => location,
+    };
+    let uri = location["uri"]
+        .as_str()
+        .or_else(|| location["targetUri"].as_str())?;
+    let range = if location["range"].is_object() {
+        &location["range"]
+    } else {
+        &location["targetSelectionRange"]
+    };
+    Some(Reply::Definition(
+        path_from_uri(uri)?,
+        range["start"]["line"].as_u64()? as usize,
+        range["start"]["character"].as_u64()? as usize,
+    ))
+}
+
+/// The text of a hover answer, which may be markup, a plain string, a
+/// string tagged with its language, or a list of those.
+fn hover_from_json(value: &Value) -> Option<Reply> {
+    fn text(contents: &Value) -> Option<String> {
+        match contents {
+            Value::String(text) => Some(text.clone()),
+            Value::Array(parts) => {
+                let parts: Vec<String> = parts.iter().filter_map(text).collect();
+                Some(parts.join("\n\n"))
+            }
+            contents => contents["value"].as_str().map(str::to_string),
+        }
+    }
+    let text = text(&value["contents"])?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| Reply::Hover(text.to_string()))
+}
+
+/// The files a workspace edit changes, and how, from either its
+/// `documentChanges` or its `changes`. Creating, renaming or deleting files
+/// isn't supported, so an edit that asks to isn't either.
+fn workspace_edit_from_json(value: &Value) -> Option<Vec<FileEdit>> {
+    let text_edits = |edits: &Value| -> Option<Vec<TextEdit>> {
+        edits
+            .as_array()?
+            .iter()
+            .map(|edit| {
+                let range = &edit["range"];
+                let at = |end: &Value| {
+                    Some((
+                        end["line"].as_u64()? as usize,
+                        end["character"].as_u64()? as usize,
+                    ))
+                };
+                Some(TextEdit {
+                    start: at(&range["start"])?,
+                    end: at(&range["end"])?,
+                    text: edit["newText"].as_str()?.to_string(),
+                })
+            })
+            .collect()
+    };
+    if let Some(changes) = value["documentChanges"].as_array() {
+        return changes
+            .iter()
+            .map(|change| {
+                let document = &change["textDocument"];
+                Some(FileEdit {
+                    path: path_from_uri(document["uri"].as_str()?)?,
+                    version: document["version"].as_u64(),
+                    edits: text_edits(&change["edits"])?,
+                })
+            })
+            .collect();
+    }
+    value["changes"]
+        .as_object()?
+        .iter()
+        .map(|(uri, edits)| {
+            Some(FileEdit {
+                path: path_from_uri(uri)?,
+                version: None,
+                edits: text_edits(edits)?,
+            })
+        })
+        .collect()
+}
+
+/// The byte ranges of `buffer` that `edits` replace, with their text, in
+/// order.
+pub fn ranges(buffer: &Buffer, edits: &[TextEdit]) -> Vec<(Range<usize>, String)> {
+    let mut ranges: Vec<_> = edits
+        .iter()
+        .map(|edit| {
+            let start = offset(buffer, edit.start.0, edit.start.1);
+            let end = offset(buffer, edit.end.0, edit.end.1);
+            (start..end.max(start), edit.text.clone())
+        })
+        .collect();
+    ranges.sort_by_key(|(range, _)| (range.start, range.end));
+    ranges
+}
+
+/// The LSP position of `offset`: its line, and its column in UTF-16 units.
+fn position(buffer: &Buffer, offset: usize) -> Value {
+    let line = buffer.line_of(offset);
+    let start = buffer.line_start(line);
+    let character = buffer.slice(start..offset).encode_utf16().count();
+    json!({ "line": line, "character": character })
+}
+
+/// The byte offset of UTF-16 column `character` on line `line`, kept inside
+/// the buffer.
+fn offset(buffer: &Buffer, line: usize, character: usize) -> usize {
+    let line = line.min(buffer.line_count().saturating_sub(1));
+    let start = buffer.line_start(line);
+    let text = buffer.line(line);
+    let mut units = 0;
+    for (idx, ch) in text.char_indices() {
+        if units >= character {
+            return start + idx;
+        }
+        units += ch.len_utf16();
+    }
+    start + text.len()
+}
+
+/// `path` made absolute, the way servers name files.
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The `file://` URI of `path`, with anything but plain characters escaped.
+fn uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in absolute(path).to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(char::from(byte))
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// The file a `file://` URI names.
+fn path_from_uri(uri: &str) -> Option<PathBuf> {
+    let mut rest = uri.strip_prefix("file://")?.bytes();
+    let mut bytes = Vec::new();
+    while let Some(byte) = rest.next() {
+        if byte == b'%' {
+            let hex = [rest.next()?, rest.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_edits() {
+        let edit = |line, from, to, text: &str| {
+            json!({
+                "range": {
+                    "start": { "line": line, "character": from },
+                    "end": { "line": line, "character": to },
+                },
+                "newText": text,
+            })
+        };
+        let changes = json!({
+            "documentChanges": [{
+                "textDocument": { "uri": "file:///src/a%20b.rs", "version": 7 },
+                "edits": [edit(2, 4, 7, "new"), edit(0, 3, 6, "new")],
+            }],
+        });
+        let files = workspace_edit_from_json(&changes).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("/src/a b.rs"));
+        assert_eq!(files[0].version, Some(7));
+
+        let mut buffer = Buffer::new();
+        buffer.insert(0, "fn old() {}\n\n    old();\n");
+        let ranges = ranges(&buffer, &files[0].edits);
+        assert_eq!(
+            ranges,
+            [(3..6, "new".to_string()), (17..20, "new".to_string())]
+        );
+
+        let changes = json!({ "changes": { "file:///x.rs": [edit(0, 0, 1, "y")] } });
+        let files = workspace_edit_from_json(&changes).unwrap();
+        assert_eq!(files[0].version, None);
+        assert_eq!(files[0].edits[0].text, "y");
+        assert!(workspace_edit_from_json(&json!({ "changes": 3 })).is_none());
+    }
+}
@@ -0,0 +1,376 @@
+use crate::keymap::{self, config_dir, Error};
+use crate::prompt;
+use crate::toml::{self, Value};
+use crate::workspace::Workspace;
+use cursive::event::{Event, Key};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::{Cursive, View};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Records the keys pressed between two `toggle`s, to play them back later.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    /// Keys recorded so far, while recording.
+    recording: Option<Vec<Event>>,
+    /// The last complete recording.
+    last: Vec<Event>,
+    /// Whether a macro is being played back; its keys aren't recorded again.
+    playing: bool,
+}
+
+impl Recorder {
+    /// Creates a recorder with nothing recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts recording, or stops and keeps what was recorded. The key that
+    /// stopped the recording isn't part of it.
+    pub fn toggle(&mut self) {
+        match self.recording.take() {
+            Some(mut events) => {
+                events.pop();
+                self.last = events;
+            }
+            None => self.recording = Some(Vec::new()),
+        }
+    }
+
+    /// Whether keys are being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Adds `event` to the recording, if it's a key press and one is running.
+    pub fn record(&mut self, event: &Event) {
+        if self.playing || !is_key(event) {
+            return;
+        }
+        if let Some(events) = self.recording.as_mut() {
+            events.push(event.clone());
+        }
+    }
+}
+
+/// A recorded macro kept under a name, with the keys that run it.
+#[derive(Clone, Debug, PartialEq)]
+struct Saved {
+    events: Vec<Event>,
+    key: Option<Vec<Event>>,
+}
+
+/// The macros saved to `macros.toml`, by name.
+///
+/// Each is a table with the keys it presses, as `keys.toml` writes them,
+/// and optionally the keys that play it:
+///
+/// ```toml
+/// [quote-word]
+/// events = "alt-b \" alt-f \""
+/// key = "ctrl-x q"
+/// ```
+///
+/// A macro's key is looked up before the keymap's, so it may take a key
+/// from a command.
+#[derive(Clone, Debug, Default)]
+pub struct Macros {
+    saved: BTreeMap<String, Saved>,
+}
+
+impl Macros {
+    /// Loads the user's saved macros, if there are any.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("macros.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the macros in `path`; a missing file just means there are none.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+        let keys = |name: &str, field: &str, value: Option<&Value>| match value {
+            None => Ok(None),
+            Some(Value::String(spec)) => keymap::parse_sequence(spec)
+                .map(Some)
+                .ok_or_else(|| invalid(format!("`{name}.{field}`: unknown key in `{spec}`"))),
+            Some(other) => Err(invalid(format!(
+                "`{name}.{field}`: expected keys, found {other}"
+            ))),
+        };
+
+        let mut macros = Self::default();
+        for (name, value) in &table {
+            let Value::Table(fields) = value else {
+                return Err(invalid(format!(
+                    "`{name}`: expected a table, found {value}"
+                )));
+            };
+            if let Some(field) = fields
+                .keys()
+                .find(|&field| field != "events" && field != "key")
+            {
+                return Err(invalid(format!("`{name}`: unknown field `{field}`")));
+            }
+            let events = keys(name, "events", fields.get("events"))?
+                .ok_or_else(|| invalid(format!("`{name}`: no `events`")))?;
+            let key = keys(name, "key", fields.get("key"))?;
+            macros.saved.insert(name.clone(), Saved { events, key });
+        }
+        Ok(macros)
+    }
+
+    /// Writes every saved macro to `macros.toml`, replacing what it held,
+    /// and returns where.
+    pub fn save(&self) -> Result<PathBuf, Error> {
+        let dir = config_dir().ok_or_else(|| {
+            Error::Invalid(
+                PathBuf::from("macros.toml"),
+                "no home directory".to_string(),
+            )
+        })?;
+        let path = dir.join("macros.toml");
+        let mut text = String::from("# Macros saved with save-macro.\n");
+        for (name, saved) in &self.saved {
+            let Some(events) = keymap::format_sequence(&saved.events) else {
+                continue;
+            };
+            text += &format!("\n[{:?}]\nevents = {}\n", name, Value::String(events));
+            if let Some(key) = saved.key.as_deref().and_then(keymap::format_sequence) {
+                text += &format!("key = {}\n", Value::String(key));
+            }
+        }
+        fs::create_dir_all(&dir)
+            .and_then(|()| fs::write(&path, text))
+            .map_err(|err| Error::Io(path.clone(), err))?;
+        Ok(path)
+    }
+
+    /// Keeps `events` as the macro `name`, played by `key` if there is one;
+    /// a macro saved under the same name, and whatever played by the same
+    /// key, make way for it.
+    pub fn insert(&mut self, name: &str, events: Vec<Event>, key: Option<Vec<Event>>) {
+        for saved in self.saved.values_mut() {
+            if key.is_some() && saved.key == key {
+                saved.key = None;
+            }
+        }
+        self.saved.insert(name.to_string(), Saved { events, key });
+    }
+
+    /// The names of the saved macros, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.saved.keys().map(String::as_str)
+    }
+
+    /// The keys the macro `name` presses.
+    pub fn events(&self, name: &str) -> Option<&[Event]> {
+        self.saved.get(name).map(|saved| saved.events.as_slice())
+    }
+
+    /// The keys that play the macro `name`, as `parse_sequence` reads them.
+    pub fn key_for(&self, name: &str) -> Option<String> {
+        keymap::format_sequence(self.saved.get(name)?.key.as_deref()?)
+    }
+
+    /// The keys of the macro `keys` play, if any.
+    pub fn bound_to(&self, keys: &[Event]) -> Option<&[Event]> {
+        self.saved
+            .values()
+            .find(|saved| saved.key.as_deref() == Some(keys))
+            .map(|saved| saved.events.as_slice())
+    }
+
+    /// The keys that play a macro and start with `keys`, as the rest of
+    /// each with the name of the macro.
+    pub fn continuations(&self, keys: &[Event]) -> Vec<(String, String)> {
+        self.saved
+            .iter()
+            .filter_map(|(name, saved)| {
+                let key = saved.key.as_deref()?;
+                let rest = key.strip_prefix(keys).filter(|rest| !rest.is_empty())?;
+                Some((keymap::format_sequence(rest)?, name.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Whether `event` comes from the keyboard, as opposed to the mouse, a resize
+/// or a redraw.
+fn is_key(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Char(_)
+            | Event::CtrlChar(_)
+            | Event::AltChar(_)
+            | Event::Key(_)
+            | Event::Shift(_)
+            | Event::Alt(_)
+            | Event::AltShift(_)
+            | Event::Ctrl(_)
+            | Event::CtrlShift(_)
+            | Event::CtrlAlt(_)
+    )
+}
+
+/// Asks how many times to play the last recorded macro, then plays it.
+pub fn play(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    {
+        let ws = workspace.lock().unwrap();
+        let recorder = ws.recorder();
+        if recorder.playing || recorder.is_recording() {
+            return;
+        }
+    }
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Play macro how many times (1): ",
+        |_, _| {},
+        move |s, count| {
+            let count = match count.trim() {
+                "" => 1,
+                count => match count.parse::<usize>() {
+                    Ok(count) => count,
+                    Err(_) => {
+                        s.add_layer(Dialog::info(format!("Not a number: {count}")));
+                        return;
+                    }
+                },
+            };
+            play_times(s, &workspace, count);
+        },
+        |_| {},
+    );
+}
+
+/// Feeds the last recorded macro through the event loop `count` times,
+/// exactly as if its keys were pressed again.
+fn play_times(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, count: usize) {
+    let events = workspace.lock().unwrap().recorder().last.clone();
+    play_events(s, workspace, &events, count);
+}
+
+/// Plays the saved macro that presses `events`, unless a macro is already
+/// being recorded or played, which it may well be bound in.
+pub fn play_saved(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, events: &[Event]) {
+    {
+        let ws = workspace.lock().unwrap();
+        let recorder = ws.recorder();
+        if recorder.playing || recorder.is_recording() {
+            return;
+        }
+    }
+    play_events(s, workspace, events, 1);
+}
+
+/// Feeds `events` through the event loop `count` times.
+fn play_events(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, events: &[Event], count: usize) {
+    workspace.lock().unwrap().recorder_mut().playing = true;
+    for _ in 0..count {
+        for event in events {
+            // Lay the screen out first, as the event loop would, so a layer the
+            // previous key opened has taken focus.
+            let size = s.screen_size();
+            s.screen_mut().layout(size);
+            s.on_event(event.clone());
+        }
+    }
+    workspace.lock().unwrap().recorder_mut().playing = false;
+}
+
+/// Asks for a name and the keys to play it with, then saves the last
+/// recorded macro under them to `macros.toml`.
+pub fn save(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let events = workspace.lock().unwrap().recorder().last.clone();
+    if events.is_empty() {
+        s.add_layer(Dialog::info("No macro has been recorded"));
+        return;
+    }
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Save macro as: ",
+        |_, _| {},
+        move |s, name| {
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                return;
+            }
+            let workspace = workspace.clone();
+            let events = events.clone();
+            prompt::open(
+                s,
+                "Play it with keys (none): ",
+                |_, _| {},
+                move |s, key| {
+                    let key = match key.trim() {
+                        "" => None,
+                        spec => match keymap::parse_sequence(spec) {
+                            Some(keys) => Some(keys),
+                            None => {
+                                s.add_layer(Dialog::info(format!("Unknown key in {spec}")));
+                                return;
+                            }
+                        },
+                    };
+                    let mut ws = workspace.lock().unwrap();
+                    let macros = ws.macros_mut();
+                    macros.insert(&name, events.clone(), key);
+                    let saved = macros.save();
+                    drop(ws);
+                    if let Err(err) = saved {
+                        s.add_layer(Dialog::info(format!("Could not save the macro: {err}")));
+                    }
+                },
+                |_| {},
+            );
+        },
+        |_| {},
+    );
+}
+
+/// Lists the saved macros, with their keys, and plays the one chosen.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut list: SelectView<String> = SelectView::new();
+    {
+        let ws = workspace.lock().unwrap();
+        let macros = ws.macros();
+        for name in macros.names() {
+            let key = macros.key_for(name).unwrap_or_default();
+            list.add_item(format!("{name:<24}{key}"), name.to_string());
+        }
+    }
+    if list.is_empty() {
+        s.add_layer(Dialog::info("No macros have been saved"));
+        return;
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, name: &String| {
+        s.pop_layer();
+        let events = workspace
+            .lock()
+            .unwrap()
+            .macros()
+            .events(name)
+            .map(<[Event]>::to_vec);
+        if let Some(events) = events {
+            play_saved(s, &workspace, &events);
+        }
+    });
+    s.add_layer(
+        OnEventView::new(Dialog::around(list).title("Macros")).on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }),
+    );
+}
@@ -1,378 +1,2183 @@
-use cursive::views::{SelectView, TextArea};
-use cursive::{event::Event, traits::*};
+mod abbreviations;
+mod autosave;
+mod batch;
+mod charset;
+mod cli;
+mod clipboard;
+mod commands;
+mod compare;
+mod complete;
+mod export;
+mod finder;
+mod format;
+mod fuzzy;
+mod git;
+mod grammar;
+mod hex;
+mod highlight;
+mod hints;
+mod insert;
+mod jobs;
+mod keymap;
+mod large;
+mod line_ending;
+mod lsp;
+mod macros;
+mod markdown;
+mod palette;
+mod pane;
+mod plugins;
+mod positions;
+mod project;
+mod prompt;
+mod quickfix;
+mod recent;
+mod recover;
+mod registers;
+mod remap;
+mod rename;
+mod replace;
+mod scripts;
+mod settings;
+mod snippet;
+mod spell;
+mod stats;
+mod status;
+mod swap;
+mod tags;
+mod tree;
+mod undo_tree;
+mod unicode;
+mod view;
+mod watch;
+mod workspace;
+
+use txt_editor::{
+    buffer, cursor, diff, edits, filetype, history, marks, motion, reflow, search, selection,
+    shell, toml, transform, wasm, width, wrap,
+};
+
+use abbreviations::Abbreviations;
+use buffer::Buffer;
+use charset::Charset;
+use cursive::event::{Event, EventTrigger};
+use cursive::traits::*;
+use cursive::views::LinearLayout;
+use cursor::EditorCore;
+use edits::Change;
+use filetype::Filetype;
+use hex::Hex;
+use highlight::{Highlighter, Token};
+use history::{Edit, History};
+use jobs::Progress;
+use keymap::Keymap;
+use line_ending::LineEnding;
+use lsp::Lsp;
+use macros::Macros;
+use marks::Marks;
+use plugins::Plugins;
+use scripts::Scripts;
+use selection::{Caret, Selection, SelectionHistory};
+use settings::Settings;
+use snippet::{Session, Snippets};
+use status::StatusBar;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::{Deref, DerefMut, Range};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use swap::Journal;
+use transform::Failure;
+use unicode_segmentation::UnicodeSegmentation;
+use view::EditorView;
+use watch::Stamp;
+use workspace::Workspace;
+
+/// A buffer as it was when saving it started, taken by `Editor::snapshot`.
+struct Snapshot {
+    path: PathBuf,
+    buffer: Buffer,
+    /// The undo history to store alongside, unless the file is large.
+    history: Option<History>,
+    /// The bytes to write as they are, when saving from the hex dump.
+    bytes: Option<Vec<u8>>,
+    charset: Charset,
+    line_ending: LineEnding,
+    revision: u64,
+}
+
+impl Snapshot {
+    /// Writes the text to the file, reporting to `progress` for large ones.
+    fn write(&self, progress: &Progress) -> io::Result<()> {
+        if let Some(bytes) = &self.bytes {
+            return fs::write(&self.path, bytes);
+        }
+        let Some(history) = &self.history else {
+            return large::write(
+                &self.path,
+                &self.buffer,
+                self.charset,
+                self.line_ending,
+                progress,
+            );
+        };
+        let text = self.buffer.to_string();
+        let bytes = self
+            .charset
+            .encode(&self.line_ending.apply(&text))
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        fs::write(&self.path, bytes)?;
+        // The undo log is a convenience: failing to store it doesn't fail the save.
+        let _ = history.store(&self.path, &text);
+        Ok(())
+    }
+}
 
-/// Enum representing the available text transformation choices.
+/// Enum representing the available line operations.
 #[derive(Clone, Copy, Debug)]
-enum Choice {
-    Upper,
-    Lower,
-    Cap,
-    Rev,
+enum LineOp {
+    Join,
+    Reflow,
+    Duplicate,
+    MoveUp,
+    MoveDown,
+    Delete,
 }
 
-/// The `Editor` struct now holds:
-/// - `selection`: the current highlighted text (if any)
-/// - `selection_start` and `selection_end`: byte indices for the current selection
-/// - `original_selection_start` and `original_selection_end`: the original boundaries when the selection was first created
+/// The `Editor` struct holds the editing state of one buffer:
+/// - `core`: the text being edited, with the cursor and the active selection
+///   in it, which the editor derefs to
+/// - `expansions`: the cursor and selection each expansion started from, innermost
+///   last, so shrinking retraces them one level at a time
+/// - `path`: the file the buffer is loaded from and saved to, if any
+/// - `modified`: whether the buffer changed since it was last loaded or saved
+/// - `scroll`: top-left (column, line) of the region last shown, so switching
+///   back to the buffer shows the same text
+/// - `history`: the tree of undo steps, branches and all, kept across sessions for files
+/// - `carets`: any cursors besides the primary one, each with its own selection
+/// - `parked`: the cursors of the other panes showing the buffer, by pane,
+///   put aside when they lost focus and following edits until they get it back
+/// - `edit_log`: while set, the (start, removed, inserted) lengths of each edit
+/// - `highlighter`: the syntax colors of the lines shown so far
+/// - `revision`: how many times the text changed, so language servers can be kept up to date
+/// - `snippet`: the expanded snippet whose placeholders are being filled in, if any
+/// - `marks`: the named positions and the jump list, following edits
+/// - `selections`: the selections left behind, to restore them
+/// - `line_ending`: how lines end in the file, put back when saving it
+/// - `charset`: how the file is encoded, used again when saving it
+/// - `autosaved`: the `revision` last saved or autosaved
+/// - `journal`: the swap file recording the changes since the file was loaded
+///   or saved, started by the first of them
+/// - `read_only`: whether the text can't be changed, only looked through
+/// - `large`: whether the file is too big for highlighting, the undo log, the
+///   swap file and watching it for changes
+/// - `disk`: the stamp of the file when it was last loaded or saved, to tell
+///   when something else changes it
+/// - `hex`: the buffer's bytes, while it's shown as a hex dump instead of text
+/// - `git`: how the text differs from the file's last commit, if git tracks it
+/// - `blame`: the last commit to change a line, shown at its end until the
+///   text changes, with the line and the `revision` it's for
+/// - `spell_check`: whether words spelled wrong are underlined
+/// - `abbreviate`: whether abbreviations expand as they're typed
+/// - `piped`: whether the text came from standard input, and goes out on
+///   standard output once the editor quits
 #[derive(Clone)]
 struct Editor {
-    selection: String,
-    selection_start: usize,
-    selection_end: usize,
-    original_selection_start: usize,
-    original_selection_end: usize,
+    core: EditorCore,
+    expansions: Vec<Caret>,
+    path: Option<PathBuf>,
+    modified: bool,
+    scroll: (usize, usize),
+    history: History,
+    carets: Vec<Caret>,
+    parked: Vec<(usize, Vec<Caret>)>,
+    edit_log: Option<Vec<(usize, usize, usize)>>,
+    highlighter: Highlighter,
+    revision: u64,
+    snippet: Option<Session>,
+    marks: Marks,
+    selections: SelectionHistory,
+    line_ending: LineEnding,
+    charset: Charset,
+    autosaved: u64,
+    journal: Option<Journal>,
+    disk: Option<Stamp>,
+    read_only: bool,
+    large: bool,
+    hex: Option<Hex>,
+    git: Option<git::Changes>,
+    blame: Option<(usize, u64, String)>,
+    spell_check: bool,
+    abbreviate: bool,
+    piped: bool,
+}
+
+// Moving the cursor and selecting are the core's, and are called on the
+// editor as they are.
+impl Deref for Editor {
+    type Target = EditorCore;
+
+    fn deref(&self) -> &EditorCore {
+        &self.core
+    }
+}
+
+impl DerefMut for Editor {
+    fn deref_mut(&mut self) -> &mut EditorCore {
+        &mut self.core
+    }
 }
 
 impl Editor {
-    /// Creates a new editor with no selection.
+    /// Creates a new editor with an empty buffer and no selection.
     fn new() -> Self {
         Self {
-            selection: String::new(),
-            selection_start: 0,
-            selection_end: 0,
-            original_selection_start: 0,
-            original_selection_end: 0,
+            core: EditorCore::new(Buffer::new()),
+            expansions: Vec::new(),
+            path: None,
+            modified: false,
+            scroll: (0, 0),
+            history: History::new(),
+            carets: Vec::new(),
+            parked: Vec::new(),
+            edit_log: None,
+            highlighter: Highlighter::new(Filetype::Plain),
+            revision: 0,
+            snippet: None,
+            marks: Marks::default(),
+            selections: SelectionHistory::default(),
+            line_ending: LineEnding::default(),
+            charset: Charset::default(),
+            autosaved: 0,
+            journal: None,
+            disk: None,
+            read_only: false,
+            large: false,
+            hex: None,
+            git: None,
+            blame: None,
+            spell_check: false,
+            abbreviate: true,
+            piped: false,
+        }
+    }
+
+    /// Creates an editor for the file at `path`, with the undo history left
+    /// from the last time it was saved, if the file hasn't changed since,
+    /// and the cursor where it was left.
+    ///
+    /// A file that doesn't exist yet opens as an empty buffer and is created on save.
+    /// How the file is encoded is guessed, unless `charset` says. A file
+    /// too big to handle like the others opens in large-file mode.
+    fn open(path: &Path, charset: Option<Charset>) -> io::Result<Self> {
+        Self::open_with_progress(path, charset, &Progress::default())
+    }
+
+    /// Like `open`, reporting to `progress` as the file is read, for a job
+    /// to show. Large files are the ones it's worth reporting on.
+    fn open_with_progress(
+        path: &Path,
+        charset: Option<Charset>,
+        progress: &Progress,
+    ) -> io::Result<Self> {
+        if charset.is_none() && large::is_large(path) {
+            return Self::open_large(path, progress);
+        }
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let mut editor = Self::decode(&bytes, charset)?;
+        let content = editor.core.buffer.to_string();
+        editor.path = Some(path.to_path_buf());
+        let first_line = content.lines().next().unwrap_or("");
+        editor.highlighter = Highlighter::new(Filetype::detect(Some(path), first_line));
+        editor.history = History::load(path, &content);
+        editor.git = git::Changes::load(path);
+        editor.disk = watch::stamp(path);
+        editor.recall_position();
+        Ok(editor)
+    }
+
+    /// Creates an editor for the text piped to standard input, without a
+    /// file. Once the editor quits, the text is printed to standard output
+    /// as it was left.
+    fn read_stdin() -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let mut editor = Self::decode(&bytes, None)?;
+        let first_line = editor.core.buffer.line(0);
+        editor.highlighter = Highlighter::new(Filetype::detect(None, &first_line));
+        editor.piped = true;
+        Ok(editor)
+    }
+
+    /// Creates an editor for `bytes`, encoded as `charset` says or as they
+    /// look, its lines ending however they do.
+    fn decode(bytes: &[u8], charset: Option<Charset>) -> io::Result<Self> {
+        let charset = charset.unwrap_or_else(|| Charset::detect(bytes));
+        let content = charset
+            .decode(bytes)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        let mut editor = Self::new();
+        editor.charset = charset;
+        editor.line_ending = LineEnding::detect(&content);
+        let content = line_ending::normalize(&content);
+        editor.core.buffer = Buffer::from(content.as_str());
+        Ok(editor)
+    }
+
+    /// Creates an editor for the large file at `path`, read a piece at a
+    /// time. It isn't highlighted, and its undo history starts afresh.
+    fn open_large(path: &Path, progress: &Progress) -> io::Result<Self> {
+        let (buffer, charset, line_ending) = large::read(path, progress)?;
+        let mut editor = Self::new();
+        editor.buffer = buffer;
+        editor.charset = charset;
+        editor.line_ending = line_ending;
+        editor.path = Some(path.to_path_buf());
+        editor.large = true;
+        editor.disk = watch::stamp(path);
+        editor.recall_position();
+        Ok(editor)
+    }
+
+    /// Puts the cursor back where it was left the last time the file was
+    /// open.
+    fn recall_position(&mut self) {
+        let Some((line, column)) = self.path.as_deref().and_then(positions::recall) else {
+            return;
+        };
+        let line = line.min(self.core.buffer.line_count() - 1);
+        self.core.cursor = self.offset_at_column(line, column);
+    }
+
+    /// Writes the buffer back to its file, encoded and with its lines
+    /// ending the way the file's were. If the text can't be encoded that
+    /// way, the file is left alone.
+    fn save(&mut self) -> io::Result<()> {
+        let snapshot = self.snapshot()?;
+        snapshot.write(&Progress::default())?;
+        self.saved(&snapshot);
+        Ok(())
+    }
+
+    /// What saving the buffer takes, copied so it can be written on
+    /// another thread while editing goes on.
+    fn snapshot(&self) -> io::Result<Snapshot> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer has no file name",
+            ));
+        };
+        Ok(Snapshot {
+            path: path.clone(),
+            buffer: self.core.buffer.clone(),
+            history: (!self.large).then(|| self.history.clone()),
+            bytes: self.hex.as_ref().map(|hex| hex.bytes().to_vec()),
+            charset: self.charset,
+            line_ending: self.line_ending,
+            revision: self.revision,
+        })
+    }
+
+    /// Writes the text to `out` the way saving would write it to the file:
+    /// encoded, and with its lines ending the way they did.
+    fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        if let Some(hex) = &self.hex {
+            return out.write_all(hex.bytes());
         }
+        let bytes = self
+            .charset
+            .encode(&self.line_ending.apply(&self.core.buffer.to_string()))
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        out.write_all(&bytes)
+    }
+
+    /// Notes that `snapshot` was written to the file: the buffer is saved,
+    /// unless it was edited since the snapshot was taken.
+    fn saved(&mut self, snapshot: &Snapshot) {
+        self.disk = watch::stamp(&snapshot.path);
+        self.modified = self.revision != snapshot.revision;
+        self.autosaved = snapshot.revision;
+        if let Some(journal) = self.journal.take() {
+            journal.remove();
+        }
+        // Saved for real, the recovery copy is of no more use.
+        if let Some(recovery) = autosave::recovery_path(&snapshot.path) {
+            let _ = fs::remove_file(recovery);
+        }
+    }
+
+    /// Whether the buffer's file changed on disk since it was last loaded,
+    /// saved, or found to have changed. A file that's gone hasn't changed,
+    /// and large files aren't looked at, being too slow to read again
+    /// behind the user's back.
+    fn changed_on_disk(&mut self) -> bool {
+        if self.large {
+            return false;
+        }
+        let Some(stamp) = self.path.as_deref().and_then(watch::stamp) else {
+            return false;
+        };
+        if self.disk == Some(stamp) {
+            return false;
+        }
+        self.disk = Some(stamp);
+        true
+    }
+
+    /// The text of the buffer's file as it is on disk, decoded like the
+    /// buffer was.
+    fn read_from_disk(&self) -> io::Result<String> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer has no file name",
+            ));
+        };
+        let text = self
+            .charset
+            .decode(&fs::read(path)?)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        Ok(line_ending::normalize(&text))
+    }
+
+    /// Replaces the buffer with its file as it is on disk, as one step that
+    /// can be undone. The cursor keeps its line and column.
+    fn reload(&mut self) -> io::Result<()> {
+        let text = self.read_from_disk()?;
+        let line = self.core.buffer.line_of(self.core.cursor);
+        let column = self.cursor_column();
+        self.snippet = None;
+        self.clear_selection();
+        self.carets.clear();
+        if text != self.core.buffer.to_string() {
+            self.history.begin();
+            self.edit(0..self.core.buffer.len(), &text);
+            self.history.end();
+        }
+        let line = line.min(self.core.buffer.line_count() - 1);
+        self.core.cursor = self.offset_at_column(line, column);
+        self.disk = self.path.as_deref().and_then(watch::stamp);
+        self.git = self.path.as_deref().and_then(git::Changes::load);
+        self.modified = false;
+        self.autosaved = self.revision;
+        self.discard_journal();
+        Ok(())
+    }
+
+    /// Removes the buffer's swap file, its changes saved or given up on.
+    fn discard_journal(&mut self) {
+        if let Some(journal) = self.journal.take() {
+            journal.remove();
+        }
+    }
+
+    /// Replaces `range` of the buffer with `text`, recording it in the
+    /// swap file. Like the undo log, the swap file is a convenience:
+    /// failing to write it doesn't stop the change.
+    fn replace(&mut self, range: Range<usize>, text: &str) {
+        if self.journal.is_none() && !self.large {
+            if let Some(path) = &self.path {
+                self.journal = Journal::start(path, &self.core.buffer.to_string()).ok();
+            }
+        }
+        if let Some(journal) = &self.journal {
+            let _ = journal.append(range.clone(), text);
+        }
+        self.core.buffer.replace(range.clone(), text);
+        for (_, carets) in &mut self.parked {
+            for caret in carets {
+                caret.adjust(range.start, range.len(), text.len());
+            }
+        }
+        // A column kept to through a run of moves up and down goes with an edit.
+        self.core.goal = None;
+    }
+
+    /// Makes the changes read back from a swap file, as one undo step,
+    /// leaving the cursor after the last. Stops at the first change that
+    /// doesn't fit the text, returning `false`.
+    fn recover(&mut self, changes: &[swap::Change]) -> bool {
+        self.history.begin();
+        let mut fits = true;
+        for (range, text) in changes {
+            if range.start > range.end
+                || range.end > self.core.buffer.len()
+                || !self.core.buffer.is_char_boundary(range.start)
+                || !self.core.buffer.is_char_boundary(range.end)
+            {
+                fits = false;
+                break;
+            }
+            self.edit(range.clone(), text);
+            self.core.cursor = range.start + text.len();
+        }
+        self.history.end();
+        self.clear_selection();
+        self.carets.clear();
+        fits
+    }
+
+    /// How many edits were made since the buffer was last saved or autosaved.
+    fn edits_since_autosave(&self) -> u64 {
+        self.revision - self.autosaved
+    }
+
+    /// Notes that the buffer was just autosaved.
+    fn mark_autosaved(&mut self) {
+        self.autosaved = self.revision;
+    }
+
+    /// How the buffer's file is encoded.
+    fn charset(&self) -> Charset {
+        self.charset
+    }
+
+    /// How lines end in the buffer's file.
+    fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Switches the buffer's file between LF and CRLF line endings, from
+    /// the next save on.
+    fn toggle_line_ending(&mut self) {
+        self.line_ending = self.line_ending.toggled();
+        self.modified = true;
+    }
+
+    /// The file backing this buffer, if any.
+    fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Sets the file this buffer saves to.
+    fn set_path(&mut self, path: PathBuf) {
+        let first_line = self.core.buffer.line(0);
+        self.highlighter = Highlighter::new(Filetype::detect(Some(&path), &first_line));
+        self.path = Some(path);
+    }
+
+    /// The syntax colors of the buffer.
+    fn highlighter(&self) -> &Highlighter {
+        &self.highlighter
+    }
+
+    /// How many times the text has changed.
+    fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Highlights the first `lines` lines, if they aren't already.
+    fn highlight(&mut self, lines: usize) {
+        self.highlighter.update(&self.core.buffer, lines);
+    }
+
+    /// The string or comment `offset` is in, as far as the lines highlighted
+    /// so far tell.
+    fn literal_at(&self, offset: usize) -> Option<Range<usize>> {
+        let line = self.core.buffer.line_of(offset);
+        let start = self.core.buffer.line_start(line);
+        self.highlighter
+            .spans(line)
+            .iter()
+            .find(|(range, token)| {
+                matches!(token, Token::String | Token::Comment) && range.contains(&(offset - start))
+            })
+            .map(|(range, _)| start + range.start..start + range.end)
+    }
+
+    /// Where the delimiter matching the one under the cursor is: the other
+    /// bracket of a pair, or the other end of a string.
+    fn matching_delimiter(&self) -> Option<usize> {
+        let ch = self.core.buffer.char_at(self.core.cursor)?;
+        if matches!(ch, '"' | '\'' | '`') {
+            return self.matching_quote(ch);
+        }
+        // Brackets in strings and comments don't pair with the code around
+        // them, unless the match starts in one.
+        let inside = self.literal_at(self.core.cursor).is_some();
+        motion::matching_bracket(&self.core.buffer, self.core.cursor, |pos| {
+            !inside && self.literal_at(pos).is_some()
+        })
+    }
+
+    /// Where the other end of the string whose `quote` is under the cursor
+    /// is. Without syntax colors to go by, quotes on a line pair up in order.
+    fn matching_quote(&self, quote: char) -> Option<usize> {
+        if let Some(range) = self.literal_at(self.core.cursor) {
+            let last = range.end - quote.len_utf8();
+            let is_quote = self.core.buffer.char_at(last) == Some(quote) && last > range.start;
+            return if range.start == self.core.cursor && is_quote {
+                Some(last)
+            } else if last == self.core.cursor && is_quote {
+                Some(range.start)
+            } else {
+                None
+            };
+        }
+        if self.highlighter.filetype() != Filetype::Plain {
+            return None;
+        }
+        let line = self.core.buffer.line_of(self.core.cursor);
+        let start = self.core.buffer.line_start(line);
+        let end = self.core.buffer.line_end(line);
+        let before = self
+            .core
+            .buffer
+            .slice(start..self.core.cursor)
+            .matches(quote)
+            .count();
+        if before.is_multiple_of(2) {
+            self.core
+                .buffer
+                .find(&quote.to_string(), self.core.cursor + 1)
+                .filter(|&pos| pos < end)
+        } else {
+            self.core.buffer.rfind(&quote.to_string(), self.core.cursor)
+        }
+    }
+
+    /// Moves the cursor to the delimiter matching the one under it.
+    fn jump_to_match(&mut self) {
+        if let Some(offset) = self.matching_delimiter() {
+            self.set_cursor(offset);
+        }
+    }
+
+    /// Wraps the selection in `ch` and the delimiter it pairs with, keeping
+    /// the wrapped text selected. Does nothing without a selection.
+    fn surround(&mut self, ch: char) {
+        if let Some(change) = self.core.surround(ch) {
+            self.make(change);
+        }
+    }
+
+    /// Replaces the innermost pair of `ch` delimiters around the selection
+    /// or cursor with the pair of `with`, or deletes them when there's
+    /// nothing to replace them with.
+    fn change_surround(&mut self, ch: char, with: Option<char>) {
+        if let Some(change) = self.core.change_surround(ch, with) {
+            self.make(change);
+        }
+    }
+
+    /// A short name for the buffer: its file name, or `[stdin]` or
+    /// `[scratch]`.
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            None if self.piped => "[stdin]".to_string(),
+            None => "[scratch]".to_string(),
+        }
+    }
+
+    /// Returns `true` if the buffer's file opened in large-file mode.
+    fn is_large(&self) -> bool {
+        self.large
+    }
+
+    /// Returns `true` if the buffer can only be looked through, not changed.
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Makes the buffer read-only, or lets it be changed again.
+    fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Switches the buffer between read-only and editable.
+    fn toggle_read_only(&mut self) {
+        self.read_only = !self.read_only;
+    }
+
+    /// Returns `true` if words spelled wrong are underlined.
+    fn is_spell_checked(&self) -> bool {
+        self.spell_check
+    }
+
+    /// Starts or stops underlining words spelled wrong.
+    fn toggle_spell_check(&mut self) {
+        self.spell_check = !self.spell_check;
+    }
+
+    /// Returns `true` if abbreviations expand as they're typed.
+    fn is_abbreviating(&self) -> bool {
+        self.abbreviate
+    }
+
+    /// Starts or stops expanding abbreviations as they're typed.
+    fn toggle_abbreviations(&mut self) {
+        self.abbreviate = !self.abbreviate;
+    }
+
+    /// The hex dump the buffer is shown as, if it is.
+    fn hex(&self) -> Option<&Hex> {
+        self.hex.as_ref()
+    }
+
+    /// The hex dump, to move its cursor.
+    fn hex_mut(&mut self) -> Option<&mut Hex> {
+        self.hex.as_mut()
+    }
+
+    /// Changes the bytes of the hex dump with `change`, which returns
+    /// whether it changed any. A read-only buffer is left alone.
+    fn edit_hex(&mut self, change: impl FnOnce(&mut Hex) -> bool) {
+        if self.read_only {
+            return;
+        }
+        if let Some(hex) = &mut self.hex {
+            if change(hex) {
+                self.modified = true;
+            }
+        }
+    }
+
+    /// Switches the buffer between its text and a hex dump of its bytes.
+    ///
+    /// The dump is of the file as it is on disk if the buffer has no changes,
+    /// so it holds exactly the bytes there, and of the text encoded the way
+    /// saving would otherwise. Switching back, changed bytes are read as
+    /// text again in one step that can be undone; if they no longer make
+    /// sense in the buffer's encoding, they're read as Latin-1, which takes
+    /// any byte.
+    fn toggle_hex(&mut self) -> Result<(), String> {
+        let Some(hex) = self.hex.take() else {
+            if self.large {
+                return Err("Large files can't be shown as hex".to_string());
+            }
+            let on_disk = match &self.path {
+                Some(path) if !self.modified => fs::read(path).ok(),
+                _ => None,
+            };
+            let bytes = match on_disk {
+                Some(bytes) => bytes,
+                None => self
+                    .charset
+                    .encode(&self.line_ending.apply(&self.core.buffer.to_string()))?,
+            };
+            self.hex = Some(Hex::new(bytes));
+            return Ok(());
+        };
+        if !hex.is_changed() {
+            return Ok(());
+        }
+        let text = self.charset.decode(hex.bytes()).unwrap_or_else(|_| {
+            self.charset = Charset::Latin1;
+            hex.bytes().iter().map(|&byte| byte as char).collect()
+        });
+        self.line_ending = LineEnding::detect(&text);
+        let text = line_ending::normalize(&text);
+        let line = self.core.buffer.line_of(self.core.cursor);
+        let column = self.cursor_column();
+        // Saved from the dump, the text is already what's on disk.
+        let modified = self.modified;
+        self.snippet = None;
+        self.clear_selection();
+        self.carets.clear();
+        if text != self.core.buffer.to_string() {
+            self.history.begin();
+            self.edit(0..self.core.buffer.len(), &text);
+            self.history.end();
+        }
+        let line = line.min(self.core.buffer.line_count() - 1);
+        self.core.cursor = self.offset_at_column(line, column);
+        self.modified = modified;
+        Ok(())
+    }
+
+    /// How the text differs from the file's last commit, as of the last
+    /// `update_git`, if git tracks the file.
+    fn git_changes(&self) -> Option<&git::Changes> {
+        self.git.as_ref()
+    }
+
+    /// Brings the lines marked as changed since the last commit up to date
+    /// with the text.
+    fn update_git(&mut self) {
+        if let Some(git) = &mut self.git {
+            git.update(&self.core.buffer, self.revision);
+        }
+    }
+
+    /// The last commit to change `line`, if it was looked up since the
+    /// text last changed.
+    fn blame(&self, line: usize) -> Option<&str> {
+        match &self.blame {
+            Some((blamed, revision, text)) if *blamed == line && *revision == self.revision => {
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    /// Shows `text` as the last commit to change `line`, as of `revision`.
+    fn set_blame(&mut self, line: usize, revision: u64, text: String) {
+        self.blame = Some((line, revision, text));
+    }
+
+    /// Moves the cursor to the next run of lines changed since the last
+    /// commit, returning `false` if there's none below.
+    fn next_hunk(&mut self) -> bool {
+        self.update_git();
+        let line = self.core.buffer.line_of(self.core.cursor);
+        match self.git.as_ref().and_then(|git| git.next(line)) {
+            Some(line) => {
+                self.goto_line(line, 0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the cursor to the run of lines changed since the last commit
+    /// before this one, returning `false` if there's none above.
+    fn previous_hunk(&mut self) -> bool {
+        self.update_git();
+        let line = self.core.buffer.line_of(self.core.cursor);
+        match self.git.as_ref().and_then(|git| git.previous(line)) {
+            Some(line) => {
+                self.goto_line(line, 0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the buffer has unsaved changes.
+    fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Returns `true` if the text came from standard input, and goes out on
+    /// standard output once the editor quits.
+    fn is_piped(&self) -> bool {
+        self.piped
+    }
+
+    /// Returns `true` if quitting would lose changes: text piped in has
+    /// none to lose, going out on standard output as it is.
+    fn has_unsaved_changes(&self) -> bool {
+        self.modified && !self.piped
+    }
+
+    /// Top-left (column, line) of the region last shown for this buffer.
+    fn scroll(&self) -> (usize, usize) {
+        self.scroll
+    }
+
+    /// Records the region shown for this buffer.
+    fn set_scroll(&mut self, scroll: (usize, usize)) {
+        self.scroll = scroll;
+    }
+
+    /// Puts the cursors aside for pane `pane`, which is losing focus, until
+    /// it gets it back.
+    fn park(&mut self, pane: usize) {
+        let carets = self.carets();
+        self.parked.retain(|&(parked, _)| parked != pane);
+        self.parked.push((pane, carets));
+    }
+
+    /// Takes back the cursors put aside for pane `pane`, which is getting
+    /// focus, if it had any. Text changed all at once, as by reloading the
+    /// file, can leave them past its end, where they're brought back to it.
+    fn unpark(&mut self, pane: usize) {
+        let Some(index) = self.parked.iter().position(|&(parked, _)| parked == pane) else {
+            return;
+        };
+        let (_, mut carets) = self.parked.remove(index);
+        let len = self.core.buffer.len();
+        for caret in &mut carets {
+            caret.cursor = caret.cursor.min(len);
+            caret.selection = caret.selection.filter(|sel| sel.range().end <= len);
+        }
+        self.snippet = None;
+        self.set_carets(carets);
+        self.core.goal = None;
+    }
+
+    /// The cursors of pane `pane` while another pane showing the buffer is
+    /// focused, or `None` if they're the editor's own.
+    fn parked(&self, pane: usize) -> Option<&[Caret]> {
+        self.parked
+            .iter()
+            .find(|&&(parked, _)| parked == pane)
+            .map(|(_, carets)| carets.as_slice())
+    }
+
+    /// Forgets the cursors of pane `pane`, which was closed, and numbers
+    /// the panes after it one lower, as the workspace does.
+    fn forget_pane(&mut self, pane: usize) {
+        self.parked.retain(|&(parked, _)| parked != pane);
+        for (parked, _) in &mut self.parked {
+            if *parked > pane {
+                *parked -= 1;
+            }
+        }
+    }
+
+    /// Replaces `range` of the buffer with `text`.
+    ///
+    /// Every change to the text goes through here, so it can be undone,
+    /// leaving cursor and selection handling to the caller.
+    fn edit(&mut self, range: Range<usize>, text: &str) {
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+        let edit = Edit {
+            offset: range.start,
+            removed: self.core.buffer.slice(range.clone()),
+            inserted: text.to_string(),
+        };
+        self.history.record(self.core.cursor, edit);
+        if let Some(log) = self.edit_log.as_mut() {
+            log.push((range.start, range.len(), text.len()));
+        }
+        if let Some(session) = self.snippet.as_mut() {
+            session.adjust(range.start, range.len(), text.len());
+        }
+        self.marks.adjust(range.start, range.len(), text.len());
+        self.selections.adjust(range.start, range.len(), text.len());
+        self.highlighter
+            .invalidate(self.core.buffer.line_of(range.start));
+        self.replace(range, text);
+        self.modified = true;
+        self.revision += 1;
+    }
+
+    /// Makes the edits `change` works out, as one undo step, leaving the
+    /// cursor and selection where it says.
+    fn make(&mut self, change: Change) {
+        self.history.begin();
+        for (range, text) in change.edits {
+            self.edit(range, &text);
+        }
+        self.history.end();
+        self.core.cursor = change.caret.cursor;
+        self.core.selection = change.caret.selection;
+    }
+
+    /// Reverts the most recent step of edits, putting the cursor back where
+    /// it was before them. Returns `false` if there was nothing to undo.
+    fn undo(&mut self) -> bool {
+        let Some(step) = self.history.undo() else {
+            return false;
+        };
+        self.snippet = None;
+        self.clear_selection();
+        for (range, text) in step.undone() {
+            self.replay(range, text);
+        }
+        self.revision += 1;
+        self.carets.clear();
+        self.core.cursor = step.cursor.min(self.core.buffer.len());
+        self.modified = true;
+        true
+    }
+
+    /// Reapplies the most recently undone step, leaving the cursor after its
+    /// last edit. Returns `false` if there was nothing to redo.
+    fn redo(&mut self) -> bool {
+        let Some(step) = self.history.redo() else {
+            return false;
+        };
+        self.snippet = None;
+        self.clear_selection();
+        for (range, text) in step.redone() {
+            self.replay(range, text);
+        }
+        self.revision += 1;
+        if let Some(last) = step.edits.last() {
+            self.core.cursor = last.offset + last.inserted.len();
+        }
+        self.carets.clear();
+        self.modified = true;
+        true
+    }
+
+    /// Replaces `range` with `text` to undo or redo an edit; going back and
+    /// forth through the undo history isn't recorded in it.
+    fn replay(&mut self, range: Range<usize>, text: &str) {
+        self.marks.adjust(range.start, range.len(), text.len());
+        self.selections.adjust(range.start, range.len(), text.len());
+        self.highlighter
+            .invalidate(self.core.buffer.line_of(range.start));
+        self.replace(range, text);
+    }
+
+    /// Every state the text has been in.
+    fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Takes the text to `state` of its history, undoing and redoing the
+    /// steps in between, whichever branch it's on.
+    fn goto_state(&mut self, state: usize) {
+        let (up, down) = self.history.route(state);
+        for _ in 0..up {
+            self.undo();
+        }
+        for state in down {
+            self.history.choose(state);
+            self.redo();
+        }
+    }
+
+    /// The buffer's file and the (line, column) of the cursor in it, to be
+    /// remembered for when the file is opened again.
+    fn position(&self) -> Option<(PathBuf, usize, usize)> {
+        let path = self.path.clone()?;
+        Some((
+            path,
+            self.core.buffer.line_of(self.core.cursor),
+            self.cursor_column(),
+        ))
     }
 
     /// Updates the editor’s selection state based on the given boundaries.
     ///
     /// If `selection_start` equals `selection_end`, the selection is cleared.
-    /// Otherwise, it extracts the substring from `content` between these indices.
-    fn update_selection(&mut self, content: String, selection_start: usize, selection_end: usize) {
+    /// Otherwise the selection is anchored at `selection_start` with its head
+    /// (and the cursor) at `selection_end`.
+    fn update_selection(&mut self, selection_start: usize, selection_end: usize) {
+        if let Some(sel) = self.core.selection {
+            self.selections.push(sel);
+        }
         if selection_start == selection_end {
-            self.selection.clear();
+            self.core.selection = None;
         } else {
-            let sel = &content[selection_start..selection_end];
-            self.selection = sel.to_string();
+            self.core.selection = Some(Selection::new(selection_start, selection_end));
         }
-        self.selection_start = selection_start;
-        self.selection_end = selection_end;
+        self.core.cursor = selection_end;
     }
 
-    /// Applies a transformation function only to the current selection.
-    ///
-    /// The function cleans the provided content (removing any capture delimiters),
-    /// then splits the text into three parts: prefix, the currently selected text, and suffix.
-    /// It then applies the provided transformation to the selected text,
-    /// updates the selection (and its boundaries), and returns the reassembled text
-    /// with the capture delimiters reinserted.
-    fn apply_transformation<F>(&mut self, content: &str, transform: F) -> String
-    where
-        F: Fn(&str) -> String,
-    {
-        // Remove any markers from the content.
-        let cleaned_content = content.replace("<|", "").replace("|>", "");
+    /// Drops the active selection, leaving the cursor where it is.
+    fn clear_selection(&mut self) {
+        if let Some(sel) = self.core.selection.take() {
+            self.selections.push(sel);
+        }
+        self.expansions.clear();
+    }
+
+    /// Selects what was selected before, again; going back once more gives
+    /// the one before that. The other cursors are dropped.
+    fn restore_selection(&mut self) {
+        if let Some(sel) = self.selections.back(self.core.selection) {
+            self.reselect(sel);
+        }
+    }
 
-        // Grab the prefix, selection, and suffix.
-        let prefix = &cleaned_content[..self.selection_start];
-        let selected = &cleaned_content[self.selection_start..self.selection_end];
-        let suffix = &cleaned_content[self.selection_end..];
+    /// Selects again what `restore_selection` went back from.
+    fn restore_selection_forward(&mut self) {
+        if let Some(sel) = self.selections.forward(self.core.selection) {
+            self.reselect(sel);
+        }
+    }
 
-        // Apply the transformation.
-        let new_selected = transform(selected);
-
-        // Update internal state.
-        self.selection = new_selected.clone();
-        self.selection_end = self.selection_start + new_selected.len();
-
-        // Reassemble the content inserting the delimiters only for display.
-        format!("{}<|{}|>{}", prefix, new_selected, suffix)
-    }
-
-    /// Runs the editor inside a Cursive text UI.
-    fn run(self) {
-        // Use an Arc<Mutex<Editor>> for shared, mutable, thread-safe state.
-        let editor = Arc::new(Mutex::new(self));
-        let mut siv = cursive::default();
-
-        // Create a full-screen text area named "main".
-        let main_text_area = TextArea::new().with_name("main").full_screen();
-        siv.add_layer(main_text_area);
-
-        // -------------------------------------------------
-        // Cursor Movement Callbacks (WASD controls)
-        // -------------------------------------------------
-        siv.add_global_callback(Event::CtrlChar('d'), |s| {
-            s.call_on_name("main", |view: &mut TextArea| {
-                let content = view.get_content();
-                let cur = view.cursor();
-                if cur < content.len() {
-                    if let Some(next_char) = content[cur..].chars().next() {
-                        view.set_cursor(cur + next_char.len_utf8());
-                    }
+    /// Makes `sel` the only selection, with the cursor on its head.
+    fn reselect(&mut self, sel: Selection) {
+        self.carets.clear();
+        self.expansions.clear();
+        self.core.selection = Some(sel);
+        self.core.cursor = sel.head;
+    }
+
+    /// Every cursor, the primary one first, with its selection.
+    fn carets(&self) -> Vec<Caret> {
+        let primary = Caret {
+            cursor: self.core.cursor,
+            selection: self.core.selection,
+        };
+        std::iter::once(primary)
+            .chain(self.carets.iter().copied())
+            .collect()
+    }
+
+    /// Drops every cursor but the primary one.
+    fn clear_carets(&mut self) {
+        self.carets.clear();
+    }
+
+    /// Replaces the `trigger_len` bytes before the cursor with the snippet
+    /// `body` and moves to its first placeholder. Lines after the first get
+    /// the indentation of the line the snippet starts on.
+    fn expand_snippet(&mut self, trigger_len: usize, body: &str) {
+        let start = self.core.cursor - trigger_len;
+        let line_start = self.core.buffer.line_start(self.core.buffer.line_of(start));
+        let indent: String = self
+            .buffer
+            .slice(line_start..start)
+            .chars()
+            .take_while(|&ch| ch == ' ' || ch == '\t')
+            .collect();
+        let (text, mut stops) = snippet::parse(&body.replace('\n', &format!("\n{indent}")));
+        self.clear_selection();
+        self.carets.clear();
+        self.snippet = None;
+        self.edit(start..self.core.cursor, &text);
+        for stop in &mut stops {
+            stop.range = start + stop.range.start..start + stop.range.end;
+        }
+        self.snippet = Some(Session::new(stops, start + text.len()));
+        self.next_stop();
+    }
+
+    /// Replaces the word before the cursor with what it abbreviates, if
+    /// it's one of `abbreviations` and they're expanded in this buffer. Lines
+    /// after the first get the indentation of the line the word is on.
+    fn expand_abbreviation(&mut self, abbreviations: &Abbreviations) {
+        if !self.abbreviate || self.core.selection.is_some_and(|sel| !sel.is_empty()) {
+            return;
+        }
+        let word = self.word_before_cursor();
+        if word.is_empty() {
+            return;
+        }
+        let Some(expansion) = abbreviations.find(self.highlighter.filetype(), &word) else {
+            return;
+        };
+        let start = self.core.cursor - word.len();
+        let line_start = self.core.buffer.line_start(self.core.buffer.line_of(start));
+        let indent: String = self
+            .buffer
+            .slice(line_start..start)
+            .chars()
+            .take_while(|&ch| ch == ' ' || ch == '\t')
+            .collect();
+        let text = expansion.replace('\n', &format!("\n{indent}"));
+        self.history.begin();
+        self.edit(start..self.core.cursor, &text);
+        self.core.cursor = start + text.len();
+        self.history.end();
+    }
+
+    /// Whether a snippet's placeholders are being filled in.
+    fn in_snippet(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    /// Moves on to the snippet's next placeholder; reaching the last one
+    /// finishes the snippet.
+    fn next_stop(&mut self) {
+        let Some(session) = self.snippet.as_mut() else {
+            return;
+        };
+        match session.next() {
+            Some(ranges) => {
+                if session.is_finished() {
+                    self.snippet = None;
                 }
-            });
+                self.select_ranges(ranges);
+            }
+            None => self.snippet = None,
+        }
+    }
+
+    /// Moves back to the snippet's previous placeholder.
+    fn previous_stop(&mut self) {
+        if let Some(ranges) = self.snippet.as_mut().and_then(Session::previous) {
+            self.select_ranges(ranges);
+        }
+    }
+
+    /// Stops filling in the snippet, leaving only the primary cursor.
+    fn end_snippet(&mut self) {
+        self.snippet = None;
+        self.carets.clear();
+    }
+
+    /// Puts a cursor at the end of each of `ranges`, selecting it.
+    fn select_ranges(&mut self, ranges: Vec<Range<usize>>) {
+        let carets = ranges
+            .into_iter()
+            .map(|range| Caret {
+                cursor: range.end,
+                selection: (!range.is_empty()).then(|| Selection::new(range.start, range.end)),
+            })
+            .collect();
+        self.set_carets(carets);
+        self.expansions.clear();
+    }
+
+    /// Adds a cursor on the line below the lowest one, at the same column.
+    fn add_caret_below(&mut self) {
+        let lowest = self
+            .carets()
+            .into_iter()
+            .map(|caret| caret.cursor)
+            .max()
+            .unwrap_or(self.core.cursor);
+        let line = self.core.buffer.line_of(lowest);
+        if line + 1 >= self.core.buffer.line_count() {
+            return;
+        }
+        let line_start = self.core.buffer.line_start(line);
+        let col = self
+            .core
+            .buffer
+            .slice(line_start..lowest)
+            .graphemes(true)
+            .count();
+        let cursor = self.offset_at_column(line + 1, col);
+        self.carets.push(Caret {
+            cursor,
+            selection: None,
         });
+    }
 
-        siv.add_global_callback(Event::CtrlChar('a'), |s| {
-            s.call_on_name("main", |view: &mut TextArea| {
-                let content = view.get_content();
-                let cur = view.cursor();
-                if cur > 0 {
-                    let new_cursor = content[..cur]
-                        .char_indices()
-                        .last()
-                        .map(|(i, _)| i)
-                        .unwrap_or(0);
-                    view.set_cursor(new_cursor);
-                }
-            });
+    /// Puts a cursor on every match of `query`, each selecting its match.
+    /// The first match at or after the cursor becomes the primary one.
+    fn carets_at_matches(&mut self, query: &str) -> bool {
+        let mut matches = self.matches(query);
+        if matches.is_empty() {
+            return false;
+        }
+        let first = matches
+            .iter()
+            .position(|&pos| pos >= self.core.cursor)
+            .unwrap_or(0);
+        matches.rotate_left(first);
+        let carets = matches.into_iter().map(|pos| Caret {
+            cursor: pos + query.len(),
+            selection: Some(Selection::new(pos, pos + query.len())),
         });
+        self.set_carets(carets.collect());
+        self.expansions.clear();
+        true
+    }
 
-        siv.add_global_callback(Event::CtrlChar('s'), |s| {
-            s.call_on_name("main", |view: &mut TextArea| {
-                let content = view.get_content();
-                let cur = view.cursor();
-                let current_line_start = content[..cur].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
-                let col = content[current_line_start..cur].chars().count();
-                let current_line_end = content[cur..]
-                    .find('\n')
-                    .map(|pos| cur + pos)
-                    .unwrap_or(content.len());
-                if current_line_end < content.len() {
-                    let next_line_start = current_line_end + 1;
-                    let next_line_end = content[next_line_start..]
-                        .find('\n')
-                        .map(|pos| next_line_start + pos)
-                        .unwrap_or(content.len());
-                    let next_line_length = content[next_line_start..next_line_end].chars().count();
-                    let new_col = col.min(next_line_length);
-                    let mut byte_offset = next_line_start;
-                    for (i, (b_index, _)) in content[next_line_start..].char_indices().enumerate() {
-                        if i == new_col {
-                            byte_offset = next_line_start + b_index;
-                            break;
-                        }
-                    }
-                    view.set_cursor(byte_offset);
-                }
-            });
+    /// Adds a cursor selecting the next occurrence of the selected text after
+    /// the last cursor, wrapping around at the end of the buffer. Returns
+    /// `false` if every occurrence is selected already.
+    fn select_next_occurrence(&mut self) -> bool {
+        let Some(query) = self.selected_text().filter(|text| !text.is_empty()) else {
+            return false;
+        };
+        let carets = self.carets();
+        let from = carets
+            .last()
+            .and_then(|caret| caret.selection)
+            .map_or(self.core.cursor, |sel| sel.end());
+        let mut matches = self.matches(&query);
+        let first = matches.iter().position(|&pos| pos >= from).unwrap_or(0);
+        matches.rotate_left(first);
+        let selected = |pos: usize| {
+            carets
+                .iter()
+                .any(|caret| caret.selection.is_some_and(|sel| sel.start() == pos))
+        };
+        let Some(pos) = matches.into_iter().find(|&pos| !selected(pos)) else {
+            return false;
+        };
+        self.carets.push(Caret {
+            cursor: pos + query.len(),
+            selection: Some(Selection::new(pos, pos + query.len())),
         });
+        true
+    }
 
-        siv.add_global_callback(Event::CtrlChar('w'), |s| {
-            s.call_on_name("main", |view: &mut TextArea| {
-                let content = view.get_content();
-                let cur = view.cursor();
-                let current_line_start = content[..cur].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
-                let col = content[current_line_start..cur].chars().count();
-                if current_line_start > 0 {
-                    let prev_line_start = content[..current_line_start - 1]
-                        .rfind('\n')
-                        .map(|pos| pos + 1)
-                        .unwrap_or(0);
-                    let prev_line_length = content[prev_line_start..current_line_start - 1]
-                        .chars()
-                        .count();
-                    let new_col = col.min(prev_line_length);
-                    let mut byte_offset = prev_line_start;
-                    for (i, (b_index, _)) in content[prev_line_start..].char_indices().enumerate() {
-                        if i == new_col {
-                            byte_offset = prev_line_start + b_index;
-                            break;
-                        }
+    /// Moves the cursor to the next occurrence of the word it's on as a
+    /// whole word, or the one before, going round the ends of the buffer.
+    /// It lands as far into the word as it was.
+    fn jump_to_occurrence(&mut self, forward: bool) {
+        let Some(word) = self.word_at_cursor() else {
+            return;
+        };
+        let text = self.core.buffer.slice(word.clone());
+        let matches: Vec<usize> = self
+            .matches(&text)
+            .into_iter()
+            .filter(|&pos| motion::is_whole_word(&self.core.buffer, pos..pos + text.len()))
+            .collect();
+        let next = if forward {
+            matches
+                .iter()
+                .find(|&&pos| pos > word.start)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|&&pos| pos < word.start)
+                .or(matches.last())
+        };
+        if let Some(&pos) = next {
+            self.core.set_cursor(pos + self.core.cursor - word.start);
+        }
+    }
+
+    /// Byte offsets of every non-overlapping occurrence of `query`.
+    fn matches(&self, query: &str) -> Vec<usize> {
+        let mut matches = Vec::new();
+        let mut from = 0;
+        while let Some(pos) = self.core.buffer.find(query, from) {
+            matches.push(pos);
+            from = pos + query.len().max(1);
+        }
+        matches
+    }
+
+    /// Replaces every cursor with `carets`, the first becoming the primary
+    /// one. Cursors that ended up in the same place are merged.
+    fn set_carets(&mut self, mut carets: Vec<Caret>) {
+        let mut seen = Vec::new();
+        carets.retain(|caret| {
+            let new = !seen.contains(&caret.cursor);
+            seen.push(caret.cursor);
+            new
+        });
+        let Some(primary) = carets.first().copied() else {
+            return;
+        };
+        self.core.cursor = primary.cursor;
+        self.core.selection = primary.selection.filter(|sel| !sel.is_empty());
+        self.carets = carets.split_off(1);
+    }
+
+    /// Runs `op` once at every cursor, as if each were the only one, and
+    /// keeps the others in place as the text around them changes. All the
+    /// edits form a single undo step.
+    fn for_each_cursor(&mut self, mut op: impl FnMut(&mut Self)) {
+        if self.carets.is_empty() {
+            op(self);
+            return;
+        }
+        let mut carets = self.carets();
+        let expansions = std::mem::take(&mut self.expansions);
+        self.history.begin();
+        for i in 0..carets.len() {
+            self.core.cursor = carets[i].cursor;
+            self.core.selection = carets[i].selection;
+            // The column kept to is the primary cursor's alone.
+            self.core.goal = None;
+            self.edit_log = Some(Vec::new());
+            op(self);
+            let log = self.edit_log.take().unwrap_or_default();
+            carets[i] = Caret {
+                cursor: self.core.cursor,
+                selection: self.core.selection,
+            };
+            for (j, caret) in carets.iter_mut().enumerate() {
+                if j != i {
+                    for &(start, removed, inserted) in &log {
+                        caret.adjust(start, removed, inserted);
                     }
-                    view.set_cursor(byte_offset);
                 }
-            });
-        });
+            }
+        }
+        self.history.end();
+        self.set_carets(carets);
+        self.expansions = expansions;
+    }
 
-        // -------------------------------------------------
-        // Custom Selection Expansion with Ctrl+p
-        // -------------------------------------------------
-        {
-            let editor = editor.clone();
-            siv.add_global_callback(Event::CtrlChar('p'), move |s| {
-                s.call_on_name("main", |view: &mut TextArea| {
-                    let content = view.get_content();
-                    let content_str = content.to_string();
-                    // Remove any existing capture delimiters.
-                    let cleaned_content = content_str.replace("<|", "").replace("|>", "");
-
-                    // Get current selection boundaries from shared state.
-                    let (selection_start, selection_end) = {
-                        let ed = editor.lock().unwrap();
-                        (ed.selection_start, ed.selection_end)
-                    };
-
-                    // Expand left: search backwards in the cleaned text for a space.
-                    let new_bound_l = if selection_start > 0 {
-                        cleaned_content[..selection_start]
-                            .rfind(' ')
-                            .map(|pos| pos + 1)
-                            .unwrap_or(0)
-                    } else {
-                        0
-                    };
-
-                    // Expand right: search forwards for a space.
-                    let new_bound_r = match cleaned_content[selection_end..].find(' ') {
-                        Some(pos) => selection_end + pos,
-                        None => cleaned_content.len(),
-                    };
-
-                    // Update the editor state with the cleaned text and new boundaries.
-                    let mut ed = editor.lock().unwrap();
-                    ed.update_selection(cleaned_content.clone(), new_bound_l, new_bound_r);
-
-                    // Update the view: insert delimiters for display.
-                    let new_content = format!(
-                        "{}<|{}|>{}",
-                        &cleaned_content[..new_bound_l],
-                        ed.selection,
-                        &cleaned_content[new_bound_r..]
-                    );
-                    view.set_content(new_content);
-                });
-            });
+    /// Removes the selected text, if any. Returns `true` if something was removed.
+    fn delete_selection(&mut self) -> bool {
+        match self.core.selection.take() {
+            Some(sel) if !sel.is_empty() => {
+                self.edit(sel.range(), "");
+                self.core.cursor = sel.start();
+                self.expansions.clear();
+                true
+            }
+            _ => false,
         }
+    }
 
-        // -------------------------------------------------
-        // Toggle Selection with Ctrl+Space
-        // -------------------------------------------------
-        {
-            let editor = editor.clone();
-            siv.add_global_callback(Event::CtrlChar(' '), move |s| {
-                s.call_on_name("main", |view: &mut TextArea| {
-                    let orig_cursor = view.cursor();
-                    let content = view.get_content();
-                    let mut ed = editor.lock().unwrap();
-                    if ed.selection.is_empty() {
-                        // When no selection is active, select the character at the cursor.
-                        if orig_cursor < content.len() {
-                            if let Some(ch) = content[orig_cursor..].chars().next() {
-                                let char_len = ch.len_utf8();
-                                let end = orig_cursor + char_len;
-                                // Update current selection and record original boundaries.
-                                ed.update_selection(content.to_string(), orig_cursor, end);
-                                ed.original_selection_start = orig_cursor;
-                                ed.original_selection_end = end;
-                                let new_content = format!(
-                                    "{}<|{}|>{}",
-                                    &content[..orig_cursor],
-                                    ed.selection,
-                                    &content[end..]
-                                );
-                                view.set_content(new_content);
-                                view.set_cursor(orig_cursor + 2);
-                            }
-                        }
-                    } else {
-                        // Remove the inserted markers and clear the selection.
-                        let marker = format!("<|{}|>", ed.selection);
-                        let new_content = content.replace(&marker, &ed.selection);
-                        view.set_content(new_content);
-                        ed.selection.clear();
-                        ed.selection_start = orig_cursor;
-                        ed.selection_end = orig_cursor;
-                        view.set_cursor(orig_cursor.saturating_sub(2));
-                    }
-                });
-            });
+    /// Inserts a character at the cursor, replacing the selection if there is one.
+    fn insert(&mut self, ch: char) {
+        let mut buf = [0; 4];
+        self.insert_str(ch.encode_utf8(&mut buf));
+    }
+
+    /// Inserts `text` at the cursor, replacing the selection if there is one.
+    fn insert_str(&mut self, text: &str) {
+        self.history.begin();
+        self.delete_selection();
+        self.edit(self.core.cursor..self.core.cursor, text);
+        self.core.cursor += text.len();
+        self.history.end();
+    }
+
+    /// Types a tab at the cursor, or with `options` saying to expand tabs,
+    /// spaces up to the next tab stop. A line indented with tabs gets a tab
+    /// either way.
+    fn insert_tab(&mut self, options: &filetype::Options) {
+        let line = self.core.buffer.line_of(self.core.cursor);
+        let start = self.core.buffer.line_start(line);
+        let before = self.core.buffer.slice(start..self.core.cursor);
+        if !options.expand_tab || before.starts_with('\t') {
+            self.insert('\t');
+            return;
         }
+        let column = width::display_width(&before);
+        let spaces = options.tab_width - column % options.tab_width;
+        self.insert_str(&" ".repeat(spaces));
+    }
 
-        // -------------------------------------------------
-        // Reduce Selection with Ctrl+n (Shrink back to original)
-        // -------------------------------------------------
-        {
-            let editor = editor.clone();
-            siv.add_global_callback(Event::CtrlChar('n'), move |s| {
-                s.call_on_name("main", |view: &mut TextArea| {
-                    // First remove any markers from the view.
-                    let content = view.get_content();
-                    let cleaned_content = content.replace("<|", "").replace("|>", "");
-
-                    // Retrieve the original selection boundaries.
-                    let (orig_start, orig_end) = {
-                        let ed = editor.lock().unwrap();
-                        (ed.original_selection_start, ed.original_selection_end)
-                    };
-
-                    // Update the internal selection back to the original boundaries.
-                    {
-                        let mut ed = editor.lock().unwrap();
-                        ed.update_selection(cleaned_content.clone(), orig_start, orig_end);
-                    }
+    /// Starts a new line at the cursor, indented like the current one, and a
+    /// level deeper, as `options` has levels, after a line that opens a
+    /// block. Between a pair of brackets, the closing one goes on a line of
+    /// its own.
+    fn newline(&mut self, options: &filetype::Options) {
+        self.history.begin();
+        self.delete_selection();
+        let line_start = self
+            .core
+            .buffer
+            .line_start(self.core.buffer.line_of(self.core.cursor));
+        let before = self.core.buffer.slice(line_start..self.core.cursor);
+        let indent: String = before
+            .chars()
+            .take_while(|&ch| ch == ' ' || ch == '\t')
+            .collect();
+        let opens = before
+            .trim_end()
+            .chars()
+            .next_back()
+            .is_some_and(|ch| self.highlighter.indents_after(ch));
+        let mut text = format!("\n{indent}");
+        let mut after = String::new();
+        if opens {
+            text += &options.indent_unit(&indent);
+            if matches!(
+                self.core.buffer.char_at(self.core.cursor),
+                Some(')' | ']' | '}')
+            ) {
+                after = format!("\n{indent}");
+            }
+        }
+        self.edit(self.core.cursor..self.core.cursor, &(text.clone() + &after));
+        self.core.cursor += text.len();
+        self.history.end();
+    }
+
+    /// Indents the selected lines, or the cursor's line, by one level.
+    /// Blank lines are left alone.
+    fn indent(&mut self, options: &filetype::Options) {
+        self.reindent(false, options);
+    }
+
+    /// Takes one level of indentation off the selected lines, or the
+    /// cursor's line.
+    fn dedent(&mut self, options: &filetype::Options) {
+        self.reindent(true, options);
+    }
+
+    /// Adds or removes a level of indentation, as `options` has levels, at
+    /// the start of each line `indent` or `dedent` work on, moving the
+    /// cursor and selection along with the text.
+    fn reindent(&mut self, dedent: bool, options: &filetype::Options) {
+        let change = self.core.reindent(dedent, options);
+        self.make(change);
+    }
+
+    /// Comments out the selected lines, or the cursor's line, by putting
+    /// `leader` and a space at the indentation they share, or takes the
+    /// leader off again if every one of them starts with it. Blank lines are
+    /// left alone.
+    fn toggle_comment(&mut self, leader: &str) {
+        if let Some(change) = self.core.toggle_comment(leader) {
+            self.make(change);
+        }
+    }
+
+    /// Moves the cursor and selection by `delta` bytes, along with the text
+    /// they're on.
+    fn shift_cursor(&mut self, delta: isize) {
+        self.core.cursor = self.core.cursor.saturating_add_signed(delta);
+        if let Some(sel) = self.core.selection.as_mut() {
+            sel.anchor = sel.anchor.saturating_add_signed(delta);
+            sel.head = sel.head.saturating_add_signed(delta);
+        }
+    }
+
+    /// Joins the selected lines, or the cursor's line and the next, into
+    /// one. The whitespace around each line break becomes a single space.
+    fn join_lines(&mut self) {
+        if let Some(change) = self.core.join_lines() {
+            self.make(change);
+        }
+    }
+
+    /// Rewraps the selected lines, or the paragraph the cursor is in, to
+    /// `width` columns, leaving the cursor at the end of them. A selection
+    /// is kept over the lines rewrapped.
+    fn reflow(&mut self, width: usize) {
+        let lines = match self.selected_lines() {
+            Some(lines) => lines,
+            None => match motion::paragraph_around(
+                &self.core.buffer,
+                self.core.cursor..self.core.cursor,
+            ) {
+                Some(paragraph) => motion::lines_of(&self.core.buffer, paragraph),
+                None => return,
+            },
+        };
+        let start = self.core.buffer.line_start(lines.start);
+        let last = lines.end - 1;
+        let end = self.core.buffer.line_start(last) + self.core.buffer.line(last).len();
+        let text = self.core.buffer.slice(start..end);
+        let reflowed = reflow::reflow(&text, width);
+        let selected = self.core.selection.is_some();
+        self.clear_selection();
+        self.edit(start..end, &reflowed);
+        self.core.cursor = start + reflowed.len();
+        if selected {
+            self.update_selection(start, self.core.cursor);
+        }
+    }
+
+    /// Makes a copy of the selected lines, or the cursor's line, below
+    /// them, moving the cursor and selection onto the copy.
+    fn duplicate_lines(&mut self) {
+        let lines = self.current_lines();
+        let start = self.core.buffer.line_start(lines.start);
+        let end = self.core.buffer.line_start(lines.end);
+        let text = self.core.buffer.slice(start..end);
+        let copy = if text.ends_with('\n') {
+            text
+        } else {
+            format!("\n{text}")
+        };
+        self.edit(end..end, &copy);
+        self.shift_cursor(copy.len() as isize);
+    }
+
+    /// Swaps the selected lines, or the cursor's line, with the line above.
+    fn move_lines_up(&mut self) {
+        let lines = self.current_lines();
+        if lines.start == 0 {
+            return;
+        }
+        let above = self.core.buffer.line(lines.start - 1);
+        self.swap_lines(lines.start - 1..lines.end, lines.start - 1);
+        self.shift_cursor(-(above.len() as isize + 1));
+    }
+
+    /// Swaps the selected lines, or the cursor's line, with the line below.
+    fn move_lines_down(&mut self) {
+        let lines = self.current_lines();
+        if lines.end >= self.core.buffer.line_count() {
+            return;
+        }
+        let below = self.core.buffer.line(lines.end);
+        self.swap_lines(lines.start..lines.end + 1, lines.end);
+        self.shift_cursor(below.len() as isize + 1);
+    }
+
+    /// Moves line `single`, the first or last of `lines`, to the other end
+    /// of them, leaving the cursor to the caller.
+    fn swap_lines(&mut self, lines: Range<usize>, single: usize) {
+        let start = self.core.buffer.line_start(lines.start);
+        let end = self.core.buffer.line_start(lines.end);
+        let text = self.core.buffer.slice(start..end);
+        let trailing = text.ends_with('\n');
+        let mut rows: Vec<&str> = text
+            .strip_suffix('\n')
+            .unwrap_or(&text)
+            .split('\n')
+            .collect();
+        if single == lines.start {
+            rows.rotate_left(1);
+        } else {
+            rows.rotate_right(1);
+        }
+        let mut swapped = rows.join("\n");
+        if trailing {
+            swapped.push('\n');
+        }
+        let cursor = self.core.cursor;
+        self.edit(start..end, &swapped);
+        self.core.cursor = cursor;
+    }
+
+    /// Deletes the selected lines, or the cursor's line, leaving the cursor
+    /// at its column on the line that takes their place.
+    fn delete_lines(&mut self) {
+        let lines = self.current_lines();
+        let col = self.cursor_column();
+        let mut start = self.core.buffer.line_start(lines.start);
+        let end = self.core.buffer.line_start(lines.end);
+        // The last line has no newline of its own, so it takes the one before it.
+        if lines.end >= self.core.buffer.line_count() && start > 0 {
+            start -= 1;
+        }
+        self.clear_selection();
+        self.edit(start..end, "");
+        let line = lines
+            .start
+            .min(self.core.buffer.line_count().saturating_sub(1));
+        self.core.cursor = self.offset_at_column(line, col);
+    }
+
+    /// Adds `delta` to the number under or after the cursor on its line,
+    /// leaving the cursor on its last digit. Does nothing if there's none.
+    fn increment(&mut self, delta: i64) {
+        if let Some(change) = self.core.increment(delta) {
+            self.clear_selection();
+            self.make(change);
+        }
+    }
+
+    /// Deletes the selection, or the character before the cursor.
+    fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if let Some(prev) = self.core.buffer.previous_grapheme(self.core.cursor) {
+            self.edit(prev..self.core.cursor, "");
+            self.core.cursor = prev;
+        }
+    }
+
+    /// Deletes the selection, or the character under the cursor.
+    fn delete(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if let Some(end) = self.core.buffer.next_grapheme(self.core.cursor) {
+            self.edit(self.core.cursor..end, "");
+        }
+    }
+
+    /// Jumps to the start of the buffer.
+    fn goto_start(&mut self) {
+        self.jump_to(0);
+    }
+
+    /// Jumps to the end of the buffer.
+    fn goto_end(&mut self) {
+        self.jump_to(self.core.buffer.len());
+    }
+
+    /// Moves the cursor to character column `col` of `line`, both counted
+    /// from 0 and clamped to the buffer, dropping the selection.
+    fn goto_line(&mut self, line: usize, col: usize) {
+        let line = line.min(self.core.buffer.line_count().saturating_sub(1));
+        self.jump_to(self.offset_at_column(line, col));
+    }
+
+    /// Moves the cursor to `offset`, dropping the selection, and records
+    /// where it was in the jump list.
+    fn jump_to(&mut self, offset: usize) {
+        self.record_jump(self.core.cursor);
+        self.clear_selection();
+        self.carets.clear();
+        self.core.set_cursor(offset.min(self.core.buffer.len()));
+    }
+
+    /// Records `from` in the jump list, as a place a large move left.
+    fn record_jump(&mut self, from: usize) {
+        self.marks.push_jump(from);
+    }
+
+    /// Puts the mark `name` at the cursor.
+    fn set_mark(&mut self, name: &str) {
+        self.marks.set(name, self.core.cursor);
+    }
+
+    /// Jumps to the mark `name`. Returns `false` if it isn't set.
+    fn goto_mark(&mut self, name: &str) -> bool {
+        match self.marks.get(name) {
+            Some(offset) => {
+                self.jump_to(offset);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The marks set in the buffer, by name.
+    fn marks(&self) -> &Marks {
+        &self.marks
+    }
+
+    /// Goes back to the place the last jump left. Returns `false` if there
+    /// is none.
+    fn jump_back(&mut self) -> bool {
+        let Some(offset) = self.marks.jump_back(self.core.cursor) else {
+            return false;
+        };
+        self.clear_selection();
+        self.carets.clear();
+        self.core.set_cursor(offset.min(self.core.buffer.len()));
+        true
+    }
+
+    /// Goes forward again to the place the last jump back left. Returns
+    /// `false` if there is none.
+    fn jump_forward(&mut self) -> bool {
+        let Some(offset) = self.marks.jump_forward() else {
+            return false;
+        };
+        self.clear_selection();
+        self.carets.clear();
+        self.core.set_cursor(offset.min(self.core.buffer.len()));
+        true
+    }
 
-                    // Update the view with the original selection reinserted.
-                    let new_content = format!(
-                        "{}<|{}|>{}",
-                        &cleaned_content[..orig_start],
-                        &cleaned_content[orig_start..orig_end],
-                        &cleaned_content[orig_end..]
-                    );
-                    view.set_content(new_content);
-                    // Position the cursor at the original selection start, adjusted for the opening delimiter.
-                    view.set_cursor(orig_start + 2);
-                });
+    /// Toggles the selection.
+    ///
+    /// When nothing is selected the character under the cursor becomes the
+    /// selection (and is remembered as the original selection); otherwise the
+    /// selection is dropped and the cursor stays where it is.
+    fn toggle_selection(&mut self) {
+        if self.core.selection.is_some() {
+            self.clear_selection();
+            return;
+        }
+        let cur = self.core.cursor;
+        if let Some(end) = self.core.buffer.next_grapheme(cur) {
+            self.update_selection(cur, end);
+            self.expansions.clear();
+        }
+    }
+
+    /// Grows the selection (or the cursor position) to the next structure
+    /// around it: the word, then the line, sentence or paragraph, the inside
+    /// of the surrounding brackets, the brackets themselves, and so on out to
+    /// the whole buffer, whichever is smallest. In code, the expression,
+    /// statement and function around it are among them, as far as the
+    /// language's brackets, separators and indentation tell them apart.
+    fn expand_selection(&mut self) {
+        let structure = self.highlighter.filetype().structure();
+        if let Some(next) = self.core.expansion(structure) {
+            self.expansions.push(Caret {
+                cursor: self.core.cursor,
+                selection: self.core.selection,
             });
+            self.update_selection(next.start, next.end);
         }
+    }
 
-        // -------------------------------------------------
-        // Transformation Menu with Ctrl+u - only applies to selected text.
-        // -------------------------------------------------
+    /// Selects the cursor's line, or the selected ones, whole: from the start
+    /// of the first through the newline of the last. Once they are, selects
+    /// the next line too.
+    fn select_lines(&mut self) {
+        let range = self
+            .selection
+            .map_or(self.core.cursor..self.core.cursor, |sel| sel.range());
+        let mut lines = motion::lines_of(&self.core.buffer, range.clone());
+        if motion::whole_lines(&self.core.buffer, lines.clone()) == range
+            && lines.end < self.core.buffer.line_count()
         {
-            let editor = editor.clone();
-            siv.add_global_callback(Event::CtrlChar('u'), move |s| {
-                let mut sv: SelectView<Choice> = SelectView::new();
-                sv.add_item("Uppercase", Choice::Upper);
-                sv.add_item("Lowercase", Choice::Lower);
-                sv.add_item("Capitalized", Choice::Cap);
-                sv.add_item("Reverse", Choice::Rev);
-
-                // On submit, only transform the selected text.
-                let value = editor.clone();
-                sv.set_on_submit(move |s, item| {
-                    s.call_on_name("main", |view: &mut TextArea| {
-                        let content = view.get_content().to_string();
-                        let mut ed = value.lock().unwrap();
-
-                        // Use the transformation API to apply a transformation only to the selection.
-                        let new_content = match item {
-                            Choice::Upper => {
-                                ed.apply_transformation(&content, |s| s.to_uppercase())
-                            }
-                            Choice::Lower => {
-                                ed.apply_transformation(&content, |s| s.to_lowercase())
-                            }
-                            Choice::Cap => ed.apply_transformation(&content, |s| capitalize(s)),
-                            Choice::Rev => ed.apply_transformation(&content, |s| {
-                                s.to_string().chars().rev().collect()
-                            }),
-                        };
-                        view.set_content(new_content);
-                    });
-                    s.pop_layer();
-                });
-                s.add_layer(sv);
-            });
+            lines.end += 1;
+        }
+        let whole = motion::whole_lines(&self.core.buffer, lines);
+        self.update_selection(whole.start, whole.end);
+    }
+
+    /// Selects the indented block around the cursor's line, or the selected
+    /// lines, whole; once it is, the block around that.
+    fn select_block(&mut self) {
+        let range = self
+            .selection
+            .map_or(self.core.cursor..self.core.cursor, |sel| sel.range());
+        let lines = motion::lines_of(&self.core.buffer, range);
+        let block = motion::block_around(&self.core.buffer, lines);
+        let whole = motion::whole_lines(&self.core.buffer, block);
+        self.update_selection(whole.start, whole.end);
+    }
+
+    /// Shrinks the selection back one level of expansion, back to the bare
+    /// cursor it started from at the last level.
+    fn shrink_selection(&mut self) {
+        if let Some(previous) = self.expansions.pop() {
+            self.core.cursor = previous.cursor;
+            self.core.selection = previous.selection;
+        }
+    }
+
+    /// Moves the cursor to the first match of `query` at or after `from`,
+    /// wrapping around to the start of the buffer. Returns `false` if there is no match.
+    fn search_from(&mut self, query: &str, from: usize) -> bool {
+        let found = self
+            .buffer
+            .find(query, from)
+            .or_else(|| self.core.buffer.find(query, 0));
+        match found {
+            Some(pos) => {
+                self.set_cursor(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the cursor to the next match of `query` after the cursor.
+    fn search_next(&mut self, query: &str) -> bool {
+        let from = self.core.cursor
+            + self
+                .core
+                .buffer
+                .char_at(self.core.cursor)
+                .map_or(0, char::len_utf8);
+        self.search_from(query, from)
+    }
+
+    /// Moves the cursor to the closest match of `query` before the cursor,
+    /// wrapping around to the end of the buffer.
+    fn search_previous(&mut self, query: &str) -> bool {
+        let found = self
+            .buffer
+            .rfind(query, self.core.cursor)
+            .or_else(|| self.core.buffer.rfind(query, self.core.buffer.len()));
+        match found {
+            Some(pos) => {
+                self.set_cursor(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces each range in `edits` (sorted, non-overlapping) with its text,
+    /// leaving the cursor just after the last replacement.
+    fn replace_ranges(&mut self, edits: Vec<(Range<usize>, String)>) {
+        let Some(last_end) = edits.last().map(|(range, _)| range.end) else {
+            return;
+        };
+        let shift: isize = edits
+            .iter()
+            .map(|(range, text)| text.len() as isize - range.len() as isize)
+            .sum();
+        self.core.selection = None;
+        self.expansions.clear();
+        self.carets.clear();
+        self.history.begin();
+        for (range, text) in edits.into_iter().rev() {
+            self.edit(range, &text);
+        }
+        self.history.end();
+        self.core.cursor = last_end.saturating_add_signed(shift);
+    }
+
+    /// Applies `transform` to every cursor's selection; with a single cursor
+    /// this is `apply_transformation`, so the whole buffer is rewritten when
+    /// nothing is selected.
+    fn transform_selections<F>(&mut self, transform: F)
+    where
+        F: Fn(&str) -> String,
+    {
+        if self.carets.is_empty() {
+            self.apply_transformation(transform);
+            return;
+        }
+        self.for_each_cursor(|ed| {
+            if ed.selection.is_some() {
+                ed.apply_transformation(&transform);
+            }
+        });
+    }
+
+    /// Like `transform_selections`, but with a transform that may fail; if
+    /// it fails on any selection, nothing is changed. When the transform can
+    /// tell where in the text it failed, that character is selected.
+    fn try_transform_selections<F>(&mut self, transform: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Result<String, Failure>,
+    {
+        let inputs: Vec<Range<usize>> = if self.carets.is_empty() {
+            vec![self
+                .selection
+                .map_or(0..self.core.buffer.len(), |sel| sel.range())]
+        } else {
+            self.carets()
+                .iter()
+                .filter_map(|caret| caret.selection)
+                .map(|sel| sel.range())
+                .collect()
+        };
+        // Everything is transformed up front, so a failure leaves the text alone.
+        let mut outputs = HashMap::new();
+        for range in inputs {
+            let input = self.core.buffer.slice(range.clone());
+            match transform(&input) {
+                Ok(output) => outputs.insert(input, output),
+                Err(failure) => {
+                    if let Some(at) = failure.at {
+                        let start = range.start + at;
+                        let end = self.core.buffer.next_grapheme(start).unwrap_or(start);
+                        self.carets.clear();
+                        self.update_selection(start, end.min(range.end));
+                    }
+                    return Err(failure.message);
+                }
+            };
         }
+        self.transform_selections(|text| outputs[text].clone());
+        Ok(())
+    }
+
+    /// Applies a transformation function to the current selection, or to the
+    /// whole buffer when nothing is selected.
+    ///
+    /// With a selection, only the selected span is rewritten; the selection is
+    /// kept over the replacement text (with its anchor/head orientation
+    /// preserved) and the cursor stays on its head. Without one, the cursor
+    /// keeps its offset, clamped to the new content.
+    fn apply_transformation<F>(&mut self, transform: F)
+    where
+        F: Fn(&str) -> String,
+    {
+        let Some(sel) = self.core.selection else {
+            let transformed = transform(&self.core.buffer.to_string());
+            self.edit(0..self.core.buffer.len(), &transformed);
+            let mut cursor = self.core.cursor.min(self.core.buffer.len());
+            while !self.core.buffer.is_char_boundary(cursor) {
+                cursor -= 1;
+            }
+            self.core.cursor = cursor;
+            return;
+        };
 
-        // -------------------------------------------------
-        // (Existing transformation menu API now only applies to the selection.)
-        // -------------------------------------------------
+        // Apply the transformation.
+        let new_selected = transform(&self.core.buffer.slice(sel.range()));
 
-        siv.run();
+        // Splice the result back in and select it, facing the same way as before.
+        let start = sel.start();
+        let end = start + new_selected.len();
+        self.edit(sel.range(), &new_selected);
+        if sel.head < sel.anchor {
+            self.update_selection(end, start);
+        } else {
+            self.update_selection(start, end);
+        }
+        self.expansions.clear();
     }
 }
 
-/// Capitalizes each word in the provided text.
-fn capitalize(text: &str) -> String {
-    text.split_whitespace()
-        .map(|word| {
-            let mut chars = word.chars();
-            match chars.next() {
-                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
-                None => String::new(),
+/// Runs the editor inside a Cursive text UI, with its keymap dispatching keys to commands.
+fn run(workspace: Workspace) {
+    // Use an Arc<Mutex<Workspace>> for shared, mutable, thread-safe state.
+    let workspace = Arc::new(Mutex::new(workspace));
+    let mut siv = cursive::default();
+
+    // Create a full-screen view named "main" holding every pane, above the
+    // status bar, in a row the file tree can be docked left of and the
+    // Markdown preview right of.
+    let main_view = EditorView::new(workspace.clone())
+        .with_name("main")
+        .full_screen();
+    let body = LinearLayout::horizontal()
+        .child(main_view)
+        .with_name(tree::BODY);
+    let screen = LinearLayout::vertical()
+        .child(body.full_screen())
+        .child(StatusBar::new(workspace.clone()));
+    siv.add_fullscreen_layer(screen);
+
+    // -------------------------------------------------
+    // Event dispatch: all input passes through here
+    // -------------------------------------------------
+    // Ctrl+c is copy, so quitting is left to the keymap (Ctrl+q by default).
+    siv.clear_global_callbacks(Event::CtrlChar('c'));
+
+    // Every key is seen here first, before any view, so macros can record it.
+    // Once a key sequence has been started, the keys after go to it.
+    {
+        let workspace = workspace.clone();
+        siv.set_on_pre_event_inner(EventTrigger::any(), move |event| {
+            let mut ws = workspace.lock().unwrap();
+            ws.note_input();
+            ws.recorder_mut().record(event);
+            let started = !ws.pending().keys().is_empty();
+            drop(ws);
+            if started && keymap::format_event(event).is_some() {
+                return hints::press(&workspace, event);
             }
-        })
-        .collect::<Vec<String>>()
-        .join(" ")
+            None
+        });
+    }
+
+    // Keys the views leave alone run the command the keymap binds them to,
+    // or start a sequence of keys.
+    {
+        let workspace = workspace.clone();
+        siv.set_on_event_inner(EventTrigger::any(), move |event| match event {
+            Event::Key(key) if view::TEXT_KEYS.contains(key) => None,
+            _ => hints::press(&workspace, event),
+        });
+    }
+
+    // Autosaves, and looking for files changed on disk, happen on the event
+    // loop, which a timer thread wakes.
+    {
+        let sink = siv.cb_sink().clone();
+        let workspace = workspace.clone();
+        thread::spawn(move || loop {
+            thread::sleep(autosave::INTERVAL);
+            let workspace = workspace.clone();
+            if sink
+                .send(Box::new(move |s| {
+                    autosave::tick(&workspace);
+                    watch::tick(s, &workspace);
+                }))
+                .is_err()
+            {
+                break;
+            }
+        });
+    }
+
+    // Once the cursor has rested on a word, its occurrences are highlighted,
+    // which takes a redraw nothing else would ask for, and the hooks hear
+    // about the selection it has come to.
+    {
+        let sink = siv.cb_sink().clone();
+        let workspace = workspace.clone();
+        thread::spawn(move || {
+            let mut shown = None;
+            loop {
+                let last = workspace.lock().unwrap().last_input();
+                let rested = last.elapsed();
+                if rested < view::REST {
+                    thread::sleep(view::REST - rested);
+                    continue;
+                }
+                if shown != Some(last) {
+                    shown = Some(last);
+                    let workspace = workspace.clone();
+                    let rested = Box::new(move |_: &mut _| {
+                        workspace.lock().unwrap().report_selection();
+                    });
+                    if sink.send(rested).is_err() {
+                        break;
+                    }
+                }
+                thread::sleep(view::REST);
+            }
+        });
+    }
+
+    // Language servers answer on threads of their own; each message wakes
+    // the event loop to handle it.
+    {
+        let sink = siv.cb_sink().clone();
+        let handle = workspace.clone();
+        workspace
+            .lock()
+            .unwrap()
+            .lsp_mut()
+            .set_waker(Arc::new(move || {
+                let workspace = handle.clone();
+                let _ = sink.send(Box::new(move |s| lsp::poll(s, &workspace)));
+            }));
+    }
+
+    // Changes a crashed session left behind are offered before anything else.
+    swap::offer(&mut siv, &workspace);
+
+    if let Err(err) = recover::run(&mut siv, &workspace) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+
+    // Quitting saves or gives up on every change, so no swap file is left.
+    let mut ws = workspace.lock().unwrap();
+    for ed in ws.editors_mut() {
+        ed.discard_journal();
+    }
+    // Text piped in goes out again as it was left, for the next command in
+    // the pipe.
+    for ed in ws.editors().iter().filter(|ed| ed.is_piped()) {
+        if let Err(err) = ed.write_to(&mut io::stdout().lock()) {
+            eprintln!("Could not write to standard output: {err}");
+        }
+    }
+    let files: Vec<_> = ws.editors().iter().filter_map(Editor::position).collect();
+    let _ = positions::remember(&files);
+}
+
+/// The configuration `loaded`, or else its error, printed before exiting.
+fn load_or_exit<T, E: std::fmt::Display>(loaded: Result<T, E>) -> T {
+    loaded.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    })
 }
 
 /// Do the thing
 fn main() {
-    let editor = Editor::new();
-    editor.run();
+    let (action, config) = match cli::parse(std::env::args_os().skip(1).collect()) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(dir) = config {
+        keymap::set_config_dir(dir);
+    }
+    let options = match action {
+        cli::Action::Edit(options) => options,
+        // `--batch` runs transforms over a file and prints the result,
+        // without starting the editor.
+        cli::Action::Batch(args) => {
+            if let Err(err) = batch::run(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        cli::Action::Version => {
+            println!("wasdev {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        cli::Action::Help => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+    };
+    let mut editors = Vec::new();
+    for open in options.files {
+        let editor = match &open.path {
+            Some(path) => Editor::open(path, None),
+            None => Editor::read_stdin(),
+        };
+        let mut editor = match editor {
+            Ok(editor) => editor,
+            Err(err) => {
+                match &open.path {
+                    Some(path) => eprintln!("{}: {}", path.display(), err),
+                    None => eprintln!("standard input: {err}"),
+                }
+                std::process::exit(1);
+            }
+        };
+        if let Some((line, column)) = open.position {
+            editor.goto_line(line, column);
+        }
+        editor.set_read_only(options.read_only);
+        editors.push(editor);
+    }
+    let keymap = load_or_exit(Keymap::load());
+    let lsp = load_or_exit(Lsp::load());
+    let snippets = load_or_exit(Snippets::load());
+    let settings = load_or_exit(Settings::load());
+    let macros = load_or_exit(Macros::load());
+    let abbreviations = load_or_exit(Abbreviations::load());
+    let scripts = load_or_exit(Scripts::load());
+    let plugins = load_or_exit(Plugins::load());
+    let mut workspace = Workspace::new(editors);
+    workspace.set_keymap(keymap);
+    workspace.set_lsp(lsp);
+    workspace.set_snippets(snippets);
+    workspace.set_abbreviations(abbreviations);
+    workspace.set_settings(settings);
+    workspace.set_scripts(scripts);
+    workspace.set_plugins(plugins);
+    workspace.set_macros(macros);
+    run(workspace);
 }
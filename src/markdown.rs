@@ -0,0 +1,546 @@
+use crate::tree::BODY;
+use crate::width::display_width;
+use crate::workspace::Workspace;
+use cursive::direction::Direction;
+use cursive::event::EventResult;
+use cursive::theme::{BaseColor, Color, ColorStyle, Effect, PaletteColor, Style};
+use cursive::utils::markup::StyledString;
+use cursive::view::{CannotFocus, Nameable};
+use cursive::views::{Dialog, LinearLayout};
+use cursive::{Cursive, Printer, Vec2, View};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use unicode_width::UnicodeWidthChar;
+
+/// Name of the preview, while it's shown.
+pub const NAME: &str = "markdown_preview";
+
+/// Whether the file at `path` is Markdown, going by its extension.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "md" || ext == "markdown")
+}
+
+/// A row of the rendered document: runs of text in their styles, and the
+/// line of the source it came from.
+struct Row {
+    spans: Vec<(String, Style)>,
+    line: usize,
+}
+
+/// The focused buffer's Markdown, rendered with terminal styling in a pane
+/// docked right of the panes: headings, emphasis, code, lists, quotes and
+/// rules. It scrolls along with the cursor, keeping the part of the
+/// document being edited in view.
+pub struct MarkdownPreview {
+    workspace: Arc<Mutex<Workspace>>,
+    rows: Vec<Row>,
+    /// The buffer, its revision and the width the rows were rendered for.
+    rendered: Option<(usize, u64, usize)>,
+    /// The first row shown.
+    top: usize,
+}
+
+impl MarkdownPreview {
+    /// Creates a preview of whichever buffer is focused.
+    pub fn new(workspace: Arc<Mutex<Workspace>>) -> Self {
+        Self {
+            workspace,
+            rows: Vec::new(),
+            rendered: None,
+            top: 0,
+        }
+    }
+}
+
+impl View for MarkdownPreview {
+    fn draw(&self, printer: &Printer) {
+        printer.with_color(ColorStyle::secondary(), |printer| {
+            for y in 0..printer.size.y {
+                printer.print_hline((0, y), printer.size.x, " ");
+            }
+            printer.print_vline((0, 0), printer.size.y, "│");
+        });
+        let printer = &printer.offset((2, 0));
+        for (y, row) in self
+            .rows
+            .iter()
+            .skip(self.top)
+            .take(printer.size.y)
+            .enumerate()
+        {
+            let mut x = 0;
+            for (text, style) in &row.spans {
+                printer.with_style(*style, |printer| printer.print((x, y), text));
+                x += display_width(text);
+            }
+        }
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        let ws = self.workspace.lock().unwrap();
+        let ed = ws.editor();
+        let width = size.x.saturating_sub(3).max(1);
+        let key = (ws.current(), ed.revision(), width);
+        if self.rendered != Some(key) {
+            self.rows = if ed.path().is_some_and(is_markdown) {
+                render(&ed.buffer().to_string(), width)
+            } else {
+                let note = "The buffer isn't Markdown".to_string();
+                let style = Style::from(ColorStyle::secondary()).combine(Effect::Italic);
+                vec![Row {
+                    spans: vec![(note, style)],
+                    line: 0,
+                }]
+            };
+            self.rendered = Some(key);
+        }
+        // The last row from at or before the cursor's line is the one it's in.
+        let line = ed.buffer().line_of(ed.cursor());
+        let row = self
+            .rows
+            .iter()
+            .rposition(|row| row.line <= line)
+            .unwrap_or(0);
+        if row < self.top {
+            self.top = row;
+        } else if size.y > 0 && row >= self.top + size.y {
+            self.top = row + 1 - size.y;
+        }
+        self.top = self.top.min(self.rows.len().saturating_sub(1));
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        // Wanting all the room, like the panes, it gets half the row.
+        constraint
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        // The keys stay with the panes, so the source can be edited alongside.
+        Err(CannotFocus)
+    }
+}
+
+/// Shows the preview right of the panes, or hides it if it's shown. It
+/// starts out only for Markdown files.
+pub fn toggle(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let markdown = workspace
+        .lock()
+        .unwrap()
+        .editor()
+        .path()
+        .is_some_and(is_markdown);
+    let workspace = workspace.clone();
+    let shown = s.call_on_name(BODY, move |body: &mut LinearLayout| {
+        match body.find_child_from_name(NAME) {
+            Some(index) => {
+                body.remove_child(index);
+                true
+            }
+            None if markdown => {
+                body.add_child(MarkdownPreview::new(workspace).with_name(NAME));
+                true
+            }
+            None => false,
+        }
+    });
+    if shown == Some(false) {
+        s.add_layer(Dialog::info("The preview is for Markdown files"));
+    }
+}
+
+/// The style of ordinary text, like the file tree's.
+fn plain() -> Style {
+    ColorStyle::secondary().into()
+}
+
+/// The style of text in `color`, on the preview's background.
+fn colored(color: Color) -> Style {
+    ColorStyle::new(color, PaletteColor::View).into()
+}
+
+/// The style of code, in spans and blocks.
+fn code_style() -> Style {
+    colored(Color::Light(BaseColor::Green))
+}
+
+/// The style of rules and the bars beside quotes.
+fn dim() -> Style {
+    colored(Color::Dark(BaseColor::White))
+}
+
+/// Renders the Markdown `text` into rows at most `width` columns wide.
+fn render(text: &str, width: usize) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let mut paragraph: Option<Paragraph> = None;
+    let mut fence: Option<&str> = None;
+    for (line, source) in text.lines().enumerate() {
+        let trimmed = source.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+                blank(&mut rows, line);
+            } else {
+                let code = source.replace('\t', "    ");
+                rows.push(Row {
+                    spans: vec![(format!("  {code}"), code_style())],
+                    line,
+                });
+            }
+            continue;
+        }
+        let item = list_item(source);
+        // Quotes and other paragraphs only carry on the same kind.
+        let joins = paragraph
+            .as_ref()
+            .is_some_and(|paragraph| paragraph.quoted == trimmed.starts_with('>'));
+        if joins && item.is_none() && !trimmed.is_empty() && !starts_block(trimmed) {
+            if let Some(paragraph) = &mut paragraph {
+                paragraph.text.push(' ');
+                paragraph
+                    .text
+                    .push_str(trimmed.trim_start_matches('>').trim());
+            }
+            continue;
+        }
+        if let Some(paragraph) = paragraph.take() {
+            paragraph.wrap(&mut rows, width);
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            blank(&mut rows, line);
+            continue;
+        }
+        if trimmed.is_empty() {
+            blank(&mut rows, line);
+        } else if let Some((level, title)) = heading(trimmed) {
+            let color = if level == 1 {
+                BaseColor::Yellow
+            } else {
+                BaseColor::Cyan
+            };
+            let mut style = colored(Color::Light(color)).combine(Effect::Bold);
+            if level == 1 {
+                style = style.combine(Effect::Underline);
+            }
+            Paragraph::new(line, title, style).wrap(&mut rows, width);
+        } else if is_rule(trimmed) {
+            rows.push(Row {
+                spans: vec![("─".repeat(width), dim())],
+                line,
+            });
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let mut quoted = Paragraph::new(line, quote.trim(), plain().combine(Effect::Italic));
+            quoted.first = ("│ ".to_string(), dim());
+            quoted.rest = quoted.first.clone();
+            quoted.quoted = true;
+            paragraph = Some(quoted);
+        } else if let Some((indent, bullet, rest)) = item {
+            let pad = "  ".repeat(indent / 2);
+            let mut listed = Paragraph::new(line, rest, plain());
+            listed.first = (
+                format!("{pad}{bullet} "),
+                colored(Color::Light(BaseColor::Cyan)),
+            );
+            listed.rest = (" ".repeat(display_width(&listed.first.0)), plain());
+            paragraph = Some(listed);
+        } else {
+            paragraph = Some(Paragraph::new(line, trimmed, plain()));
+        }
+    }
+    if let Some(paragraph) = paragraph {
+        paragraph.wrap(&mut rows, width);
+    }
+    rows
+}
+
+/// Renders the Markdown `text` at most `width` columns wide, as styled
+/// text for a view of its own, such as a popup.
+pub fn styled(text: &str, width: usize) -> StyledString {
+    let mut rows = render(text, width);
+    while rows.last().is_some_and(|row| row.spans.is_empty()) {
+        rows.pop();
+    }
+    let mut styled = StyledString::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        if index > 0 {
+            styled.append_plain("\n");
+        }
+        for (text, style) in row.spans {
+            styled.append_styled(text, style);
+        }
+    }
+    styled
+}
+
+/// Adds a blank row for `line`, unless the last row is blank already.
+fn blank(rows: &mut Vec<Row>, line: usize) {
+    if rows.last().is_some_and(|row| !row.spans.is_empty()) {
+        rows.push(Row {
+            spans: Vec::new(),
+            line,
+        });
+    }
+}
+
+/// Whether a line starting with `trimmed` begins a block of its own
+/// rather than carrying on a paragraph.
+fn starts_block(trimmed: &str) -> bool {
+    heading(trimmed).is_some()
+        || is_rule(trimmed)
+        || trimmed.starts_with("```")
+        || trimmed.starts_with("~~~")
+}
+
+/// The level and text of a heading such as `## Usage`.
+fn heading(trimmed: &str) -> Option<(usize, &str)> {
+    let level = trimmed.chars().take_while(|&ch| ch == '#').count();
+    let rest = &trimmed[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')))
+        .then(|| (level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Whether `trimmed` is a horizontal rule: three or more `-`, `*` or `_`.
+fn is_rule(trimmed: &str) -> bool {
+    let marks: String = trimmed.chars().filter(|ch| !ch.is_whitespace()).collect();
+    marks.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| marks.chars().all(|ch| ch == mark))
+}
+
+/// The indent, the bullet to show and the text of a list item such as
+/// `  - item` or `3. item`.
+fn list_item(line: &str) -> Option<(usize, String, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    if let Some(rest) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| trimmed.strip_prefix(bullet))
+    {
+        let rest = rest.trim_start();
+        // A task list item shows its box.
+        for (task, bullet) in [("[ ] ", "☐"), ("[x] ", "☑"), ("[X] ", "☑")] {
+            if let Some(rest) = rest.strip_prefix(task) {
+                return Some((indent, bullet.to_string(), rest));
+            }
+        }
+        return Some((indent, "•".to_string(), rest));
+    }
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let rest = &trimmed[digits..];
+    if digits > 0 && (rest.starts_with(". ") || rest.starts_with(") ")) {
+        return Some((
+            indent,
+            trimmed[..=digits].to_string(),
+            rest[2..].trim_start(),
+        ));
+    }
+    None
+}
+
+/// A block of text wrapped to the width, with something before its first
+/// row and the rest, like a list item's bullet and the indent under it.
+struct Paragraph {
+    line: usize,
+    text: String,
+    style: Style,
+    first: (String, Style),
+    rest: (String, Style),
+    /// Whether it's a quote, whose lines start with `>`.
+    quoted: bool,
+}
+
+impl Paragraph {
+    fn new(line: usize, text: &str, style: Style) -> Self {
+        Self {
+            line,
+            text: text.to_string(),
+            style,
+            first: (String::new(), style),
+            rest: (String::new(), style),
+            quoted: false,
+        }
+    }
+
+    /// Adds the paragraph's rows to `rows`, wrapping between words.
+    fn wrap(self, rows: &mut Vec<Row>, width: usize) {
+        let prefix_width = display_width(&self.first.0);
+        let room = width.saturating_sub(prefix_width).max(1);
+        let mut row: Vec<(String, Style)> = vec![self.first.clone()];
+        let mut used = 0;
+        for word in words(&inline(&self.text, self.style)) {
+            let word_width: usize = word.iter().map(|(piece, _)| display_width(piece)).sum();
+            let space = usize::from(used > 0);
+            if used > 0 && used + space + word_width > room {
+                rows.push(Row {
+                    spans: row,
+                    line: self.line,
+                });
+                row = vec![self.rest.clone()];
+                used = 0;
+            }
+            if used > 0 {
+                row.push((" ".to_string(), self.style));
+                used += 1;
+            }
+            used += word_width;
+            row.extend(
+                word.into_iter()
+                    .map(|(piece, style)| (fit(&piece, room), style)),
+            );
+        }
+        rows.push(Row {
+            spans: row,
+            line: self.line,
+        });
+    }
+}
+
+/// `text` cut short to `width` columns, for words too long for a row.
+fn fit(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|&ch| {
+            used += ch.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+/// Splits styled runs into words, each made of the pieces of the runs it
+/// spans, so a word only partly emphasized still wraps as one.
+fn words(spans: &[(String, Style)]) -> Vec<Vec<(String, Style)>> {
+    let mut words: Vec<Vec<(String, Style)>> = Vec::new();
+    // Whether the next piece carries on the last word.
+    let mut joined = false;
+    for (text, style) in spans {
+        for (i, piece) in text.split(' ').enumerate() {
+            if i > 0 {
+                joined = false;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            match words.last_mut() {
+                Some(word) if joined => word.push((piece.to_string(), *style)),
+                _ => words.push(vec![(piece.to_string(), *style)]),
+            }
+            joined = true;
+        }
+    }
+    words
+}
+
+/// The runs of `text` in their styles, once the markers for emphasis, code
+/// and links are taken out, on top of `base`.
+fn inline(text: &str, base: Style) -> Vec<(String, Style)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut spans: Vec<(String, Style)> = Vec::new();
+    let push = |spans: &mut Vec<(String, Style)>, text: &str, style: Style| match spans.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.push_str(text),
+        _ => spans.push((text.to_string(), style)),
+    };
+    let (mut bold, mut italic, mut struck) = (false, false, false);
+    let style = |bold: bool, italic: bool, struck: bool| {
+        let mut style = base;
+        if bold {
+            style = style.combine(Effect::Bold);
+        }
+        if italic {
+            style = style.combine(Effect::Italic);
+        }
+        if struck {
+            style = style.combine(Effect::Strikethrough);
+        }
+        style
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let next = chars.get(i + 1).copied();
+        let before = i.checked_sub(1).map(|i| chars[i]);
+        match ch {
+            '\\' if next.is_some_and(|next| next.is_ascii_punctuation()) => {
+                push(
+                    &mut spans,
+                    &next.unwrap_or_default().to_string(),
+                    style(bold, italic, struck),
+                );
+                i += 2;
+                continue;
+            }
+            '`' => {
+                if let Some(end) = chars[i + 1..].iter().position(|&ch| ch == '`') {
+                    let code: String = chars[i + 1..i + 1 + end].iter().collect();
+                    push(&mut spans, &code, code_style());
+                    i += end + 2;
+                    continue;
+                }
+            }
+            '*' | '_' if next == Some(ch) => {
+                bold = !bold;
+                i += 2;
+                continue;
+            }
+            '~' if next == Some('~') => {
+                struck = !struck;
+                i += 2;
+                continue;
+            }
+            // Underscores inside words, as in snake_case, are kept.
+            '_' if before.is_some_and(char::is_alphanumeric)
+                && next.is_some_and(char::is_alphanumeric) => {}
+            '*' | '_' => {
+                italic = !italic;
+                i += 1;
+                continue;
+            }
+            '!' | '[' => {
+                let image = ch == '!';
+                let start = i + usize::from(image);
+                if let Some((label, end)) = link(&chars, start) {
+                    let label = if image {
+                        format!("[image: {label}]")
+                    } else {
+                        label
+                    };
+                    let link_style =
+                        colored(Color::Light(BaseColor::Blue)).combine(Effect::Underline);
+                    push(&mut spans, &label, link_style);
+                    i = end;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        push(&mut spans, &ch.to_string(), style(bold, italic, struck));
+        i += 1;
+    }
+    spans
+}
+
+/// The label of a link `[label](target)` starting at `start` in `chars`,
+/// and where it ends.
+fn link(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') {
+        return None;
+    }
+    let close = start + chars[start..].iter().position(|&ch| ch == ']')?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = close + chars[close..].iter().position(|&ch| ch == ')')?;
+    Some((chars[start + 1..close].iter().collect(), end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styled_text_drops_the_markup() {
+        let styled = styled("```rust\nfn f()\n```\n\nCalls **g**.\n\n", 20);
+        assert_eq!(styled.source(), "  fn f()\n\nCalls g.");
+    }
+}
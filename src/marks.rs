@@ -0,0 +1,117 @@
+use crate::selection::shift;
+use std::collections::BTreeMap;
+
+/// Most places kept in the jump list; older ones are dropped.
+const MAX_JUMPS: usize = 100;
+
+/// Named positions in a buffer, plus the jump list: the places the cursor
+/// left by a large move, to go back and forth between.
+///
+/// Both are byte offsets that follow edits to the text around them.
+#[derive(Clone, Debug, Default)]
+pub struct Marks {
+    named: BTreeMap<String, usize>,
+    jumps: Vec<usize>,
+    /// Where in `jumps` the cursor is; `jumps.len()` unless it went back.
+    position: usize,
+}
+
+impl Marks {
+    /// Puts the mark `name` at `offset`, moving it if it's already set.
+    pub fn set(&mut self, name: &str, offset: usize) {
+        self.named.insert(name.to_string(), offset);
+    }
+
+    /// Where the mark `name` is, if it's set.
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.named.get(name).copied()
+    }
+
+    /// Every mark, by name.
+    pub fn named(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.named
+            .iter()
+            .map(|(name, &offset)| (name.as_str(), offset))
+    }
+
+    /// Records `offset` as a place the cursor jumped from. Places that were
+    /// gone back past are forgotten.
+    pub fn push_jump(&mut self, offset: usize) {
+        self.jumps.truncate(self.position);
+        if self.jumps.last() != Some(&offset) {
+            self.jumps.push(offset);
+        }
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.position = self.jumps.len();
+    }
+
+    /// The place jumped from before the cursor got to `current`, if any.
+    /// The first step back remembers `current`, so going forward again
+    /// returns there.
+    pub fn jump_back(&mut self, current: usize) -> Option<usize> {
+        if self.position == 0 {
+            return None;
+        }
+        if self.position == self.jumps.len() {
+            self.jumps.push(current);
+        }
+        self.position -= 1;
+        Some(self.jumps[self.position])
+    }
+
+    /// The place gone back from last, if any.
+    pub fn jump_forward(&mut self) -> Option<usize> {
+        if self.position + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.position += 1;
+        Some(self.jumps[self.position])
+    }
+
+    /// Moves every mark and place to follow an edit that replaced `removed`
+    /// bytes at `start` with `inserted` bytes.
+    pub fn adjust(&mut self, start: usize, removed: usize, inserted: usize) {
+        for offset in self.named.values_mut().chain(self.jumps.iter_mut()) {
+            *offset = shift(*offset, start, removed, inserted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jumps_go_back_and_forward() {
+        let mut marks = Marks::default();
+        marks.push_jump(10);
+        marks.push_jump(20);
+        assert_eq!(marks.jump_back(30), Some(20));
+        assert_eq!(marks.jump_back(20), Some(10));
+        assert_eq!(marks.jump_back(10), None);
+        assert_eq!(marks.jump_forward(), Some(20));
+        assert_eq!(marks.jump_forward(), Some(30));
+        assert_eq!(marks.jump_forward(), None);
+        marks.jump_back(30);
+        marks.push_jump(5);
+        assert_eq!(marks.jump_forward(), None);
+        assert_eq!(marks.jump_back(7), Some(5));
+        assert_eq!(marks.jump_back(5), Some(10));
+    }
+
+    #[test]
+    fn marks_follow_edits() {
+        let mut marks = Marks::default();
+        marks.set("a", 2);
+        marks.set("b", 8);
+        marks.push_jump(6);
+        marks.adjust(4, 3, 0);
+        assert_eq!(marks.named().collect::<Vec<_>>(), vec![("a", 2), ("b", 5)]);
+        assert_eq!(marks.jump_back(0), Some(4));
+        marks.set("a", 0);
+        assert_eq!(marks.get("a"), Some(0));
+        assert_eq!(marks.get("c"), None);
+    }
+}
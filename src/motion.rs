@@ -0,0 +1,370 @@
+use crate::buffer::Buffer;
+use std::ops::Range;
+
+/// Whether `ch` can be part of a word.
+pub fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// The kinds of characters a word motion tells apart.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+/// What kind of character `ch` is. With `big`, words are only broken up by
+/// whitespace, so punctuation counts as part of them.
+fn class(ch: char, big: bool) -> Class {
+    if ch.is_whitespace() {
+        Class::Space
+    } else if big || is_word(ch) {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+/// The start of the word after `offset`: past the rest of the word or run
+/// of punctuation `offset` is in, then past the whitespace after it.
+pub fn next_word(buffer: &Buffer, offset: usize, big: bool) -> usize {
+    let mut chars = buffer.chars_at(offset).peekable();
+    let Some(&(_, first)) = chars.peek() else {
+        return offset;
+    };
+    let first = class(first, big);
+    chars
+        .skip_while(|&(_, ch)| first != Class::Space && class(ch, big) == first)
+        .find(|&(_, ch)| class(ch, big) != Class::Space)
+        .map_or(buffer.len(), |(pos, _)| pos)
+}
+
+/// The start of the word before `offset`, or of the one it is in.
+pub fn previous_word(buffer: &Buffer, offset: usize, big: bool) -> usize {
+    let mut chars = buffer
+        .chars_before(offset)
+        .skip_while(|&(_, ch)| class(ch, big) == Class::Space)
+        .peekable();
+    let Some(&(_, last)) = chars.peek() else {
+        return 0;
+    };
+    let last = class(last, big);
+    chars
+        .take_while(|&(_, ch)| class(ch, big) == last)
+        .last()
+        .map_or(offset, |(pos, _)| pos)
+}
+
+/// The start of the first blank line after the paragraph at or below the
+/// line of `offset`, or the end of the buffer.
+pub fn next_paragraph(buffer: &Buffer, offset: usize) -> usize {
+    let is_blank = |line| buffer.line(line).trim().is_empty();
+    (buffer.line_of(offset)..buffer.line_count())
+        .skip_while(|&line| is_blank(line))
+        .find(|&line| is_blank(line))
+        .map_or(buffer.len(), |line| buffer.line_start(line))
+}
+
+/// The start of the last blank line before the paragraph at or above the
+/// line of `offset`, or the start of the buffer.
+pub fn previous_paragraph(buffer: &Buffer, offset: usize) -> usize {
+    let is_blank = |line| buffer.line(line).trim().is_empty();
+    (0..buffer.line_of(offset))
+        .rev()
+        .skip_while(|&line| is_blank(line))
+        .find(|&line| is_blank(line))
+        .map_or(0, |line| buffer.line_start(line))
+}
+
+/// The word `range` lies in, if it holds nothing but word characters.
+pub fn word_around(buffer: &Buffer, range: Range<usize>) -> Option<Range<usize>> {
+    if !buffer.slice(range.clone()).chars().all(is_word) {
+        return None;
+    }
+    let start = buffer
+        .chars_before(range.start)
+        .take_while(|&(_, ch)| is_word(ch))
+        .last()
+        .map_or(range.start, |(pos, _)| pos);
+    let end = buffer
+        .chars_at(range.end)
+        .find(|&(_, ch)| !is_word(ch))
+        .map_or(buffer.len(), |(pos, _)| pos);
+    Some(start..end)
+}
+
+/// Whether `range` of the buffer is a whole word: not part of a longer one.
+pub fn is_whole_word(buffer: &Buffer, range: Range<usize>) -> bool {
+    let before = buffer.chars_before(range.start).next();
+    let after = buffer.chars_at(range.end).next();
+    !before.is_some_and(|(_, ch)| is_word(ch)) && !after.is_some_and(|(_, ch)| is_word(ch))
+}
+
+/// The lines `range` touches, without their leading and trailing blanks.
+pub fn lines_around(buffer: &Buffer, range: Range<usize>) -> Range<usize> {
+    let first = buffer.line_of(range.start);
+    let last = buffer.line_of(range.end);
+    let text = buffer.line(first);
+    let start = buffer.line_start(first) + text.len() - text.trim_start().len();
+    let end = buffer.line_start(last) + buffer.line(last).trim_end().len();
+    start..end.max(start)
+}
+
+/// The lines `range` touches, not counting the line it ends at the start of.
+pub fn lines_of(buffer: &Buffer, range: Range<usize>) -> Range<usize> {
+    let first = buffer.line_of(range.start);
+    let mut last = buffer.line_of(range.end);
+    if last > first && buffer.line_start(last) == range.end {
+        last -= 1;
+    }
+    first..last + 1
+}
+
+/// The text of `lines`, newlines and all.
+pub fn whole_lines(buffer: &Buffer, lines: Range<usize>) -> Range<usize> {
+    let end = if lines.end < buffer.line_count() {
+        buffer.line_start(lines.end)
+    } else {
+        buffer.len()
+    };
+    buffer.line_start(lines.start)..end
+}
+
+/// The indented block around `lines`: the lines next to them indented at
+/// least as deep as the shallowest of them, with the blank lines among
+/// them. When that doesn't add anything, the block opened by the line
+/// above, which is less indented, is taken instead.
+pub fn block_around(buffer: &Buffer, lines: Range<usize>) -> Range<usize> {
+    let is_blank = |line| buffer.line(line).trim().is_empty();
+    let indent = |line| {
+        let text = buffer.line(line);
+        text.len() - text.trim_start().len()
+    };
+    let depth = lines
+        .clone()
+        .filter(|&line| !is_blank(line))
+        .map(indent)
+        .min()
+        .unwrap_or(0);
+    let inside = |line| is_blank(line) || indent(line) >= depth;
+    let mut start = (0..lines.start)
+        .rev()
+        .take_while(|&line| inside(line))
+        .last()
+        .unwrap_or(lines.start);
+    let mut end = (lines.end..buffer.line_count())
+        .take_while(|&line| inside(line))
+        .last()
+        .map_or(lines.end, |line| line + 1);
+    while start < lines.start && is_blank(start) {
+        start += 1;
+    }
+    while end > lines.end && is_blank(end - 1) {
+        end -= 1;
+    }
+    if start == lines.start && end == lines.end && depth > 0 {
+        if let Some(header) = (0..lines.start).rev().find(|&line| !is_blank(line)) {
+            return block_around(buffer, header..lines.end);
+        }
+    }
+    start..end
+}
+
+/// The paragraph `range` lies in, without its leading and trailing blanks:
+/// the run of lines between blank ones. None if `range` starts or ends on
+/// a blank line.
+pub fn paragraph_around(buffer: &Buffer, range: Range<usize>) -> Option<Range<usize>> {
+    let is_blank = |line| buffer.line(line).trim().is_empty();
+    let first = (0..=buffer.line_of(range.start))
+        .rev()
+        .take_while(|&line| !is_blank(line))
+        .last()?;
+    let last = (buffer.line_of(range.end)..buffer.line_count())
+        .take_while(|&line| !is_blank(line))
+        .last()?;
+    Some(lines_around(
+        buffer,
+        buffer.line_start(first)..buffer.line_start(last),
+    ))
+}
+
+/// The sentence `range` lies in, within its paragraph: from just after the
+/// whitespace following the last `.`, `!` or `?` before it, through the
+/// first one after it.
+pub fn sentence_around(buffer: &Buffer, range: Range<usize>) -> Option<Range<usize>> {
+    let paragraph = paragraph_around(buffer, range.clone())?;
+    let text = buffer.slice(paragraph.clone());
+    let ends: Vec<usize> = text
+        .char_indices()
+        .filter(|&(i, ch)| {
+            matches!(ch, '.' | '!' | '?')
+                && text[i + 1..].chars().next().is_none_or(char::is_whitespace)
+        })
+        .map(|(i, _)| i + 1)
+        .collect();
+    let from = range.start.saturating_sub(paragraph.start);
+    let to = range.end.saturating_sub(paragraph.start);
+    let start = ends
+        .iter()
+        .rev()
+        .find(|&&end| end <= from)
+        .map_or(0, |&end| text.len() - text[end..].trim_start().len());
+    let end = ends
+        .iter()
+        .copied()
+        .find(|&end| end >= to && end > start)
+        .unwrap_or(text.len());
+    Some(paragraph.start + start..paragraph.start + end)
+}
+
+/// The innermost pair of brackets around `range`: the text inside them,
+/// and the pair itself.
+pub fn brackets_around(
+    buffer: &Buffer,
+    range: Range<usize>,
+) -> Option<(Range<usize>, Range<usize>)> {
+    let mut depth = 0;
+    let (open, open_ch) = buffer.chars_before(range.start).find(|&(_, ch)| {
+        match ch {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth == 0 => return true,
+            '(' | '[' | '{' => depth -= 1,
+            _ => {}
+        }
+        false
+    })?;
+    let close_ch = match open_ch {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    };
+    let mut depth = 0;
+    let (close, _) = buffer.chars_at(range.end).find(|&(_, ch)| {
+        if ch == open_ch {
+            depth += 1;
+        } else if ch == close_ch {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    Some((open + 1..close, open..close + 1))
+}
+
+/// Where the bracket matching the one at `offset` is, counting the pairs
+/// nested in between. Brackets at offsets `skip` says to ignore, such as
+/// ones in strings or comments, don't count.
+pub fn matching_bracket(
+    buffer: &Buffer,
+    offset: usize,
+    skip: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    let ch = buffer.char_at(offset)?;
+    let (open, close, forward) = match ch {
+        '(' => ('(', ')', true),
+        '[' => ('[', ']', true),
+        '{' => ('{', '}', true),
+        ')' => ('(', ')', false),
+        ']' => ('[', ']', false),
+        '}' => ('{', '}', false),
+        _ => return None,
+    };
+    let (inner, outer) = if forward {
+        (open, close)
+    } else {
+        (close, open)
+    };
+    let mut depth = 0;
+    let mut chars: Box<dyn Iterator<Item = (usize, char)>> = if forward {
+        Box::new(buffer.chars_at(offset + 1))
+    } else {
+        Box::new(buffer.chars_before(offset))
+    };
+    chars
+        .find(|&(pos, ch)| {
+            if skip(pos) {
+                return false;
+            }
+            if ch == inner {
+                depth += 1;
+            } else if ch == outer {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })
+        .map(|(pos, _)| pos)
+}
+
+/// The opening and closing delimiters of the pair `ch` belongs to: brackets
+/// come in pairs, anything else, such as a quote, closes itself.
+pub fn pair(ch: char) -> (char, char) {
+    match ch {
+        '(' | ')' => ('(', ')'),
+        '[' | ']' => ('[', ']'),
+        '{' | '}' => ('{', '}'),
+        '<' | '>' => ('<', '>'),
+        _ => (ch, ch),
+    }
+}
+
+/// Where the innermost `open` and `close` around `range` are. Brackets are
+/// matched across lines, counting the pairs nested in between; a delimiter
+/// that closes itself is looked for on the line of `range`, pairing up in
+/// order, so a quote at the start of `range` can open it too.
+pub fn pair_around(
+    buffer: &Buffer,
+    range: Range<usize>,
+    open: char,
+    close: char,
+) -> Option<(usize, usize)> {
+    if open == close {
+        let line = buffer.line_of(range.start);
+        let line_start = buffer.line_start(line);
+        let line_end = buffer.line_end(line);
+        let before = buffer.slice(line_start..range.start).matches(open).count();
+        let start = if before.is_multiple_of(2) {
+            (buffer.char_at(range.start) == Some(open)).then_some(range.start)?
+        } else {
+            buffer.rfind(&open.to_string(), range.start)?
+        };
+        let end = buffer
+            .find(&close.to_string(), range.end.max(start + 1))
+            .filter(|&pos| pos < line_end)?;
+        return Some((start, end));
+    }
+    let under = buffer
+        .char_at(range.start)
+        .map_or(range.start, |ch| range.start + ch.len_utf8());
+    let mut depth = 0;
+    let (start, _) = buffer.chars_before(under).find(|&(pos, ch)| {
+        // A bracket under the cursor closing a pair isn't what it is in.
+        if ch == close && pos != range.start {
+            depth += 1;
+        } else if ch == open {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    let mut depth = 0;
+    let (end, _) = buffer.chars_at(range.end.max(start + 1)).find(|&(_, ch)| {
+        if ch == open {
+            depth += 1;
+        } else if ch == close {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    Some((start, end))
+}
@@ -0,0 +1,91 @@
+use std::ops::Range;
+
+/// The first number in `line` that ends after byte `column`: the one there,
+/// or the next one along. A number is a run of decimal digits, with the
+/// `-` before it unless that joins it to a word, or `0x` and hex digits.
+pub fn find(line: &str, column: usize) -> Option<Range<usize>> {
+    let bytes = line.as_bytes();
+    let is_word = |i: usize| bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_';
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes[i..].starts_with(b"0x") || bytes[i..].starts_with(b"0X");
+        let (start, digits) = if hex && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit) {
+            (i, i + 2)
+        } else if bytes[i].is_ascii_digit() {
+            let negative = i > 0 && bytes[i - 1] == b'-' && (i < 2 || !is_word(i - 2));
+            (if negative { i - 1 } else { i }, i)
+        } else {
+            i += 1;
+            continue;
+        };
+        let end = digits
+            + bytes[digits..]
+                .iter()
+                .take_while(|byte| {
+                    if hex {
+                        byte.is_ascii_hexdigit()
+                    } else {
+                        byte.is_ascii_digit()
+                    }
+                })
+                .count();
+        if end > column {
+            return Some(start..end);
+        }
+        i = end;
+    }
+    None
+}
+
+/// Adds `delta` to the number `text`, as found by `find`. Leading zeros
+/// keep the number as wide as it was, and hex digits keep their case;
+/// hex numbers wrap around at 64 bits. Gives up on numbers too long to
+/// hold.
+pub fn add(text: &str, delta: i64) -> Option<String> {
+    if let Some(digits) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        let value = u64::from_str_radix(digits, 16).ok()?;
+        let value = value.wrapping_add_signed(delta);
+        let width = digits.len();
+        let number = if digits.chars().any(|ch| ch.is_ascii_uppercase()) {
+            format!("{value:0width$X}")
+        } else {
+            format!("{value:0width$x}")
+        };
+        return Some(format!("{}{number}", &text[..2]));
+    }
+    let value: i128 = text.parse().ok()?;
+    let value = value.saturating_add(delta.into());
+    let digits = text.trim_start_matches('-');
+    if digits.len() > 1 && digits.starts_with('0') {
+        let width = digits.len();
+        let sign = if value < 0 { "-" } else { "" };
+        Some(format!("{sign}{:0width$}", value.unsigned_abs()))
+    } else {
+        Some(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_number_at_or_after_the_column() {
+        assert_eq!(find("x = 42;", 0), Some(4..6));
+        assert_eq!(find("x = 42;", 5), Some(4..6));
+        assert_eq!(find("x = 42;", 6), None);
+        assert_eq!(find("a -3 b-4", 0), Some(2..4));
+        assert_eq!(find("b-4", 0), Some(2..3));
+        assert_eq!(find("0xff", 0), Some(0..4));
+    }
+
+    #[test]
+    fn adds_keeping_the_width_and_case() {
+        assert_eq!(add("9", 1).as_deref(), Some("10"));
+        assert_eq!(add("007", 1).as_deref(), Some("008"));
+        assert_eq!(add("-01", 2).as_deref(), Some("01"));
+        assert_eq!(add("0xFF", 1).as_deref(), Some("0x100"));
+        assert_eq!(add("0x0a", -1).as_deref(), Some("0x09"));
+        assert_eq!(add("1".repeat(50).as_str(), 1), None);
+    }
+}
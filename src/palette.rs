@@ -0,0 +1,93 @@
+use crate::commands::Command;
+use crate::fuzzy;
+use crate::workspace::Workspace;
+use cursive::event::{EventResult, Key};
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{Dialog, EditView, LinearLayout, OnEventView, SelectView};
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+
+/// Name of the list of matching commands.
+const LIST: &str = "palette_list";
+
+/// Opens the command palette: typing narrows the list of every command by a
+/// fuzzy match on its name, Up/Down pick one and Enter runs it.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut list: SelectView<Command> = SelectView::new();
+    fill(&mut list, workspace, "");
+    let list = {
+        let workspace = workspace.clone();
+        list.on_submit(move |s, command: &Command| {
+            s.pop_layer();
+            command.run(s, &workspace);
+        })
+    };
+
+    let input = {
+        let on_edit = workspace.clone();
+        let on_submit = workspace.clone();
+        EditView::new()
+            .on_edit(move |s, text, _| {
+                s.call_on_name(LIST, |list: &mut SelectView<Command>| {
+                    fill(list, &on_edit, text);
+                });
+            })
+            .on_submit(move |s, _| {
+                let chosen = s
+                    .call_on_name(LIST, |list: &mut SelectView<Command>| list.selection())
+                    .flatten();
+                if let Some(command) = chosen {
+                    s.pop_layer();
+                    command.run(s, &on_submit);
+                }
+            })
+    };
+
+    // The input keeps focus; Up and Down move through the list underneath it.
+    let input = OnEventView::new(input)
+        .on_pre_event_inner(Key::Up, |_, _| Some(move_selection(true)))
+        .on_pre_event_inner(Key::Down, |_, _| Some(move_selection(false)));
+
+    let content = LinearLayout::vertical()
+        .child(input)
+        .child(list.with_name(LIST).scrollable().max_height(12));
+    let palette = OnEventView::new(Dialog::around(content).title("Commands").min_width(40))
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+    s.add_layer(palette);
+}
+
+/// Moves the list's selection one entry up or down.
+fn move_selection(up: bool) -> EventResult {
+    EventResult::with_cb(move |s| {
+        s.call_on_name(LIST, |list: &mut SelectView<Command>| {
+            // Nothing listens for selection changes, so the callback can be dropped.
+            let _ = if up {
+                list.select_up(1)
+            } else {
+                list.select_down(1)
+            };
+        });
+    })
+}
+
+/// Lists the commands matching `pattern`, best match first, with their keys.
+fn fill(list: &mut SelectView<Command>, workspace: &Arc<Mutex<Workspace>>, pattern: &str) {
+    let ws = workspace.lock().unwrap();
+    let mut matches: Vec<(i64, Command)> = Command::ALL
+        .iter()
+        .filter_map(|&command| Some((fuzzy::score(pattern, command.name())?, command)))
+        .collect();
+    // Stable, so equal scores keep the order of `Command::ALL`, which is
+    // also the order shown before anything is typed.
+    if !pattern.is_empty() {
+        matches.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    }
+
+    list.clear();
+    for (_, command) in matches {
+        let keys = ws.keymap().keys_for(command).join(", ");
+        list.add_item(format!("{:<24}{}", command.name(), keys), command);
+    }
+}
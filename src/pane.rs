@@ -0,0 +1,158 @@
+use cursive::direction::Orientation;
+use cursive::{Rect, Vec2};
+
+/// A window onto one of the open buffers.
+#[derive(Clone, Copy, Debug)]
+pub struct Pane {
+    /// Index of the buffer shown, into the workspace's editors.
+    pub editor: usize,
+    /// Top-left (column, line) of the visible region.
+    pub scroll: (usize, usize),
+    /// Number of lines shown, as of the last layout.
+    pub height: usize,
+    /// Number of columns the text is shown in, besides the gutter and the
+    /// scrollbar, as of the last layout.
+    pub width: usize,
+}
+
+/// How the screen is divided between panes: a binary tree whose leaves are
+/// pane indices.
+#[derive(Clone, Debug)]
+pub enum Layout {
+    Pane(usize),
+    /// Two layouts sharing the space; `Horizontal` puts them side by side,
+    /// `Vertical` stacks `first` above `second`.
+    Split {
+        orientation: Orientation,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+/// Where a pane or a separator line was placed by `Layout::arrange`.
+pub enum Region {
+    Pane(usize, Rect),
+    /// A one-cell-thick line between two split halves, drawn along `orientation`.
+    Separator(Orientation, Vec2, usize),
+}
+
+impl Layout {
+    /// Splits the leaf holding `pane`, putting `new_pane` after it.
+    pub fn split(&mut self, pane: usize, new_pane: usize, orientation: Orientation) -> bool {
+        match self {
+            Layout::Pane(p) if *p == pane => {
+                *self = Layout::Split {
+                    orientation,
+                    first: Box::new(Layout::Pane(pane)),
+                    second: Box::new(Layout::Pane(new_pane)),
+                };
+                true
+            }
+            Layout::Pane(_) => false,
+            Layout::Split { first, second, .. } => {
+                first.split(pane, new_pane, orientation)
+                    || second.split(pane, new_pane, orientation)
+            }
+        }
+    }
+
+    /// Removes the leaf holding `pane`, giving its space to its sibling, and
+    /// renumbers the panes after it. The last pane can't be removed.
+    pub fn remove(&mut self, pane: usize) -> bool {
+        if !self.detach(pane) {
+            return false;
+        }
+        self.renumber(pane);
+        true
+    }
+
+    fn detach(&mut self, pane: usize) -> bool {
+        let Layout::Split { first, second, .. } = self else {
+            return false;
+        };
+        if matches!(**first, Layout::Pane(p) if p == pane) {
+            *self = (**second).clone();
+            true
+        } else if matches!(**second, Layout::Pane(p) if p == pane) {
+            *self = (**first).clone();
+            true
+        } else {
+            first.detach(pane) || second.detach(pane)
+        }
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Pane(p) if *p > removed => *p -= 1,
+            Layout::Pane(_) => {}
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    /// Pane indices in screen order: left to right, top to bottom.
+    pub fn panes(&self) -> Vec<usize> {
+        match self {
+            Layout::Pane(p) => vec![*p],
+            Layout::Split { first, second, .. } => {
+                let mut panes = first.panes();
+                panes.extend(second.panes());
+                panes
+            }
+        }
+    }
+
+    /// Divides `rect` between the panes, leaving a separator between each split.
+    pub fn arrange(&self, rect: Rect) -> Vec<Region> {
+        let mut regions = Vec::new();
+        self.arrange_into(rect, &mut regions);
+        regions
+    }
+
+    fn arrange_into(&self, rect: Rect, regions: &mut Vec<Region>) {
+        let (orientation, first, second) = match self {
+            Layout::Pane(p) => {
+                regions.push(Region::Pane(*p, rect));
+                return;
+            }
+            Layout::Split {
+                orientation,
+                first,
+                second,
+            } => (*orientation, first, second),
+        };
+
+        let size = rect.size();
+        let total = *size.get(orientation);
+        if total < 3 {
+            // Too small to share: the first half takes everything.
+            return first.arrange_into(rect, regions);
+        }
+        let head = (total - 1) / 2;
+        let tail = total - 1 - head;
+        let origin = rect.top_left();
+        let (first_rect, line, second_rect) = match orientation {
+            Orientation::Horizontal => (
+                Rect::from_size(origin, (head, size.y)),
+                origin + (head, 0),
+                Rect::from_size(origin + (head + 1, 0), (tail, size.y)),
+            ),
+            Orientation::Vertical => (
+                Rect::from_size(origin, (size.x, head)),
+                origin + (0, head),
+                Rect::from_size(origin + (0, head + 1), (size.x, tail)),
+            ),
+        };
+
+        first.arrange_into(first_rect, regions);
+        // The separator runs across the split direction.
+        regions.push(Region::Separator(
+            orientation.swap(),
+            line,
+            *size.get(orientation.swap()),
+        ));
+        second.arrange_into(second_rect, regions);
+    }
+}
@@ -0,0 +1,609 @@
+use crate::keymap::{config_dir, Error};
+use crate::scripts::Hook;
+use crate::transform::{Failure, Registry, Transform};
+use crate::wasm::{self, FuncType, Instance, Limits, ValType};
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use serde_json::{json, Value};
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The version of the interface plugins are written against; a plugin
+/// asking for another one isn't loaded.
+pub const ABI: u64 = 1;
+
+/// What a WebAssembly transform may use each time it runs.
+const WASM_LIMITS: Limits = Limits {
+    memory: 256 << 20,
+    time: Duration::from_secs(5),
+};
+
+/// Resolve every symbol when the library is opened, so a broken one fails
+/// at startup rather than halfway through a command.
+const RTLD_NOW: c_int = 2;
+
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+}
+
+/// `wasdev_manifest`: what the plugin offers, as JSON it keeps hold of.
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+/// `wasdev_call`: answers a request, as JSON the editor hands back to
+/// `wasdev_free`.
+type CallFn = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+/// The extensions in the plugin directory, loaded at startup.
+///
+/// A plugin is a dynamic library in `plugins/` under the configuration
+/// directory, exporting three C functions:
+///
+/// ```c
+/// const char *wasdev_manifest(void);
+/// char *wasdev_call(const char *request);
+/// void wasdev_free(char *reply);
+/// ```
+///
+/// Everything they pass is UTF-8 JSON. The manifest says what the plugin
+/// offers; any of the lists may be left out:
+///
+/// ```json
+/// {"abi": 1, "name": "Words",
+///  "commands": ["Count words"], "transforms": ["Rot13"],
+///  "hooks": ["on_open", "on_save"], "status": ["words"]}
+/// ```
+///
+/// The transforms join the transform menu, and the commands are listed by
+/// `run-plugin`. Then each request names what it's for, and the
+/// reply is an object, with `error` set if the request failed:
+///
+/// - `{"transform": name, "text": ...}`, answered with the new `text`
+/// - `{"command": name, "text": ..., "file": ...}`, with the selection or
+///   else the whole buffer, answered with a `text` to replace it, a
+///   `message` for the status bar, both or neither
+/// - `{"hook": "on_open" or "on_save", "file": ...}`, whose answer is
+///   dropped, and `{"hook": "on_selection_change", "file": ..., "text":
+///   ...}`, with the text now selected
+/// - `{"status": name, "file": ..., "line": ..., "column": ...}`, answered
+///   with the `text` to show, or nothing
+///
+/// Files are `null` for buffers that have none, and lines and columns are
+/// counted from 1. The calls are made on whichever thread wants them, the
+/// status ones on every redraw, so they should be quick. A plugin runs
+/// inside the editor; one that crashes takes the editor with it.
+///
+/// A transform can be a WebAssembly module instead, `plugins/Rot13.wasm`,
+/// listed in the menu under its file's name. It runs sandboxed: it can't
+/// import anything, and each run gets a fresh instance with at most 256 MiB
+/// of memory and 5 seconds. It exports two functions:
+///
+/// ```rust,ignore
+/// /// Makes room for the text to transform, returning where it is.
+/// extern "C" fn alloc(len: usize) -> *mut u8;
+/// /// Rewrites the text, returning where the result is in the high 32
+/// /// bits and its length in the low ones.
+/// extern "C" fn transform(text: *const u8, len: usize) -> u64;
+/// ```
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+    wasm: Vec<WasmTransform>,
+}
+
+/// A loaded plugin, and what it said it offers.
+struct Plugin {
+    library: Arc<Library>,
+    manifest: Manifest,
+}
+
+/// What a plugin's manifest says.
+#[derive(Debug, PartialEq)]
+struct Manifest {
+    name: String,
+    commands: Vec<String>,
+    transforms: Vec<String>,
+    hooks: Vec<Hook>,
+    status: Vec<String>,
+}
+
+/// The functions of a plugin's library. The library is never closed, since
+/// the transform menu may still hold its transforms when the editor quits.
+struct Library {
+    path: PathBuf,
+    call: CallFn,
+    free: FreeFn,
+}
+
+/// A command offered by a plugin.
+#[derive(Clone)]
+pub struct PluginCommand {
+    pub name: String,
+    /// The name of the plugin it comes from.
+    pub plugin: String,
+    library: Arc<Library>,
+}
+
+/// What running a plugin command came to.
+pub struct Outcome {
+    /// The text to replace the selection, or the buffer, with.
+    pub text: Option<String>,
+    /// A message for the status bar.
+    pub message: Option<String>,
+}
+
+/// A transform offered by a plugin.
+struct PluginTransform {
+    name: String,
+    library: Arc<Library>,
+}
+
+/// A transform compiled to WebAssembly.
+#[derive(Clone)]
+struct WasmTransform {
+    name: String,
+    path: PathBuf,
+    module: Arc<wasm::Module>,
+}
+
+impl Plugins {
+    /// Loads the user's plugins, if there are any.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("plugins")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads every library in `dir`, in order of their names; a missing
+    /// directory just means there are none.
+    pub fn load_from(dir: &Path) -> Result<Self, Error> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(dir.to_path_buf(), err)),
+        };
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|err| Error::Io(dir.to_path_buf(), err))?
+                .path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext == DLL_EXTENSION || ext == "wasm")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut plugins = Self::default();
+        for path in paths {
+            let invalid = |message: String| Error::Invalid(path.clone(), message);
+            if path.extension().is_some_and(|ext| ext == "wasm") {
+                let bytes = fs::read(&path).map_err(|err| Error::Io(path.clone(), err))?;
+                plugins
+                    .wasm
+                    .push(WasmTransform::load(&path, &bytes).map_err(invalid)?);
+                continue;
+            }
+            let (library, manifest) = Library::open(&path).map_err(invalid)?;
+            let default_name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let manifest = Manifest::parse(&manifest, &default_name).map_err(invalid)?;
+            plugins.plugins.push(Plugin {
+                library: Arc::new(library),
+                manifest,
+            });
+        }
+        Ok(plugins)
+    }
+
+    /// Adds the plugins' transforms to `registry`, in place of any others
+    /// of the same name.
+    pub fn register_transforms(&self, registry: &mut Registry) {
+        for plugin in &self.plugins {
+            for name in &plugin.manifest.transforms {
+                registry.register(PluginTransform {
+                    name: name.clone(),
+                    library: Arc::clone(&plugin.library),
+                });
+            }
+        }
+        for transform in &self.wasm {
+            registry.register(transform.clone());
+        }
+    }
+
+    /// Every command the plugins offer, plugin by plugin.
+    pub fn commands(&self) -> Vec<PluginCommand> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| {
+                plugin.manifest.commands.iter().map(|name| PluginCommand {
+                    name: name.clone(),
+                    plugin: plugin.manifest.name.clone(),
+                    library: Arc::clone(&plugin.library),
+                })
+            })
+            .collect()
+    }
+
+    /// Tells the plugins hooked to `hook` about the file at `path`. Like
+    /// the hooks in `scripts.toml`, what they answer is dropped.
+    pub fn run_hook(&self, hook: Hook, path: &Path) {
+        self.call_hook(hook, &json!({ "hook": hook.name(), "file": path }));
+    }
+
+    /// Tells the plugins hooked to a change of selection that `selection`
+    /// is now selected in the file at `path`.
+    pub fn run_selection_hook(&self, path: &Path, selection: &str) {
+        let hook = Hook::SelectionChange;
+        let request = json!({ "hook": hook.name(), "file": path, "text": selection });
+        self.call_hook(hook, &request);
+    }
+
+    fn call_hook(&self, hook: Hook, request: &Value) {
+        for plugin in &self.plugins {
+            if plugin.manifest.hooks.contains(&hook) {
+                let _ = plugin.library.call(request);
+            }
+        }
+    }
+
+    /// The status segments the plugins have to show for the cursor at
+    /// `line` and `column` in `file`, leaving out any that fail.
+    pub fn status(&self, file: Option<&Path>, line: usize, column: usize) -> Vec<String> {
+        let mut segments = Vec::new();
+        for plugin in &self.plugins {
+            for name in &plugin.manifest.status {
+                let request =
+                    json!({ "status": name, "file": file, "line": line, "column": column });
+                if let Ok(reply) = plugin.library.call(&request) {
+                    match reply["text"].as_str() {
+                        Some("") | None => {}
+                        Some(text) => segments.push(text.to_string()),
+                    }
+                }
+            }
+        }
+        segments
+    }
+}
+
+impl PluginCommand {
+    /// Runs the command on `text`, the selection or else the whole buffer
+    /// of the file at `file`.
+    pub fn run(&self, text: &str, file: Option<&Path>) -> Result<Outcome, String> {
+        let reply = self
+            .library
+            .call(&json!({ "command": self.name, "text": text, "file": file }))?;
+        let field = |name: &str| reply[name].as_str().map(str::to_string);
+        Ok(Outcome {
+            text: field("text"),
+            message: field("message"),
+        })
+    }
+}
+
+impl Transform for PluginTransform {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        let reply = self
+            .library
+            .call(&json!({ "transform": self.name, "text": text }))?;
+        match reply["text"].as_str() {
+            Some(text) => Ok(text.to_string()),
+            None => Err(format!("{} gave no text back", self.library.path.display()).into()),
+        }
+    }
+}
+
+impl WasmTransform {
+    /// Decodes the module in `bytes`, read from `path`, checking it exports
+    /// what a transform needs.
+    fn load(path: &Path, bytes: &[u8]) -> Result<Self, String> {
+        let module = wasm::Module::parse(bytes).map_err(|err| err.to_string())?;
+        let wants = |name: &str, params: &[ValType], results: &[ValType]| {
+            let ty = FuncType {
+                params: params.to_vec(),
+                results: results.to_vec(),
+            };
+            match module.export_type(name) {
+                Some(found) if *found == ty => Ok(()),
+                Some(_) => Err(format!("its `{name}` function has the wrong type")),
+                None => Err(format!("it doesn't export `{name}`")),
+            }
+        };
+        wants("alloc", &[ValType::I32], &[ValType::I32])?;
+        wants("transform", &[ValType::I32, ValType::I32], &[ValType::I64])?;
+        Ok(Self {
+            name: path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            path: path.to_path_buf(),
+            module: Arc::new(module),
+        })
+    }
+
+    /// Runs the module over `text`, in a new instance.
+    fn run(&self, text: &str) -> Result<String, String> {
+        let len = i32::try_from(text.len()).map_err(|_| "the text is too long".to_string())?;
+        let mut instance =
+            Instance::new(&self.module, WASM_LIMITS).map_err(|err| err.to_string())?;
+        let at = match instance
+            .call("alloc", &[wasm::Value::I32(len)])
+            .map_err(|err| err.to_string())?[..]
+        {
+            [wasm::Value::I32(at)] => at as u32 as usize,
+            _ => unreachable!("the type of `alloc` was checked"),
+        };
+        instance
+            .memory_mut()
+            .get_mut(at..at + text.len())
+            .ok_or("`alloc` gave an address outside its memory")?
+            .copy_from_slice(text.as_bytes());
+        let result = instance
+            .call(
+                "transform",
+                &[wasm::Value::I32(at as i32), wasm::Value::I32(len)],
+            )
+            .map_err(|err| err.to_string())?;
+        let (at, len) = match result[..] {
+            [wasm::Value::I64(result)] => ((result as u64 >> 32) as usize, result as u32 as usize),
+            _ => unreachable!("the type of `transform` was checked"),
+        };
+        let bytes = instance
+            .memory()
+            .get(at..at + len)
+            .ok_or("`transform` gave an address outside its memory")?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| "it gave back text that isn't UTF-8".to_string())
+    }
+}
+
+impl Transform for WasmTransform {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        self.run(text)
+            .map_err(|err| format!("{}: {err}", self.path.display()).into())
+    }
+}
+
+impl Manifest {
+    /// Reads a plugin's manifest, naming the plugin `default_name` if it
+    /// doesn't say.
+    fn parse(text: &str, default_name: &str) -> Result<Self, String> {
+        let manifest: Value =
+            serde_json::from_str(text).map_err(|err| format!("the manifest isn't JSON: {err}"))?;
+        if !manifest.is_object() {
+            return Err("the manifest isn't an object".to_string());
+        }
+        match manifest["abi"].as_u64() {
+            Some(ABI) => {}
+            Some(abi) => {
+                return Err(format!(
+                    "written for version {abi} of the interface, not {ABI}"
+                ))
+            }
+            None => return Err("the manifest doesn't say which `abi` it's for".to_string()),
+        }
+        let names = |field: &str| -> Result<Vec<String>, String> {
+            match &manifest[field] {
+                Value::Null => Ok(Vec::new()),
+                Value::Array(names) => names
+                    .iter()
+                    .map(|name| match name {
+                        Value::String(name) => Ok(name.clone()),
+                        other => Err(format!("`{field}`: expected a string, found {other}")),
+                    })
+                    .collect(),
+                other => Err(format!("`{field}`: expected a list, found {other}")),
+            }
+        };
+        let hooks = names("hooks")?
+            .iter()
+            .map(|name| Hook::from_name(name).ok_or_else(|| format!("unknown hook `{name}`")))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: manifest["name"]
+                .as_str()
+                .unwrap_or(default_name)
+                .to_string(),
+            commands: names("commands")?,
+            transforms: names("transforms")?,
+            hooks,
+            status: names("status")?,
+        })
+    }
+}
+
+impl Library {
+    /// Opens the library at `path`, returning its functions and its
+    /// manifest.
+    fn open(path: &Path) -> Result<(Self, String), String> {
+        let filename = CString::new(path.as_os_str().as_encoded_bytes())
+            .map_err(|_| "the file name has a NUL in it".to_string())?;
+        // SAFETY: opening a library runs its initialisers, which is taking
+        // the plugin on trust, as loading any plugin does.
+        let handle = unsafe { dlopen(filename.as_ptr(), RTLD_NOW) };
+        if handle.is_null() {
+            return Err(last_error(path));
+        }
+        let symbol = |name: &CStr| {
+            // SAFETY: `handle` is an open library, and `name` ends in NUL.
+            let found = unsafe { dlsym(handle, name.as_ptr()) };
+            if found.is_null() {
+                Err(format!("it has no `{}` function", name.to_string_lossy()))
+            } else {
+                Ok(found)
+            }
+        };
+        let manifest = symbol(c"wasdev_manifest")?;
+        let call = symbol(c"wasdev_call")?;
+        let free = symbol(c"wasdev_free")?;
+        // SAFETY: the three functions have the signatures of the interface;
+        // the manifest is a string the plugin keeps for as long as it's
+        // loaded.
+        let (manifest, library) = unsafe {
+            let manifest = std::mem::transmute::<*mut c_void, ManifestFn>(manifest)();
+            let library = Self {
+                path: path.to_path_buf(),
+                call: std::mem::transmute::<*mut c_void, CallFn>(call),
+                free: std::mem::transmute::<*mut c_void, FreeFn>(free),
+            };
+            (manifest, library)
+        };
+        if manifest.is_null() {
+            return Err("it has no manifest".to_string());
+        }
+        // SAFETY: checked for NULL above.
+        let manifest = unsafe { CStr::from_ptr(manifest) }
+            .to_string_lossy()
+            .into_owned();
+        Ok((library, manifest))
+    }
+
+    /// Sends `request` to the plugin, returning its reply, or the `error`
+    /// it gave.
+    fn call(&self, request: &Value) -> Result<Value, String> {
+        // JSON escapes NUL, so the request has none of its own.
+        let request = CString::new(request.to_string()).expect("JSON has no NUL");
+        // SAFETY: the request is a string ending in NUL, and a reply that
+        // isn't NULL is freed where the plugin says, once it's been copied.
+        let reply = unsafe {
+            let reply = (self.call)(request.as_ptr());
+            if reply.is_null() {
+                None
+            } else {
+                let text = CStr::from_ptr(reply).to_string_lossy().into_owned();
+                (self.free)(reply);
+                Some(text)
+            }
+        };
+        let name = self.path.display();
+        let reply = reply.ok_or_else(|| format!("{name} didn't answer"))?;
+        let reply: Value = serde_json::from_str(&reply)
+            .map_err(|err| format!("{name} answered with something that isn't JSON: {err}"))?;
+        match reply["error"].as_str() {
+            Some(error) => Err(error.to_string()),
+            None => Ok(reply),
+        }
+    }
+}
+
+/// Lists the commands the plugins offer, and runs the one picked.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut list: SelectView<PluginCommand> = SelectView::new();
+    for command in workspace.lock().unwrap().plugins().commands() {
+        let label = format!("{:<24}{}", command.name, command.plugin);
+        list.add_item(label, command);
+    }
+    if list.is_empty() {
+        s.add_layer(Dialog::info("No plugin offers any commands"));
+        return;
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, command: &PluginCommand| {
+        s.pop_layer();
+        run(s, &workspace, command);
+    });
+    s.add_layer(
+        OnEventView::new(Dialog::around(list).title("Plugin commands")).on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/// Runs `command` on the selection, or the whole buffer, putting in the
+/// text it gives back and showing its message.
+fn run(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, command: &PluginCommand) {
+    let mut ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let text = ed
+        .selected_text()
+        .unwrap_or_else(|| ed.buffer().to_string());
+    let file = ed.path().map(Path::to_path_buf);
+    let outcome = match command.run(&text, file.as_deref()) {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            s.add_layer(Dialog::info(err).title(&command.name));
+            return;
+        }
+    };
+    if let Some(replacement) = outcome.text.filter(|replacement| *replacement != text) {
+        if ws.editor().is_read_only() {
+            ws.notify("The buffer is read-only");
+            return;
+        }
+        ws.editor_mut()
+            .apply_transformation(|_| replacement.clone());
+    }
+    if let Some(message) = outcome.message {
+        ws.notify(message);
+    }
+}
+
+/// What went wrong with the last `dlopen`, of the library at `path`.
+fn last_error(path: &Path) -> String {
+    // SAFETY: `dlerror` gives NULL or a string ending in NUL.
+    let error = unsafe { dlerror() };
+    if error.is_null() {
+        return "it could not be loaded".to_string();
+    }
+    // SAFETY: checked for NULL above.
+    let error = unsafe { CStr::from_ptr(error) }.to_string_lossy();
+    // The message starts with the path, which the error gives already.
+    let prefix = format!("{}: ", path.display());
+    error.strip_prefix(&prefix).unwrap_or(&error).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_manifest_lists_what_a_plugin_offers() {
+        let manifest = Manifest::parse(
+            r#"{"abi": 1, "commands": ["Count words"], "hooks": ["on_save"]}"#,
+            "words",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                name: "words".to_string(),
+                commands: vec!["Count words".to_string()],
+                transforms: Vec::new(),
+                hooks: vec![Hook::Save],
+                status: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn manifests_that_cant_be_loaded() {
+        let parse = |text: &str| Manifest::parse(text, "plugin").is_err();
+        assert!(parse(r#"{"commands": []}"#));
+        assert!(parse(r#"{"abi": 2}"#));
+        assert!(parse(r#"{"abi": 1, "hooks": ["on_close"]}"#));
+        assert!(!parse(r#"{"abi": 1, "hooks": ["on_selection_change"]}"#));
+        assert!(parse(r#"{"abi": 1, "status": "words"}"#));
+        assert!(parse("[1]"));
+    }
+}
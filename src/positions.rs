@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Most files whose cursor is remembered; the ones left longest ago are
+/// forgotten first.
+const MAX_FILES: usize = 1000;
+
+/// Where what the editor remembers between sessions is kept:
+/// `$XDG_DATA_HOME/wasdev`, or `~/.local/share/wasdev`.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("wasdev"))
+}
+
+/// Where the cursor positions are kept, in the data directory.
+fn store_path() -> Option<PathBuf> {
+    Some(data_dir()?.join("positions.json"))
+}
+
+/// `path` made absolute, so it names the file wherever the editor is
+/// started from.
+pub fn absolute(path: &Path) -> Option<PathBuf> {
+    path.canonicalize()
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .ok()
+}
+
+/// Every remembered (file, line, column), the most recent first.
+fn load() -> Vec<(PathBuf, usize, usize)> {
+    let value = store_path()
+        .and_then(|store| fs::read_to_string(store).ok())
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+    let Some(Value::Array(entries)) = value else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            Some((
+                PathBuf::from(entry[0].as_str()?),
+                entry[1].as_u64()? as usize,
+                entry[2].as_u64()? as usize,
+            ))
+        })
+        .collect()
+}
+
+/// The (line, column) the cursor was left at in the file at `path`.
+pub fn recall(path: &Path) -> Option<(usize, usize)> {
+    let path = absolute(path)?;
+    load()
+        .into_iter()
+        .find(|(file, _, _)| *file == path)
+        .map(|(_, line, column)| (line, column))
+}
+
+/// Remembers where the cursor is in each of `files`, given as (file,
+/// line, column).
+pub fn remember(files: &[(PathBuf, usize, usize)]) -> io::Result<()> {
+    let Some(store) = store_path() else {
+        return Ok(());
+    };
+    let mut entries: Vec<(PathBuf, usize, usize)> = files
+        .iter()
+        .filter_map(|(path, line, column)| Some((absolute(path)?, *line, *column)))
+        .collect();
+    let kept: Vec<_> = load()
+        .into_iter()
+        .filter(|(file, _, _)| entries.iter().all(|(path, _, _)| path != file))
+        .collect();
+    entries.extend(kept);
+    entries.truncate(MAX_FILES);
+    let json: Vec<Value> = entries
+        .iter()
+        .map(|(path, line, column)| json!([path.to_string_lossy(), line, column]))
+        .collect();
+    if let Some(dir) = store.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(store, Value::Array(json).to_string())
+}
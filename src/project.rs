@@ -0,0 +1,236 @@
+use crate::jobs::{self, Progress};
+use crate::large;
+use crate::prompt;
+use crate::search::Regex;
+use crate::swap;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Most matches listed; the search stops once it has found this many.
+const MAX_HITS: usize = 1000;
+
+/// Directories that hold build output or downloaded packages rather than
+/// the project's own files, left out of the search like hidden ones are.
+const SKIPPED: &[&str] = &["target", "node_modules"];
+
+/// Characters of a matching line shown in the list.
+const PREVIEW: usize = 80;
+
+/// A line matching the pattern: the file, the line and the column in
+/// characters, both from 0, and the line itself.
+struct Hit {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    text: String,
+}
+
+/// Asks for a pattern and looks for it in every file of the project, in
+/// the background, then lists the matching lines. Picking
+/// one opens its file with the cursor on the match.
+pub fn search(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        "Search files: ",
+        |_, _| {},
+        move |s, pattern| {
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(err) => {
+                    s.add_layer(Dialog::info(format!("Invalid pattern: {err}")));
+                    return;
+                }
+            };
+            let pattern = pattern.to_string();
+            let label = format!("Searching for {pattern}");
+            let listed = workspace.clone();
+            jobs::spawn(
+                s,
+                &workspace,
+                label,
+                move |progress| find(&regex, progress),
+                move |s, hits| show_hits(s, &listed, &pattern, hits),
+            );
+        },
+        |_| {},
+    );
+}
+
+/// The lines matching `regex` in the project's files, in the order `files`
+/// lists them, up to `MAX_HITS`. Files too big to search quickly or that
+/// don't read as text are skipped.
+fn find(regex: &Regex, progress: &Progress) -> Vec<Hit> {
+    let files = files(progress);
+    progress.set_total(files.len() as u64);
+    let mut hits = Vec::new();
+    for path in files {
+        if progress.is_cancelled() || hits.len() >= MAX_HITS {
+            break;
+        }
+        if !large::is_large(&path) {
+            search_file(&path, regex, &mut hits);
+        }
+        progress.advance(1);
+    }
+    hits
+}
+
+/// The project's files under the working directory, by paths relative to
+/// it: the ones git tracks or would, leaving out what `.gitignore` says, in
+/// a repository, and elsewhere every one not hidden or in a directory of
+/// `SKIPPED`.
+pub fn files(progress: &Progress) -> Vec<PathBuf> {
+    git_files().unwrap_or_else(|| walk(progress))
+}
+
+/// The files git tracks under the working directory, and the untracked
+/// ones it doesn't ignore, if it's in a repository.
+fn git_files() -> Option<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut files: Vec<PathBuf> = output
+        .stdout
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| PathBuf::from(String::from_utf8_lossy(name).into_owned()))
+        // Files deleted but not yet committed are still tracked.
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    files.dedup();
+    Some(files)
+}
+
+/// Every file under the working directory not hidden or under a directory
+/// of `SKIPPED`, in the order they sort in.
+fn walk(progress: &Progress) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        if progress.is_cancelled() {
+            break;
+        }
+        let read = if dir.as_os_str().is_empty() {
+            fs::read_dir(".")
+        } else {
+            fs::read_dir(&dir)
+        };
+        let Ok(entries) = read else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = dir.join(&name);
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() {
+                if !SKIPPED.iter().any(|&skipped| name == skipped) {
+                    subdirs.push(path);
+                }
+            } else if kind.is_file() {
+                files.push(path);
+            }
+        }
+        // Popped last first, so the subdirectories come in order.
+        dirs.extend(subdirs.into_iter().rev());
+    }
+    files
+}
+
+/// Adds the lines of the file at `path` that match `regex` to `hits`.
+fn search_file(path: &Path, regex: &Regex, hits: &mut Vec<Hit>) {
+    let Ok(bytes) = fs::read(path) else {
+        return;
+    };
+    // A NUL byte near the start gives a binary file away.
+    if bytes.iter().take(8192).any(|&byte| byte == 0) {
+        return;
+    }
+    let Ok(text) = String::from_utf8(bytes) else {
+        return;
+    };
+    for (line, content) in text.lines().enumerate() {
+        if hits.len() >= MAX_HITS {
+            return;
+        }
+        if let Some(m) = regex.find_at(content, 0) {
+            hits.push(Hit {
+                path: path.to_path_buf(),
+                line,
+                column: content[..m.start()].graphemes(true).count(),
+                text: content.trim().chars().take(PREVIEW).collect(),
+            });
+        }
+    }
+}
+
+/// Lists `hits` as `file:line: text`, opening the one picked.
+fn show_hits(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, pattern: &str, hits: Vec<Hit>) {
+    if hits.is_empty() {
+        s.add_layer(Dialog::info(format!("No files match {pattern}")));
+        return;
+    }
+    let title = if hits.len() >= MAX_HITS {
+        format!("The first {MAX_HITS} matches for {pattern}")
+    } else {
+        format!("{} matches for {pattern}", hits.len())
+    };
+    let mut list: SelectView<usize> = SelectView::new();
+    for (index, hit) in hits.iter().enumerate() {
+        let label = format!("{}:{}: {}", hit.path.display(), hit.line + 1, hit.text);
+        list.add_item(label, index);
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, &index| {
+        s.pop_layer();
+        let hit = &hits[index];
+        let result = workspace.lock().unwrap().open(&hit.path);
+        match result {
+            Ok(()) => {
+                let mut ws = workspace.lock().unwrap();
+                ws.editor_mut().goto_line(hit.line, hit.column);
+                drop(ws);
+                swap::offer(s, &workspace);
+            }
+            Err(err) => s.add_layer(Dialog::info(format!(
+                "Could not open {}: {err}",
+                hit.path.display()
+            ))),
+        }
+    });
+    let list = Dialog::around(list.scrollable())
+        .title(title)
+        .max_height(20);
+    s.add_layer(OnEventView::new(list).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
@@ -0,0 +1,69 @@
+use cursive::event::{Event, EventResult, EventTrigger, Key};
+use cursive::view::{Position, Resizable};
+use cursive::views::stack_view::{LayerAt, NoShadow};
+use cursive::views::{EditView, LinearLayout, OnEventView, TextView};
+use cursive::{Cursive, View};
+use std::sync::Arc;
+
+/// Opens a one-line prompt along the bottom of the screen.
+///
+/// `on_edit` runs after every change to the input, `on_submit` when Enter is
+/// pressed and `on_cancel` when Esc is pressed. The prompt layer is popped
+/// before `on_submit`/`on_cancel` run, so they may open a follow-up prompt.
+pub fn open<E, S, C>(s: &mut Cursive, label: &str, on_edit: E, on_submit: S, on_cancel: C)
+where
+    E: Fn(&mut Cursive, &str) + 'static + Send + Sync,
+    S: Fn(&mut Cursive, &str) + 'static + Send + Sync,
+    C: Fn(&mut Cursive) + 'static + Send + Sync,
+{
+    let input = EditView::new()
+        .on_edit(move |s, text, _| on_edit(s, text))
+        .on_submit(move |s, text| {
+            s.pop_layer();
+            on_submit(s, text);
+        })
+        .full_width();
+
+    let row = LinearLayout::horizontal()
+        .child(TextView::new(label))
+        .child(input);
+    let row = OnEventView::new(row).on_event(Key::Esc, move |s| {
+        s.pop_layer();
+        on_cancel(s);
+    });
+
+    open_bar(s, row);
+}
+
+/// Shows `label` along the bottom of the screen and waits for a character
+/// to be typed, handing it to `on_char`. Esc gives up.
+pub fn read_char<F>(s: &mut Cursive, label: &str, on_char: F)
+where
+    F: Fn(&mut Cursive, char) + 'static + Send + Sync,
+{
+    let on_char = Arc::new(on_char);
+    let bar = OnEventView::new(TextView::new(label))
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        })
+        .on_pre_event_inner(EventTrigger::any(), move |_, event| {
+            let Event::Char(ch) = *event else {
+                return None;
+            };
+            let on_char = on_char.clone();
+            Some(EventResult::with_cb(move |s| {
+                s.pop_layer();
+                on_char(s, ch);
+            }))
+        });
+    open_bar(s, bar);
+}
+
+/// Shows `view` as a full-width, one-line layer along the bottom of the screen.
+pub fn open_bar<V: View>(s: &mut Cursive, view: V) {
+    let size = s.screen_size();
+    s.screen_mut().add_layer(LayerAt(
+        Position::absolute((0, size.y.saturating_sub(1))),
+        NoShadow(view.fixed_width(size.x)),
+    ));
+}
@@ -0,0 +1,442 @@
+use crate::jobs;
+use crate::keymap::Error;
+use crate::settings::Settings;
+use crate::swap;
+use crate::toml;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView, TextView};
+use cursive::Cursive;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use txt_editor::shell;
+
+/// The file in a project's directory that can say how to build it,
+/// overriding the user's settings:
+///
+/// ```toml
+/// [build]
+/// command = "cargo build --all-targets"
+/// ```
+pub const PROJECT_FILE: &str = ".wasdev.toml";
+
+/// Most bytes of output a build may print before it's stopped.
+const MAX_OUTPUT: usize = 16 << 20;
+
+/// Characters of a message shown in the list.
+const PREVIEW: usize = 100;
+
+/// Files whose presence says how a project builds, when nothing else does,
+/// and the command that builds it.
+const GUESSES: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo build"),
+    ("go.mod", "go build ./..."),
+    ("Makefile", "make"),
+    ("makefile", "make"),
+];
+
+/// A place the build complained about: the file, the line and the column
+/// in characters, both from 0, and what it said.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Entry {
+    /// Whether the build said it was an error, rather than a warning or a
+    /// note.
+    fn is_error(&self) -> bool {
+        self.message.starts_with("error")
+    }
+}
+
+/// What the last build complained about, and which of it was last jumped
+/// to.
+#[derive(Debug, Default)]
+pub struct Quickfix {
+    command: String,
+    entries: Vec<Entry>,
+    current: Option<usize>,
+}
+
+impl Quickfix {
+    /// Replaces the list with what `command` complained about.
+    fn set(&mut self, command: String, entries: Vec<Entry>) {
+        self.command = command;
+        self.entries = entries;
+        self.current = None;
+    }
+
+    /// Moves to the entry after the current one, or before it, going round,
+    /// returning its index.
+    fn step(&mut self, forward: bool) -> Option<usize> {
+        let count = self.entries.len();
+        if count == 0 {
+            return None;
+        }
+        let next = match (self.current, forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(current), true) => (current + 1) % count,
+            (Some(current), false) => (current + count - 1) % count,
+        };
+        self.current = Some(next);
+        Some(next)
+    }
+}
+
+/// The command that builds the project in the working directory: the one
+/// its `PROJECT_FILE` gives, the user's own, or one guessed from the files
+/// it has.
+fn build_command(settings: &Settings) -> Result<Option<String>, Error> {
+    if let Some(command) = project_command(Path::new(PROJECT_FILE))? {
+        return Ok(Some(command));
+    }
+    if let Some(command) = &settings.build_command {
+        return Ok(Some(command.clone()));
+    }
+    Ok(GUESSES
+        .iter()
+        .find(|(file, _)| Path::new(file).is_file())
+        .map(|(_, command)| command.to_string()))
+}
+
+/// The build command the project file at `path` gives, if it's there and
+/// gives one.
+fn project_command(path: &Path) -> Result<Option<String>, Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+    };
+    let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+    let Some(toml::Value::Table(build)) = table.get("build") else {
+        return Ok(None);
+    };
+    match build.get("command") {
+        None => Ok(None),
+        Some(toml::Value::String(command)) => Ok(Some(command.clone())),
+        Some(value) => Err(Error::Invalid(
+            path.to_path_buf(),
+            format!("`build.command`: expected a command, found {value}"),
+        )),
+    }
+}
+
+/// Builds the project in the background, then lists the places the build
+/// complained about, in files that are there to open. A clean build just
+/// says so; a failed one that names no places shows what it printed.
+pub fn build(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let command = build_command(workspace.lock().unwrap().settings());
+    let command = match command {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            s.add_layer(Dialog::info(format!(
+                "No build command: set one under [build] in {PROJECT_FILE} or settings.toml"
+            )));
+            return;
+        }
+        Err(err) => {
+            s.add_layer(Dialog::info(err.to_string()));
+            return;
+        }
+    };
+    let label = format!("Running {command}");
+    let built = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        label,
+        move |progress| {
+            let output = shell::read(&command, MAX_OUTPUT, &|| progress.is_cancelled());
+            let output = output.map(|output| {
+                // Compilers mostly complain on standard error, but not all.
+                let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+                printed += &String::from_utf8_lossy(&output.stderr);
+                let entries = parse(&printed)
+                    .into_iter()
+                    .filter(|entry| entry.path.is_file())
+                    .collect::<Vec<_>>();
+                (output.status, printed, entries)
+            });
+            (command, output)
+        },
+        move |s, (command, output)| {
+            let (status, printed, entries) = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    s.add_layer(Dialog::info(format!("Could not run {command}: {err}")));
+                    return;
+                }
+            };
+            let empty = entries.is_empty();
+            built
+                .lock()
+                .unwrap()
+                .quickfix_mut()
+                .set(command.clone(), entries);
+            if !empty {
+                show(s, &built);
+            } else if status.success() {
+                built.lock().unwrap().notify(format!("{command} succeeded"));
+            } else {
+                let printed = printed.trim_end();
+                let message = if printed.is_empty() {
+                    format!("{command}: {status}")
+                } else {
+                    printed.to_string()
+                };
+                s.add_layer(
+                    Dialog::around(TextView::new(message).scrollable())
+                        .title(command)
+                        .dismiss_button("Ok")
+                        .max_height(20),
+                );
+            }
+        },
+    );
+}
+
+/// Lists what the last build complained about. Picking an entry opens its
+/// file at the place.
+pub fn show(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let quickfix = ws.quickfix();
+    if quickfix.entries.is_empty() {
+        drop(ws);
+        s.add_layer(Dialog::info("The last build had nothing to say"));
+        return;
+    }
+    let errors = quickfix.entries.iter().filter(|e| e.is_error()).count();
+    let others = quickfix.entries.len() - errors;
+    let title = format!(
+        "{}: {errors} error{}, {others} other{}",
+        quickfix.command,
+        if errors == 1 { "" } else { "s" },
+        if others == 1 { "" } else { "s" },
+    );
+    let mut list: SelectView<usize> = SelectView::new();
+    for (index, entry) in quickfix.entries.iter().enumerate() {
+        let label = format!(
+            "{}:{}:{}: {}",
+            entry.path.display(),
+            entry.line + 1,
+            entry.column + 1,
+            entry.message.chars().take(PREVIEW).collect::<String>()
+        );
+        list.add_item(label, index);
+    }
+    if let Some(current) = quickfix.current {
+        list.set_selection(current);
+    }
+    drop(ws);
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, &index| {
+        s.pop_layer();
+        workspace.lock().unwrap().quickfix_mut().current = Some(index);
+        jump(s, &workspace, index);
+    });
+    let list = Dialog::around(list.scrollable())
+        .title(title)
+        .max_height(20);
+    s.add_layer(OnEventView::new(list).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Jumps to the next place the last build complained about, or the
+/// previous one, going round.
+pub fn step(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, forward: bool) {
+    let next = workspace.lock().unwrap().quickfix_mut().step(forward);
+    match next {
+        Some(index) => jump(s, workspace, index),
+        None => workspace
+            .lock()
+            .unwrap()
+            .notify("The last build had nothing to say"),
+    }
+}
+
+/// Opens the file of entry `index` with the cursor at its place, and shows
+/// what the build said there.
+fn jump(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, index: usize) {
+    let mut ws = workspace.lock().unwrap();
+    let count = ws.quickfix().entries.len();
+    let entry = ws.quickfix().entries[index].clone();
+    match ws.open(&entry.path) {
+        Ok(()) => {
+            ws.editor_mut().goto_line(entry.line, entry.column);
+            ws.notify(format!("({}/{count}) {}", index + 1, entry.message));
+            drop(ws);
+            swap::offer(s, workspace);
+        }
+        Err(err) => {
+            drop(ws);
+            s.add_layer(Dialog::info(format!(
+                "Could not open {}: {err}",
+                entry.path.display()
+            )));
+        }
+    }
+}
+
+/// The places a build's output names, in the order it names them. Two
+/// shapes are understood: `file:line:column: message`, as most compilers
+/// print, the column being optional; and rustc's, where the message comes
+/// on a line of its own before an arrow to the place:
+///
+/// ```text
+/// error[E0425]: cannot find value `x` in this scope
+///   --> src/main.rs:3:5
+/// ```
+pub fn parse(output: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    // The last message that wasn't on the same line as its place.
+    let mut heading = None;
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        if let Some(place) = trimmed.strip_prefix("--> ") {
+            if let Some((path, line, column, _)) = location(place) {
+                entries.push(Entry {
+                    path: PathBuf::from(path),
+                    line,
+                    column,
+                    message: heading.take().unwrap_or_default(),
+                });
+            }
+        } else if trimmed.starts_with("::: ") {
+            // Where something the message mentions is, not the place itself.
+        } else if let Some((path, line, column, message)) = location(line) {
+            entries.push(Entry {
+                path: PathBuf::from(path),
+                line,
+                column,
+                message: message.to_string(),
+            });
+        } else if is_heading(line) {
+            heading = Some(line.trim().to_string());
+        }
+    }
+    entries
+}
+
+/// Whether `line` is a message that rustc puts the place of below it.
+fn is_heading(line: &str) -> bool {
+    let word = line.split([':', '[']).next().unwrap_or_default();
+    ["error", "warning", "note", "help"].contains(&word) && line.contains(':')
+}
+
+/// Reads `file:line`, `file:line:column`, and either followed by
+/// `: message`, the line and column counted from 1, into the file, the
+/// place counted from 0, and the message.
+fn location(text: &str) -> Option<(&str, usize, usize, &str)> {
+    let text = text.trim_end();
+    text.match_indices(':').find_map(|(i, _)| {
+        let path = &text[..i];
+        if path.is_empty() || path.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (line, rest) = number(&text[i + 1..])?;
+        let (column, rest) = match rest.strip_prefix(':').and_then(number) {
+            Some((column, rest)) => (column, rest),
+            None => (1, rest),
+        };
+        let message = match rest {
+            "" => "",
+            rest => rest.strip_prefix(':')?.trim(),
+        };
+        Some((path, line - 1, column - 1, message))
+    })
+}
+
+/// Reads the number from 1 that `text` starts with, and what follows it.
+fn number(text: &str) -> Option<(usize, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let n = text[..end].parse::<usize>().ok().filter(|&n| n > 0)?;
+    Some((n, &text[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, line: usize, column: usize, message: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn rustc_messages() {
+        let output = "\
+   Compiling demo v0.1.0 (/home/me/demo)
+error[E0425]: cannot find value `x` in this scope
+ --> src/main.rs:3:5
+  |
+3 |     x
+  |     ^ not found in this scope
+
+warning: unused variable: `y`
+  --> src/lib.rs:12:9
+   |
+  ::: /rustc/library/core/src/option.rs:571:1
+error: could not compile `demo` (bin \"demo\") due to 1 previous error
+";
+        assert_eq!(
+            parse(output),
+            [
+                entry(
+                    "src/main.rs",
+                    2,
+                    4,
+                    "error[E0425]: cannot find value `x` in this scope"
+                ),
+                entry("src/lib.rs", 11, 8, "warning: unused variable: `y`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn compiler_lines() {
+        let output = "\
+main.c:10:3: error: expected ';' before 'return'
+./cmd/main.go:7:2: undefined: fmt
+Makefile:4: *** missing separator.  Stop.
+make: *** [all] Error 2
+see http://example.com:8080/docs
+";
+        assert_eq!(
+            parse(output),
+            [
+                entry("main.c", 9, 2, "error: expected ';' before 'return'"),
+                entry("./cmd/main.go", 6, 1, "undefined: fmt"),
+                entry("Makefile", 3, 0, "*** missing separator.  Stop."),
+            ]
+        );
+    }
+
+    #[test]
+    fn stepping_goes_round() {
+        let mut quickfix = Quickfix::default();
+        assert_eq!(quickfix.step(true), None);
+        quickfix.set(
+            "make".to_string(),
+            vec![entry("a", 0, 0, ""), entry("b", 0, 0, "")],
+        );
+        assert_eq!(quickfix.step(false), Some(1));
+        assert_eq!(quickfix.step(true), Some(0));
+        assert_eq!(quickfix.step(true), Some(1));
+        assert_eq!(quickfix.step(true), Some(0));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::positions;
+use crate::swap;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Most files remembered as recently opened.
+const MAX_FILES: usize = 100;
+
+/// Where the recently opened files are kept, in the data directory.
+fn store_path() -> Option<PathBuf> {
+    Some(positions::data_dir()?.join("recent.json"))
+}
+
+/// The files opened lately, the most recent first.
+fn load() -> Vec<PathBuf> {
+    let value = store_path()
+        .and_then(|store| fs::read_to_string(store).ok())
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+    let Some(Value::Array(entries)) = value else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter_map(|entry| Some(PathBuf::from(entry.as_str()?)))
+        .collect()
+}
+
+/// Remembers that the file at `path` was just opened.
+pub fn note(path: &Path) -> io::Result<()> {
+    let (Some(store), Some(path)) = (store_path(), positions::absolute(path)) else {
+        return Ok(());
+    };
+    let mut files = load();
+    files.retain(|file| *file != path);
+    files.insert(0, path);
+    files.truncate(MAX_FILES);
+    let json: Vec<Value> = files
+        .iter()
+        .map(|file| Value::from(file.to_string_lossy()))
+        .collect();
+    if let Some(dir) = store.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(store, Value::Array(json).to_string())
+}
+
+/// Lists the files opened lately that are still there, the most recent
+/// first, and opens the one picked.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let files: Vec<PathBuf> = load().into_iter().filter(|file| file.exists()).collect();
+    if files.is_empty() {
+        s.add_layer(Dialog::info("No files were opened lately"));
+        return;
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut list: SelectView<PathBuf> = SelectView::new();
+    for file in files {
+        // Shortened the way a shell would, to fit more in.
+        let label = match home
+            .as_deref()
+            .and_then(|home| file.strip_prefix(home).ok())
+        {
+            Some(rest) => format!("~/{}", rest.display()),
+            None => file.display().to_string(),
+        };
+        list.add_item(label, file);
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, path: &PathBuf| {
+        s.pop_layer();
+        let result = workspace.lock().unwrap().open(path);
+        match result {
+            Ok(()) => swap::offer(s, &workspace),
+            Err(err) => s.add_layer(Dialog::info(format!(
+                "Could not open {}: {err}",
+                path.display()
+            ))),
+        }
+    });
+    let list = Dialog::around(list.scrollable())
+        .title("Recent files")
+        .max_height(20);
+    s.add_layer(OnEventView::new(list).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
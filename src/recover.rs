@@ -0,0 +1,113 @@
+use crate::workspace::Workspace;
+use cursive::views::Dialog;
+use cursive::CursiveRunnable;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// How many steps of the event loop may fail one after the other before
+/// the editor gives up: by then it's something every step goes through
+/// that fails, and carrying on can't help.
+const MAX_FAILURES: usize = 3;
+
+/// What the last panic said, until the event loop shows it.
+static PANICKED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether panics are kept for the event loop to show, rather than printed
+/// over the screen.
+static CATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Runs the event loop, keeping the editor going when a key, a callback or
+/// a background thread panics: what went wrong is shown in a dialog, and
+/// everything carries on from the state it was left in. If every step
+/// fails, or the screen can't even be drawn to show why, the editor stops,
+/// putting the terminal back first; the error is returned, and the swap
+/// files are left for the next session to offer.
+pub fn run(siv: &mut CursiveRunnable, workspace: &Arc<Mutex<Workspace>>) -> Result<(), String> {
+    let mut runner = siv
+        .try_runner()
+        .map_err(|err| format!("Could not start the terminal: {err}"))?;
+    catch_panics();
+    CATCHING.store(true, Ordering::Relaxed);
+    let mut failures = 0;
+    let mut first = true;
+    while first || runner.is_running() {
+        let stepped = panic::catch_unwind(AssertUnwindSafe(|| {
+            if first {
+                runner.refresh();
+                true
+            } else {
+                runner.step()
+            }
+        }));
+        first = false;
+        match stepped {
+            // Steps with nothing to do succeed whatever is wrong.
+            Ok(false) => {}
+            Ok(true) => failures = 0,
+            Err(_) => failures += 1,
+        }
+        let Some(message) = take() else {
+            continue;
+        };
+        // A panic with the workspace locked leaves it poisoned, though
+        // what's in it is still good to keep working in and save.
+        workspace.clear_poison();
+        let shown = failures < MAX_FAILURES
+            && panic::catch_unwind(AssertUnwindSafe(|| {
+                runner.add_layer(
+                    Dialog::info(format!(
+                        "{message}\n\nThe editor carried on, but it's worth saving your work."
+                    ))
+                    .title("Something went wrong"),
+                );
+                runner.refresh();
+            }))
+            .is_ok();
+        if !shown {
+            CATCHING.store(false, Ordering::Relaxed);
+            drop(runner);
+            return Err(format!(
+                "The editor stopped: {message}\n\
+                 Unsaved changes are in swap files, offered the next time the files are opened."
+            ));
+        }
+    }
+    CATCHING.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Puts in a panic hook that keeps what a panic says while `CATCHING` is
+/// set, and leaves the rest to the usual one.
+fn catch_panics() {
+    let default = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if CATCHING.load(Ordering::Relaxed) {
+            *PANICKED.lock().unwrap_or_else(PoisonError::into_inner) = Some(describe(info));
+        } else {
+            default(info);
+        }
+    }));
+}
+
+/// Takes what the last panic said, if one happened since.
+fn take() -> Option<String> {
+    PANICKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
+/// What a panic says, and where it happened.
+fn describe(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("a panic");
+    match info.location() {
+        Some(location) => format!("{message} ({}:{})", location.file(), location.line()),
+        None => message.to_string(),
+    }
+}
@@ -0,0 +1,83 @@
+use crate::width::display_width;
+
+/// Comment leaders the lines of a paragraph may start with, longest first
+/// so that `///` isn't taken for `//`.
+const LEADERS: &[&str] = &["//!", "///", "//", "--", "#", ";", ">", "*", "%"];
+
+/// What every line `line` is reflowed into starts with: its indentation,
+/// then any comment leader with the spaces after it. A leader only counts
+/// followed by a space or the end of the line, so `#include` or `**bold**`
+/// stay words.
+fn prefix(line: &str) -> &str {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let leader = LEADERS
+        .iter()
+        .find(|leader| {
+            rest.strip_prefix(**leader)
+                .is_some_and(|after| after.is_empty() || after.starts_with([' ', '\t']))
+        })
+        .map_or(0, |leader| leader.len());
+    let after = &rest[leader..];
+    &line[..indent + leader + after.len() - after.trim_start().len()]
+}
+
+/// Whether `line` has nothing past its prefix, which ends a paragraph.
+fn is_blank(line: &str) -> bool {
+    line.len() == prefix(line).len()
+}
+
+/// Rewraps the paragraphs of `text`, which is made of whole lines, so that
+/// no line is longer than `width` columns unless a single word is. The
+/// first line of a paragraph keeps its prefix and the rest take the second
+/// line's, so hanging indents and comments survive; blank lines, and a
+/// change of comment leader, separate paragraphs.
+pub fn reflow(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next() {
+        if is_blank(line) {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let first = prefix(line);
+        let leader = first.trim();
+        let same = |next: &&str| !is_blank(next) && prefix(next).trim() == leader;
+        let rest = match lines.peek() {
+            Some(next) if same(next) => prefix(next),
+            _ => first,
+        };
+        let mut words: Vec<&str> = line[first.len()..].split_whitespace().collect();
+        while let Some(next) = lines.next_if(same) {
+            words.extend(next[prefix(next).len()..].split_whitespace());
+        }
+        fill(&mut out, &words, first, rest, width);
+    }
+    if !text.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// Adds `words` to `out` as lines of up to `width` columns, the first
+/// starting with `first` and the others with `rest`.
+fn fill(out: &mut String, words: &[&str], first: &str, rest: &str, width: usize) {
+    let mut line = first.to_string();
+    let mut empty = true;
+    for word in words {
+        if !empty && display_width(&line) + 1 + display_width(word) > width {
+            out.push_str(&line);
+            out.push('\n');
+            line = rest.to_string();
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        empty = false;
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
@@ -0,0 +1,38 @@
+use std::collections::BTreeMap;
+
+/// Text kept under names of its own, apart from the clipboard: one register
+/// for each letter, each yanked into and pasted from on its own.
+#[derive(Debug, Default)]
+pub struct Registers {
+    named: BTreeMap<char, String>,
+}
+
+impl Registers {
+    /// Puts `text` in the register named by the letter `name`; in upper
+    /// case, after what the register already holds.
+    pub fn set(&mut self, name: char, text: &str) {
+        let register = self.named.entry(name.to_ascii_lowercase()).or_default();
+        if !name.is_ascii_uppercase() {
+            register.clear();
+        }
+        register.push_str(text);
+    }
+
+    /// What the register named by the letter `name` holds, if anything.
+    pub fn get(&self, name: char) -> Option<&str> {
+        self.named
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Every register holding something, in alphabetical order.
+    pub fn named(&self) -> impl Iterator<Item = (char, &str)> {
+        self.named.iter().map(|(&name, text)| (name, text.as_str()))
+    }
+}
+
+/// Whether `name` names a register: a letter for one of its own, or a
+/// digit for the text copied that many times ago.
+pub fn is_register(name: char) -> bool {
+    name.is_ascii_alphabetic() || name.is_ascii_digit()
+}
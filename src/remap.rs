@@ -0,0 +1,177 @@
+use crate::commands::Command;
+use crate::keymap::{self, Keymap};
+use crate::workspace::Workspace;
+use cursive::event::{Event, EventResult, Key};
+use cursive::view::{CannotFocus, Nameable, Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::{direction::Direction, Cursive, Printer, Vec2, View};
+use std::sync::{Arc, Mutex};
+
+/// Name of the list of commands and their keys.
+const LIST: &str = "remap_list";
+
+/// What the key capture dialog asks for.
+const PROMPT: &str = "Press the new key, or Esc to cancel.";
+
+/// Opens the key bindings screen: every command with the keys bound to it.
+/// Enter on one waits for a key press and binds that key to it instead,
+/// then saves the bindings to `keys.toml`.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut list: SelectView<Command> = SelectView::new();
+    fill(&mut list, workspace.lock().unwrap().keymap());
+    let list = {
+        let workspace = workspace.clone();
+        list.on_submit(move |s, &command: &Command| capture(s, &workspace, command))
+    };
+    let screen = Dialog::around(list.with_name(LIST).scrollable().max_height(16))
+        .title("Key bindings")
+        .button("Done", |s| {
+            s.pop_layer();
+        })
+        .min_width(50);
+    s.add_layer(OnEventView::new(screen).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Lists every command, in the palette's order, with its keys.
+fn fill(list: &mut SelectView<Command>, keymap: &Keymap) {
+    let selected = list.selected_id();
+    list.clear();
+    for &command in Command::ALL {
+        let keys = keymap.keys_for(command).join(", ");
+        list.add_item(format!("{:<24}{}", command.name(), keys), command);
+    }
+    if let Some(selected) = selected {
+        // Nothing listens for selection changes, so the callback can be dropped.
+        let _ = list.set_selection(selected);
+    }
+}
+
+/// Waits for the key to bind to `command`.
+fn capture(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, command: Command) {
+    let workspace = workspace.clone();
+    let capture = KeyCapture::new(PROMPT, move |s, event| {
+        s.pop_layer();
+        bind(s, &workspace, command, event);
+    });
+    s.add_layer(Dialog::around(capture).title(format!("Rebind {}", command.name())));
+}
+
+/// Binds `event` to `command`, once it's been confirmed if that takes the
+/// key from another command or unbinds the sequences it starts.
+fn bind(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, command: Command, event: Event) {
+    let key = keymap::format_event(&event).unwrap_or_default();
+    // The buffer takes these before the keymap ever sees them.
+    let typing = matches!(event, Event::Char(_) | Event::Key(Key::Enter | Key::Tab));
+    if typing {
+        s.add_layer(Dialog::info(format!(
+            "{key} types in the buffer, so it can't run a command"
+        )));
+        return;
+    }
+    let (taken, started) = {
+        let ws = workspace.lock().unwrap();
+        let keys = std::slice::from_ref(&event);
+        (
+            ws.keymap().command_for(keys),
+            ws.keymap().continuations(keys),
+        )
+    };
+    let mut conflicts = Vec::new();
+    if let Some(other) = taken.filter(|&other| other != command) {
+        conflicts.push(format!("{key} runs {}.", other.name()));
+    }
+    if !started.is_empty() {
+        // A key bound on its own would run before the rest were pressed.
+        let sequences: Vec<_> = started
+            .iter()
+            .map(|(rest, other)| format!("{key} {rest} ({})", other.name()))
+            .collect();
+        conflicts.push(format!(
+            "{key} starts {}, which would be unbound.",
+            sequences.join(", ")
+        ));
+    }
+    if conflicts.is_empty() {
+        apply(s, workspace, command, event);
+        return;
+    }
+    let workspace = workspace.clone();
+    s.add_layer(
+        Dialog::text(format!(
+            "{} Bind it to {} instead?",
+            conflicts.join(" "),
+            command.name()
+        ))
+        .button("Rebind", move |s| {
+            s.pop_layer();
+            apply(s, &workspace, command, event.clone());
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+/// Binds `event` to `command`, saves the result and shows it in the list.
+fn apply(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, command: Command, event: Event) {
+    let mut keymap = workspace.lock().unwrap().keymap().clone();
+    keymap.bind(command, event);
+    if let Err(err) = keymap.save() {
+        s.add_layer(Dialog::info(format!("Could not save the bindings: {err}")));
+    }
+    s.call_on_name(LIST, |list: &mut SelectView<Command>| fill(list, &keymap));
+    workspace.lock().unwrap().set_keymap(keymap);
+}
+
+/// Called with the key pressed into a `KeyCapture`.
+type OnKey = Box<dyn FnOnce(&mut Cursive, Event) + Send + Sync>;
+
+/// Takes the next key pressed, whatever it is, rather than letting it act.
+pub struct KeyCapture {
+    prompt: &'static str,
+    on_key: Option<OnKey>,
+}
+
+impl KeyCapture {
+    /// Shows `prompt` until a key is pressed, then calls `on_key` with it.
+    /// Esc takes the layer down instead.
+    pub fn new(
+        prompt: &'static str,
+        on_key: impl FnOnce(&mut Cursive, Event) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            prompt,
+            on_key: Some(Box::new(on_key)),
+        }
+    }
+}
+
+impl View for KeyCapture {
+    fn draw(&self, printer: &Printer) {
+        printer.print((0, 0), self.prompt);
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(self.prompt.chars().count(), 1)
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        if event == Event::Key(Key::Esc) {
+            return EventResult::with_cb(|s| {
+                s.pop_layer();
+            });
+        }
+        // Mouse and resize events aren't keys, and pass.
+        if keymap::format_event(&event).is_none() {
+            return EventResult::Ignored;
+        }
+        match self.on_key.take() {
+            Some(on_key) => EventResult::with_cb_once(move |s| on_key(s, event)),
+            None => EventResult::Consumed(None),
+        }
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::consumed())
+    }
+}
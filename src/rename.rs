@@ -0,0 +1,205 @@
+use crate::lsp::{self, FileEdit};
+use crate::prompt;
+use crate::scripts::Hook;
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, TextView};
+use cursive::Cursive;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Characters of replaced text shown in the preview.
+const PREVIEW: usize = 40;
+
+/// Asks what to rename the symbol under the cursor to, then asks the
+/// buffer's language server how; its answer comes in later, to `preview`.
+pub fn rename(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let word = ed.word_at_cursor().map(|word| ed.buffer().slice(word));
+    drop(ws);
+    let label = match word {
+        Some(word) => format!("Rename {word} to: "),
+        None => "Rename to: ".to_string(),
+    };
+    let workspace = workspace.clone();
+    prompt::open(
+        s,
+        &label,
+        |_, _| {},
+        move |s, name| {
+            let name = name.trim();
+            if name.is_empty() {
+                return;
+            }
+            if !workspace.lock().unwrap().rename(name) {
+                s.add_layer(Dialog::info("No language server for this buffer"));
+            }
+        },
+        |_| {},
+    );
+}
+
+/// Shows what a rename changes, file by file, to apply or not.
+pub fn preview(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, files: Vec<FileEdit>) {
+    let mut text = String::new();
+    let mut changes = 0;
+    // Files in the project are shown by the path from it.
+    let here = std::env::current_dir().unwrap_or_default();
+    let ws = workspace.lock().unwrap();
+    for file in &files {
+        let shown = file.path.strip_prefix(&here).unwrap_or(&file.path);
+        text += &format!("{}\n", shown.display());
+        let replaced = match ws.position_of(&file.path) {
+            Some(index) => Ok(replaced(&ws.editors()[index], file)),
+            None => Editor::open(&file.path, None).map(|ed| replaced(&ed, file)),
+        };
+        match replaced {
+            Ok(replaced) => {
+                for (edit, old) in file.edits.iter().zip(replaced) {
+                    text += &format!(
+                        "  {}:{}  {old} → {}\n",
+                        edit.start.0 + 1,
+                        edit.start.1 + 1,
+                        edit.text
+                    );
+                }
+            }
+            Err(err) => text += &format!("  could not be read: {err}\n"),
+        }
+        changes += file.edits.len();
+    }
+    drop(ws);
+    let title = format!(
+        "Rename: {changes} change{} in {} file{}",
+        if changes == 1 { "" } else { "s" },
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+    );
+    let workspace = workspace.clone();
+    s.add_layer(
+        Dialog::around(TextView::new(text.trim_end()).scrollable())
+            .title(title)
+            .button("Apply", move |s| {
+                s.pop_layer();
+                match apply(&workspace, &files) {
+                    Ok(()) => workspace.lock().unwrap().notify(format!(
+                        "Renamed in {} file{}",
+                        files.len(),
+                        if files.len() == 1 { "" } else { "s" }
+                    )),
+                    Err(err) => s.add_layer(Dialog::info(err)),
+                }
+            })
+            .dismiss_button("Cancel")
+            .max_height(20),
+    );
+}
+
+/// The text each edit of `file` replaces in `ed`, cut short for showing.
+fn replaced(ed: &Editor, file: &FileEdit) -> Vec<String> {
+    let buffer = ed.buffer();
+    file.edits
+        .iter()
+        .map(|edit| {
+            let (range, _) = lsp::ranges(buffer, std::slice::from_ref(edit)).remove(0);
+            let old = buffer.slice(range);
+            let mut shown: String = old.chars().take(PREVIEW).collect();
+            if old.chars().count() > PREVIEW {
+                shown.push('…');
+            }
+            shown.replace('\n', "⏎")
+        })
+        .collect()
+}
+
+/// Makes the changes of a rename: in the buffers that are open, as one
+/// step each to undo, and in the files that aren't, saved straight away.
+/// Nothing is changed if a buffer is read-only, or changed since the
+/// server worked out the rename.
+fn apply(workspace: &Arc<Mutex<Workspace>>, files: &[FileEdit]) -> Result<(), String> {
+    let mut ws = workspace.lock().unwrap();
+    // Every file is checked before any is changed.
+    let mut closed = Vec::new();
+    for file in files {
+        let name = file.path.display();
+        match ws.position_of(&file.path) {
+            Some(index) => {
+                let ed = &ws.editors()[index];
+                if ed.is_read_only() {
+                    return Err(format!("{name} is read-only"));
+                }
+                if file.version.is_some_and(|version| version != ed.revision()) {
+                    return Err(format!("{name} changed since; rename again"));
+                }
+            }
+            None => {
+                let ed = Editor::open(&file.path, None)
+                    .map_err(|err| format!("Could not open {name}: {err}"))?;
+                closed.push(ed);
+            }
+        }
+    }
+    let mut closed = closed.into_iter();
+    let mut saved: Vec<PathBuf> = Vec::new();
+    for file in files {
+        match ws.position_of(&file.path) {
+            Some(index) => {
+                let ed = &mut ws.editors_mut()[index];
+                let edits = lsp::ranges(ed.buffer(), &file.edits);
+                replace_keeping_cursor(ed, edits);
+            }
+            None => {
+                let mut ed = closed.next().expect("every closed file was opened");
+                let edits = lsp::ranges(ed.buffer(), &file.edits);
+                ed.replace_ranges(edits);
+                ed.save()
+                    .map_err(|err| format!("Could not save {}: {err}", file.path.display()))?;
+                saved.push(file.path.clone());
+            }
+        }
+    }
+    for path in saved {
+        ws.run_hook(Hook::Save, &path);
+    }
+    Ok(())
+}
+
+/// Replaces `edits` in `ed`, leaving the cursor by the same text it was
+/// by, or at the start of the replacement it was in.
+fn replace_keeping_cursor(ed: &mut Editor, edits: Vec<(Range<usize>, String)>) {
+    let cursor = ed.cursor();
+    ed.replace_ranges(edits.clone());
+    ed.set_cursor(moved(cursor, &edits));
+}
+
+/// Where `offset` is after `edits`, sorted and apart, are made.
+fn moved(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+    let mut moved = offset;
+    for (range, text) in edits {
+        if range.end <= offset {
+            moved = moved + text.len() - range.len();
+        } else if range.start < offset {
+            moved = moved + range.start - offset;
+            break;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_follows_the_text() {
+        let edits = vec![(2..5, "abcdef".to_string()), (10..14, "x".to_string())];
+        assert_eq!(moved(1, &edits), 1);
+        assert_eq!(moved(5, &edits), 8);
+        assert_eq!(moved(7, &edits), 10);
+        assert_eq!(moved(12, &edits), 13);
+        assert_eq!(moved(20, &edits), 20);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::prompt;
+use crate::search::{self, Regex};
+use crate::workspace::Workspace;
+use crate::Editor;
+use cursive::event::{Event, Key};
+use cursive::view::Nameable;
+use cursive::views::{Dialog, OnEventView, TextView};
+use cursive::Cursive;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// Name of the text shown in the confirmation bar.
+const CONFIRM: &str = "replace_confirm";
+
+/// State of an interactive replace, from the first match to the last.
+struct Session {
+    regex: Regex,
+    template: String,
+    /// Where to look for the next match; `None` once the end was reached.
+    next: Option<usize>,
+    /// The match currently offered for replacement, with its replacement text.
+    current: Option<(Range<usize>, String)>,
+    replaced: usize,
+}
+
+/// What the user answered for the current match.
+#[derive(Clone, Copy)]
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+/// Starts an interactive regex replace from the cursor to the end of the buffer.
+///
+/// Prompts for a pattern and a replacement template (see `search::expand`),
+/// then selects each match in turn and asks whether to replace it.
+pub fn start(s: &mut Cursive, workspace: Arc<Mutex<Workspace>>) {
+    prompt::open(
+        s,
+        "Replace regex: ",
+        |_, _| {},
+        move |s, pattern| {
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(err) => {
+                    s.add_layer(Dialog::info(format!("Invalid pattern: {err}")));
+                    return;
+                }
+            };
+            let workspace = workspace.clone();
+            prompt::open(
+                s,
+                "With: ",
+                |_, _| {},
+                move |s, template| {
+                    let from = workspace.lock().unwrap().editor().cursor();
+                    let session = Arc::new(Mutex::new(Session {
+                        regex: regex.clone(),
+                        template: template.to_string(),
+                        next: Some(from),
+                        current: None,
+                        replaced: 0,
+                    }));
+                    open_confirm_bar(s, workspace.clone(), session.clone());
+                    advance(s, &workspace, &session);
+                },
+                |_| {},
+            );
+        },
+        |_| {},
+    );
+}
+
+/// Shows the bar asking what to do with the current match.
+fn open_confirm_bar(
+    s: &mut Cursive,
+    workspace: Arc<Mutex<Workspace>>,
+    session: Arc<Mutex<Session>>,
+) {
+    let mut bar = OnEventView::new(TextView::new("").with_name(CONFIRM));
+    let answers = [
+        (Event::Char('y'), Answer::Yes),
+        (Event::Char(' '), Answer::Yes),
+        (Event::Char('n'), Answer::No),
+        (Event::Key(Key::Backspace), Answer::No),
+        (Event::Char('a'), Answer::All),
+        (Event::Char('!'), Answer::All),
+        (Event::Char('q'), Answer::Quit),
+        (Event::Key(Key::Esc), Answer::Quit),
+    ];
+    for (event, answer) in answers {
+        let workspace = workspace.clone();
+        let session = session.clone();
+        bar.set_on_event(event, move |s| {
+            answer_current(s, &workspace, &session, answer)
+        });
+    }
+    prompt::open_bar(s, bar);
+}
+
+/// Acts on the user's answer for the current match and moves on.
+fn answer_current(
+    s: &mut Cursive,
+    workspace: &Arc<Mutex<Workspace>>,
+    session: &Arc<Mutex<Session>>,
+    answer: Answer,
+) {
+    {
+        let mut ws = workspace.lock().unwrap();
+        let ed = ws.editor_mut();
+        let mut sess = session.lock().unwrap();
+        let Some((range, replacement)) = sess.current.take() else {
+            return;
+        };
+        match answer {
+            Answer::Yes => {
+                let end = range.start + replacement.len();
+                let was_empty = range.is_empty();
+                ed.replace_ranges(vec![(range, replacement)]);
+                sess.replaced += 1;
+                sess.next = step_past(ed, end, was_empty);
+            }
+            Answer::No => {
+                sess.next = step_past(ed, range.end, range.is_empty());
+            }
+            Answer::All => {
+                let text = ed.buffer().to_string();
+                let edits = search::replacements(&sess.regex, &sess.template, &text, range.start);
+                sess.replaced += edits.len();
+                ed.replace_ranges(edits);
+                sess.next = None;
+            }
+            Answer::Quit => sess.next = None,
+        }
+    }
+    advance(s, workspace, session);
+}
+
+/// Where the search continues after a match ending at `end`; empty matches
+/// skip one character so the same spot isn't offered again.
+fn step_past(ed: &Editor, end: usize, empty: bool) -> Option<usize> {
+    if !empty {
+        return Some(end);
+    }
+    ed.buffer().char_at(end).map(|ch| end + ch.len_utf8())
+}
+
+/// Selects the next match and asks about it, or wraps up when none is left.
+fn advance(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, session: &Arc<Mutex<Session>>) {
+    let mut ws = workspace.lock().unwrap();
+    let ed = ws.editor_mut();
+    let mut sess = session.lock().unwrap();
+
+    let text = ed.buffer().to_string();
+    let found = sess.next.and_then(|from| sess.regex.find_at(&text, from));
+    let Some(m) = found else {
+        let replaced = sess.replaced;
+        drop(sess);
+        drop(ws);
+        s.pop_layer();
+        let plural = if replaced == 1 { "" } else { "s" };
+        s.add_layer(Dialog::info(format!(
+            "Replaced {replaced} occurrence{plural}"
+        )));
+        return;
+    };
+
+    let replacement = search::expand(&sess.template, &m, &text);
+    let question = format!(
+        "Replace {:?} with {:?}? [y]es [n]o [a]ll [q]uit",
+        &text[m.range()],
+        replacement
+    );
+    ed.update_selection(m.start(), m.end());
+    sess.current = Some((m.range(), replacement));
+    drop(sess);
+    drop(ws);
+    s.call_on_name(CONFIRM, |view: &mut TextView| view.set_content(question));
+}
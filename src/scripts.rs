@@ -0,0 +1,162 @@
+use crate::keymap::{config_dir, Error};
+use crate::toml;
+use crate::transform::{Registry, Shell};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// When a hook runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hook {
+    Open,
+    Save,
+    /// Once the keys have rested on a selection other than the last one
+    /// reported, or on none after one.
+    SelectionChange,
+}
+
+impl Hook {
+    /// Every hook, by the name it has in `scripts.toml`.
+    const ALL: &'static [(Hook, &'static str)] = &[
+        (Hook::Open, "on_open"),
+        (Hook::Save, "on_save"),
+        (Hook::SelectionChange, "on_selection_change"),
+    ];
+
+    /// The hook's name in `scripts.toml`.
+    pub fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|&&(hook, _)| hook == self)
+            .map_or("", |&(_, name)| name)
+    }
+
+    /// The hook called `name` in `scripts.toml`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|&&(_, hook)| hook == name)
+            .map(|&(hook, _)| hook)
+    }
+}
+
+/// The user's own transforms and hooks, all of them shell commands.
+///
+/// Rather than a scripting language of its own, the editor hands text to
+/// the shell, the same way piping the selection does: a transform gets the
+/// selection on its input and replaces it with its output, and a hook is
+/// told about the file it runs for in `$WASDEV_FILE`, and the
+/// selection-change hook about the text selected in `$WASDEV_SELECTION`.
+#[derive(Clone, Debug, Default)]
+pub struct Scripts {
+    transforms: BTreeMap<String, String>,
+    hooks: BTreeMap<Hook, String>,
+}
+
+impl Scripts {
+    /// Loads the user's scripts, if there are any.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("scripts.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the scripts in `path`; a missing file just means there are
+    /// none. Transforms are listed in the transform menu by name:
+    ///
+    /// ```toml
+    /// [transforms]
+    /// "Sort unique" = "sort -u"
+    /// "Format JSON" = "jq ."
+    ///
+    /// [hooks]
+    /// on_save = "git add \"$WASDEV_FILE\""
+    /// on_selection_change = "printf %s \"$WASDEV_SELECTION\" | xclip"
+    /// ```
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut scripts = Self::default();
+        for (section, value) in &table {
+            let toml::Value::Table(entries) = value else {
+                return Err(invalid(format!(
+                    "`{section}`: expected a table, found {value}"
+                )));
+            };
+            for (name, command) in entries {
+                let toml::Value::String(command) = command else {
+                    return Err(invalid(format!(
+                        "`{section}.{name}`: expected a string, found {command}"
+                    )));
+                };
+                match section.as_str() {
+                    "transforms" => {
+                        scripts.transforms.insert(name.clone(), command.clone());
+                    }
+                    "hooks" => {
+                        let hook = Hook::from_name(name)
+                            .ok_or_else(|| invalid(format!("unknown hook `{name}`")))?;
+                        scripts.hooks.insert(hook, command.clone());
+                    }
+                    _ => return Err(invalid(format!("unknown section `{section}`"))),
+                }
+            }
+        }
+        Ok(scripts)
+    }
+
+    /// Adds the user's transforms to `registry`, in place of any built-in
+    /// ones of the same name.
+    pub fn register_transforms(&self, registry: &mut Registry) {
+        for (name, command) in &self.transforms {
+            registry.register(Shell {
+                name: name.clone(),
+                command: command.clone(),
+            });
+        }
+    }
+
+    /// Starts the command hooked to `hook`, if any, for the file at `path`.
+    /// It runs in the background, and what it prints is dropped.
+    pub fn run_hook(&self, hook: Hook, path: &Path) {
+        self.spawn(hook, path, None);
+    }
+
+    /// Starts the command hooked to a change of selection, if any, for the
+    /// file at `path`, where `selection` is now selected.
+    pub fn run_selection_hook(&self, path: &Path, selection: &str) {
+        self.spawn(Hook::SelectionChange, path, Some(selection));
+    }
+
+    fn spawn(&self, hook: Hook, path: &Path, selection: Option<&str>) {
+        let Some(script) = self.hooks.get(&hook) else {
+            return;
+        };
+        let mut command = Command::new("sh");
+        if let Some(selection) = selection {
+            command.env("WASDEV_SELECTION", selection);
+        }
+        let child = command
+            .arg("-c")
+            .arg(script)
+            .env("WASDEV_FILE", path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        // Waited for from another thread, so it doesn't linger once done.
+        if let Ok(mut child) = child {
+            thread::spawn(move || child.wait());
+        }
+    }
+}
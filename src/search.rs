@@ -0,0 +1,932 @@
+use std::fmt;
+use std::ops::Range;
+
+/// A compiled regular expression.
+///
+/// Supported syntax: literals and escapes (`\n`, `\t`, `\.`, ...), `.`
+/// (anything but a newline), bracket classes (`[a-z_]`, `[^0-9]`), the
+/// `\d \w \s` classes and their negations, `^`/`$` (line start/end),
+/// `\b`/`\B`, `\A`/`\z` (text start/end), groups `( )` and `(?: )`,
+/// alternation `|` and the quantifiers `* + ? {n} {n,} {n,m}` with an
+/// optional trailing `?` for lazy matching. A leading `(?i)` makes the whole
+/// pattern case-insensitive.
+///
+/// Matching runs a Pike VM over the compiled program, so the time taken
+/// stays linear in the length of the text whatever the pattern looks like.
+#[derive(Clone, Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+/// A successful match, holding the byte range of every capture group.
+/// Group 0 is the whole match.
+#[derive(Clone, Debug)]
+pub struct Match {
+    groups: Vec<Option<Range<usize>>>,
+}
+
+/// The largest count a `{n,m}` repetition may have; each repeat is a copy
+/// of what's repeated in the compiled program.
+const MAX_REPEAT: u32 = 1000;
+
+/// The most instructions a compiled pattern may have, however its
+/// repetitions nest.
+const MAX_PROGRAM: usize = 100_000;
+
+/// Why a pattern failed to compile, and where.
+#[derive(Clone, Debug)]
+pub struct Error {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl Match {
+    /// Byte offset where the match starts.
+    pub fn start(&self) -> usize {
+        self.range().start
+    }
+
+    /// Byte offset just past the end of the match.
+    pub fn end(&self) -> usize {
+        self.range().end
+    }
+
+    /// Byte range of the whole match.
+    pub fn range(&self) -> Range<usize> {
+        self.groups[0].clone().unwrap()
+    }
+
+    /// Byte range of capture group `index`, if it took part in the match.
+    pub fn group(&self, index: usize) -> Option<Range<usize>> {
+        self.groups.get(index).cloned().flatten()
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`.
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let (pattern, icase) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (rest, true),
+            None => (pattern, false),
+        };
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 1,
+            icase,
+        };
+        let node = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        if node.size() > MAX_PROGRAM {
+            return Err(Error {
+                message: "pattern too large".to_string(),
+                position: 0,
+            });
+        }
+
+        let mut program = Vec::new();
+        program.push(Inst::Save(0));
+        compile(&node, &mut program);
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+        Ok(Self {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    /// Finds the leftmost match in `text` starting at or after byte `start`.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<Match> {
+        let slots = self.groups * 2;
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched: Option<Vec<Option<usize>>> = None;
+
+        let mut pos = start;
+        loop {
+            // Keep seeding new threads at each position until something matches;
+            // they have the lowest priority, so earlier starts win.
+            if matched.is_none() {
+                self.add_thread(text, pos, &mut current, 0, vec![None; slots]);
+            }
+            if current.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let ch = text[pos..].chars().next();
+            for i in 0..current.len() {
+                let (pc, caps) = current.take(i);
+                match &self.program[pc] {
+                    Inst::Match => {
+                        matched = Some(caps);
+                        // Lower-priority threads can't beat this match.
+                        break;
+                    }
+                    inst => {
+                        if let Some(ch) = ch {
+                            if inst.accepts(ch) {
+                                self.add_thread(text, pos + ch.len_utf8(), &mut next, pc + 1, caps);
+                            }
+                        }
+                    }
+                }
+            }
+
+            match ch {
+                Some(ch) => pos += ch.len_utf8(),
+                None => break,
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        matched.map(|caps| Match {
+            groups: caps
+                .chunks(2)
+                .map(|pair| match (pair[0], pair[1]) {
+                    (Some(start), Some(end)) => Some(start..end),
+                    _ => None,
+                })
+                .collect(),
+        })
+    }
+
+    /// Iterates over successive non-overlapping matches in `text` from `start`.
+    pub fn find_iter<'a>(
+        &'a self,
+        text: &'a str,
+        start: usize,
+    ) -> impl Iterator<Item = Match> + 'a {
+        let mut pos = Some(start);
+        std::iter::from_fn(move || {
+            let m = self.find_at(text, pos?)?;
+            pos = next_search_start(text, &m);
+            Some(m)
+        })
+    }
+
+    /// Follows the empty transitions out of `pc` and queues every thread that
+    /// ends up waiting on a character (or a match). The transitions are
+    /// followed depth first, on a stack of their own rather than the call
+    /// stack, so that long alternations can't overflow it.
+    fn add_thread(
+        &self,
+        text: &str,
+        pos: usize,
+        threads: &mut Threads,
+        pc: usize,
+        caps: Vec<Option<usize>>,
+    ) {
+        let mut stack = vec![(pc, caps)];
+        while let Some((pc, mut caps)) = stack.pop() {
+            if threads.contains(pc) {
+                continue;
+            }
+            threads.mark(pc);
+            match &self.program[pc] {
+                Inst::Jmp(target) => stack.push((*target, caps)),
+                Inst::Split(first, second) => {
+                    // The second goes on first, so the first is followed first.
+                    stack.push((*second, caps.clone()));
+                    stack.push((*first, caps));
+                }
+                Inst::Save(slot) => {
+                    caps[*slot] = Some(pos);
+                    stack.push((pc + 1, caps));
+                }
+                Inst::Assert(assertion) => {
+                    if assertion.holds(text, pos) {
+                        stack.push((pc + 1, caps));
+                    }
+                }
+                _ => threads.push(pc, caps),
+            }
+        }
+    }
+}
+
+/// Where to look for the match after `m`: past its end, or one character
+/// further for empty matches so the search always makes progress.
+pub fn next_search_start(text: &str, m: &Match) -> Option<usize> {
+    if m.start() < m.end() {
+        Some(m.end())
+    } else {
+        text[m.end()..]
+            .chars()
+            .next()
+            .map(|ch| m.end() + ch.len_utf8())
+    }
+}
+
+/// Expands a replacement template for match `m` against `text`.
+///
+/// `$0` is the whole match, `$1`..`$9` (or `${12}`) the capture groups, and
+/// `$$` a literal dollar sign. `\n` and `\t` insert a newline and a tab.
+pub fn expand(template: &str, m: &Match, text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '$' => match chars.peek() {
+                Some('$') => {
+                    chars.next();
+                    out.push('$');
+                }
+                Some(d) if d.is_ascii_digit() => {
+                    let index = chars.next().unwrap().to_digit(10).unwrap() as usize;
+                    if let Some(range) = m.group(index) {
+                        out.push_str(&text[range]);
+                    }
+                }
+                Some('{') => {
+                    chars.next();
+                    let digits: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    if let Some(range) = digits.parse().ok().and_then(|i| m.group(i)) {
+                        out.push_str(&text[range]);
+                    }
+                }
+                _ => out.push('$'),
+            },
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// The threads alive at one step of the Pike VM, in priority order.
+struct Threads {
+    /// Program counters already visited at this position.
+    seen: Vec<bool>,
+    list: Vec<(usize, Vec<Option<usize>>)>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Self {
+            seen: vec![false; len],
+            list: Vec::new(),
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        self.seen[pc]
+    }
+
+    fn mark(&mut self, pc: usize) {
+        self.seen[pc] = true;
+    }
+
+    fn push(&mut self, pc: usize, caps: Vec<Option<usize>>) {
+        self.list.push((pc, caps));
+    }
+
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    fn take(&mut self, i: usize) -> (usize, Vec<Option<usize>>) {
+        let (pc, caps) = &mut self.list[i];
+        (*pc, std::mem::take(caps))
+    }
+
+    fn clear(&mut self) {
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+        self.list.clear();
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(char),
+    /// Any character except a newline.
+    Any,
+    Class(Class),
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    Assert(Assertion),
+    Match,
+}
+
+impl Inst {
+    fn accepts(&self, ch: char) -> bool {
+        match self {
+            Inst::Char(c) => *c == ch,
+            Inst::Any => ch != '\n',
+            Inst::Class(class) => class.matches(ch),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Assertion {
+    LineStart,
+    LineEnd,
+    TextStart,
+    TextEnd,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+impl Assertion {
+    fn holds(self, text: &str, pos: usize) -> bool {
+        let before = text[..pos].chars().next_back();
+        let after = text[pos..].chars().next();
+        let at_boundary = before.is_some_and(is_word_char) != after.is_some_and(is_word_char);
+        match self {
+            Assertion::LineStart => before.is_none_or(|c| c == '\n'),
+            Assertion::LineEnd => after.is_none_or(|c| c == '\n'),
+            Assertion::TextStart => before.is_none(),
+            Assertion::TextEnd => after.is_none(),
+            Assertion::WordBoundary => at_boundary,
+            Assertion::NotWordBoundary => !at_boundary,
+        }
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, ch: char) -> bool {
+        match self {
+            Perl::Digit => ch.is_ascii_digit(),
+            Perl::Word => is_word_char(ch),
+            Perl::Space => ch.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Perl(Perl, bool),
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    icase: bool,
+}
+
+impl Class {
+    fn matches(&self, ch: char) -> bool {
+        let hit = |ch: char| {
+            self.items.iter().any(|item| match *item {
+                ClassItem::Range(lo, hi) => (lo..=hi).contains(&ch),
+                ClassItem::Perl(perl, negated) => perl.matches(ch) != negated,
+            })
+        };
+        let found =
+            hit(ch) || (self.icase && (ch.to_lowercase().any(hit) || ch.to_uppercase().any(hit)));
+        found != self.negated
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+impl Node {
+    /// How many instructions `compile` makes of the node, saturating.
+    fn size(&self) -> usize {
+        match self {
+            Node::Empty => 0,
+            Node::Char(_) | Node::Any | Node::Class(_) | Node::Assert(_) => 1,
+            Node::Group(inner, index) => inner.size() + if index.is_some() { 2 } else { 0 },
+            Node::Concat(items) => items.iter().map(Node::size).fold(0, usize::saturating_add),
+            Node::Alternation(branches) => branches
+                .iter()
+                .map(|branch| branch.size().saturating_add(2))
+                .fold(0, usize::saturating_add),
+            Node::Repeat { node, min, max, .. } => {
+                let copies = max.unwrap_or(*min + 1) as usize;
+                node.size().saturating_add(1).saturating_mul(copies)
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+    icase: bool,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> Error {
+        Error {
+            message: message.to_string(),
+            position: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Steps over the current character, passing `value` through.
+    fn skip<T>(&mut self, value: T) -> T {
+        self.pos += 1;
+        value
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, Error> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternation(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut items = Vec::new();
+        while let Some(ch) = self.peek() {
+            if ch == '|' || ch == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_quantifiers(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn parse_quantifiers(&mut self, mut node: Node) -> Result<Node, Error> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => self.skip((0, None)),
+                Some('+') => self.skip((1, None)),
+                Some('?') => self.skip((0, Some(1))),
+                Some('{') => match self.parse_braces()? {
+                    Some(bounds) => bounds,
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            if matches!(node, Node::Empty | Node::Assert(_)) {
+                return Err(self.error("nothing to repeat"));
+            }
+            let greedy = !self.eat('?');
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`. A brace that doesn't start a valid
+    /// counted repetition is left alone and later read as a literal.
+    fn parse_braces(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let start = self.pos;
+        let close = match self.chars[start..].iter().position(|&c| c == '}') {
+            Some(offset) => start + offset,
+            None => return Ok(None),
+        };
+        let inner: String = self.chars[start + 1..close].iter().collect();
+        let parse = |s: &str| s.trim().parse::<u32>().ok();
+        let bounds = match inner.split_once(',') {
+            None => parse(&inner).map(|n| (n, Some(n))),
+            Some((lo, "")) => parse(lo).map(|n| (n, None)),
+            Some((lo, hi)) => parse(lo).zip(parse(hi)).map(|(lo, hi)| (lo, Some(hi))),
+        };
+        match bounds {
+            Some((lo, Some(hi))) if hi < lo => Err(self.error("invalid repetition range")),
+            Some((lo, hi)) if lo.max(hi.unwrap_or(0)) > MAX_REPEAT => {
+                Err(self.error(&format!("repetition count over {MAX_REPEAT}")))
+            }
+            Some(bounds) => {
+                self.pos = close + 1;
+                Ok(Some(bounds))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        let ch = self.peek().unwrap();
+        self.pos += 1;
+        match ch {
+            '(' => {
+                let index = if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups - 1)
+                };
+                let inner = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                Ok(Node::Group(Box::new(inner), index))
+            }
+            '[' => self.parse_class(),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::LineStart)),
+            '$' => Ok(Node::Assert(Assertion::LineEnd)),
+            '*' | '+' | '?' => Err(Error {
+                message: "nothing to repeat".to_string(),
+                position: self.pos - 1,
+            }),
+            '\\' => self.parse_escape(),
+            ch => Ok(self.literal(ch)),
+        }
+    }
+
+    fn literal(&self, ch: char) -> Node {
+        if self.icase && (ch.is_lowercase() || ch.is_uppercase()) {
+            Node::Class(Class {
+                items: vec![ClassItem::Range(ch, ch)],
+                negated: false,
+                icase: true,
+            })
+        } else {
+            Node::Char(ch)
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, Error> {
+        let Some(ch) = self.peek() else {
+            return Err(self.error("trailing backslash"));
+        };
+        self.pos += 1;
+        let perl = |perl, negated| {
+            Node::Class(Class {
+                items: vec![ClassItem::Perl(perl, negated)],
+                negated: false,
+                icase: false,
+            })
+        };
+        Ok(match ch {
+            'd' => perl(Perl::Digit, false),
+            'D' => perl(Perl::Digit, true),
+            'w' => perl(Perl::Word, false),
+            'W' => perl(Perl::Word, true),
+            's' => perl(Perl::Space, false),
+            'S' => perl(Perl::Space, true),
+            'b' => Node::Assert(Assertion::WordBoundary),
+            'B' => Node::Assert(Assertion::NotWordBoundary),
+            'A' => Node::Assert(Assertion::TextStart),
+            'z' => Node::Assert(Assertion::TextEnd),
+            other => self.literal(escaped_char(other)),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, Error> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let Some(ch) = self.peek() else {
+                return Err(self.error("unclosed character class"));
+            };
+            self.pos += 1;
+            if ch == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if ch == '\\' {
+                let Some(esc) = self.peek() else {
+                    return Err(self.error("trailing backslash"));
+                };
+                self.pos += 1;
+                let perl = match esc {
+                    'd' => Some((Perl::Digit, false)),
+                    'D' => Some((Perl::Digit, true)),
+                    'w' => Some((Perl::Word, false)),
+                    'W' => Some((Perl::Word, true)),
+                    's' => Some((Perl::Space, false)),
+                    'S' => Some((Perl::Space, true)),
+                    _ => None,
+                };
+                if let Some((perl, negated)) = perl {
+                    items.push(ClassItem::Perl(perl, negated));
+                    continue;
+                }
+                escaped_char(esc)
+            } else {
+                ch
+            };
+
+            // A `-` between two characters makes a range; anywhere else it's literal.
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let mut hi = self.peek().unwrap();
+                self.pos += 1;
+                if hi == '\\' {
+                    let Some(esc) = self.peek() else {
+                        return Err(self.error("trailing backslash"));
+                    };
+                    self.pos += 1;
+                    hi = escaped_char(esc);
+                }
+                if hi < lo {
+                    return Err(self.error("invalid class range"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+        Ok(Node::Class(Class {
+            items,
+            negated,
+            icase: self.icase,
+        }))
+    }
+}
+
+/// Maps the character after a backslash to the character it stands for.
+fn escaped_char(ch: char) -> char {
+    match ch {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        other => other,
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Empty => {}
+        Node::Char(ch) => program.push(Inst::Char(*ch)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Assert(assertion) => program.push(Inst::Assert(*assertion)),
+        Node::Group(inner, index) => match index {
+            Some(index) => {
+                program.push(Inst::Save(index * 2));
+                compile(inner, program);
+                program.push(Inst::Save(index * 2 + 1));
+            }
+            None => compile(inner, program),
+        },
+        Node::Concat(items) => items.iter().for_each(|item| compile(item, program)),
+        Node::Alternation(branches) => {
+            let mut jumps = Vec::new();
+            for (i, branch) in branches.iter().enumerate() {
+                if i + 1 < branches.len() {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(branch, program);
+                    jumps.push(program.len());
+                    program.push(Inst::Jmp(0));
+                    let next = program.len();
+                    program[split] = Inst::Split(split + 1, next);
+                } else {
+                    compile(branch, program);
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jmp(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            for _ in 0..*min {
+                compile(node, program);
+            }
+            let split = |body: usize, out: usize| {
+                if *greedy {
+                    Inst::Split(body, out)
+                } else {
+                    Inst::Split(out, body)
+                }
+            };
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Jmp(0));
+                    compile(node, program);
+                    program.push(Inst::Jmp(start));
+                    let out = program.len();
+                    program[start] = split(start + 1, out);
+                }
+                Some(max) => {
+                    let mut holes = Vec::new();
+                    for _ in *min..*max {
+                        holes.push(program.len());
+                        program.push(Inst::Jmp(0));
+                        compile(node, program);
+                    }
+                    let out = program.len();
+                    for hole in holes {
+                        program[hole] = split(hole + 1, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Applies `template` to every match of `regex` in `text` at or after
+/// `start`, returning each replaced range with its replacement text.
+pub fn replacements(
+    regex: &Regex,
+    template: &str,
+    text: &str,
+    start: usize,
+) -> Vec<(Range<usize>, String)> {
+    regex
+        .find_iter(text, start)
+        .map(|m| (m.range(), expand(template, &m, text)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of the leftmost match of `pattern` in `text`.
+    fn found(pattern: &str, text: &str) -> Option<String> {
+        let regex = Regex::new(pattern).unwrap();
+        regex.find_at(text, 0).map(|m| text[m.range()].to_string())
+    }
+
+    fn replaced(pattern: &str, template: &str, text: &str) -> String {
+        let regex = Regex::new(pattern).unwrap();
+        let mut out = text.to_string();
+        for (range, new) in replacements(&regex, template, text, 0).into_iter().rev() {
+            out.replace_range(range, &new);
+        }
+        out
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(found("lo w", "hello world").as_deref(), Some("lo w"));
+        assert_eq!(found("a.c", "abc").as_deref(), Some("abc"));
+        assert_eq!(found("a.c", "a\nc"), None);
+        assert_eq!(found(r"\.", "a.b").as_deref(), Some("."));
+        assert_eq!(found("[a-c_]+", "xx_ab_d").as_deref(), Some("_ab_"));
+        assert_eq!(found("[^0-9 ]+", "12 ab3").as_deref(), Some("ab"));
+        assert_eq!(found(r"\d+", "id 042").as_deref(), Some("042"));
+        assert_eq!(found(r"\w+\s\W", "foo_1 -").as_deref(), Some("foo_1 -"));
+        assert_eq!(found("(?i)HeLLo", "say hello").as_deref(), Some("hello"));
+        assert_eq!(found("(?i)[A-C]+", "xbCa").as_deref(), Some("bCa"));
+    }
+
+    #[test]
+    fn anchors() {
+        let text = "one\ntwo";
+        assert_eq!(found("^t..$", text).as_deref(), Some("two"));
+        assert_eq!(found(r"\Atwo", text), None);
+        assert_eq!(found(r"o\z", text).as_deref(), Some("o"));
+        assert_eq!(found(r"\bcat\b", "concat cat").map(|_| ()), Some(()));
+        let regex = Regex::new(r"\bcat\b").unwrap();
+        assert_eq!(regex.find_at("concat cat", 0).unwrap().start(), 7);
+        assert_eq!(found(r"\Bcat", "concat").as_deref(), Some("cat"));
+    }
+
+    #[test]
+    fn alternation_prefers_the_first_branch() {
+        assert_eq!(found("ab|a", "abc").as_deref(), Some("ab"));
+        assert_eq!(found("a|ab", "abc").as_deref(), Some("a"));
+        assert_eq!(found("x(?:cat|dog)s", "xdogs").as_deref(), Some("xdogs"));
+        let long = (0..5000)
+            .map(|i| format!("w{i}"))
+            .collect::<Vec<_>>()
+            .join("|");
+        assert_eq!(
+            found(&format!("(?:{long})$"), "say w4999").as_deref(),
+            Some("w4999")
+        );
+    }
+
+    #[test]
+    fn greedy_and_lazy_quantifiers() {
+        let text = "<a><b>";
+        assert_eq!(found("<.*>", text).as_deref(), Some("<a><b>"));
+        assert_eq!(found("<.*?>", text).as_deref(), Some("<a>"));
+        assert_eq!(found("<.+>", text).as_deref(), Some("<a><b>"));
+        assert_eq!(found("<.+?>", text).as_deref(), Some("<a>"));
+        assert_eq!(found("ab?", "abb").as_deref(), Some("ab"));
+        assert_eq!(found("ab??", "abb").as_deref(), Some("a"));
+        assert_eq!(found("a{2}", "aaaa").as_deref(), Some("aa"));
+        assert_eq!(found("a{2,}", "aaaa").as_deref(), Some("aaaa"));
+        assert_eq!(found("a{1,3}", "aaaa").as_deref(), Some("aaa"));
+        assert_eq!(found("a{1,3}?", "aaaa").as_deref(), Some("a"));
+        assert_eq!(found("a{3}", "aa"), None);
+        // A brace that isn't a count is a literal.
+        assert_eq!(found("a{x}", "a{x}").as_deref(), Some("a{x}"));
+    }
+
+    #[test]
+    fn replacing_with_groups() {
+        assert_eq!(replaced(r"(\w+)=(\w+)", "$2=$1", "a=1, b=2"), "1=a, 2=b");
+        assert_eq!(replaced("(x)", "${1}$$0$0", "x"), "x$0x");
+        assert_eq!(replaced("(a)|(b)", "[$2]", "ab"), "[][b]");
+        assert_eq!(replaced(",", r"\n", "a,b"), "a\nb");
+    }
+
+    #[test]
+    fn empty_matches_replace_between_every_character() {
+        assert_eq!(replaced("x*", "-", "abc"), "-a-b-c-");
+        assert_eq!(replaced("b*", "-", "abc"), "-a--c-");
+        assert_eq!(replaced("^", "> ", "one\ntwo"), "> one\n> two");
+    }
+
+    #[test]
+    fn multibyte_text() {
+        let text = "naïve café ☕ ok";
+        let regex = Regex::new("caf.").unwrap();
+        let m = regex.find_at(text, 0).unwrap();
+        assert_eq!(&text[m.range()], "café");
+        assert_eq!(found("[☕é]+", text).as_deref(), Some("é"));
+        assert_eq!(found(r"\w+$", text).as_deref(), Some("ok"));
+        assert_eq!(replaced(".", "_", "é☕"), "__");
+        assert_eq!(replaced("", "|", "é☕"), "|é|☕|");
+    }
+
+    #[test]
+    fn malformed_patterns() {
+        for (pattern, message) in [
+            ("(ab", "unclosed group"),
+            ("ab)", "unmatched ')'"),
+            ("[ab", "unclosed character class"),
+            ("*a", "nothing to repeat"),
+            ("^*", "nothing to repeat"),
+            (r"ab\", "trailing backslash"),
+            ("[z-a]", "invalid class range"),
+            ("a{3,1}", "invalid repetition range"),
+            ("a{1000000000}", "repetition count over 1000"),
+            ("a{2,5000}", "repetition count over 1000"),
+            ("(?:(?:a{1000}){1000}){1000}", "pattern too large"),
+        ] {
+            let err = Regex::new(pattern).unwrap_err();
+            assert_eq!(err.message, message, "{pattern}");
+        }
+        assert_eq!(Regex::new("(ab").unwrap_err().position, 3);
+        assert!(Regex::new("a{1000}").is_ok());
+    }
+}
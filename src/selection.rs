@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+/// A selection expressed as an anchor/head pair of byte offsets.
+///
+/// The `anchor` is the end that stays put while the selection is adjusted,
+/// the `head` is the end that follows the cursor. The head may sit before the
+/// anchor, so use `start()`/`end()` when the ordered bounds are needed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    /// Creates a selection spanning from `anchor` to `head`.
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    /// The lower bound of the selection.
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    /// The upper bound of the selection (exclusive).
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    /// The selected byte range, ordered.
+    pub fn range(&self) -> Range<usize> {
+        self.start()..self.end()
+    }
+
+    /// Returns `true` if the selection covers no text.
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Returns `true` if the byte at `offset` lies inside the selection.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.range().contains(&offset)
+    }
+}
+
+/// A cursor together with the selection it drags along, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Caret {
+    pub cursor: usize,
+    pub selection: Option<Selection>,
+}
+
+impl Caret {
+    /// Moves the caret's offsets to follow an edit that replaced `removed`
+    /// bytes at `start` with `inserted` bytes. Offsets inside the replaced
+    /// range end up just after the new text.
+    pub fn adjust(&mut self, start: usize, removed: usize, inserted: usize) {
+        let shift = |offset| shift(offset, start, removed, inserted);
+        self.cursor = shift(self.cursor);
+        if let Some(sel) = self.selection.as_mut() {
+            sel.anchor = shift(sel.anchor);
+            sel.head = shift(sel.head);
+        }
+    }
+}
+
+/// Where `offset` ends up after an edit that replaced `removed` bytes at
+/// `start` with `inserted` bytes. An offset inside the replaced range ends up
+/// just after the new text.
+pub fn shift(offset: usize, start: usize, removed: usize, inserted: usize) -> usize {
+    if offset <= start {
+        offset
+    } else if offset >= start + removed {
+        offset - removed + inserted
+    } else {
+        start + inserted
+    }
+}
+
+/// Most selections kept to go back to; older ones are dropped.
+const MAX_SELECTIONS: usize = 100;
+
+/// The selections left behind, to go back through and forward again.
+///
+/// Their offsets follow edits to the text around them.
+#[derive(Clone, Debug, Default)]
+pub struct SelectionHistory {
+    past: Vec<Selection>,
+    /// The selections gone back from, the latest last.
+    future: Vec<Selection>,
+}
+
+impl SelectionHistory {
+    /// Records `selection` as one that was left. Selections that were gone
+    /// back from are forgotten.
+    pub fn push(&mut self, selection: Selection) {
+        self.future.clear();
+        if self.past.last() != Some(&selection) {
+            self.past.push(selection);
+        }
+        if self.past.len() > MAX_SELECTIONS {
+            self.past.remove(0);
+        }
+    }
+
+    /// The selection before `current`, if any. `current` is remembered, so
+    /// going forward again returns to it.
+    pub fn back(&mut self, current: Option<Selection>) -> Option<Selection> {
+        let previous = self.past.pop()?;
+        self.future.extend(current);
+        Some(previous)
+    }
+
+    /// The selection gone back from last, if any; `current` can be gone back
+    /// to again.
+    pub fn forward(&mut self, current: Option<Selection>) -> Option<Selection> {
+        let next = self.future.pop()?;
+        self.past.extend(current);
+        Some(next)
+    }
+
+    /// Moves every selection to follow an edit that replaced `removed` bytes
+    /// at `start` with `inserted` bytes.
+    pub fn adjust(&mut self, start: usize, removed: usize, inserted: usize) {
+        for selection in self.past.iter_mut().chain(self.future.iter_mut()) {
+            selection.anchor = shift(selection.anchor, start, removed, inserted);
+            selection.head = shift(selection.head, start, removed, inserted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_follows_an_edit() {
+        assert_eq!(shift(2, 5, 3, 1), 2);
+        assert_eq!(shift(6, 5, 3, 1), 6);
+        assert_eq!(shift(10, 5, 3, 1), 8);
+    }
+
+    #[test]
+    fn a_backwards_selection_orders_its_bounds() {
+        let sel = Selection::new(7, 3);
+        assert_eq!(sel.range(), 3..7);
+        assert!(sel.contains(3) && !sel.contains(7));
+    }
+}
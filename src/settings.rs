@@ -0,0 +1,430 @@
+use crate::filetype::{self, Filetype};
+use crate::keymap::{config_dir, Error};
+use crate::toml;
+use cursive::theme::{BaseColor, Color};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The user's preferences that aren't key bindings, servers or scripts.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Spaces per level when pretty-printing JSON.
+    pub json_indent: usize,
+    /// Seconds without a key press before changes are autosaved; 0 never.
+    pub autosave_idle: u64,
+    /// Edits to a buffer after which it's autosaved anyway; 0 never.
+    pub autosave_edits: u64,
+    /// Whether autosaves write the file itself, rather than a copy kept for
+    /// recovery.
+    pub autosave_to_file: bool,
+    /// The dictionary to check spelling with, rather than the first found
+    /// where Hunspell's usually are.
+    pub spell_dictionary: Option<PathBuf>,
+    /// The column reflowing wraps paragraphs at.
+    pub reflow_width: usize,
+    /// How the insert menu offers to write the date and time, as `date`
+    /// formats them.
+    pub date_formats: Vec<String>,
+    /// What the gutter numbers lines with.
+    pub line_numbers: LineNumbers,
+    /// Whether the indentation of lines has a guide drawn down each level.
+    pub indent_guides: bool,
+    /// The column, from 1, picked out to show how long lines should get.
+    pub color_column: Option<usize>,
+    /// Lines kept in view above and below the cursor's, where there are
+    /// any, as it moves.
+    pub scroll_margin: usize,
+    /// Whether long lines are wrapped to the width of their panes, rather
+    /// than scrolled sideways.
+    pub wrap: bool,
+    /// Whether tabs, trailing spaces and non-breaking spaces are drawn
+    /// with glyphs of their own.
+    pub whitespace: bool,
+    /// The background of the cursor's line, if it's to stand out.
+    pub current_line: Option<Color>,
+    /// The background of selected text, which keeps its colors; the
+    /// palette's highlight when there's none.
+    pub selection: Option<Color>,
+    /// The command that builds projects without one of their own.
+    pub build_command: Option<String>,
+    /// The command each filetype is formatted with, reading the text and
+    /// printing it formatted.
+    pub formatters: HashMap<Filetype, String>,
+    /// Whether buffers are formatted as they're saved.
+    pub format_on_save: bool,
+    /// How each filetype is indented, commented and reflowed.
+    pub filetypes: HashMap<Filetype, filetype::Options>,
+}
+
+/// The formatters used unless `settings.toml` says otherwise.
+const DEFAULT_FORMATTERS: &[(Filetype, &str)] = &[
+    (Filetype::Rust, "rustfmt --edition 2021"),
+    (Filetype::Python, "black --quiet -"),
+    (
+        Filetype::JavaScript,
+        "prettier --stdin-filepath \"$WASDEV_FILE\"",
+    ),
+];
+
+/// How lines are numbered in the gutter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    /// Each line's own number.
+    Absolute,
+    /// How many lines away from the cursor's each line is; the cursor's
+    /// own line keeps its number.
+    Relative,
+}
+
+impl LineNumbers {
+    /// The mode after this one, going round.
+    pub fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            json_indent: 2,
+            autosave_idle: 30,
+            autosave_edits: 300,
+            autosave_to_file: false,
+            spell_dictionary: None,
+            reflow_width: 80,
+            date_formats: ["%Y-%m-%d", "%Y-%m-%d %H:%M", "%H:%M:%S"]
+                .map(String::from)
+                .to_vec(),
+            line_numbers: LineNumbers::Off,
+            indent_guides: false,
+            color_column: None,
+            scroll_margin: 0,
+            wrap: false,
+            whitespace: false,
+            current_line: Some(Color::Light(BaseColor::Black)),
+            selection: None,
+            build_command: None,
+            formatters: DEFAULT_FORMATTERS
+                .iter()
+                .map(|&(filetype, command)| (filetype, command.to_string()))
+                .collect(),
+            format_on_save: false,
+            filetypes: std::iter::once(&Filetype::Plain)
+                .chain(Filetype::ALL)
+                .map(|&filetype| (filetype, filetype.options()))
+                .collect(),
+        }
+    }
+}
+
+impl Settings {
+    /// How buffers of `filetype` are indented, commented and reflowed.
+    pub fn filetype(&self, filetype: Filetype) -> &filetype::Options {
+        &self.filetypes[&filetype]
+    }
+
+    /// Loads the user's settings, or the defaults.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("settings.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the settings in `path`; a missing file, or a setting left out,
+    /// means the default:
+    ///
+    /// ```toml
+    /// [json]
+    /// indent = 4
+    ///
+    /// [autosave]
+    /// idle = 10
+    /// edits = 0
+    /// to = "file"
+    ///
+    /// [spell]
+    /// dictionary = "/usr/share/hunspell/en_GB.dic"
+    ///
+    /// [reflow]
+    /// width = 72
+    ///
+    /// [insert]
+    /// date_formats = ["%Y-%m-%d", "%A %-d %B %Y", "%Y-%m-%dT%H:%M:%S%z"]
+    ///
+    /// [view]
+    /// line_numbers = "relative"
+    /// whitespace = true
+    /// wrap = true
+    /// indent_guides = true
+    /// color_column = 100
+    /// scroll_margin = 3
+    ///
+    /// [theme]
+    /// current_line = "none"
+    /// selection = "#005f87"
+    ///
+    /// [build]
+    /// command = "make -j8"
+    ///
+    /// [format]
+    /// on_save = true
+    /// rust = "rustfmt --edition 2024"
+    /// python = ""
+    ///
+    /// [filetype.python]
+    /// tab_width = 4
+    /// expand_tab = true
+    /// comment = "#"
+    /// wrap_column = 79
+    /// ```
+    ///
+    /// A formatter is given the text on its input, and the file's name in
+    /// `$WASDEV_FILE`; an empty one turns formatting the filetype off. An
+    /// empty comment leader means the filetype has none, and `text` names
+    /// the settings for files of no language in particular.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut settings = Self::default();
+        for (section, value) in &table {
+            let toml::Value::Table(entries) = value else {
+                return Err(invalid(format!(
+                    "`{section}`: expected a table, found {value}"
+                )));
+            };
+            for (name, value) in entries {
+                match (section.as_str(), name.as_str(), value) {
+                    ("json", "indent", &toml::Value::Integer(indent @ 0..=16)) => {
+                        settings.json_indent = indent as usize;
+                    }
+                    ("json", "indent", value) => {
+                        return Err(invalid(format!(
+                            "`json.indent`: expected a number of spaces up to 16, found {value}"
+                        )));
+                    }
+                    ("autosave", "idle", &toml::Value::Integer(seconds @ 0..)) => {
+                        settings.autosave_idle = seconds as u64;
+                    }
+                    ("autosave", "edits", &toml::Value::Integer(edits @ 0..)) => {
+                        settings.autosave_edits = edits as u64;
+                    }
+                    ("autosave", "to", toml::Value::String(to)) if to == "file" => {
+                        settings.autosave_to_file = true;
+                    }
+                    ("autosave", "to", toml::Value::String(to)) if to == "recovery" => {
+                        settings.autosave_to_file = false;
+                    }
+                    ("autosave", "idle" | "edits", value) => {
+                        return Err(invalid(format!(
+                            "`autosave.{name}`: expected a count, found {value}"
+                        )));
+                    }
+                    ("autosave", "to", value) => {
+                        return Err(invalid(format!(
+                            "`autosave.to`: expected \"file\" or \"recovery\", found {value}"
+                        )));
+                    }
+                    ("spell", "dictionary", toml::Value::String(path)) => {
+                        settings.spell_dictionary = Some(PathBuf::from(path));
+                    }
+                    ("spell", "dictionary", value) => {
+                        return Err(invalid(format!(
+                            "`spell.dictionary`: expected a path, found {value}"
+                        )));
+                    }
+                    ("reflow", "width", &toml::Value::Integer(width @ 1..)) => {
+                        settings.reflow_width = width as usize;
+                    }
+                    ("reflow", "width", value) => {
+                        return Err(invalid(format!(
+                            "`reflow.width`: expected a number of columns, found {value}"
+                        )));
+                    }
+                    ("insert", "date_formats", toml::Value::Array(formats)) => {
+                        settings.date_formats = formats
+                            .iter()
+                            .map(|format| match format {
+                                toml::Value::String(format) => Ok(format.clone()),
+                                other => Err(invalid(format!(
+                                    "`insert.date_formats`: expected a format, found {other}"
+                                ))),
+                            })
+                            .collect::<Result<_, _>>()?;
+                    }
+                    ("insert", "date_formats", value) => {
+                        return Err(invalid(format!(
+                            "`insert.date_formats`: expected a list of formats, found {value}"
+                        )));
+                    }
+                    ("view", "line_numbers", toml::Value::String(mode)) if mode == "off" => {
+                        settings.line_numbers = LineNumbers::Off;
+                    }
+                    ("view", "line_numbers", toml::Value::String(mode)) if mode == "absolute" => {
+                        settings.line_numbers = LineNumbers::Absolute;
+                    }
+                    ("view", "line_numbers", toml::Value::String(mode)) if mode == "relative" => {
+                        settings.line_numbers = LineNumbers::Relative;
+                    }
+                    ("view", "line_numbers", value) => {
+                        return Err(invalid(format!(
+                            "`view.line_numbers`: expected \"off\", \"absolute\" or \"relative\", found {value}"
+                        )));
+                    }
+                    ("view", "whitespace", &toml::Value::Boolean(show)) => {
+                        settings.whitespace = show;
+                    }
+                    ("view", "wrap", &toml::Value::Boolean(wrap)) => {
+                        settings.wrap = wrap;
+                    }
+                    ("view", "indent_guides", &toml::Value::Boolean(show)) => {
+                        settings.indent_guides = show;
+                    }
+                    ("view", "color_column", &toml::Value::Integer(column @ 1..)) => {
+                        settings.color_column = Some(column as usize);
+                    }
+                    ("view", "scroll_margin", &toml::Value::Integer(lines @ 0..)) => {
+                        settings.scroll_margin = lines as usize;
+                    }
+                    ("view", "scroll_margin", value) => {
+                        return Err(invalid(format!(
+                            "`view.scroll_margin`: expected a number of lines, found {value}"
+                        )));
+                    }
+                    ("view", "color_column", value) => {
+                        return Err(invalid(format!(
+                            "`view.color_column`: expected a column from 1, found {value}"
+                        )));
+                    }
+                    ("view", "whitespace" | "wrap" | "indent_guides", value) => {
+                        return Err(invalid(format!(
+                            "`view.{name}`: expected true or false, found {value}"
+                        )));
+                    }
+                    ("theme", "current_line" | "selection", value) => {
+                        let color = match value {
+                            toml::Value::String(color) if color == "none" => None,
+                            toml::Value::String(color) => {
+                                Some(Color::parse(color).ok_or_else(|| {
+                                    invalid(format!("`theme.{name}`: unknown color {value}"))
+                                })?)
+                            }
+                            _ => {
+                                return Err(invalid(format!(
+                                    "`theme.{name}`: expected a color or \"none\", found {value}"
+                                )))
+                            }
+                        };
+                        if name == "current_line" {
+                            settings.current_line = color;
+                        } else {
+                            settings.selection = color;
+                        }
+                    }
+                    ("build", "command", toml::Value::String(command)) => {
+                        settings.build_command = Some(command.clone());
+                    }
+                    ("build", "command", value) => {
+                        return Err(invalid(format!(
+                            "`build.command`: expected a command, found {value}"
+                        )));
+                    }
+                    ("format", "on_save", &toml::Value::Boolean(on)) => {
+                        settings.format_on_save = on;
+                    }
+                    ("format", "on_save", value) => {
+                        return Err(invalid(format!(
+                            "`format.on_save`: expected true or false, found {value}"
+                        )));
+                    }
+                    ("format", _, toml::Value::String(command)) => {
+                        let filetype = Filetype::ALL
+                            .iter()
+                            .copied()
+                            .find(|filetype| filetype.name() == name)
+                            .ok_or_else(|| invalid(format!("unknown filetype `{name}`")))?;
+                        if command.trim().is_empty() {
+                            settings.formatters.remove(&filetype);
+                        } else {
+                            settings.formatters.insert(filetype, command.clone());
+                        }
+                    }
+                    ("format", _, value) => {
+                        return Err(invalid(format!(
+                            "`format.{name}`: expected a command, found {value}"
+                        )));
+                    }
+                    ("filetype", _, toml::Value::Table(entries)) => {
+                        let filetype = Filetype::named(name)
+                            .ok_or_else(|| invalid(format!("unknown filetype `{name}`")))?;
+                        let options = settings.filetypes.entry(filetype).or_default();
+                        for (key, value) in entries {
+                            match (key.as_str(), value) {
+                                ("tab_width", &toml::Value::Integer(width @ 1..=16)) => {
+                                    options.tab_width = width as usize;
+                                }
+                                ("expand_tab", &toml::Value::Boolean(expand)) => {
+                                    options.expand_tab = expand;
+                                }
+                                ("comment", toml::Value::String(leader)) => {
+                                    let leader = leader.trim();
+                                    options.comment =
+                                        (!leader.is_empty()).then(|| leader.to_string());
+                                }
+                                ("wrap_column", &toml::Value::Integer(column @ 1..)) => {
+                                    options.wrap_column = Some(column as usize);
+                                }
+                                ("tab_width", value) => {
+                                    return Err(invalid(format!(
+                                        "`filetype.{name}.tab_width`: expected a number of columns up to 16, found {value}"
+                                    )));
+                                }
+                                ("expand_tab", value) => {
+                                    return Err(invalid(format!(
+                                        "`filetype.{name}.expand_tab`: expected true or false, found {value}"
+                                    )));
+                                }
+                                ("comment", value) => {
+                                    return Err(invalid(format!(
+                                        "`filetype.{name}.comment`: expected a comment leader, found {value}"
+                                    )));
+                                }
+                                ("wrap_column", value) => {
+                                    return Err(invalid(format!(
+                                        "`filetype.{name}.wrap_column`: expected a number of columns, found {value}"
+                                    )));
+                                }
+                                _ => {
+                                    return Err(invalid(format!(
+                                        "unknown setting `filetype.{name}.{key}`"
+                                    )))
+                                }
+                            }
+                        }
+                    }
+                    ("filetype", _, value) => {
+                        return Err(invalid(format!(
+                            "`filetype.{name}`: expected a table, found {value}"
+                        )));
+                    }
+                    _ => return Err(invalid(format!("unknown setting `{section}.{name}`"))),
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// How often a filter command is checked on, to see whether it's done.
+const POLL: Duration = Duration::from_millis(10);
+
+/// Runs `command` with the shell, feeding it `input`, and waits for it,
+/// unless `cancelled` says to give up first, which kills it.
+pub fn filter(command: &str, input: &str, cancelled: &dyn Fn() -> bool) -> io::Result<Output> {
+    run(command, Some(input), None, usize::MAX, cancelled)
+}
+
+/// Runs `command` like `filter`, telling it the text is the file at `path`
+/// in `$WASDEV_FILE`, as hooks are.
+pub fn filter_file(
+    command: &str,
+    input: &str,
+    path: &Path,
+    cancelled: &dyn Fn() -> bool,
+) -> io::Result<Output> {
+    run(command, Some(input), Some(path), usize::MAX, cancelled)
+}
+
+/// Runs `command` with the shell on no input, like `filter`, killing it
+/// if it prints more than `limit` bytes.
+pub fn read(command: &str, limit: usize, cancelled: &dyn Fn() -> bool) -> io::Result<Output> {
+    run(command, None, None, limit, cancelled)
+}
+
+/// Runs `command`, feeding it `input` if there is any, and waits for it,
+/// unless `cancelled` says to give up first or it prints more than `limit`
+/// bytes, either of which kills it.
+fn run(
+    command: &str,
+    input: Option<&str>,
+    file: Option<&Path>,
+    limit: usize,
+    cancelled: &dyn Fn() -> bool,
+) -> io::Result<Output> {
+    let mut shell = Command::new("sh");
+    if let Some(file) = file {
+        shell.env("WASDEV_FILE", file);
+    }
+    let mut child = shell
+        .arg("-c")
+        .arg(command)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Written from another thread, so a command that prints as it reads
+    // can't fill up its output pipe while waiting on its input.
+    let writer = input.map(|input| {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+    // Its output is read on threads too, leaving this one free to notice
+    // being cancelled.
+    let mut stdout = Some(read_all(
+        child.stdout.take().expect("stdout is piped"),
+        limit,
+    ));
+    let stderr = read_all(child.stderr.take().expect("stderr is piped"), limit);
+    let mut printed = None;
+    let too_much = || io::Error::other(format!("printed more than {limit} bytes"));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::other("cancelled"));
+        }
+        // Reading stops at the limit, so the output ending early is the sign.
+        if let Some(reader) = stdout.take_if(|reader| reader.is_finished()) {
+            let bytes = reader.join().unwrap_or_default();
+            if bytes.len() > limit {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(too_much());
+            }
+            printed = Some(bytes);
+        }
+        thread::sleep(POLL);
+    };
+    // A command that exits without reading everything closes the pipe early,
+    // which isn't an error worth reporting.
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let stdout = match (printed, stdout) {
+        (Some(bytes), _) => bytes,
+        (None, Some(reader)) => reader.join().unwrap_or_default(),
+        (None, None) => Vec::new(),
+    };
+    if stdout.len() > limit {
+        return Err(too_much());
+    }
+    Ok(Output {
+        status,
+        stdout,
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Reads everything from `reader` on a thread of its own, up to a byte
+/// past `limit`.
+fn read_all(reader: impl Read + Send + 'static, limit: usize) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = reader
+            .take((limit as u64).saturating_add(1))
+            .read_to_end(&mut bytes);
+        bytes
+    })
+}
@@ -0,0 +1,252 @@
+use crate::filetype::Filetype;
+use crate::keymap::{config_dir, Error};
+use crate::toml;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Name of the set of snippets that apply whatever the filetype.
+const ALL: &str = "all";
+
+/// Snippets by set, each a map of triggers to bodies. A set is named after
+/// the filetype it applies to, or `all`.
+#[derive(Clone, Debug, Default)]
+pub struct Snippets {
+    sets: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Snippets {
+    /// Loads the user's snippets, if there are any.
+    pub fn load() -> Result<Self, Error> {
+        match config_dir() {
+            Some(dir) => Self::load_from(&dir.join("snippets.toml")),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the snippets in `path`; a missing file just means there are
+    /// none. Each table is a set of triggers and the text they expand to:
+    ///
+    /// ```toml
+    /// [rust]
+    /// fn = "fn ${1:name}($2) {\n    $0\n}"
+    ///
+    /// [all]
+    /// todo = "TODO($1): $0"
+    /// ```
+    ///
+    /// `$1`, `$2`, … are the places Tab moves through in order, and `$0` is
+    /// where the cursor ends up; `${1:text}` starts out holding `text`. A
+    /// `$$` stands for a literal `$`.
+    pub fn load_from(path: &Path) -> Result<Self, Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::Io(path.to_path_buf(), err)),
+        };
+        let table = toml::parse(&text).map_err(|err| Error::Parse(path.to_path_buf(), err))?;
+        let invalid = |message: String| Error::Invalid(path.to_path_buf(), message);
+
+        let mut snippets = Self::default();
+        for (name, value) in &table {
+            if name != ALL && !Filetype::ALL.iter().any(|filetype| filetype.name() == name) {
+                return Err(invalid(format!("unknown filetype `{name}`")));
+            }
+            let toml::Value::Table(set) = value else {
+                return Err(invalid(format!(
+                    "`{name}`: expected a table, found {value}"
+                )));
+            };
+            let mut bodies = BTreeMap::new();
+            for (trigger, body) in set {
+                let toml::Value::String(body) = body else {
+                    return Err(invalid(format!(
+                        "`{name}.{trigger}`: expected a string, found {body}"
+                    )));
+                };
+                bodies.insert(trigger.clone(), body.clone());
+            }
+            snippets.sets.insert(name.clone(), bodies);
+        }
+        Ok(snippets)
+    }
+
+    /// The body `trigger` expands to in a buffer of `filetype`.
+    pub fn find(&self, filetype: Filetype, trigger: &str) -> Option<&str> {
+        [filetype.name(), ALL]
+            .into_iter()
+            .find_map(|set| self.sets.get(set)?.get(trigger))
+            .map(String::as_str)
+    }
+}
+
+/// A placeholder of an expanded snippet: its number, and where its text is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stop {
+    pub number: u32,
+    pub range: Range<usize>,
+}
+
+/// Splits a snippet body into the text it expands to and its placeholders,
+/// whose ranges are byte offsets into that text.
+pub fn parse(body: &str) -> (String, Vec<Stop>) {
+    let mut text = String::new();
+    let mut stops = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '$' {
+            text.push(ch);
+            continue;
+        }
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                text.push('$');
+            }
+            Some(ch) if ch.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&digit) = chars.peek().filter(|ch| ch.is_ascii_digit()) {
+                    number.push(digit);
+                    chars.next();
+                }
+                stops.push(Stop {
+                    number: number.parse().unwrap_or(0),
+                    range: text.len()..text.len(),
+                });
+            }
+            Some('{') => {
+                chars.next();
+                let mut inner = String::new();
+                let mut closed = false;
+                for ch in chars.by_ref() {
+                    if ch == '}' {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(ch);
+                }
+                let (number, default) = inner.split_once(':').unwrap_or((&inner, ""));
+                match number.parse::<u32>() {
+                    Ok(number) if closed => {
+                        let start = text.len();
+                        text.push_str(default);
+                        stops.push(Stop {
+                            number,
+                            range: start..text.len(),
+                        });
+                    }
+                    // Anything else was never meant as a placeholder.
+                    _ => {
+                        text.push_str("${");
+                        text.push_str(&inner);
+                        if closed {
+                            text.push('}');
+                        }
+                    }
+                }
+            }
+            _ => text.push('$'),
+        }
+    }
+    (text, stops)
+}
+
+/// An expanded snippet whose placeholders are being filled in.
+///
+/// The placeholders keep up with edits to the buffer; the one being filled
+/// in grows with text typed at either end of it, the others only with text
+/// typed inside them.
+#[derive(Clone, Debug)]
+pub struct Session {
+    stops: Vec<Stop>,
+    current: Option<u32>,
+}
+
+impl Session {
+    /// Starts filling in `stops`, none of them yet. Without a `$0`, the
+    /// cursor ends up at `end`.
+    pub fn new(mut stops: Vec<Stop>, end: usize) -> Self {
+        if !stops.iter().any(|stop| stop.number == 0) {
+            stops.push(Stop {
+                number: 0,
+                range: end..end,
+            });
+        }
+        Self {
+            stops,
+            current: None,
+        }
+    }
+
+    /// The placeholder numbers in the order Tab visits them, `0` last.
+    fn order(&self) -> Vec<u32> {
+        let mut numbers: Vec<u32> = self.stops.iter().map(|stop| stop.number).collect();
+        numbers.sort_by_key(|&number| (number == 0, number));
+        numbers.dedup();
+        numbers
+    }
+
+    fn select(&mut self, number: u32) -> Vec<Range<usize>> {
+        self.current = Some(number);
+        self.stops
+            .iter()
+            .filter(|stop| stop.number == number)
+            .map(|stop| stop.range.clone())
+            .collect()
+    }
+
+    /// Moves to the next placeholder, returning where each of its copies is.
+    pub fn next(&mut self) -> Option<Vec<Range<usize>>> {
+        let order = self.order();
+        let index = match self.current {
+            Some(current) => order.iter().position(|&n| n == current)? + 1,
+            None => 0,
+        };
+        let number = *order.get(index)?;
+        Some(self.select(number))
+    }
+
+    /// Moves back to the previous placeholder, returning where each of its
+    /// copies is.
+    pub fn previous(&mut self) -> Option<Vec<Range<usize>>> {
+        let order = self.order();
+        let index = order.iter().position(|&n| Some(n) == self.current)?;
+        let number = *order.get(index.checked_sub(1)?)?;
+        Some(self.select(number))
+    }
+
+    /// Whether the cursor reached its final place, `$0`.
+    pub fn is_finished(&self) -> bool {
+        self.current == Some(0)
+    }
+
+    /// Moves the placeholders to follow an edit that replaced `removed`
+    /// bytes at `start` with `inserted` bytes.
+    pub fn adjust(&mut self, start: usize, removed: usize, inserted: usize) {
+        let end = start + removed;
+        for stop in &mut self.stops {
+            let range = &mut stop.range;
+            let inside = if Some(stop.number) == self.current {
+                start >= range.start && end <= range.end
+            } else {
+                start > range.start && end < range.end
+            };
+            if inside {
+                range.end = range.end - removed + inserted;
+            } else if end <= range.start {
+                range.start = range.start - removed + inserted;
+                range.end = range.end - removed + inserted;
+            } else if start < range.end {
+                // The edit overlaps the placeholder's edge: keep what's left.
+                range.start = range.start.min(start);
+                range.end = if end >= range.end {
+                    start + inserted
+                } else {
+                    range.end - removed + inserted
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,556 @@
+use crate::highlight::Token;
+use crate::jobs;
+use crate::keymap::config_dir;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Dictionaries looked for when the settings name none: Hunspell's for
+/// English in the usual places, then the plain word list many systems have.
+const DEFAULT_PATHS: &[&str] = &[
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/usr/share/myspell/dicts/en_US.dic",
+    "/usr/share/dict/words",
+];
+
+/// Most suggestions offered for a misspelled word.
+const MAX_SUGGESTIONS: usize = 10;
+
+/// The words spelled right: those of a dictionary, with the forms its
+/// affix rules make of them, and the user's own.
+///
+/// A Hunspell dictionary is a `.dic` file of stems, each with flags naming
+/// the prefixes and suffixes it takes, and an `.aff` file beside it with
+/// the rules those flags stand for. Any other file is read as a plain list
+/// of words, one a line.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    /// Words added by the user, kept in `dictionary.txt` in the config
+    /// directory.
+    personal: HashSet<String>,
+    /// Every character the words are made of, for building suggestions.
+    alphabet: Vec<char>,
+}
+
+impl Dictionary {
+    /// Loads the dictionary at `path`, or the first of `DEFAULT_PATHS` that
+    /// exists, along with the user's own words.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => DEFAULT_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.is_file())
+                .ok_or("No dictionary found; set `spell.dictionary` in settings.toml")?,
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read {}: {err}", path.display()))?;
+        let aff = path.with_extension("aff");
+        let words = if path.extension().is_some_and(|ext| ext == "dic") && aff.is_file() {
+            let rules = fs::read_to_string(&aff)
+                .map_err(|err| format!("Could not read {}: {err}", aff.display()))?;
+            expand(&text, &Affixes::parse(&rules))
+        } else {
+            text.lines()
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let personal = personal_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::trim).map(str::to_string).collect())
+            .unwrap_or_default();
+        let mut alphabet: Vec<char> = words.iter().flat_map(|word| word.chars()).collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        Ok(Self {
+            words,
+            personal,
+            alphabet,
+        })
+    }
+
+    /// Whether `word` is spelled right. A word is also right capitalized, at
+    /// the start of a sentence, and in capitals, but not a name in lower case.
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.replace('’', "'");
+        if self.known(&word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        let mut chars = word.chars();
+        let capitalized = chars.next().is_some_and(char::is_uppercase)
+            && chars.as_str().chars().all(|ch| !ch.is_uppercase());
+        let capitals = word.chars().all(|ch| !ch.is_lowercase());
+        if capitals {
+            self.known(&lower) || self.known(&title(&lower))
+        } else {
+            capitalized && self.known(&lower)
+        }
+    }
+
+    /// Whether `word` is in the dictionary or the user's words, as it is.
+    fn known(&self, word: &str) -> bool {
+        self.words.contains(word) || self.personal.contains(word)
+    }
+
+    /// Words `word` may have been meant to be: those one typo away, then
+    /// those two away, capitalized like it.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let near = edits(&lower, &self.alphabet);
+        let mut found: Vec<String> = near
+            .iter()
+            .filter(|edit| self.check(edit))
+            .cloned()
+            .collect();
+        found.sort();
+        if found.len() < MAX_SUGGESTIONS {
+            let mut far: Vec<String> = near
+                .iter()
+                .flat_map(|edit| edits(edit, &self.alphabet))
+                .filter(|edit| *edit != lower && self.check(edit))
+                .collect();
+            far.sort();
+            far.dedup();
+            found.extend(far.into_iter().filter(|edit| !near.contains(edit)));
+        }
+        found.truncate(MAX_SUGGESTIONS);
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        found
+            .into_iter()
+            .map(|found| if capitalized { title(&found) } else { found })
+            .collect()
+    }
+
+    /// Adds `word` to the user's words, for good.
+    pub fn add(&mut self, word: &str) -> io::Result<()> {
+        let path = personal_path()
+            .ok_or_else(|| io::Error::other("there is no config directory to keep it in"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{word}")?;
+        self.personal.insert(word.to_string());
+        Ok(())
+    }
+}
+
+/// Where the words the user added are kept.
+fn personal_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("dictionary.txt"))
+}
+
+/// `word` with its first letter in capitals.
+fn title(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or(String::new(), |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// Every string one deletion, swap of neighbours, replacement or insertion
+/// of a character of `alphabet` away from `word`.
+fn edits(word: &str, alphabet: &[char]) -> HashSet<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let join = |parts: &[&[char]]| parts.concat().into_iter().collect::<String>();
+    let mut edits = HashSet::new();
+    for i in 0..=chars.len() {
+        let (before, after) = chars.split_at(i);
+        for &ch in alphabet {
+            edits.insert(join(&[before, &[ch], after]));
+        }
+        let Some((&first, rest)) = after.split_first() else {
+            continue;
+        };
+        edits.insert(join(&[before, rest]));
+        for &ch in alphabet {
+            edits.insert(join(&[before, &[ch], rest]));
+        }
+        if let Some((&second, rest)) = rest.split_first() {
+            edits.insert(join(&[before, &[second, first], rest]));
+        }
+    }
+    edits.remove(word);
+    edits
+}
+
+/// The words of `line` that aren't spelled right, as byte ranges. In code,
+/// given the highlighter's `spans` for the line, only comments and strings
+/// are checked; in plain text, `spans` is empty and every word is.
+pub fn misspelled(
+    dictionary: &Dictionary,
+    line: &str,
+    spans: &[(Range<usize>, Token)],
+    code: bool,
+) -> Vec<Range<usize>> {
+    line.unicode_word_indices()
+        .map(|(start, word)| start..start + word.len())
+        .filter(|range| {
+            !code
+                || spans.iter().any(|(span, token)| {
+                    matches!(token, Token::Comment | Token::String)
+                        && span.start <= range.start
+                        && range.end <= span.end
+                })
+        })
+        .filter(|range| is_checked(&line[range.clone()]) && !dictionary.check(&line[range.clone()]))
+        .collect()
+}
+
+/// Whether `word` looks like one to check the spelling of, rather than a
+/// letter, a number or a name from code, such as `snake_case` or `camelCase`.
+fn is_checked(word: &str) -> bool {
+    let mut chars = word.chars();
+    let rest_lower = chars.next().is_some() && chars.all(|ch| !ch.is_uppercase());
+    let capitals = word.chars().all(|ch| !ch.is_lowercase());
+    word.chars().count() > 1
+        && word
+            .chars()
+            .all(|ch| ch.is_alphabetic() || ch == '\'' || ch == '’')
+        && (rest_lower || capitals)
+}
+
+/// The word of `line` at byte `index`, or just before it, as a byte range.
+pub fn word_at(line: &str, index: usize) -> Option<Range<usize>> {
+    line.unicode_word_indices()
+        .map(|(start, word)| start..start + word.len())
+        .find(|range| range.start <= index && index <= range.end)
+}
+
+/// Every word of the `dic` file, with the forms `affixes` make of it.
+fn expand(dic: &str, affixes: &Affixes) -> HashSet<String> {
+    let mut words = HashSet::new();
+    // The first line is only how many stems follow.
+    for entry in dic.lines().skip(1) {
+        // Anything after white space is morphological data.
+        let entry = entry.split(['\t', ' ']).next().unwrap_or_default();
+        let (stem, flags) = entry.split_once('/').unwrap_or((entry, ""));
+        if stem.is_empty() {
+            continue;
+        }
+        let flags = affixes.flags(flags);
+        let mut suffixed = Vec::new();
+        for flag in &flags {
+            for rule in affixes.suffixes.get(flag).into_iter().flatten() {
+                if let Some(word) = rule.apply(stem) {
+                    suffixed.push((word, rule.cross));
+                }
+            }
+        }
+        for flag in &flags {
+            for rule in affixes.prefixes.get(flag).into_iter().flatten() {
+                words.extend(rule.apply(stem));
+                // Both affixes have to allow being combined.
+                if rule.cross {
+                    let crossing = suffixed.iter().filter(|(_, cross)| *cross);
+                    words.extend(crossing.filter_map(|(word, _)| rule.apply(word)));
+                }
+            }
+        }
+        words.insert(stem.to_string());
+        words.extend(suffixed.into_iter().map(|(word, _)| word));
+    }
+    words
+}
+
+/// How the flags of a `.dic` file are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum FlagStyle {
+    /// One character each, the default.
+    #[default]
+    Char,
+    /// Two characters each.
+    Long,
+    /// Numbers, separated by commas.
+    Number,
+}
+
+/// The prefix and suffix rules of an `.aff` file, by flag.
+#[derive(Debug, Default)]
+struct Affixes {
+    style: FlagStyle,
+    prefixes: HashMap<String, Vec<Affix>>,
+    suffixes: HashMap<String, Vec<Affix>>,
+}
+
+impl Affixes {
+    /// Reads the rules of an `.aff` file, ignoring what it says about
+    /// anything else.
+    fn parse(text: &str) -> Self {
+        let mut affixes = Self::default();
+        // Whether each flag's rules may combine with the other kind's.
+        let mut cross = HashMap::new();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => affixes.style = FlagStyle::Long,
+                ["FLAG", "num", ..] => affixes.style = FlagStyle::Number,
+                [kind @ ("PFX" | "SFX"), flag, combines @ ("Y" | "N"), count]
+                    if count.parse::<usize>().is_ok() =>
+                {
+                    cross.insert((*kind, *flag), *combines == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                    let prefix = *kind == "PFX";
+                    let empty = |field: &str| {
+                        if field == "0" {
+                            String::new()
+                        } else {
+                            field.to_string()
+                        }
+                    };
+                    // What follows a slash are flags for further affixes.
+                    let add = add.split('/').next().unwrap_or_default();
+                    let rule = Affix {
+                        prefix,
+                        strip: empty(strip),
+                        add: empty(add),
+                        condition: Condition::parse(condition),
+                        cross: cross.get(&(*kind, *flag)).copied().unwrap_or(false),
+                    };
+                    let rules = if prefix {
+                        &mut affixes.prefixes
+                    } else {
+                        &mut affixes.suffixes
+                    };
+                    rules.entry(flag.to_string()).or_default().push(rule);
+                }
+                _ => {}
+            }
+        }
+        affixes
+    }
+
+    /// The flags written in `flags`, in the style the `.aff` file set.
+    fn flags(&self, flags: &str) -> Vec<String> {
+        match self.style {
+            FlagStyle::Char => flags.chars().map(String::from).collect(),
+            FlagStyle::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+            }
+            FlagStyle::Number => flags.split(',').map(str::to_string).collect(),
+        }
+    }
+}
+
+/// A rule making a word of a stem: strip some characters from the start or
+/// end, then add others, if the stem meets the condition there.
+#[derive(Debug)]
+struct Affix {
+    prefix: bool,
+    strip: String,
+    add: String,
+    condition: Condition,
+    /// Whether it combines with affixes of the other kind.
+    cross: bool,
+}
+
+impl Affix {
+    /// The word the rule makes of `stem`, if it applies to it.
+    fn apply(&self, stem: &str) -> Option<String> {
+        if self.prefix {
+            let rest = stem.strip_prefix(self.strip.as_str())?;
+            self.condition
+                .matches_start(stem)
+                .then(|| format!("{}{rest}", self.add))
+        } else {
+            let rest = stem.strip_suffix(self.strip.as_str())?;
+            self.condition
+                .matches_end(stem)
+                .then(|| format!("{rest}{}", self.add))
+        }
+    }
+}
+
+/// What an affix needs the stem to start or end with: a run of characters,
+/// each one alone, any of a set, anything but a set, or anything at all.
+#[derive(Debug)]
+struct Condition(Vec<Class>);
+
+#[derive(Debug)]
+enum Class {
+    Any,
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl Condition {
+    fn parse(text: &str) -> Self {
+        let mut classes = Vec::new();
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            classes.push(match ch {
+                '.' => Class::Any,
+                '[' => {
+                    let set: String = chars.by_ref().take_while(|&ch| ch != ']').collect();
+                    match set.strip_prefix('^') {
+                        Some(set) => Class::NoneOf(set.chars().collect()),
+                        None => Class::OneOf(set.chars().collect()),
+                    }
+                }
+                ch => Class::OneOf(vec![ch]),
+            });
+        }
+        Self(classes)
+    }
+
+    /// Whether `stem` starts the way the condition says.
+    fn matches_start(&self, stem: &str) -> bool {
+        let mut chars = stem.chars();
+        self.0
+            .iter()
+            .all(|class| chars.next().is_some_and(|ch| class.matches(ch)))
+    }
+
+    /// Whether `stem` ends the way the condition says.
+    fn matches_end(&self, stem: &str) -> bool {
+        let mut chars = stem.chars().rev();
+        self.0
+            .iter()
+            .rev()
+            .all(|class| chars.next().is_some_and(|ch| class.matches(ch)))
+    }
+}
+
+impl Class {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            Class::Any => true,
+            Class::OneOf(set) => set.contains(&ch),
+            Class::NoneOf(set) => !set.contains(&ch),
+        }
+    }
+}
+
+/// What picking an entry of the suggestions does.
+#[derive(Clone)]
+enum Choice {
+    Replace(String),
+    Add,
+}
+
+/// Starts or stops underlining the words of the focused buffer spelled
+/// wrong, loading the dictionary in the background the first time.
+pub fn toggle(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let index = workspace.lock().unwrap().current();
+    let toggled = workspace.clone();
+    with_dictionary(s, workspace, move |_| {
+        if let Some(ed) = toggled.lock().unwrap().editors_mut().get_mut(index) {
+            ed.toggle_spell_check();
+        }
+    });
+}
+
+/// Lists the words the one at the cursor may have been meant to be, to
+/// replace it with, and offers to add it to the user's words instead.
+pub fn suggest(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let suggested = workspace.clone();
+    with_dictionary(s, workspace, move |s| show_suggestions(s, &suggested));
+}
+
+/// Runs `then` once the dictionary is loaded, loading it first in the
+/// background if it isn't yet.
+fn with_dictionary(
+    s: &mut Cursive,
+    workspace: &Arc<Mutex<Workspace>>,
+    then: impl FnOnce(&mut Cursive) + Send + 'static,
+) {
+    let ws = workspace.lock().unwrap();
+    if ws.dictionary().is_some() {
+        drop(ws);
+        then(s);
+        return;
+    }
+    let path = ws.settings().spell_dictionary.clone();
+    drop(ws);
+    let loaded = workspace.clone();
+    jobs::spawn(
+        s,
+        workspace,
+        "Loading the dictionary".to_string(),
+        move |_| Dictionary::load(path.as_deref()),
+        move |s, result| match result {
+            Ok(dictionary) => {
+                loaded.lock().unwrap().set_dictionary(dictionary);
+                then(s);
+            }
+            Err(message) => s.add_layer(Dialog::info(message)),
+        },
+    );
+}
+
+/// Shows the suggestions for the word at the cursor.
+fn show_suggestions(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let Some(dictionary) = ws.dictionary() else {
+        return;
+    };
+    let ed = ws.editor();
+    let buffer = ed.buffer();
+    let line = buffer.line_of(ed.cursor());
+    let start = buffer.line_start(line);
+    let text = buffer.line(line);
+    let Some(range) = word_at(&text, ed.cursor() - start) else {
+        drop(ws);
+        s.add_layer(Dialog::info("There is no word at the cursor"));
+        return;
+    };
+    let word = text[range.clone()].to_string();
+    if dictionary.check(&word) {
+        drop(ws);
+        s.add_layer(Dialog::info(format!("{word} is spelled right")));
+        return;
+    }
+    let suggestions = dictionary.suggest(&word);
+    let index = ws.current();
+    drop(ws);
+    let range = start + range.start..start + range.end;
+
+    let mut list: SelectView<Choice> = SelectView::new();
+    for suggestion in suggestions {
+        list.add_item(suggestion.clone(), Choice::Replace(suggestion));
+    }
+    list.add_item(format!("Add {word} to the dictionary"), Choice::Add);
+    let workspace = workspace.clone();
+    let title = word.clone();
+    list.set_on_submit(move |s, choice: &Choice| {
+        s.pop_layer();
+        let mut ws = workspace.lock().unwrap();
+        match choice {
+            Choice::Replace(with) => {
+                if let Some(ed) = ws.editors_mut().get_mut(index) {
+                    ed.replace_ranges(vec![(range.clone(), with.clone())]);
+                }
+            }
+            Choice::Add => {
+                let added = ws.dictionary_mut().map(|dictionary| dictionary.add(&word));
+                drop(ws);
+                if let Some(Err(err)) = added {
+                    s.add_layer(Dialog::info(format!("Could not add {word}: {err}")));
+                }
+            }
+        }
+    });
+    let list = Dialog::around(list.scrollable())
+        .title(title)
+        .max_height(16);
+    s.add_layer(OnEventView::new(list).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
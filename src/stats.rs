@@ -0,0 +1,88 @@
+use crate::workspace::Workspace;
+use cursive::views::Dialog;
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What each of the counts is called, in the order they're shown.
+const LABELS: [&str; 5] = [
+    "Characters",
+    "Without spaces",
+    "Words",
+    "Lines",
+    "Paragraphs",
+];
+
+/// How much there is of a text, counted the ways writers usually want.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub characters: usize,
+    /// Characters that aren't white space.
+    pub visible: usize,
+    pub words: usize,
+    pub lines: usize,
+    /// Runs of lines that aren't blank.
+    pub paragraphs: usize,
+}
+
+impl Counts {
+    /// The counts, in the order of `LABELS`.
+    fn values(self) -> [usize; 5] {
+        [
+            self.characters,
+            self.visible,
+            self.words,
+            self.lines,
+            self.paragraphs,
+        ]
+    }
+}
+
+/// Counts the characters, words, lines and paragraphs of `text`.
+pub fn count(text: &str) -> Counts {
+    let mut paragraphs = 0;
+    let mut blank = true;
+    for line in text.split('\n') {
+        let was_blank = blank;
+        blank = line.trim().is_empty();
+        if was_blank && !blank {
+            paragraphs += 1;
+        }
+    }
+    Counts {
+        characters: text.chars().count(),
+        visible: text.chars().filter(|ch| !ch.is_whitespace()).count(),
+        words: words(text),
+        // A line break at the very end doesn't start another line.
+        lines: text.lines().count(),
+        paragraphs,
+    }
+}
+
+/// The number of words in `text`, by Unicode's rules for where words break.
+pub fn words(text: &str) -> usize {
+    text.unicode_words().count()
+}
+
+/// Shows the counts for the focused buffer, and for the selection beside
+/// them if there is one.
+pub fn show(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let document = count(&ed.buffer().to_string());
+    let selection = ed.selected_text().map(|text| count(&text));
+    let title = ed.name();
+    drop(ws);
+
+    let mut table = format!("{:<16}{:>10}", "", "Document");
+    if selection.is_some() {
+        table += &format!("{:>11}", "Selection");
+    }
+    for (i, label) in LABELS.iter().enumerate() {
+        table += &format!("\n{label:<16}{:>10}", document.values()[i]);
+        if let Some(selection) = selection {
+            table += &format!("{:>11}", selection.values()[i]);
+        }
+    }
+    s.add_layer(Dialog::info(table).title(title));
+}
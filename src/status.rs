@@ -0,0 +1,144 @@
+use crate::stats;
+use crate::width::display_width;
+use crate::workspace::Workspace;
+use cursive::theme::PaletteStyle;
+use cursive::{Printer, Vec2, View};
+use std::sync::{Arc, Mutex};
+
+/// One-line bar along the bottom of the screen describing the focused buffer.
+///
+/// - On the left: its name and its modified, read-only, large-file and hex
+///   flags, then any notice, or else any diagnostic on the cursor's line.
+/// - On the right: the selection's length and words, the number of cursors,
+///   any background job, the grammar's mode while it's on, `REC` while a
+///   macro is being recorded, `no abbrev` while abbreviations are off, the
+///   count while one is being typed and what the plugins have to say; then
+///   the cursor's line:column and how far down the pane is scrolled (or its
+///   offset in a hex dump), the filetype, its line endings and encoding, and
+///   the active keymap.
+pub struct StatusBar {
+    workspace: Arc<Mutex<Workspace>>,
+}
+
+impl StatusBar {
+    /// Creates a status bar over the given workspace.
+    pub fn new(workspace: Arc<Mutex<Workspace>>) -> Self {
+        Self { workspace }
+    }
+}
+
+impl View for StatusBar {
+    fn draw(&self, printer: &Printer) {
+        let ws = self.workspace.lock().unwrap();
+        let ed = ws.editor();
+
+        let flag = if ed.is_modified() { " [+]" } else { "" };
+        let read_only = if ed.is_read_only() { " [RO]" } else { "" };
+        let large = if ed.is_large() { " [large]" } else { "" };
+        let hex = if ed.hex().is_some() { " [hex]" } else { "" };
+        let left = format!(" {}{}{}{}{}", ed.name(), flag, read_only, large, hex);
+
+        let line = ed.buffer().line_of(ed.cursor()) + 1;
+        let column = ed.cursor_column() + 1;
+        let mut right = match ed.selected_text() {
+            Some(text) => format!(
+                "sel {}, {} words  ",
+                text.chars().count(),
+                stats::words(&text)
+            ),
+            None => String::new(),
+        };
+        let cursors = ed.carets().len();
+        if cursors > 1 {
+            right += &format!("{cursors} cursors  ");
+        }
+        if let Some(job) = ws.jobs().describe() {
+            right += &format!("{job}  ");
+        }
+        if let Some(mode) = ws.grammar().mode().name() {
+            right += &format!("{mode}  ");
+        }
+        if ws.recorder().is_recording() {
+            right += "REC  ";
+        }
+        if !ed.is_abbreviating() {
+            right += "no abbrev  ";
+        }
+        match ws.count() {
+            Some(0) => right += "count  ",
+            Some(count) => right += &format!("count {count}  "),
+            None => {}
+        }
+        for segment in ws.plugins().status(ed.path(), line, column) {
+            right += &format!("{segment}  ");
+        }
+        let position = match ed.hex() {
+            Some(hex) => format!("{:#010x}", hex.cursor()),
+            None => format!("{line}:{column} {}", scrolled(&ws)),
+        };
+        right += &format!(
+            "{position}  {}  {}  {}  {} ",
+            ed.highlighter().filetype().name(),
+            ed.line_ending().name(),
+            ed.charset().name(),
+            ws.keymap().name()
+        );
+
+        // A notice is more pressing than what the language server says.
+        let message = ws.notice().or_else(|| {
+            let path = ed.path()?;
+            ws.lsp()
+                .diagnostics(path)
+                .iter()
+                .find(|diagnostic| diagnostic.line + 1 == line)
+                .map(|diagnostic| diagnostic.message.lines().next().unwrap_or(""))
+        });
+
+        printer.with_style(PaletteStyle::Highlight, |printer| {
+            printer.print_hline((0, 0), printer.size.x, " ");
+            printer.print((0, 0), &left);
+            let width = display_width(&right);
+            if width + display_width(&left) < printer.size.x {
+                printer.print((printer.size.x - width, 0), &right);
+            }
+            // The message gets whatever room is left in between.
+            if let Some(message) = message {
+                let start = display_width(&left) + 2;
+                let room = printer.size.x.saturating_sub(start + width + 2);
+                printer.print((start, 0), &truncate(message, room));
+            }
+        });
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        Vec2::new(constraint.x, 1)
+    }
+}
+
+/// How far down the focused pane is scrolled: `Top`, `Bot`, a percentage
+/// in between, or `All` when every line fits.
+fn scrolled(ws: &Workspace) -> String {
+    let pane = ws.panes()[ws.focus()];
+    let lines = ws.editor().buffer().line_count();
+    let first = pane.scroll.1;
+    if lines <= pane.height {
+        "All".to_string()
+    } else if first == 0 {
+        "Top".to_string()
+    } else if first + pane.height >= lines {
+        "Bot".to_string()
+    } else {
+        format!("{}%", first * 100 / (lines - pane.height))
+    }
+}
+
+/// The longest start of `text` that fits in `width` columns.
+fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|&ch| {
+            used += display_width(ch.encode_utf8(&mut [0; 4]));
+            used <= width
+        })
+        .collect()
+}
@@ -0,0 +1,432 @@
+use crate::buffer::Buffer;
+use crate::motion;
+use std::ops::Range;
+
+/// How a language's code is put together, as far as growing a selection
+/// through it goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Structure {
+    /// Statements end in `;` and bodies are in braces, as in Rust, C and
+    /// JavaScript.
+    Braces,
+    /// Bodies are indented under a `def` or `class` line, as in Python.
+    Indented,
+    /// Nothing to go on, as in prose.
+    Plain,
+}
+
+/// Words that start a block which isn't a function, though parentheses may
+/// come before its brace.
+const CONTROL: &[&str] = &[
+    "if", "else", "for", "while", "loop", "match", "switch", "catch", "do", "try",
+];
+
+/// The expression `range` lies in: what's between the commas, semicolons,
+/// assignments and braces around it that aren't nested in brackets, or
+/// the line breaks in an indented language, less the blanks and comments
+/// at either end. None if `range` spans one of them, or the language has
+/// no structure to go by.
+pub fn expression_around(
+    buffer: &Buffer,
+    range: Range<usize>,
+    structure: Structure,
+) -> Option<Range<usize>> {
+    if structure == Structure::Plain {
+        return None;
+    }
+    let region = region_around(buffer, range.clone());
+    let text = buffer.slice(region.clone());
+    let from = range.start - region.start;
+    let to = range.end - region.start;
+    let stops = expression_stops(&text, structure);
+    if stops.iter().any(|stop| stop.start < to && stop.end > from) {
+        return None;
+    }
+    let start = stops
+        .iter()
+        .rev()
+        .find(|stop| stop.end <= from)
+        .map_or(0, |stop| stop.end);
+    let end = stops
+        .iter()
+        .find(|stop| stop.start >= to)
+        .map_or(text.len(), |stop| stop.start);
+    let piece = trim(&text, start..end, structure);
+    Some(region.start + piece.start..region.start + piece.end)
+}
+
+/// The statement `range` lies in, in a language whose statements end in
+/// `;`: from the end of the one before, through its `;`, or through the
+/// brace closing its body, as in an `if` or a loop.
+pub fn statement_around(
+    buffer: &Buffer,
+    range: Range<usize>,
+    structure: Structure,
+) -> Option<Range<usize>> {
+    if structure != Structure::Braces {
+        return None;
+    }
+    let region = region_around(buffer, range.clone());
+    let text = buffer.slice(region.clone());
+    let piece = statement_in(&text, range.start - region.start..range.end - region.start);
+    Some(region.start + piece.start..region.start + piece.end)
+}
+
+/// The innermost function around `range`, signature and all: in a language
+/// with braces, a body whose header has parameters in parentheses and
+/// doesn't start with a word such as `if`; in an indented one, a `def` or
+/// `class` line and the lines indented under it.
+pub fn function_around(
+    buffer: &Buffer,
+    range: Range<usize>,
+    structure: Structure,
+) -> Option<Range<usize>> {
+    match structure {
+        Structure::Braces => braced_function_around(buffer, range),
+        Structure::Indented => indented_function_around(buffer, range),
+        Structure::Plain => None,
+    }
+}
+
+fn braced_function_around(buffer: &Buffer, range: Range<usize>) -> Option<Range<usize>> {
+    let mut inner = range;
+    loop {
+        let (_, pair) = motion::brackets_around(buffer, inner)?;
+        inner = pair.clone();
+        if buffer.char_at(pair.start) != Some('{') {
+            continue;
+        }
+        let region = region_around(buffer, pair.clone());
+        let text = buffer.slice(region.clone());
+        let open = pair.start - region.start;
+        let statement = statement_in(&text, open..open);
+        let header = &text[statement.start..open];
+        let first = header
+            .split(|ch: char| !motion::is_word(ch))
+            .next()
+            .unwrap_or("");
+        if header.contains('(') && !CONTROL.contains(&first) {
+            return Some(region.start + statement.start..pair.end);
+        }
+    }
+}
+
+fn indented_function_around(buffer: &Buffer, range: Range<usize>) -> Option<Range<usize>> {
+    let is_blank = |line| buffer.line(line).trim().is_empty();
+    let indent = |line| {
+        let text = buffer.line(line);
+        text.len() - text.trim_start().len()
+    };
+    let lines = motion::lines_of(buffer, range);
+    let mut depth = lines
+        .clone()
+        .filter(|&line| !is_blank(line))
+        .map(indent)
+        .min()
+        .unwrap_or(0)
+        + 1;
+    // Headers further up, each less indented than the last, until one
+    // opens a block reaching past the range.
+    for header in (0..=lines.start).rev() {
+        if is_blank(header) || indent(header) >= depth {
+            continue;
+        }
+        depth = indent(header);
+        let text = buffer.line(header);
+        let text = text.trim_start();
+        let defines = ["def ", "async def ", "class "]
+            .iter()
+            .any(|keyword| text.starts_with(keyword));
+        if !defines {
+            continue;
+        }
+        let mut end = header + 1;
+        for line in header + 1..buffer.line_count() {
+            if is_blank(line) {
+                continue;
+            }
+            if indent(line) <= depth {
+                break;
+            }
+            end = line + 1;
+        }
+        if end >= lines.end {
+            let start = buffer.line_start(header) + depth;
+            return Some(start..buffer.line_end(end - 1));
+        }
+    }
+    None
+}
+
+/// The text inside the innermost brackets around `range`, or the whole
+/// buffer if there are none.
+fn region_around(buffer: &Buffer, range: Range<usize>) -> Range<usize> {
+    motion::brackets_around(buffer, range).map_or(0..buffer.len(), |(inside, _)| inside)
+}
+
+/// The statement of `text` that `range` lies in, as `statement_around`
+/// has it.
+fn statement_in(text: &str, range: Range<usize>) -> Range<usize> {
+    let (top, _) = top_level(text, Structure::Braces);
+    let ends: Vec<usize> = top
+        .iter()
+        .filter(|&&(i, ch)| ch == ';' || (ch == '}' && ends_statement(&text[i + 1..])))
+        .map(|&(i, _)| i + 1)
+        .collect();
+    let start = ends
+        .iter()
+        .rev()
+        .find(|&&end| end <= range.start)
+        .copied()
+        .unwrap_or(0);
+    let end = ends
+        .iter()
+        .find(|&&end| end > range.start.max(start) && end >= range.end)
+        .copied()
+        .unwrap_or(text.len());
+    trim(text, start..end, Structure::Braces)
+}
+
+/// Whether a brace followed by `rest` closes a statement, rather than a
+/// value the statement goes on using, or an `if` with an `else` to come.
+fn ends_statement(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    !(rest.starts_with([';', ',', '.', ')', '?']) || rest.starts_with("else"))
+}
+
+/// Where the separators between expressions in `text` are.
+fn expression_stops(text: &str, structure: Structure) -> Vec<Range<usize>> {
+    let byte = |i: usize| text.as_bytes().get(i).copied();
+    let (top, comments) = top_level(text, structure);
+    // A comment separates what's before it from what's after.
+    let mut stops = comments;
+    for (i, ch) in top {
+        match ch {
+            ',' | ';' | '{' | '}' => stops.push(i..i + 1),
+            '\n' if structure == Structure::Indented => stops.push(i..i + 1),
+            '=' => {
+                let before = i.checked_sub(1).and_then(byte);
+                let after = byte(i + 1);
+                // Comparisons are part of an expression.
+                if matches!(before, Some(b'=' | b'!' | b'<' | b'>')) || after == Some(b'=') {
+                    continue;
+                }
+                // So is the operator of an assignment such as `+=`.
+                let start = match before {
+                    Some(b'+' | b'-' | b'*' | b'/' | b'%' | b'&' | b'|' | b'^') => i - 1,
+                    _ => i,
+                };
+                let end = if after == Some(b'>') { i + 2 } else { i + 1 };
+                stops.push(start..end);
+            }
+            _ => {}
+        }
+    }
+    stops.sort_by_key(|stop| stop.start);
+    stops
+}
+
+/// The characters of `text` not nested in brackets, with their offsets,
+/// leaving out strings and comments, and where the comments among them
+/// are. Brackets opening and closing a nested part are included.
+fn top_level(text: &str, structure: Structure) -> (Vec<(usize, char)>, Vec<Range<usize>>) {
+    let mut top = Vec::new();
+    let mut comments = Vec::new();
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        match ch {
+            // Rust has `'` in lifetimes too, so only `"` is sure to quote.
+            '"' | '\'' if ch == '"' || structure == Structure::Indented => {
+                while let Some((_, inside)) = chars.next() {
+                    match inside {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if inside == ch => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if structure == Structure::Braces && next == Some('*') => {
+                chars.next();
+                let mut star = false;
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, ch)| {
+                        let closes = star && ch == '/';
+                        star = ch == '*';
+                        closes
+                    })
+                    .map_or(text.len(), |(end, _)| end + 1);
+                if depth == 0 {
+                    comments.push(i..end);
+                }
+            }
+            '/' | '#'
+                if (structure == Structure::Braces && ch == '/' && next == Some('/'))
+                    || (structure == Structure::Indented && ch == '#') =>
+            {
+                // The line break ending the comment still counts.
+                let end = text[i..].find('\n').map_or(text.len(), |end| i + end);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+                if depth == 0 {
+                    comments.push(i..end);
+                }
+            }
+            '(' | '[' | '{' => {
+                if depth == 0 {
+                    top.push((i, ch));
+                }
+                depth += 1;
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    top.push((i, ch));
+                }
+            }
+            _ if depth == 0 => top.push((i, ch)),
+            _ => {}
+        }
+    }
+    (top, comments)
+}
+
+/// `range` of `text`, less the blanks at either end and the line comments
+/// at the start.
+fn trim(text: &str, range: Range<usize>, structure: Structure) -> Range<usize> {
+    let comment = match structure {
+        Structure::Braces => "//",
+        Structure::Indented => "#",
+        Structure::Plain => "\n",
+    };
+    let mut start = range.start;
+    loop {
+        let rest = &text[start..range.end];
+        let trimmed = rest.trim_start();
+        start += rest.len() - trimmed.len();
+        if !trimmed.starts_with(comment) {
+            break;
+        }
+        start = match trimmed.find('\n') {
+            Some(newline) => start + newline + 1,
+            None => range.end,
+        };
+    }
+    start..start + text[start..range.end].trim_end().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = "\
+/// Adds.
+fn add(a: i32, b: i32) -> i32 {
+    let sum = twice(a + b, \"{;}\"); // odd; really
+    if sum > 0 {
+        sum += 1;
+    } else {
+        sum
+    }
+}
+";
+
+    /// The text of what `find` gives for the range of the first `at` in
+    /// `text`.
+    fn around(
+        find: fn(&Buffer, Range<usize>, Structure) -> Option<Range<usize>>,
+        text: &str,
+        at: &str,
+        structure: Structure,
+    ) -> Option<String> {
+        let buffer = Buffer::from(text);
+        let start = text.find(at).unwrap();
+        find(&buffer, start..start + at.len(), structure).map(|range| buffer.slice(range))
+    }
+
+    #[test]
+    fn expressions() {
+        let expression = |at| around(expression_around, RUST, at, Structure::Braces);
+        assert_eq!(expression("+ b").unwrap(), "a + b");
+        assert_eq!(expression("twice").unwrap(), "twice(a + b, \"{;}\")");
+        assert_eq!(expression("let").unwrap(), "let sum");
+        assert_eq!(expression("> 0").unwrap(), "if sum > 0");
+        assert_eq!(expression(" 1").unwrap(), "1");
+        assert_eq!(expression("+= 1"), None);
+        assert_eq!(expression("b, \""), None);
+        assert_eq!(
+            around(expression_around, RUST, "add", Structure::Plain),
+            None
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let statement = |at| around(statement_around, RUST, at, Structure::Braces).unwrap();
+        assert_eq!(statement("twice"), "let sum = twice(a + b, \"{;}\");");
+        assert_eq!(
+            statement("> 0"),
+            "if sum > 0 {\n        sum += 1;\n    } else {\n        sum\n    }"
+        );
+        assert_eq!(statement("+= 1"), "sum += 1;");
+        assert_eq!(statement("sum\n"), "sum");
+    }
+
+    #[test]
+    fn braced_functions() {
+        let function = around(function_around, RUST, "+= 1", Structure::Braces).unwrap();
+        assert_eq!(function, RUST["/// Adds.\n".len()..].trim_end());
+        let closure = "let f = |x| { x + 1 };\nstruct S { a: u8 }\n";
+        assert_eq!(
+            around(function_around, closure, "x + 1", Structure::Braces),
+            None
+        );
+        assert_eq!(
+            around(function_around, closure, "a:", Structure::Braces),
+            None
+        );
+        let js = "x = 1;\nfunction f(a) {\n  if (a) { return g(a); }\n}\n";
+        assert_eq!(
+            around(function_around, js, "g(a)", Structure::Braces).unwrap(),
+            "function f(a) {\n  if (a) { return g(a); }\n}"
+        );
+    }
+
+    #[test]
+    fn indented_functions() {
+        let python = "\
+class A:
+    def f(self):
+        x = 1
+
+        return x
+
+    def g(self):
+        pass
+";
+        let function = |at| around(function_around, python, at, Structure::Indented).unwrap();
+        assert_eq!(
+            function("return"),
+            "def f(self):\n        x = 1\n\n        return x"
+        );
+        assert_eq!(function("def f"), function("return"));
+        assert_eq!(function("A:"), python.trim_end());
+        assert_eq!(function("pass"), "def g(self):\n        pass");
+        assert_eq!(
+            around(expression_around, python, "1", Structure::Indented).unwrap(),
+            "1"
+        );
+        let call = "print(f'{x}', y)  # (it's fine\n";
+        assert_eq!(
+            around(expression_around, call, "y", Structure::Indented).unwrap(),
+            "y"
+        );
+        assert_eq!(
+            around(expression_around, call, "print", Structure::Indented).unwrap(),
+            "print(f'{x}', y)"
+        );
+    }
+}
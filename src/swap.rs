@@ -0,0 +1,141 @@
+use crate::history;
+use crate::workspace::Workspace;
+use cursive::views::Dialog;
+use cursive::Cursive;
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// One change read back from a swap file: `range` was replaced by the text.
+pub type Change = (Range<usize>, String);
+
+/// Where the swap file for the file at `path` lives.
+fn swap_path(path: &Path) -> Option<PathBuf> {
+    history::cache_file(path, "swap", "jsonl")
+}
+
+/// The swap file of a buffer with unsaved changes: a first line naming the
+/// process editing it and the text it started from, then one line per
+/// change, only ever appended to, so a crash loses at most the last one.
+#[derive(Clone, Debug)]
+pub struct Journal {
+    file: PathBuf,
+}
+
+impl Journal {
+    /// Starts the swap file for the file at `path`, whose buffer holds
+    /// `content` before any of the changes.
+    pub fn start(path: &Path, content: &str) -> io::Result<Self> {
+        let file = swap_path(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let header = json!({
+            "path": path.to_string_lossy(),
+            "pid": std::process::id(),
+            "content": history::content_hash(content),
+        });
+        fs::write(&file, format!("{header}\n"))?;
+        Ok(Self { file })
+    }
+
+    /// Appends the replacement of `range` by `text`.
+    pub fn append(&self, range: Range<usize>, text: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().append(true).open(&self.file)?;
+        writeln!(file, "{}", json!([range.start, range.end, text]))
+    }
+
+    /// Removes the swap file, once its changes are saved or thrown away.
+    pub fn remove(self) {
+        let _ = fs::remove_file(self.file);
+    }
+}
+
+/// The changes left for the file at `path` by a session that ended without
+/// saving them, if its buffer started out as `content`, as it does now.
+///
+/// Swap files of a session still running, this one included, are left
+/// alone. Telling whether it is takes `/proc`; without one every swap file
+/// counts as left behind.
+pub fn stale(path: &Path, content: &str) -> Option<Vec<Change>> {
+    let text = fs::read_to_string(swap_path(path)?).ok()?;
+    let mut lines = text.lines();
+    let header: Value = serde_json::from_str(lines.next()?).ok()?;
+    let pid = header["pid"].as_u64()?;
+    if pid == u64::from(std::process::id()) || Path::new("/proc").join(pid.to_string()).exists() {
+        return None;
+    }
+    if header["content"].as_str() != Some(&history::content_hash(content)) {
+        return None;
+    }
+    // A crash while writing a change leaves it cut short; the ones before
+    // it are still good.
+    let changes: Vec<Change> = lines
+        .map_while(|line| {
+            let change: Value = serde_json::from_str(line).ok()?;
+            let start = change[0].as_u64()? as usize;
+            let end = change[1].as_u64()? as usize;
+            Some((start..end, change[2].as_str()?.to_string()))
+        })
+        .collect();
+    (!changes.is_empty()).then_some(changes)
+}
+
+/// Removes the swap file for the file at `path`, without recovering it.
+fn discard(path: &Path) {
+    if let Some(file) = swap_path(path) {
+        let _ = fs::remove_file(file);
+    }
+}
+
+/// Offers to recover the changes left in swap files for any open buffer.
+pub fn offer(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let stale: Vec<(PathBuf, String, Vec<Change>)> = ws
+        .editors()
+        .iter()
+        .filter(|ed| !ed.is_modified() && !ed.is_large())
+        .filter_map(|ed| {
+            let path = ed.path()?;
+            let changes = stale(path, &ed.buffer().to_string())?;
+            Some((path.to_path_buf(), ed.name(), changes))
+        })
+        .collect();
+    drop(ws);
+    for (path, name, changes) in stale {
+        let message = format!(
+            "{name} has changes that weren't saved when the editor last stopped. Recover them?"
+        );
+        let recover = workspace.clone();
+        let path_for_recover = path.clone();
+        s.add_layer(
+            Dialog::text(message)
+                .title("Recover")
+                .button("Recover", move |s| {
+                    s.pop_layer();
+                    let mut ws = recover.lock().unwrap();
+                    let Some(ed) = ws
+                        .editors_mut()
+                        .iter_mut()
+                        .find(|ed| ed.path() == Some(path_for_recover.as_path()))
+                    else {
+                        return;
+                    };
+                    if !ed.recover(&changes) {
+                        drop(ws);
+                        s.add_layer(Dialog::info(
+                            "Some changes didn't fit the file and were left out",
+                        ));
+                    }
+                })
+                .button("Discard", move |s| {
+                    s.pop_layer();
+                    discard(&path);
+                }),
+        );
+    }
+}
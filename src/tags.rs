@@ -0,0 +1,315 @@
+use crate::swap;
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// The tags file, as `ctags` writes it, in the project's directory.
+const TAGS_FILE: &str = "tags";
+
+/// Most places the tag stack goes back through; the oldest are forgotten.
+const MAX_STACK: usize = 100;
+
+/// Characters of a definition's line shown when picking between several.
+const PREVIEW: usize = 80;
+
+/// Where a tags file says a name is defined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Tag {
+    /// The file, relative to the tags file's directory unless it's absolute.
+    path: PathBuf,
+    address: Address,
+    /// What kind of thing it is, such as `f` or `function`, if it says.
+    kind: Option<String>,
+}
+
+/// How a tag finds its line in the file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Address {
+    /// The line, counted from 0.
+    Line(usize),
+    /// Text in the line, at its `start` or `end` if it's anchored there,
+    /// the whole line if both.
+    Pattern {
+        text: String,
+        start: bool,
+        end: bool,
+    },
+}
+
+/// Looks up the word under the cursor, or the selected text, in the
+/// project's tags file and opens the file where it's defined, with the
+/// cursor on its name. Where it was is pushed on the tag stack, for `pop`
+/// to go back to. A name defined in several places asks which.
+pub fn goto(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let name = ed
+        .selected_text()
+        .filter(|text| !text.is_empty())
+        .or_else(|| ed.word_at_cursor().map(|word| ed.buffer().slice(word)));
+    drop(ws);
+    let Some(name) = name else {
+        s.add_layer(Dialog::info("No word under the cursor to look up"));
+        return;
+    };
+    let tags = match fs::read_to_string(TAGS_FILE) {
+        Ok(text) => lookup(&text, &name),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            s.add_layer(Dialog::info(format!(
+                "No {TAGS_FILE} file here; make one with ctags -R"
+            )));
+            return;
+        }
+        Err(err) => {
+            s.add_layer(Dialog::info(format!("Could not read {TAGS_FILE}: {err}")));
+            return;
+        }
+    };
+    match tags.len() {
+        0 => s.add_layer(Dialog::info(format!("{name} isn't in {TAGS_FILE}"))),
+        1 => jump(s, workspace, &tags[0]),
+        _ => pick(s, workspace, &name, tags),
+    }
+}
+
+/// Goes back to where the cursor was before the last jump to a definition.
+pub fn pop(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let Some((path, offset)) = ws.tag_stack_mut().pop() else {
+        ws.notify("The tag stack is empty");
+        return;
+    };
+    match ws.open(&path) {
+        Ok(()) => ws.editor_mut().jump_to(offset),
+        Err(err) => {
+            drop(ws);
+            s.add_layer(Dialog::info(format!(
+                "Could not open {}: {err}",
+                path.display()
+            )));
+        }
+    }
+}
+
+/// Asks which of the places `name` is defined to go to.
+fn pick(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, name: &str, tags: Vec<Tag>) {
+    let mut list: SelectView<usize> = SelectView::new();
+    for (index, tag) in tags.iter().enumerate() {
+        let place = match &tag.address {
+            Address::Line(line) => format!("line {}", line + 1),
+            Address::Pattern { text, .. } => text.trim().chars().take(PREVIEW).collect(),
+        };
+        let kind = tag.kind.as_deref().map(|kind| format!(" ({kind})"));
+        let label = format!(
+            "{}{}: {place}",
+            tag.path.display(),
+            kind.unwrap_or_default()
+        );
+        list.add_item(label, index);
+    }
+    let workspace = workspace.clone();
+    list.set_on_submit(move |s, &index| {
+        s.pop_layer();
+        jump(s, &workspace, &tags[index]);
+    });
+    let list = Dialog::around(list.scrollable())
+        .title(format!("Definitions of {name}"))
+        .max_height(20);
+    s.add_layer(OnEventView::new(list).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// Opens the file `tag` is in with the cursor on its definition, pushing
+/// where the cursor was on the tag stack.
+fn jump(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, tag: &Tag) {
+    let mut ws = workspace.lock().unwrap();
+    let from = ws
+        .editor()
+        .path()
+        .map(|from| (from.to_path_buf(), ws.editor().cursor()));
+    if let Err(err) = ws.open(&tag.path) {
+        drop(ws);
+        s.add_layer(Dialog::info(format!(
+            "Could not open {}: {err}",
+            tag.path.display()
+        )));
+        return;
+    }
+    let text = ws.editor().buffer().to_string();
+    let Some((line, column)) = find(&text, &tag.address) else {
+        ws.notify(format!(
+            "The definition isn't in {} any more; run ctags again",
+            tag.path.display()
+        ));
+        return;
+    };
+    if let Some(from) = from {
+        let stack = ws.tag_stack_mut();
+        if stack.len() == MAX_STACK {
+            stack.remove(0);
+        }
+        stack.push(from);
+    }
+    ws.editor_mut().goto_line(line, column);
+    drop(ws);
+    swap::offer(s, workspace);
+}
+
+/// The places a tags file says `name` is defined, in the order it lists
+/// them. Its lines are `name<TAB>file<TAB>address`, followed by `;"` and
+/// fields such as the kind in the extended format; an address is a line
+/// number or a pattern between slashes or question marks, the way `ex`
+/// searches.
+fn lookup(text: &str, name: &str) -> Vec<Tag> {
+    text.lines()
+        .filter(|line| !line.starts_with("!_TAG_"))
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            if fields.next()? != name {
+                return None;
+            }
+            let path = PathBuf::from(fields.next()?);
+            let (address, rest) = parse_address(fields.next()?)?;
+            let kind = rest
+                .strip_prefix(";\"")
+                .into_iter()
+                .flat_map(|fields| fields.split('\t'))
+                .filter(|field| !field.is_empty())
+                .find_map(|field| match field.split_once(':') {
+                    Some(("kind", kind)) => Some(kind),
+                    Some(_) => None,
+                    None => Some(field),
+                })
+                .map(String::from);
+            Some(Tag {
+                path,
+                address,
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Reads the address a tag line's third field starts with, returning what
+/// follows it.
+fn parse_address(field: &str) -> Option<(Address, &str)> {
+    let Some(delimiter) = field.chars().next().filter(|&c| c == '/' || c == '?') else {
+        let end = field
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(field.len());
+        let line = field[..end].parse::<usize>().ok().filter(|&n| n > 0)?;
+        return Some((Address::Line(line - 1), &field[end..]));
+    };
+    let mut text = String::new();
+    let mut chars = field[1..].char_indices();
+    let after = loop {
+        match chars.next()? {
+            (_, '\\') => match chars.next()? {
+                (_, c) if c == delimiter || c == '\\' => text.push(c),
+                (_, c) => {
+                    text.push('\\');
+                    text.push(c);
+                }
+            },
+            (i, c) if c == delimiter => break 1 + i + c.len_utf8(),
+            (_, c) => text.push(c),
+        }
+    };
+    let (text, start) = match text.strip_prefix('^') {
+        Some(text) => (text, true),
+        None => (text.as_str(), false),
+    };
+    let (text, end) = match text.strip_suffix('$') {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let address = Address::Pattern {
+        text: text.to_string(),
+        start,
+        end,
+    };
+    Some((address, &field[after..]))
+}
+
+/// The line and column, both from 0, `address` points to in `text`: the
+/// line it matches, at the first character that isn't space, since tags
+/// don't say which column the name is in.
+fn find(text: &str, address: &Address) -> Option<(usize, usize)> {
+    let (line, content) = match address {
+        Address::Line(line) => (*line, text.lines().nth(*line)?),
+        Address::Pattern {
+            text: pattern,
+            start,
+            end,
+        } => text.lines().enumerate().find(|(_, line)| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            match (start, end) {
+                (true, true) => line == pattern,
+                (true, false) => line.starts_with(pattern.as_str()),
+                (false, true) => line.ends_with(pattern.as_str()),
+                (false, false) => line.contains(pattern.as_str()),
+            }
+        })?,
+    };
+    let column = content.chars().take_while(|c| c.is_whitespace()).count();
+    Some((line, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAGS: &str = "\
+!_TAG_FILE_FORMAT\t2\t/extended format/
+Buffer\tsrc/buffer.rs\t/^pub struct Buffer {$/;\"\ts
+main\tsrc/main.rs\t/^fn main() {$/;\"\tkind:function\tline:2101
+main\texamples/demo.c\t12;\"\tf
+path\tsrc/a.rs\t/^    let path = \\/tmp\\/x;$/
+";
+
+    #[test]
+    fn lookup_names() {
+        let mains = lookup(TAGS, "main");
+        assert_eq!(mains.len(), 2);
+        assert_eq!(mains[0].kind.as_deref(), Some("function"));
+        assert_eq!(
+            mains[1],
+            Tag {
+                path: PathBuf::from("examples/demo.c"),
+                address: Address::Line(11),
+                kind: Some("f".to_string()),
+            }
+        );
+        assert_eq!(
+            lookup(TAGS, "path")[0].address,
+            Address::Pattern {
+                text: "    let path = /tmp/x;".to_string(),
+                start: true,
+                end: true,
+            }
+        );
+        assert!(lookup(TAGS, "Buf").is_empty());
+        assert!(lookup(TAGS, "!_TAG_FILE_FORMAT").is_empty());
+    }
+
+    #[test]
+    fn find_definitions() {
+        let text = "use x;\n\n    pub struct Buffer {\npub struct Buffer {\n";
+        let tag = &lookup(TAGS, "Buffer")[0];
+        assert_eq!(find(text, &tag.address), Some((3, 0)));
+        let loose = Address::Pattern {
+            text: "pub struct".to_string(),
+            start: false,
+            end: false,
+        };
+        assert_eq!(find(text, &loose), Some((2, 4)));
+        assert_eq!(find(text, &Address::Line(9)), None);
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A table of keys to values, kept sorted by key.
+pub type Table = BTreeMap<String, Value>;
+
+/// A value from a TOML document.
+///
+/// Only the subset of TOML used by the configuration files is understood:
+/// basic and literal strings, integers, floats, booleans, arrays, inline
+/// tables, `[table]` headers and dotted keys. Multi-line strings, dates and
+/// arrays of tables are rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+/// Why a document couldn't be parsed, and on which line (1-based).
+#[derive(Clone, Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{s:?}"),
+            Value::Integer(n) => write!(f, "{n}"),
+            Value::Float(x) => write!(f, "{x:?}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Table(table) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in table.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {key:?} = {value}")?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// Parses a whole document into its root table.
+pub fn parse(text: &str) -> Result<Table, Error> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+    };
+    let mut root = Table::new();
+    // Path of the table named by the last `[header]`.
+    let mut current: Vec<String> = Vec::new();
+
+    loop {
+        parser.skip_blank_lines();
+        match parser.chars.peek() {
+            None => return Ok(root),
+            Some('[') => {
+                parser.chars.next();
+                if parser.chars.peek() == Some(&'[') {
+                    return Err(parser.error("arrays of tables are not supported"));
+                }
+                let path = parser.key_path()?;
+                parser.expect(']')?;
+                parser.end_of_line()?;
+                table_at(&mut root, &path, parser.line)?;
+                current = path;
+            }
+            Some(_) => {
+                let path = parser.key_path()?;
+                parser.expect('=')?;
+                let value = parser.value()?;
+                parser.end_of_line()?;
+                let line = parser.line;
+                let (last, parents) = path.split_last().expect("key paths are never empty");
+                let mut full = current.clone();
+                full.extend_from_slice(parents);
+                let table = table_at(&mut root, &full, line)?;
+                if table.insert(last.clone(), value).is_some() {
+                    return Err(Error {
+                        line,
+                        message: format!("duplicate key `{last}`"),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The table at `path` below `root`, created if missing.
+fn table_at<'a>(root: &'a mut Table, path: &[String], line: usize) -> Result<&'a mut Table, Error> {
+    let mut table = root;
+    for key in path {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        table = match entry {
+            Value::Table(inner) => inner,
+            _ => {
+                return Err(Error {
+                    line,
+                    message: format!("`{key}` is not a table"),
+                })
+            }
+        };
+    }
+    Ok(table)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// Skips spaces and tabs.
+    fn skip_spaces(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\t')) {
+            self.chars.next();
+        }
+    }
+
+    /// Skips a `#` comment up to (not including) the end of the line.
+    fn skip_comment(&mut self) {
+        if self.chars.peek() == Some(&'#') {
+            while !matches!(self.chars.peek(), None | Some('\n')) {
+                self.chars.next();
+            }
+        }
+    }
+
+    /// Skips whitespace, comments and newlines.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.chars.peek() {
+                Some('\n') => {
+                    self.chars.next();
+                    self.line += 1;
+                }
+                Some('\r') => {
+                    self.chars.next();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Requires the rest of the line to be blank or a comment.
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.chars.peek().copied() {
+            None | Some('\n' | '\r') => Ok(()),
+            Some(ch) => Err(self.error(format!("unexpected `{ch}` after value"))),
+        }
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<(), Error> {
+        self.skip_spaces();
+        match self.chars.next() {
+            Some(ch) if ch == wanted => Ok(()),
+            Some(ch) => Err(self.error(format!("expected `{wanted}`, found `{ch}`"))),
+            None => Err(self.error(format!("expected `{wanted}`, found end of file"))),
+        }
+    }
+
+    /// A key, possibly dotted: `a`, `"quoted key"`, `a.b.c`.
+    fn key_path(&mut self) -> Result<Vec<String>, Error> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.chars.peek() {
+                Some('"') => {
+                    self.chars.next();
+                    self.basic_string()?
+                }
+                Some('\'') => {
+                    self.chars.next();
+                    self.literal_string()?
+                }
+                _ => {
+                    let mut key = String::new();
+                    while let Some(&ch) = self.chars.peek() {
+                        if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                            key.push(ch);
+                            self.chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if key.is_empty() {
+                        return Err(self.error("expected a key"));
+                    }
+                    key
+                }
+            };
+            path.push(key);
+            self.skip_spaces();
+            if self.chars.peek() != Some(&'.') {
+                return Ok(path);
+            }
+            self.chars.next();
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_spaces();
+        match self.chars.peek() {
+            Some('"') => {
+                self.chars.next();
+                if self.chars.peek() == Some(&'"') {
+                    self.chars.next();
+                    if self.chars.peek() == Some(&'"') {
+                        return Err(self.error("multi-line strings are not supported"));
+                    }
+                    return Ok(Value::String(String::new()));
+                }
+                Ok(Value::String(self.basic_string()?))
+            }
+            Some('\'') => {
+                self.chars.next();
+                Ok(Value::String(self.literal_string()?))
+            }
+            Some('[') => {
+                self.chars.next();
+                self.array()
+            }
+            Some('{') => {
+                self.chars.next();
+                self.inline_table()
+            }
+            Some(_) => self.scalar(),
+            None => Err(self.error("expected a value, found end of file")),
+        }
+    }
+
+    /// The rest of a `"..."` string, after the opening quote.
+    fn basic_string(&mut self) -> Result<String, Error> {
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => {
+                    let escaped = match self.chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some(kind @ ('u' | 'U')) => {
+                            let digits = if kind == 'u' { 4 } else { 8 };
+                            let hex: String =
+                                (0..digits).filter_map(|_| self.chars.next()).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| {
+                                    self.error(format!("invalid escape `\\{kind}{hex}`"))
+                                })?
+                        }
+                        Some(ch) => return Err(self.error(format!("invalid escape `\\{ch}`"))),
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    out.push(escaped);
+                }
+                Some(ch) => out.push(ch),
+            }
+        }
+    }
+
+    /// The rest of a `'...'` string, after the opening quote.
+    fn literal_string(&mut self) -> Result<String, Error> {
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(out),
+                Some(ch) => out.push(ch),
+            }
+        }
+    }
+
+    /// The rest of an array, after the `[`. Arrays may span lines.
+    fn array(&mut self) -> Result<Value, Error> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.chars.peek() == Some(&']') {
+                self.chars.next();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                Some(ch) => return Err(self.error(format!("expected `,` or `]`, found `{ch}`"))),
+                None => return Err(self.error("unterminated array")),
+            }
+        }
+    }
+
+    /// The rest of an inline table, after the `{`.
+    fn inline_table(&mut self) -> Result<Value, Error> {
+        let mut table = Table::new();
+        self.skip_spaces();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Value::Table(table));
+        }
+        loop {
+            let path = self.key_path()?;
+            self.expect('=')?;
+            let value = self.value()?;
+            let (last, parents) = path.split_last().expect("key paths are never empty");
+            let line = self.line;
+            let inner = table_at(&mut table, parents, line)?;
+            if inner.insert(last.clone(), value).is_some() {
+                return Err(self.error(format!("duplicate key `{last}`")));
+            }
+            self.skip_spaces();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Table(table)),
+                Some(ch) => return Err(self.error(format!("expected `,` or `}}`, found `{ch}`"))),
+                None => return Err(self.error("unterminated inline table")),
+            }
+        }
+    }
+
+    /// A bare word: a boolean, an integer or a float.
+    fn scalar(&mut self) -> Result<Value, Error> {
+        let mut word = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '+' | '-' | '_' | '.') {
+                word.push(ch);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        match word.as_str() {
+            "true" => return Ok(Value::Boolean(true)),
+            "false" => return Ok(Value::Boolean(false)),
+            "" => {
+                let found = self
+                    .chars
+                    .peek()
+                    .map_or("end of line".to_string(), |ch| format!("`{ch}`"));
+                return Err(self.error(format!("expected a value, found {found}")));
+            }
+            _ => {}
+        }
+
+        let digits = word.replace('_', "");
+        let (negative, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        let radix = match unsigned.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        let integer = match radix {
+            Some(radix) => i64::from_str_radix(&unsigned[2..], radix).ok(),
+            None => unsigned.parse::<i64>().ok(),
+        };
+        if let Some(n) = integer {
+            return Ok(Value::Integer(if negative { -n } else { n }));
+        }
+        if radix.is_none() {
+            if let Ok(x) = digits.parse::<f64>() {
+                return Ok(Value::Float(x));
+            }
+        }
+        Err(self.error(format!("invalid value `{word}`")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tables_and_dotted_keys() {
+        let table = parse(
+            "top = 1 # a comment\n\n[keys]\nsave = 'ctrl-s'\nview.wrap = true\nlist = [1.5, \"a\\tb\"]\n",
+        )
+        .unwrap();
+        assert_eq!(table["top"], Value::Integer(1));
+        let Value::Table(keys) = &table["keys"] else {
+            panic!("no [keys] table");
+        };
+        assert_eq!(keys["save"], Value::String("ctrl-s".to_string()));
+        assert_eq!(
+            keys["view"],
+            Value::Table(Table::from([("wrap".to_string(), Value::Boolean(true))]))
+        );
+        assert_eq!(
+            keys["list"],
+            Value::Array(vec![Value::Float(1.5), Value::String("a\tb".to_string())])
+        );
+    }
+
+    #[test]
+    fn inline_tables_print_back() {
+        let table = parse("point = { x = 1, y = -2 }").unwrap();
+        assert_eq!(table["point"].to_string(), "{ \"x\" = 1, \"y\" = -2 }");
+    }
+
+    #[test]
+    fn errors_say_which_line() {
+        let error = parse("a = 1\nb = \"open\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(parse("a = 1\na = 2").is_err());
+        assert!(parse("[[tables]]").is_err());
+    }
+}
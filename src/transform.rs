@@ -0,0 +1,544 @@
+use crate::{encoding, json, shell};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// A way of rewriting text, listed in the transform menu.
+///
+/// A transform gets the selected text (or the whole buffer) and gives back
+/// what to replace it with, or why it couldn't; then nothing is replaced.
+pub trait Transform: Send + Sync {
+    /// The name shown in the menu.
+    fn name(&self) -> &str;
+
+    /// Rewrites `text`.
+    fn apply(&self, text: &str) -> Result<String, Failure>;
+
+    /// The submenu the transform is listed in, if not the menu itself.
+    fn menu(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Why a transform couldn't rewrite some text.
+#[derive(Debug)]
+pub struct Failure {
+    pub message: String,
+    /// The byte offset in the text of what the transform choked on, if it
+    /// can tell.
+    pub at: Option<usize>,
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self { message, at: None }
+    }
+}
+
+/// A transform built into the editor, which can't fail.
+#[derive(Clone, Copy)]
+struct Builtin {
+    name: &'static str,
+    apply: fn(&str) -> String,
+}
+
+impl Transform for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        Ok((self.apply)(text))
+    }
+}
+
+/// A built-in encoding or decoding, listed in the "Encode/Decode" submenu.
+/// Decoding fails on text that isn't encoded that way.
+#[derive(Clone, Copy)]
+struct Codec {
+    name: &'static str,
+    apply: fn(&str) -> Result<String, String>,
+}
+
+impl Transform for Codec {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        (self.apply)(text).map_err(Failure::from)
+    }
+
+    fn menu(&self) -> Option<&str> {
+        Some("Encode/Decode")
+    }
+}
+
+/// Every encoding and decoding, in menu order.
+const CODECS: &[Codec] = &[
+    Codec {
+        name: "Base64 encode",
+        apply: |text| Ok(encoding::base64_encode(text)),
+    },
+    Codec {
+        name: "Base64 decode",
+        apply: encoding::base64_decode,
+    },
+    Codec {
+        name: "URL encode",
+        apply: |text| Ok(encoding::percent_encode(text)),
+    },
+    Codec {
+        name: "URL decode",
+        apply: encoding::percent_decode,
+    },
+    Codec {
+        name: "ROT13",
+        apply: |text| Ok(encoding::rot13(text)),
+    },
+];
+
+/// Every built-in transform, in menu order.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "Uppercase",
+        apply: uppercase,
+    },
+    Builtin {
+        name: "Lowercase",
+        apply: lowercase,
+    },
+    Builtin {
+        name: "Capitalized",
+        apply: capitalize,
+    },
+    Builtin {
+        name: "Reverse",
+        apply: reverse,
+    },
+    Builtin {
+        name: "snake_case",
+        apply: snake_case,
+    },
+    Builtin {
+        name: "camelCase",
+        apply: camel_case,
+    },
+    Builtin {
+        name: "kebab-case",
+        apply: kebab_case,
+    },
+    Builtin {
+        name: "Title Case",
+        apply: title_case,
+    },
+    Builtin {
+        name: "Sort lines",
+        apply: sort_lines,
+    },
+    Builtin {
+        name: "Sort lines descending",
+        apply: sort_lines_descending,
+    },
+    Builtin {
+        name: "Sort lines by number",
+        apply: sort_lines_numeric,
+    },
+    Builtin {
+        name: "Reverse lines",
+        apply: reverse_lines,
+    },
+    Builtin {
+        name: "Remove duplicate lines",
+        apply: remove_duplicate_lines,
+    },
+];
+
+/// Pretty-prints JSON with `indent` spaces per level, or minifies it when
+/// there is no indent. Listed in the "JSON" submenu.
+pub struct Json {
+    pub indent: Option<usize>,
+}
+
+impl Transform for Json {
+    fn name(&self) -> &str {
+        match self.indent {
+            Some(_) => "Pretty-print JSON",
+            None => "Minify JSON",
+        }
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        json::reformat(text, self.indent).map_err(|err| Failure {
+            message: format!(
+                "Invalid JSON at line {}, column {}: {err}",
+                err.line, err.column
+            ),
+            at: Some(err.offset),
+        })
+    }
+
+    fn menu(&self) -> Option<&str> {
+        Some("JSON")
+    }
+}
+
+/// A transform running a shell command, which gets the text on its input
+/// and prints the replacement.
+pub struct Shell {
+    pub name: String,
+    pub command: String,
+}
+
+impl Transform for Shell {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, text: &str) -> Result<String, Failure> {
+        let output = shell::filter(&self.command, text, &|| false)
+            .map_err(|err| format!("Could not run {}: {err}", self.command))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.trim_end() {
+                "" => Err(format!("{}: {}", self.command, output.status).into()),
+                stderr => Err(stderr.to_string().into()),
+            }
+        }
+    }
+}
+
+/// The transforms the menu offers: the built-in ones, then any registered
+/// later, such as the user's own.
+pub struct Registry {
+    transforms: Vec<Arc<dyn Transform>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let builtins = BUILTINS
+            .iter()
+            .map(|&builtin| Arc::new(builtin) as Arc<dyn Transform>);
+        let codecs = CODECS
+            .iter()
+            .map(|&codec| Arc::new(codec) as Arc<dyn Transform>);
+        let json = [Some(2), None].map(|indent| Arc::new(Json { indent }) as Arc<dyn Transform>);
+        let transforms = builtins.chain(codecs).chain(json).collect();
+        Self { transforms }
+    }
+}
+
+impl Registry {
+    /// Adds `transform` to the end of the menu, or puts it in place of the
+    /// one with the same name.
+    pub fn register(&mut self, transform: impl Transform + 'static) {
+        let transform = Arc::new(transform);
+        match self
+            .transforms
+            .iter_mut()
+            .find(|old| old.name() == transform.name())
+        {
+            Some(old) => *old = transform,
+            None => self.transforms.push(transform),
+        }
+    }
+
+    /// Every transform, in menu order.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Transform>> {
+        self.transforms.iter()
+    }
+
+    /// The transform named `name`, if there is one.
+    pub fn find(&self, name: &str) -> Option<Arc<dyn Transform>> {
+        self.transforms
+            .iter()
+            .find(|transform| transform.name() == name)
+            .cloned()
+    }
+}
+
+fn uppercase(text: &str) -> String {
+    text.to_uppercase()
+}
+
+fn lowercase(text: &str) -> String {
+    text.to_lowercase()
+}
+
+fn reverse(text: &str) -> String {
+    text.chars().rev().collect()
+}
+
+fn sort_lines(text: &str) -> String {
+    map_lines(text, |lines| lines.sort())
+}
+
+fn sort_lines_descending(text: &str) -> String {
+    map_lines(text, |lines| lines.sort_by(|a, b| b.cmp(a)))
+}
+
+fn sort_lines_numeric(text: &str) -> String {
+    map_lines(text, |lines| {
+        lines.sort_by(|a, b| leading_number(a).total_cmp(&leading_number(b)))
+    })
+}
+
+fn reverse_lines(text: &str) -> String {
+    map_lines(text, |lines| lines.reverse())
+}
+
+fn remove_duplicate_lines(text: &str) -> String {
+    map_lines(text, |lines| {
+        let mut seen = HashSet::new();
+        lines.retain(|line| seen.insert(*line));
+    })
+}
+
+/// Splits an identifier into its lowercased words, breaking at underscores,
+/// hyphens and other punctuation, and at camel humps: `parseHTTPRequest`
+/// gives `parse`, `http` and `request`.
+fn split_words(ident: &str) -> Vec<String> {
+    let chars: Vec<char> = ident.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &ch) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1).copied();
+        // A hump starts a word after a lowercase letter or a digit, and
+        // before the last capital of an acronym followed by lowercase.
+        let hump = ch.is_uppercase()
+            && prev.is_some_and(|prev| {
+                prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_uppercase() && next.is_some_and(char::is_lowercase))
+            });
+        if hump && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(ch.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Rewrites each whitespace-separated identifier in `text` by joining its
+/// words with `join`, leaving the whitespace between them as it is.
+fn recase(text: &str, join: fn(Vec<String>) -> String) -> String {
+    let mut out = String::new();
+    let mut ident = String::new();
+    for ch in text.chars() {
+        if ch.is_whitespace() {
+            if !ident.is_empty() {
+                out += &join(split_words(&std::mem::take(&mut ident)));
+            }
+            out.push(ch);
+        } else {
+            ident.push(ch);
+        }
+    }
+    if !ident.is_empty() {
+        out += &join(split_words(&ident));
+    }
+    out
+}
+
+/// `word` with its first letter uppercased.
+fn title(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// Converts identifiers to `snake_case`.
+fn snake_case(text: &str) -> String {
+    recase(text, |words| words.join("_"))
+}
+
+/// Converts identifiers to `camelCase`.
+fn camel_case(text: &str) -> String {
+    recase(text, |words| {
+        let mut words = words.into_iter();
+        let first = words.next().unwrap_or_default();
+        first + &words.map(|word| title(&word)).collect::<String>()
+    })
+}
+
+/// Converts identifiers to `kebab-case`.
+fn kebab_case(text: &str) -> String {
+    recase(text, |words| words.join("-"))
+}
+
+/// Converts identifiers to `Title Case`, their words separated by spaces.
+fn title_case(text: &str) -> String {
+    recase(text, |words| {
+        words
+            .iter()
+            .map(|word| title(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+}
+
+/// Rearranges the lines of `text` with `op`, keeping a final newline.
+fn map_lines(text: &str, op: impl FnOnce(&mut Vec<&str>)) -> String {
+    let body = text.strip_suffix('\n');
+    let mut lines: Vec<&str> = body.unwrap_or(text).split('\n').collect();
+    op(&mut lines);
+    let mut out = lines.join("\n");
+    if body.is_some() {
+        out.push('\n');
+    }
+    out
+}
+
+/// The number a line starts with, ignoring leading blanks; lines that don't
+/// start with one count as 0, the way `sort -n` has it.
+fn leading_number(line: &str) -> f64 {
+    let line = line.trim_start();
+    let len = line
+        .char_indices()
+        .find(|&(i, ch)| !(ch.is_ascii_digit() || ch == '.' || (i == 0 && ch == '-')))
+        .map_or(line.len(), |(i, _)| i);
+    line[..len].parse().unwrap_or(0.0)
+}
+
+/// Capitalizes each word in the provided text.
+fn capitalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(title)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(name: &str, text: &str) -> Result<String, Failure> {
+        Registry::default().find(name).unwrap().apply(text)
+    }
+
+    fn applied(name: &str, text: &str) -> String {
+        apply(name, text).unwrap()
+    }
+
+    #[test]
+    fn case_changes() {
+        assert_eq!(applied("Uppercase", "straße ok"), "STRASSE OK");
+        assert_eq!(applied("Lowercase", "ÀB c"), "àb c");
+        assert_eq!(
+            applied("Capitalized", "hello  wide\nworld"),
+            "Hello Wide World"
+        );
+        assert_eq!(applied("Reverse", "abé☕"), "☕éba");
+    }
+
+    #[test]
+    fn identifier_cases() {
+        let text = "parseHTTPRequest some_value\n  kebab-case2go";
+        assert_eq!(
+            applied("snake_case", text),
+            "parse_http_request some_value\n  kebab_case2go"
+        );
+        assert_eq!(
+            applied("camelCase", text),
+            "parseHttpRequest someValue\n  kebabCase2go"
+        );
+        assert_eq!(
+            applied("kebab-case", text),
+            "parse-http-request some-value\n  kebab-case2go"
+        );
+        assert_eq!(applied("Title Case", "fooBar baz_qux"), "Foo Bar Baz Qux");
+        assert_eq!(applied("snake_case", "version2Beta"), "version2_beta");
+    }
+
+    #[test]
+    fn line_operations_keep_a_final_newline() {
+        let text = "pear\napple\npear\nfig\n";
+        assert_eq!(applied("Sort lines", text), "apple\nfig\npear\npear\n");
+        assert_eq!(
+            applied("Sort lines descending", text),
+            "pear\npear\nfig\napple\n"
+        );
+        assert_eq!(applied("Reverse lines", text), "fig\npear\napple\npear\n");
+        assert_eq!(
+            applied("Remove duplicate lines", text),
+            "pear\napple\nfig\n"
+        );
+        assert_eq!(applied("Sort lines", "b\na"), "a\nb");
+        assert_eq!(
+            applied("Sort lines by number", "10 x\n  9 y\n-1.5 z\nnone\n"),
+            "-1.5 z\nnone\n  9 y\n10 x\n"
+        );
+    }
+
+    #[test]
+    fn codecs() {
+        assert_eq!(applied("Base64 encode", "hé"), "aMOp");
+        assert_eq!(applied("Base64 decode", "aMOp"), "hé");
+        assert_eq!(applied("URL encode", "a b/é"), "a%20b%2F%C3%A9");
+        assert_eq!(applied("URL decode", "a%20b%2F%C3%A9"), "a b/é");
+        assert_eq!(applied("ROT13", "Hello, World"), "Uryyb, Jbeyq");
+        assert!(apply("Base64 decode", "not base64!").is_err());
+        assert!(apply("URL decode", "%zz").is_err());
+    }
+
+    #[test]
+    fn json() {
+        let text = r#"{"a": [1, 2], "b": {}}"#;
+        assert_eq!(applied("Minify JSON", text), r#"{"a":[1,2],"b":{}}"#);
+        assert_eq!(
+            applied("Pretty-print JSON", text),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}"
+        );
+        let failure = apply("Minify JSON", "[1,\n 2,]").unwrap_err();
+        assert!(failure.message.starts_with("Invalid JSON at line 2"));
+        assert!(failure.at.is_some());
+    }
+
+    #[test]
+    fn shell_transforms() {
+        let upper = Shell {
+            name: "Upper".into(),
+            command: "tr a-z A-Z".into(),
+        };
+        assert_eq!(upper.apply("abc\n").unwrap(), "ABC\n");
+        let failing = Shell {
+            name: "Fail".into(),
+            command: "echo oops >&2; exit 3".into(),
+        };
+        assert_eq!(failing.apply("").unwrap_err().message, "oops");
+    }
+
+    #[test]
+    fn registering_replaces_by_name() {
+        let mut registry = Registry::default();
+        let count = registry.iter().count();
+        registry.register(Shell {
+            name: "Uppercase".into(),
+            command: "cat".into(),
+        });
+        registry.register(Shell {
+            name: "Mine".into(),
+            command: "cat".into(),
+        });
+        assert_eq!(registry.iter().count(), count + 1);
+        assert_eq!(registry.find("Uppercase").unwrap().apply("x").unwrap(), "x");
+        assert_eq!(registry.iter().last().unwrap().name(), "Mine");
+        assert_eq!(
+            registry.find("ROT13").unwrap().menu(),
+            Some("Encode/Decode")
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use crate::prompt;
+use crate::swap;
+use crate::workspace::Workspace;
+use cursive::direction::Direction;
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::PaletteStyle;
+use cursive::view::{CannotFocus, Nameable};
+use cursive::views::{Dialog, LinearLayout};
+use cursive::{Cursive, Printer, Vec2, View};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Name of the file tree, while it's shown.
+pub const NAME: &str = "file_tree";
+
+/// Name of the row holding the panes, and the file tree and the Markdown
+/// preview either side of them when they're shown.
+pub const BODY: &str = "body";
+
+/// Columns the file tree takes up.
+const WIDTH: usize = 30;
+
+/// A file or directory listed in the tree, `depth` levels under the root.
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+}
+
+/// The files under the working directory as a tree, docked left of the
+/// panes.
+///
+/// Up and Down pick an entry, Enter opens a file or expands or collapses
+/// a directory, and Right and Left do the same to directories. `n` creates
+/// a file, or a directory if its name ends in `/`, next to the picked entry
+/// or inside it if it's an opened directory; `r` renames the picked entry
+/// and `d` deletes it, after asking. Esc goes back to the panes. Hidden
+/// files are left out.
+pub struct FileTree {
+    workspace: Arc<Mutex<Workspace>>,
+    root: PathBuf,
+    expanded: HashSet<PathBuf>,
+    entries: Vec<Entry>,
+    selected: usize,
+    /// The first entry shown.
+    top: usize,
+    /// Rows the tree had room for at the last layout.
+    height: usize,
+}
+
+impl FileTree {
+    /// Creates a tree of the working directory, with every directory closed.
+    pub fn new(workspace: Arc<Mutex<Workspace>>) -> Self {
+        let mut tree = Self {
+            workspace,
+            root: PathBuf::from("."),
+            expanded: HashSet::new(),
+            entries: Vec::new(),
+            selected: 0,
+            top: 0,
+            height: 0,
+        };
+        tree.refresh();
+        tree
+    }
+
+    /// Reads the directories again, keeping the same entry picked as far
+    /// as it's still there.
+    pub fn refresh(&mut self) {
+        let selected = self.selected_path().map(Path::to_path_buf);
+        self.entries.clear();
+        let root = self.root.clone();
+        self.list(&root, 0);
+        if let Some(selected) = selected {
+            if let Some(index) = self.entries.iter().position(|e| e.path == selected) {
+                self.selected = index;
+            }
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Adds the entries of `dir`, and those of its expanded subdirectories,
+    /// directories first and each sorted by name.
+    fn list(&mut self, dir: &Path, depth: usize) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries: Vec<(bool, PathBuf)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| (entry.path().is_dir(), entry.path()))
+            .collect();
+        entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        for (is_dir, path) in entries {
+            let expanded = is_dir && self.expanded.contains(&path);
+            self.entries.push(Entry {
+                path: path.clone(),
+                depth,
+                is_dir,
+            });
+            if expanded {
+                self.list(&path, depth + 1);
+            }
+        }
+    }
+
+    /// The path of the picked entry, if there are any.
+    fn selected_path(&self) -> Option<&Path> {
+        self.entries
+            .get(self.selected)
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// Picks the entry `delta` rows away, stopping at either end.
+    fn move_by(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Opens or closes the picked directory, returning `false` if it's a file.
+    fn set_expanded(&mut self, expanded: bool) -> bool {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return false;
+        };
+        if !entry.is_dir {
+            return false;
+        }
+        let path = entry.path.clone();
+        if expanded {
+            self.expanded.insert(path);
+        } else {
+            self.expanded.remove(&path);
+        }
+        self.refresh();
+        true
+    }
+
+    /// Picks the directory the picked entry is in.
+    fn select_parent(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+        let depth = entry.depth;
+        if let Some(index) = self.entries[..self.selected]
+            .iter()
+            .rposition(|entry| entry.depth < depth)
+        {
+            self.selected = index;
+        }
+    }
+
+    /// Where a new entry goes: inside the picked directory if it's open,
+    /// next to the picked entry otherwise.
+    fn new_entry_dir(&self) -> PathBuf {
+        match self.entries.get(self.selected) {
+            Some(entry) if entry.is_dir && self.expanded.contains(&entry.path) => {
+                entry.path.clone()
+            }
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+}
+
+impl View for FileTree {
+    fn draw(&self, printer: &Printer) {
+        printer.with_style(PaletteStyle::Secondary, |printer| {
+            for y in 0..printer.size.y {
+                printer.print_hline((0, y), printer.size.x, " ");
+            }
+        });
+        for (y, entry) in self
+            .entries
+            .iter()
+            .skip(self.top)
+            .take(printer.size.y)
+            .enumerate()
+        {
+            let name = entry
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let marker = match (entry.is_dir, self.expanded.contains(&entry.path)) {
+                (false, _) => "  ",
+                (true, false) => "▸ ",
+                (true, true) => "▾ ",
+            };
+            let text = format!("{}{marker}{name}", "  ".repeat(entry.depth));
+            let style = if self.top + y != self.selected {
+                PaletteStyle::Secondary
+            } else if printer.focused {
+                PaletteStyle::Highlight
+            } else {
+                PaletteStyle::HighlightInactive
+            };
+            printer.with_style(style, |printer| printer.print((0, y), &text));
+        }
+        printer.print_vline((printer.size.x.saturating_sub(1), 0), printer.size.y, "│");
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        self.height = size.y;
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if size.y > 0 && self.selected >= self.top + size.y {
+            self.top = self.selected + 1 - size.y;
+        }
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        Vec2::new(WIDTH, constraint.y)
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        let page = self.height.max(1) as isize;
+        match event {
+            Event::Key(Key::Up) => self.move_by(-1),
+            Event::Key(Key::Down) => self.move_by(1),
+            Event::Key(Key::PageUp) => self.move_by(-page),
+            Event::Key(Key::PageDown) => self.move_by(page),
+            Event::Key(Key::Home) => self.selected = 0,
+            Event::Key(Key::End) => self.move_by(isize::MAX),
+            Event::Key(Key::Right) => {
+                self.set_expanded(true);
+            }
+            Event::Key(Key::Left) => {
+                let open = self
+                    .entries
+                    .get(self.selected)
+                    .is_some_and(|entry| self.expanded.contains(&entry.path));
+                if open {
+                    self.set_expanded(false);
+                } else {
+                    self.select_parent();
+                }
+            }
+            Event::Key(Key::Enter) => {
+                let Some(entry) = self.entries.get(self.selected) else {
+                    return EventResult::Consumed(None);
+                };
+                if entry.is_dir {
+                    let expanded = self.expanded.contains(&entry.path);
+                    self.set_expanded(!expanded);
+                } else {
+                    let path = entry.path.clone();
+                    let workspace = self.workspace.clone();
+                    return EventResult::with_cb(move |s| open(s, &workspace, &path));
+                }
+            }
+            Event::Char('n') => {
+                let dir = self.new_entry_dir();
+                let workspace = self.workspace.clone();
+                return EventResult::with_cb(move |s| create(s, &workspace, &dir));
+            }
+            Event::Char('r') => {
+                let Some(path) = self.selected_path().map(Path::to_path_buf) else {
+                    return EventResult::Consumed(None);
+                };
+                let workspace = self.workspace.clone();
+                return EventResult::with_cb(move |s| rename(s, &workspace, &path));
+            }
+            Event::Char('d') => {
+                let Some(path) = self.selected_path().map(Path::to_path_buf) else {
+                    return EventResult::Consumed(None);
+                };
+                return EventResult::with_cb(move |s| delete(s, &path));
+            }
+            Event::Key(Key::Esc) => return EventResult::with_cb(focus_panes),
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::consumed())
+    }
+}
+
+/// Shows the file tree left of the panes and moves to it, moves to it if
+/// it's shown already, or hides it if it's where the keys go.
+pub fn toggle(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let workspace = workspace.clone();
+    s.call_on_name(BODY, move |body: &mut LinearLayout| {
+        match body.find_child_from_name(NAME) {
+            None => {
+                body.insert_child(0, FileTree::new(workspace).with_name(NAME));
+                let _ = body.set_focus_index(0);
+            }
+            Some(index) if body.get_focus_index() == index => {
+                body.remove_child(index);
+            }
+            Some(index) => {
+                let _ = body.set_focus_index(index);
+            }
+        }
+    });
+}
+
+/// Moves back from the file tree to the panes.
+fn focus_panes(s: &mut Cursive) {
+    s.call_on_name(BODY, |body: &mut LinearLayout| {
+        if let Some(index) = body.find_child_from_name("main") {
+            let _ = body.set_focus_index(index);
+        }
+    });
+}
+
+/// Reads the directories of the file tree again.
+fn refresh(s: &mut Cursive) {
+    s.call_on_name(NAME, FileTree::refresh);
+}
+
+/// Opens the file at `path` in a buffer, then moves to the panes.
+fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, path: &Path) {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    let result = workspace.lock().unwrap().open(path);
+    match result {
+        Ok(()) => {
+            focus_panes(s);
+            swap::offer(s, workspace);
+        }
+        Err(err) => s.add_layer(Dialog::info(format!(
+            "Could not open {}: {err}",
+            path.display()
+        ))),
+    }
+}
+
+/// Asks for the name of a file, or of a directory if it ends in `/`, and
+/// creates it in `dir`. A new file is opened too.
+fn create(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, dir: &Path) {
+    let workspace = workspace.clone();
+    let dir = dir.to_path_buf();
+    prompt::open(
+        s,
+        "New file (end in / for a directory): ",
+        |_, _| {},
+        move |s, name| {
+            if name.trim().is_empty() {
+                return;
+            }
+            let path = dir.join(name);
+            let result = if name.ends_with('/') {
+                fs::create_dir_all(&path)
+            } else {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .map(drop)
+            };
+            refresh(s);
+            match result {
+                Ok(()) if !name.ends_with('/') => open(s, &workspace, &path),
+                Ok(()) => {}
+                Err(err) => s.add_layer(Dialog::info(format!(
+                    "Could not create {}: {err}",
+                    path.display()
+                ))),
+            }
+        },
+        |_| {},
+    );
+}
+
+/// Asks for a new name for the file or directory at `path`, in the same
+/// directory, and renames it. A buffer holding the file follows it.
+fn rename(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, path: &Path) {
+    let workspace = workspace.clone();
+    let path = path.to_path_buf();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    prompt::open(
+        s,
+        &format!("Rename {name} to: "),
+        |_, _| {},
+        move |s, name| {
+            if name.trim().is_empty() {
+                return;
+            }
+            let to = path.with_file_name(name);
+            if to.exists() {
+                s.add_layer(Dialog::info(format!("{} already exists", to.display())));
+                return;
+            }
+            let mut ws = workspace.lock().unwrap();
+            let open = ws.position_of(&path);
+            let result: io::Result<()> = fs::rename(&path, &to);
+            if result.is_ok() {
+                if let Some(index) = open {
+                    let to = to.strip_prefix(".").unwrap_or(&to);
+                    ws.editors_mut()[index].set_path(to.to_path_buf());
+                }
+            }
+            drop(ws);
+            refresh(s);
+            if let Err(err) = result {
+                s.add_layer(Dialog::info(format!(
+                    "Could not rename {}: {err}",
+                    path.display()
+                )));
+            }
+        },
+        |_| {},
+    );
+}
+
+/// Asks whether to delete the file or directory at `path`, with everything
+/// in it, and does.
+fn delete(s: &mut Cursive, path: &Path) {
+    let path = path.to_path_buf();
+    let shown = path
+        .strip_prefix(".")
+        .unwrap_or(&path)
+        .display()
+        .to_string();
+    let what = if path.is_dir() {
+        format!("Delete {shown} and everything in it?")
+    } else {
+        format!("Delete {shown}?")
+    };
+    s.add_layer(
+        Dialog::text(what)
+            .title("Delete")
+            .button("Delete", move |s| {
+                s.pop_layer();
+                let result = if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                };
+                refresh(s);
+                if let Err(err) = result {
+                    s.add_layer(Dialog::info(format!(
+                        "Could not delete {}: {err}",
+                        path.display()
+                    )));
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
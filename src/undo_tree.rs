@@ -0,0 +1,54 @@
+use crate::history::{History, Step};
+use crate::workspace::Workspace;
+use cursive::event::Key;
+use cursive::view::{Resizable, Scrollable};
+use cursive::views::{Dialog, OnEventView, SelectView};
+use cursive::Cursive;
+use std::sync::{Arc, Mutex};
+
+/// Opens the undo tree of the focused buffer: every state its text has been
+/// in, with the branches left by undoing and then editing again indented
+/// under the state they start from. Enter on one takes the text there.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let ws = workspace.lock().unwrap();
+    let history = ws.editor().history();
+    let mut list: SelectView<usize> = SelectView::new();
+    let mut selected = 0;
+    for (row, (state, branch)) in history.states().into_iter().enumerate() {
+        if state == history.current() {
+            selected = row;
+        }
+        list.add_item(label(history, state, branch), state);
+    }
+    drop(ws);
+    // Nothing listens for selection changes, so the callback can be dropped.
+    let _ = list.set_selection(selected);
+    let list = {
+        let workspace = workspace.clone();
+        list.on_submit(move |s, &state: &usize| {
+            workspace.lock().unwrap().editor_mut().goto_state(state);
+            s.pop_layer();
+        })
+    };
+    let screen = Dialog::around(list.scrollable().max_height(16))
+        .title("Undo tree")
+        .dismiss_button("Close")
+        .min_width(40);
+    s.add_layer(OnEventView::new(screen).on_event(Key::Esc, |s| {
+        s.pop_layer();
+    }));
+}
+
+/// The row of the tree for `state`, `branch` branches in: the current state
+/// is filled in.
+fn label(history: &History, state: usize, branch: usize) -> String {
+    let mark = if state == history.current() {
+        '●'
+    } else {
+        '○'
+    };
+    let what = history
+        .step(state)
+        .map_or("as loaded".to_string(), Step::summary);
+    format!("{}{mark} {state:>3} {what}", "  ".repeat(branch))
+}
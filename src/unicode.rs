@@ -0,0 +1,503 @@
+use crate::width::char_width;
+use crate::workspace::Workspace;
+use cursive::event::{EventResult, Key};
+use cursive::view::{Nameable, Resizable, Scrollable};
+use cursive::views::{Dialog, EditView, LinearLayout, OnEventView, SelectView};
+use cursive::Cursive;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Name of the list of matching characters.
+const LIST: &str = "unicode_list";
+
+/// Where the Unicode Character Database's list of names usually is.
+const DATA: &[&str] = &[
+    "/usr/share/unicode/UnicodeData.txt",
+    "/usr/share/unicode/ucd/UnicodeData.txt",
+    "/usr/share/unicode-data/UnicodeData.txt",
+    "/usr/share/unicode-character-database/UnicodeData.txt",
+    "/usr/local/share/unicode/UnicodeData.txt",
+];
+
+/// Names of the characters most often looked for, for when the database
+/// isn't installed.
+const COMMON: &[(char, &str)] = &[
+    ('\t', "CHARACTER TABULATION"),
+    ('\n', "LINE FEED (LF)"),
+    ('\r', "CARRIAGE RETURN (CR)"),
+    (' ', "SPACE"),
+    ('\u{a0}', "NO-BREAK SPACE"),
+    ('¡', "INVERTED EXCLAMATION MARK"),
+    ('¢', "CENT SIGN"),
+    ('£', "POUND SIGN"),
+    ('¤', "CURRENCY SIGN"),
+    ('¥', "YEN SIGN"),
+    ('§', "SECTION SIGN"),
+    ('©', "COPYRIGHT SIGN"),
+    ('«', "LEFT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    ('¬', "NOT SIGN"),
+    ('®', "REGISTERED SIGN"),
+    ('°', "DEGREE SIGN"),
+    ('±', "PLUS-MINUS SIGN"),
+    ('²', "SUPERSCRIPT TWO"),
+    ('³', "SUPERSCRIPT THREE"),
+    ('µ', "MICRO SIGN"),
+    ('¶', "PILCROW SIGN"),
+    ('·', "MIDDLE DOT"),
+    ('¹', "SUPERSCRIPT ONE"),
+    ('»', "RIGHT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    ('¼', "VULGAR FRACTION ONE QUARTER"),
+    ('½', "VULGAR FRACTION ONE HALF"),
+    ('¾', "VULGAR FRACTION THREE QUARTERS"),
+    ('¿', "INVERTED QUESTION MARK"),
+    ('À', "LATIN CAPITAL LETTER A WITH GRAVE"),
+    ('Á', "LATIN CAPITAL LETTER A WITH ACUTE"),
+    ('Ä', "LATIN CAPITAL LETTER A WITH DIAERESIS"),
+    ('Å', "LATIN CAPITAL LETTER A WITH RING ABOVE"),
+    ('Æ', "LATIN CAPITAL LETTER AE"),
+    ('Ç', "LATIN CAPITAL LETTER C WITH CEDILLA"),
+    ('É', "LATIN CAPITAL LETTER E WITH ACUTE"),
+    ('Ñ', "LATIN CAPITAL LETTER N WITH TILDE"),
+    ('Ö', "LATIN CAPITAL LETTER O WITH DIAERESIS"),
+    ('×', "MULTIPLICATION SIGN"),
+    ('Ø', "LATIN CAPITAL LETTER O WITH STROKE"),
+    ('Ü', "LATIN CAPITAL LETTER U WITH DIAERESIS"),
+    ('ß', "LATIN SMALL LETTER SHARP S"),
+    ('à', "LATIN SMALL LETTER A WITH GRAVE"),
+    ('á', "LATIN SMALL LETTER A WITH ACUTE"),
+    ('â', "LATIN SMALL LETTER A WITH CIRCUMFLEX"),
+    ('ä', "LATIN SMALL LETTER A WITH DIAERESIS"),
+    ('å', "LATIN SMALL LETTER A WITH RING ABOVE"),
+    ('æ', "LATIN SMALL LETTER AE"),
+    ('ç', "LATIN SMALL LETTER C WITH CEDILLA"),
+    ('è', "LATIN SMALL LETTER E WITH GRAVE"),
+    ('é', "LATIN SMALL LETTER E WITH ACUTE"),
+    ('ê', "LATIN SMALL LETTER E WITH CIRCUMFLEX"),
+    ('ë', "LATIN SMALL LETTER E WITH DIAERESIS"),
+    ('í', "LATIN SMALL LETTER I WITH ACUTE"),
+    ('ï', "LATIN SMALL LETTER I WITH DIAERESIS"),
+    ('ñ', "LATIN SMALL LETTER N WITH TILDE"),
+    ('ó', "LATIN SMALL LETTER O WITH ACUTE"),
+    ('ô', "LATIN SMALL LETTER O WITH CIRCUMFLEX"),
+    ('ö', "LATIN SMALL LETTER O WITH DIAERESIS"),
+    ('÷', "DIVISION SIGN"),
+    ('ø', "LATIN SMALL LETTER O WITH STROKE"),
+    ('ú', "LATIN SMALL LETTER U WITH ACUTE"),
+    ('ü', "LATIN SMALL LETTER U WITH DIAERESIS"),
+    ('œ', "LATIN SMALL LIGATURE OE"),
+    ('ƒ', "LATIN SMALL LETTER F WITH HOOK"),
+    ('\u{301}', "COMBINING ACUTE ACCENT"),
+    ('Α', "GREEK CAPITAL LETTER ALPHA"),
+    ('Β', "GREEK CAPITAL LETTER BETA"),
+    ('Γ', "GREEK CAPITAL LETTER GAMMA"),
+    ('Δ', "GREEK CAPITAL LETTER DELTA"),
+    ('Θ', "GREEK CAPITAL LETTER THETA"),
+    ('Λ', "GREEK CAPITAL LETTER LAMDA"),
+    ('Π', "GREEK CAPITAL LETTER PI"),
+    ('Σ', "GREEK CAPITAL LETTER SIGMA"),
+    ('Φ', "GREEK CAPITAL LETTER PHI"),
+    ('Ψ', "GREEK CAPITAL LETTER PSI"),
+    ('Ω', "GREEK CAPITAL LETTER OMEGA"),
+    ('α', "GREEK SMALL LETTER ALPHA"),
+    ('β', "GREEK SMALL LETTER BETA"),
+    ('γ', "GREEK SMALL LETTER GAMMA"),
+    ('δ', "GREEK SMALL LETTER DELTA"),
+    ('ε', "GREEK SMALL LETTER EPSILON"),
+    ('ζ', "GREEK SMALL LETTER ZETA"),
+    ('η', "GREEK SMALL LETTER ETA"),
+    ('θ', "GREEK SMALL LETTER THETA"),
+    ('ι', "GREEK SMALL LETTER IOTA"),
+    ('κ', "GREEK SMALL LETTER KAPPA"),
+    ('λ', "GREEK SMALL LETTER LAMDA"),
+    ('μ', "GREEK SMALL LETTER MU"),
+    ('ν', "GREEK SMALL LETTER NU"),
+    ('ξ', "GREEK SMALL LETTER XI"),
+    ('π', "GREEK SMALL LETTER PI"),
+    ('ρ', "GREEK SMALL LETTER RHO"),
+    ('σ', "GREEK SMALL LETTER SIGMA"),
+    ('τ', "GREEK SMALL LETTER TAU"),
+    ('υ', "GREEK SMALL LETTER UPSILON"),
+    ('φ', "GREEK SMALL LETTER PHI"),
+    ('χ', "GREEK SMALL LETTER CHI"),
+    ('ψ', "GREEK SMALL LETTER PSI"),
+    ('ω', "GREEK SMALL LETTER OMEGA"),
+    ('\u{2002}', "EN SPACE"),
+    ('\u{2003}', "EM SPACE"),
+    ('\u{2009}', "THIN SPACE"),
+    ('\u{200b}', "ZERO WIDTH SPACE"),
+    ('\u{200c}', "ZERO WIDTH NON-JOINER"),
+    ('\u{200d}', "ZERO WIDTH JOINER"),
+    ('‐', "HYPHEN"),
+    ('‑', "NON-BREAKING HYPHEN"),
+    ('‒', "FIGURE DASH"),
+    ('–', "EN DASH"),
+    ('—', "EM DASH"),
+    ('―', "HORIZONTAL BAR"),
+    ('‖', "DOUBLE VERTICAL LINE"),
+    ('‘', "LEFT SINGLE QUOTATION MARK"),
+    ('’', "RIGHT SINGLE QUOTATION MARK"),
+    ('‚', "SINGLE LOW-9 QUOTATION MARK"),
+    ('“', "LEFT DOUBLE QUOTATION MARK"),
+    ('”', "RIGHT DOUBLE QUOTATION MARK"),
+    ('„', "DOUBLE LOW-9 QUOTATION MARK"),
+    ('†', "DAGGER"),
+    ('‡', "DOUBLE DAGGER"),
+    ('•', "BULLET"),
+    ('…', "HORIZONTAL ELLIPSIS"),
+    ('‰', "PER MILLE SIGN"),
+    ('′', "PRIME"),
+    ('″', "DOUBLE PRIME"),
+    ('‹', "SINGLE LEFT-POINTING ANGLE QUOTATION MARK"),
+    ('›', "SINGLE RIGHT-POINTING ANGLE QUOTATION MARK"),
+    ('‽', "INTERROBANG"),
+    ('⁄', "FRACTION SLASH"),
+    ('€', "EURO SIGN"),
+    ('₹', "INDIAN RUPEE SIGN"),
+    ('₿', "BITCOIN SIGN"),
+    ('℃', "DEGREE CELSIUS"),
+    ('№', "NUMERO SIGN"),
+    ('™', "TRADE MARK SIGN"),
+    ('\u{2126}', "OHM SIGN"),
+    ('←', "LEFTWARDS ARROW"),
+    ('↑', "UPWARDS ARROW"),
+    ('→', "RIGHTWARDS ARROW"),
+    ('↓', "DOWNWARDS ARROW"),
+    ('↔', "LEFT RIGHT ARROW"),
+    ('↕', "UP DOWN ARROW"),
+    ('↩', "LEFTWARDS ARROW WITH HOOK"),
+    ('⇐', "LEFTWARDS DOUBLE ARROW"),
+    ('⇒', "RIGHTWARDS DOUBLE ARROW"),
+    ('⇔', "LEFT RIGHT DOUBLE ARROW"),
+    ('∀', "FOR ALL"),
+    ('∂', "PARTIAL DIFFERENTIAL"),
+    ('∃', "THERE EXISTS"),
+    ('∅', "EMPTY SET"),
+    ('∆', "INCREMENT"),
+    ('∇', "NABLA"),
+    ('∈', "ELEMENT OF"),
+    ('∉', "NOT AN ELEMENT OF"),
+    ('∏', "N-ARY PRODUCT"),
+    ('∑', "N-ARY SUMMATION"),
+    ('−', "MINUS SIGN"),
+    ('∓', "MINUS-OR-PLUS SIGN"),
+    ('∘', "RING OPERATOR"),
+    ('√', "SQUARE ROOT"),
+    ('∝', "PROPORTIONAL TO"),
+    ('∞', "INFINITY"),
+    ('∧', "LOGICAL AND"),
+    ('∨', "LOGICAL OR"),
+    ('∩', "INTERSECTION"),
+    ('∪', "UNION"),
+    ('∫', "INTEGRAL"),
+    ('≈', "ALMOST EQUAL TO"),
+    ('≠', "NOT EQUAL TO"),
+    ('≡', "IDENTICAL TO"),
+    ('≤', "LESS-THAN OR EQUAL TO"),
+    ('≥', "GREATER-THAN OR EQUAL TO"),
+    ('⊂', "SUBSET OF"),
+    ('⊃', "SUPERSET OF"),
+    ('⊆', "SUBSET OF OR EQUAL TO"),
+    ('⊇', "SUPERSET OF OR EQUAL TO"),
+    ('⊕', "CIRCLED PLUS"),
+    ('⊗', "CIRCLED TIMES"),
+    ('⊥', "UP TACK"),
+    ('⌘', "PLACE OF INTEREST SIGN"),
+    ('⌥', "OPTION KEY"),
+    ('⎋', "BROKEN CIRCLE WITH NORTHWEST ARROW"),
+    ('␣', "OPEN BOX"),
+    ('─', "BOX DRAWINGS LIGHT HORIZONTAL"),
+    ('│', "BOX DRAWINGS LIGHT VERTICAL"),
+    ('┌', "BOX DRAWINGS LIGHT DOWN AND RIGHT"),
+    ('┐', "BOX DRAWINGS LIGHT DOWN AND LEFT"),
+    ('└', "BOX DRAWINGS LIGHT UP AND RIGHT"),
+    ('┘', "BOX DRAWINGS LIGHT UP AND LEFT"),
+    ('├', "BOX DRAWINGS LIGHT VERTICAL AND RIGHT"),
+    ('┤', "BOX DRAWINGS LIGHT VERTICAL AND LEFT"),
+    ('┼', "BOX DRAWINGS LIGHT VERTICAL AND HORIZONTAL"),
+    ('█', "FULL BLOCK"),
+    ('░', "LIGHT SHADE"),
+    ('■', "BLACK SQUARE"),
+    ('□', "WHITE SQUARE"),
+    ('▲', "BLACK UP-POINTING TRIANGLE"),
+    ('▶', "BLACK RIGHT-POINTING TRIANGLE"),
+    ('▼', "BLACK DOWN-POINTING TRIANGLE"),
+    ('◀', "BLACK LEFT-POINTING TRIANGLE"),
+    ('◆', "BLACK DIAMOND"),
+    ('○', "WHITE CIRCLE"),
+    ('●', "BLACK CIRCLE"),
+    ('★', "BLACK STAR"),
+    ('☆', "WHITE STAR"),
+    ('☐', "BALLOT BOX"),
+    ('☑', "BALLOT BOX WITH CHECK"),
+    ('☒', "BALLOT BOX WITH X"),
+    ('☺', "WHITE SMILING FACE"),
+    ('♠', "BLACK SPADE SUIT"),
+    ('♣', "BLACK CLUB SUIT"),
+    ('♥', "BLACK HEART SUIT"),
+    ('♦', "BLACK DIAMOND SUIT"),
+    ('♪', "EIGHTH NOTE"),
+    ('⚠', "WARNING SIGN"),
+    ('✓', "CHECK MARK"),
+    ('✔', "HEAVY CHECK MARK"),
+    ('✗', "BALLOT X"),
+    ('✘', "HEAVY BALLOT X"),
+    ('❤', "HEAVY BLACK HEART"),
+    ('⟨', "MATHEMATICAL LEFT ANGLE BRACKET"),
+    ('⟩', "MATHEMATICAL RIGHT ANGLE BRACKET"),
+    ('、', "IDEOGRAPHIC COMMA"),
+    ('。', "IDEOGRAPHIC FULL STOP"),
+    ('\u{feff}', "ZERO WIDTH NO-BREAK SPACE"),
+    ('\u{fffd}', "REPLACEMENT CHARACTER"),
+    ('👍', "THUMBS UP SIGN"),
+    ('😀', "GRINNING FACE"),
+    ('😂', "FACE WITH TEARS OF JOY"),
+    ('🙂', "SLIGHTLY SMILING FACE"),
+    ('🎉', "PARTY POPPER"),
+    ('🔥', "FIRE"),
+    ('🚀', "ROCKET"),
+];
+
+/// How many characters a search lists at most.
+const LIMIT: usize = 200;
+
+/// The names of characters, by code point.
+#[derive(Clone, Debug)]
+pub struct Names {
+    chars: Vec<(char, String)>,
+}
+
+impl Names {
+    /// Reads the names from the Unicode Character Database where it's
+    /// usually installed, or makes do with the common characters it knows
+    /// without it.
+    pub fn load() -> Self {
+        let chars = DATA
+            .iter()
+            .find_map(|path| fs::read_to_string(path).ok())
+            .map(|text| parse(&text))
+            .filter(|chars| !chars.is_empty())
+            .unwrap_or_else(|| {
+                let mut chars: Vec<_> = COMMON
+                    .iter()
+                    .map(|&(ch, name)| (ch, name.to_string()))
+                    .collect();
+                chars.sort();
+                chars
+            });
+        Self { chars }
+    }
+
+    /// The name of `ch`, if it's known.
+    pub fn name(&self, ch: char) -> Option<&str> {
+        let index = self.chars.binary_search_by_key(&ch, |&(ch, _)| ch).ok()?;
+        Some(&self.chars[index].1)
+    }
+
+    /// The characters `query` picks out: the one it gives the code point of,
+    /// as `U+2014`, `0x2014` or just `2014`, then those with every word of
+    /// it in their names, shortest name first.
+    pub fn search(&self, query: &str) -> Vec<(char, &str)> {
+        let mut found: Vec<(char, &str)> = Vec::new();
+        if let Some(ch) = codepoint(query) {
+            found.push((ch, self.name(ch).unwrap_or("")));
+        }
+        let words: Vec<String> = query.split_whitespace().map(str::to_uppercase).collect();
+        if words.is_empty() {
+            return found;
+        }
+        let mut named: Vec<(char, &str)> = self
+            .chars
+            .iter()
+            .filter(|(_, name)| words.iter().all(|word| name.contains(word.as_str())))
+            .map(|(ch, name)| (*ch, name.as_str()))
+            .filter(|&(ch, _)| found.first().is_none_or(|&(first, _)| first != ch))
+            .collect();
+        named.sort_by_key(|&(ch, name)| (name.len(), ch));
+        found.extend(named.into_iter().take(LIMIT));
+        found
+    }
+}
+
+/// The characters listed in `UnicodeData.txt`, by code point. Controls are
+/// named by their old names, and the ranges the file gives only the ends of,
+/// such as the CJK ideographs, are left out.
+fn parse(text: &str) -> Vec<(char, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(';');
+            let code = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let name = fields.next()?;
+            let ch = char::from_u32(code)?;
+            let name = match name {
+                "<control>" => fields.nth(8).filter(|old| !old.is_empty())?,
+                name if name.starts_with('<') => return None,
+                name => name,
+            };
+            Some((ch, name.to_string()))
+        })
+        .collect()
+}
+
+/// The character `query` gives the code point of, in hex.
+fn codepoint(query: &str) -> Option<char> {
+    let query = query.trim();
+    let hex = ["U+", "u+", "0x", "0X", "\\u"]
+        .iter()
+        .find_map(|prefix| query.strip_prefix(prefix))
+        .unwrap_or(query);
+    if hex.is_empty() || hex.len() > 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    // A word made of hex digits, like `face`, names characters rather than
+    // giving a code point.
+    if hex.len() == query.len() && !hex.bytes().any(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Describes `ch` as its code point and name, such as `U+2014 EM DASH`.
+pub fn describe(names: &Names, ch: char) -> String {
+    match names.name(ch) {
+        Some(name) => format!("U+{:04X} {name}", ch as u32),
+        None => format!("U+{:04X}", ch as u32),
+    }
+}
+
+/// Opens the character picker: typing a name, or words from it, or a code
+/// point narrows the list of characters, and Enter types the one picked at
+/// every cursor.
+pub fn open(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut list: SelectView<char> = SelectView::new();
+    fill(&mut list, workspace, "");
+    let list = {
+        let workspace = workspace.clone();
+        list.on_submit(move |s, &ch: &char| {
+            s.pop_layer();
+            insert(&workspace, ch);
+        })
+    };
+
+    let input = {
+        let on_edit = workspace.clone();
+        let on_submit = workspace.clone();
+        EditView::new()
+            .on_edit(move |s, text, _| {
+                s.call_on_name(LIST, |list: &mut SelectView<char>| {
+                    fill(list, &on_edit, text);
+                });
+            })
+            .on_submit(move |s, _| {
+                let chosen = s
+                    .call_on_name(LIST, |list: &mut SelectView<char>| list.selection())
+                    .flatten();
+                if let Some(ch) = chosen {
+                    s.pop_layer();
+                    insert(&on_submit, *ch);
+                }
+            })
+    };
+
+    // The input keeps focus; Up and Down move through the list underneath it.
+    let input = OnEventView::new(input)
+        .on_pre_event_inner(Key::Up, |_, _| Some(move_selection(true)))
+        .on_pre_event_inner(Key::Down, |_, _| Some(move_selection(false)));
+
+    let content = LinearLayout::vertical()
+        .child(input)
+        .child(list.with_name(LIST).scrollable().max_height(12));
+    let picker = OnEventView::new(Dialog::around(content).title("Characters").min_width(50))
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        });
+    s.add_layer(picker);
+}
+
+/// Moves the list's selection one entry up or down.
+fn move_selection(up: bool) -> EventResult {
+    EventResult::with_cb(move |s| {
+        s.call_on_name(LIST, |list: &mut SelectView<char>| {
+            // Nothing listens for selection changes, so the callback can be dropped.
+            let _ = if up {
+                list.select_up(1)
+            } else {
+                list.select_down(1)
+            };
+        });
+    })
+}
+
+/// Lists the characters `query` picks out, or the common ones before
+/// anything is typed.
+fn fill(list: &mut SelectView<char>, workspace: &Arc<Mutex<Workspace>>, query: &str) {
+    let mut ws = workspace.lock().unwrap();
+    let names = ws.character_names();
+    let found: Vec<(char, String)> = if query.trim().is_empty() {
+        COMMON
+            .iter()
+            .map(|&(ch, name)| (ch, name.to_string()))
+            .collect()
+    } else {
+        names
+            .search(query)
+            .into_iter()
+            .map(|(ch, name)| (ch, name.to_string()))
+            .collect()
+    };
+    list.clear();
+    for (ch, name) in found {
+        // Controls and marks that combine with what's before them would
+        // garble the list, so they're only named.
+        let shown = if ch.is_control() || is_mark(ch) {
+            ' '
+        } else {
+            ch
+        };
+        let pad = " ".repeat(3 - char_width(shown, 0).min(2));
+        list.add_item(format!("{shown}{pad}U+{:<6X}{name}", ch as u32), ch);
+    }
+}
+
+/// Whether `ch` is one of the combining diacritical marks, which are drawn
+/// over the character before them.
+fn is_mark(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{300}'..='\u{36f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+/// Types `ch` at every cursor, unless the buffer is read-only.
+fn insert(workspace: &Arc<Mutex<Workspace>>, ch: char) {
+    let mut ws = workspace.lock().unwrap();
+    let ed = ws.editor_mut();
+    if !ed.is_read_only() {
+        ed.for_each_cursor(|ed| ed.insert(ch));
+    }
+}
+
+/// Shows the code points and names of the characters making up what reads
+/// as the one under the cursor, such as a letter and its accents, with the
+/// bytes they're stored as.
+pub fn describe_at_cursor(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let ed = ws.editor();
+    let cursor = ed.cursor();
+    let end = ed.buffer().next_grapheme(cursor).unwrap_or(cursor);
+    let text = ed.buffer().slice(cursor..end);
+    if text.is_empty() {
+        drop(ws);
+        s.add_layer(Dialog::info("No character under the cursor"));
+        return;
+    }
+    let names = ws.character_names();
+    let mut lines: Vec<String> = text.chars().map(|ch| describe(names, ch)).collect();
+    let bytes: Vec<String> = text.bytes().map(|byte| format!("{byte:02X}")).collect();
+    lines.push(format!("UTF-8: {}", bytes.join(" ")));
+    drop(ws);
+    s.add_layer(Dialog::info(lines.join("\n")).title("Character"));
+}
@@ -0,0 +1,902 @@
+use crate::filetype::Filetype;
+use crate::git::Sign;
+use crate::grammar::{self, Mode};
+use crate::hex::Hex;
+use crate::highlight::Token;
+use crate::lsp::Severity;
+use crate::motion;
+use crate::pane::Region;
+use crate::settings::LineNumbers;
+use crate::width::{char_width, display_width, TAB_WIDTH};
+use crate::workspace::Workspace;
+use crate::{hints, spell};
+use crate::{wrap, Editor};
+use cursive::direction::{Direction, Orientation};
+use cursive::event::{Event, EventResult, Key, MouseButton, MouseEvent};
+use cursive::theme::{
+    BaseColor, Color, ColorStyle, ColorType, Effect, PaletteColor, PaletteStyle, Style, StyleType,
+};
+use cursive::view::CannotFocus;
+use cursive::{Printer, Rect, Vec2, View};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How long the cursor rests on a word before the word's other occurrences
+/// are highlighted.
+pub const REST: Duration = Duration::from_millis(500);
+
+/// Keys that move through the text or delete it. The editor runs their
+/// commands itself, so they only act on the text while it has the focus,
+/// and not when a list above it leaves them alone at its edge.
+pub const TEXT_KEYS: &[Key] = &[
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Down,
+    Key::Home,
+    Key::End,
+    Key::Backspace,
+    Key::Del,
+];
+
+/// The background of the color column.
+const COLUMN: Color = Color::Dark(BaseColor::Black);
+
+/// Full-screen editing view rendering the panes of the shared `Workspace`.
+///
+/// The buffer is never modified for display purposes: syntax colors, the
+/// selection, any search matches, words spelled wrong and the other
+/// occurrences of the word the cursor rests on are drawn by styling the
+/// cells they cover, and the cursor is drawn on top of that, in the
+/// focused pane only. Buffers a language server has open get a gutter
+/// flagging lines with diagnostics, and files git tracks one marking the
+/// lines changed since the last commit; the gutter can number lines too,
+/// or count them from the cursor's. Tabs, trailing spaces and non-breaking
+/// spaces can be shown, dimmed, as can guides down each level of
+/// indentation and a column marking how long lines should get; long lines
+/// can be wrapped rather than scrolled sideways. Text longer than its pane
+/// gets a scrollbar down the right edge. The mouse places the cursor,
+/// drags out selections and drags the scrollbar.
+pub struct EditorView {
+    workspace: Arc<Mutex<Workspace>>,
+    /// Panes and separators placed during the last layout.
+    regions: Vec<Region>,
+    /// What the mouse is doing while its button is held.
+    drag: Option<Drag>,
+}
+
+/// What dragging the mouse does, depending on where it was pressed.
+#[derive(Clone, Copy, Debug)]
+enum Drag {
+    /// Selects from the offset pressed on.
+    Select(usize),
+    /// Scrolls the focused pane, whose scrollbar was pressed on.
+    Scroll,
+}
+
+impl EditorView {
+    /// Creates a view over the given workspace.
+    pub fn new(workspace: Arc<Mutex<Workspace>>) -> Self {
+        Self {
+            workspace,
+            regions: Vec::new(),
+            drag: None,
+        }
+    }
+
+    /// Where the cursor of the focused pane is on screen, as of the last layout.
+    pub fn cursor_position(&self) -> Option<Vec2> {
+        let ws = self.workspace.lock().unwrap();
+        let rect = self.regions.iter().find_map(|region| match *region {
+            Region::Pane(index, rect) if index == ws.focus() => Some(rect),
+            _ => None,
+        })?;
+        let ed = ws.editor();
+        let pane = ws.panes()[ws.focus()];
+        let scroll = Vec2::from(pane.scroll);
+        if ed.hex().is_some() {
+            return Some(rect.top_left() + Vec2::from(cursor_cell(ed)).saturating_sub(scroll));
+        }
+        let buffer = ed.buffer();
+        let line = buffer.line_of(ed.cursor());
+        let index = ed.cursor() - buffer.line_start(line);
+        let rows = screen_rows(&ws, ed, pane.scroll.1, (pane.width, rect.height()).into());
+        let y = rows
+            .iter()
+            .rposition(|(i, row)| *i == line && row.start <= index)?;
+        let text = buffer.line(line);
+        let col = display_width(&text[rows[y].1.start..index]).saturating_sub(scroll.x);
+        Some(rect.top_left() + Vec2::new(col + gutter_width(&ws, ed), y))
+    }
+
+    /// Where the focused pane was placed during the last layout.
+    fn focused_rect(&self, ws: &Workspace) -> Option<Rect> {
+        self.regions.iter().find_map(|region| match *region {
+            Region::Pane(index, rect) if index == ws.focus() => Some(rect),
+            _ => None,
+        })
+    }
+
+    /// Clicking puts the cursor on the cell clicked, in the pane clicked,
+    /// and dragging from there selects to the cell under the mouse.
+    fn on_mouse(&mut self, at: Vec2, mouse: MouseEvent) -> EventResult {
+        let workspace = self.workspace.clone();
+        let mut ws = workspace.lock().unwrap();
+        match mouse {
+            MouseEvent::Press(MouseButton::Left) => {
+                let Some((index, rect)) = self.regions.iter().find_map(|region| match *region {
+                    Region::Pane(index, rect) if rect.contains(at) => Some((index, rect)),
+                    _ => None,
+                }) else {
+                    return EventResult::Ignored;
+                };
+                ws.focus_pane(index);
+                self.drag = None;
+                if at.x == rect.right() && has_scrollbar(ws.editor(), rect.height()) {
+                    scroll_to_row(&mut ws, rect, at.y);
+                    self.drag = Some(Drag::Scroll);
+                    return EventResult::Consumed(None);
+                }
+                let Some(offset) = self.offset_at(&ws, at) else {
+                    return EventResult::Consumed(None);
+                };
+                let ed = ws.editor_mut();
+                ed.clear_carets();
+                ed.clear_selection();
+                ed.set_cursor(offset);
+                self.drag = Some(Drag::Select(offset));
+            }
+            MouseEvent::Hold(MouseButton::Left) => match self.drag {
+                Some(Drag::Select(anchor)) => {
+                    if let Some(offset) = self.offset_at(&ws, at) {
+                        ws.editor_mut().drag_selection(anchor, offset);
+                    }
+                }
+                Some(Drag::Scroll) => {
+                    if let Some(rect) = self.focused_rect(&ws) {
+                        scroll_to_row(&mut ws, rect, at.y);
+                    }
+                }
+                None => return EventResult::Ignored,
+            },
+            MouseEvent::Release(MouseButton::Left) if self.drag.is_some() => self.drag = None,
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+
+    /// The byte offset of the text shown in cell `at` of the focused pane,
+    /// or of the nearest text when the cell is past the end of its line or
+    /// below the last one, as of the last layout. Hex dumps have none.
+    fn offset_at(&self, ws: &Workspace, at: Vec2) -> Option<usize> {
+        let rect = self.focused_rect(ws)?;
+        let ed = ws.editor();
+        if ed.hex().is_some() {
+            return None;
+        }
+        let buffer = ed.buffer();
+        let pane = ws.panes()[ws.focus()];
+        let rows = screen_rows(ws, ed, pane.scroll.1, (pane.width, rect.height()).into());
+        let Some((line, row)) = rows.get(at.y.saturating_sub(rect.top())).cloned() else {
+            return Some(buffer.len());
+        };
+        let col = pane.scroll.0 + at.x.saturating_sub(rect.left() + gutter_width(ws, ed));
+        Some(buffer.line_start(line) + wrap::index_at(&buffer.line(line), row, col))
+    }
+}
+
+/// The (column, line) of the cell the cursor of `ed` is drawn in, before
+/// scrolling: in the text, or in the hex dump while there is one.
+fn cursor_cell(ed: &Editor) -> (usize, usize) {
+    if let Some(hex) = ed.hex() {
+        return hex.cursor_cell();
+    }
+    let buffer = ed.buffer();
+    let line = buffer.line_of(ed.cursor());
+    let col = display_width(&buffer.slice(buffer.line_start(line)..ed.cursor()));
+    (col, line)
+}
+
+/// Lines to keep in view above and below the cursor's in the focused
+/// pane, `height` lines tall: as many as the settings say, where there are
+/// any, but no more than leave the cursor room in the middle.
+fn scroll_margins(ws: &Workspace, height: usize) -> (usize, usize) {
+    let ed = ws.editor();
+    if ed.hex().is_some() {
+        return (0, 0);
+    }
+    let margin = ws
+        .settings()
+        .scroll_margin
+        .min(height.saturating_sub(1) / 2);
+    let line = ed.buffer().line_of(ed.cursor());
+    let last = ed.buffer().line_count().saturating_sub(1);
+    (margin.min(line), margin.min(last - line))
+}
+
+/// Scrolls the focused pane just enough to keep the cursor cell inside a
+/// viewport of `size`, with the scroll margin around it.
+fn scroll_to_cursor(ws: &mut Workspace, size: Vec2) {
+    if ws.settings().wrap && ws.editor().hex().is_none() {
+        return scroll_to_wrapped_cursor(ws, size);
+    }
+    let (col, line) = cursor_cell(ws.editor());
+    let (above, below) = scroll_margins(ws, size.y);
+    let pane = ws.pane_mut(ws.focus());
+    let mut offset = Vec2::from(pane.scroll);
+
+    if line < offset.y + above {
+        offset.y = line - above;
+    } else if size.y > 0 && line + below >= offset.y + size.y {
+        offset.y = line + below + 1 - size.y;
+    }
+
+    if col < offset.x {
+        offset.x = col;
+    } else if size.x > 0 && col >= offset.x + size.x {
+        offset.x = col + 1 - size.x;
+    }
+
+    pane.scroll = offset.pair();
+}
+
+/// Scrolls the focused pane, its lines wrapped, down to the first line
+/// that keeps the row the cursor is in, and the lines of the scroll margin
+/// below, inside a viewport of `size`, or up to the cursor's line less the
+/// margin above.
+fn scroll_to_wrapped_cursor(ws: &mut Workspace, size: Vec2) {
+    let (margin_above, margin_below) = scroll_margins(ws, size.y);
+    let ed = ws.editor();
+    let buffer = ed.buffer();
+    let line = buffer.line_of(ed.cursor());
+    let mut first = ws.panes()[ws.focus()].scroll.1;
+    if line < first + margin_above {
+        first = line - margin_above;
+    } else if size.y > 0 {
+        // Every line takes a row at least, so the lines further up than
+        // this never fit.
+        first = first.max((line + 1 + margin_below).saturating_sub(size.y));
+        let mut heights: Vec<usize> = (first..line)
+            .map(|i| wrap::rows(&buffer.line(i), size.x).len())
+            .collect();
+        let text = buffer.line(line);
+        let rows = wrap::rows(&text, size.x);
+        let margin: usize = (line + 1..=line + margin_below)
+            .map(|i| wrap::rows(&buffer.line(i), size.x).len())
+            .sum();
+        let below = wrap::row_of(&rows, ed.cursor() - buffer.line_start(line)) + 1 + margin;
+        let mut height: usize = heights.iter().sum::<usize>() + below;
+        heights.reverse();
+        while height > size.y {
+            let Some(top) = heights.pop() else {
+                break;
+            };
+            height -= top;
+            first += 1;
+        }
+    }
+    ws.pane_mut(ws.focus()).scroll = (0, first);
+}
+
+/// The rows of a pane `size` big showing `ed` from line `first` down, as
+/// the line each shows and the bytes of it shown: whole lines, or the rows
+/// wrapping splits them into.
+fn screen_rows(
+    ws: &Workspace,
+    ed: &Editor,
+    first: usize,
+    size: Vec2,
+) -> Vec<(usize, Range<usize>)> {
+    let buffer = ed.buffer();
+    let mut rows = Vec::new();
+    for line in first..buffer.line_count() {
+        let text = buffer.line(line);
+        if ws.settings().wrap {
+            rows.extend(wrap::rows(&text, size.x).into_iter().map(|row| (line, row)));
+        } else {
+            rows.push((line, 0..text.len()));
+        }
+        if rows.len() >= size.y {
+            rows.truncate(size.y);
+            break;
+        }
+    }
+    rows
+}
+
+/// Whether a pane `height` lines tall showing `ed` gets a scrollbar: only
+/// text with more lines than fit does.
+fn has_scrollbar(ed: &Editor, height: usize) -> bool {
+    ed.hex().is_none() && ed.buffer().line_count() > height
+}
+
+/// Draws a scrollbar a column wide filling `printer`, its thumb as long
+/// and as far down as the lines from `first` that fit are in all `lines`.
+fn draw_scrollbar(printer: &Printer, lines: usize, first: usize) {
+    let height = printer.size.y;
+    let length = (height * height / lines.max(1)).clamp(1, height);
+    let start = (first * height / lines.max(1)).min(height - length);
+    printer.with_color(ColorStyle::secondary(), |printer| {
+        printer.print_vline((0, 0), height, "│");
+    });
+    printer.with_color(ColorStyle::highlight(), |printer| {
+        printer.print_vline((0, start), length, " ");
+    });
+}
+
+/// Scrolls the focused pane, placed at `rect`, to where row `y` of its
+/// scrollbar stands for, bringing the cursors along to stay in view.
+fn scroll_to_row(ws: &mut Workspace, rect: Rect, y: usize) {
+    let lines = ws.editor().buffer().line_count();
+    let height = rect.height();
+    let row = y.saturating_sub(rect.top()).min(height.saturating_sub(1));
+    let first = (row * lines / height.max(1)).min(lines.saturating_sub(height));
+    let ed = ws.editor();
+    let line = ed.buffer().line_of(ed.cursor());
+    let margin = ws
+        .settings()
+        .scroll_margin
+        .min(height.saturating_sub(1) / 2);
+    let top = if first == 0 { 0 } else { first + margin };
+    let bottom = if first + height >= lines {
+        lines.saturating_sub(1)
+    } else {
+        first + height - 1 - margin
+    };
+    let target = line.clamp(top, bottom.max(top));
+    ws.editor_mut()
+        .for_each_cursor(|ed| ed.move_lines(target as isize - line as isize));
+    ws.pane_mut(ws.focus()).scroll.1 = first;
+}
+
+/// The color text of the kind `token` is drawn in.
+pub fn token_color(token: Token) -> Color {
+    match token {
+        Token::Keyword => Color::Light(BaseColor::Yellow),
+        Token::Type => Color::Light(BaseColor::Cyan),
+        Token::String => Color::Light(BaseColor::Green),
+        Token::Number => Color::Light(BaseColor::Magenta),
+        Token::Comment => Color::Dark(BaseColor::Cyan),
+    }
+}
+
+/// The style of text colored as `token`, on the editing background.
+fn token_style(token: Token) -> StyleType {
+    ColorStyle::new(token_color(token), PaletteColor::Secondary).into()
+}
+
+/// What stands in for `ch` while white space is shown: tabs, spaces that
+/// are `trailing` and non-breaking spaces get a glyph; the rest none.
+fn whitespace_glyph(ch: char, trailing: bool) -> Option<&'static str> {
+    match ch {
+        '\t' => Some("→"),
+        '\u{a0}' | '\u{202f}' => Some("␣"),
+        ' ' if trailing => Some("·"),
+        _ => None,
+    }
+}
+
+/// The style of a word spelled wrong: underlined in red, on `background`.
+fn misspelled_style(background: ColorType) -> StyleType {
+    let colors = ColorStyle::new(Color::Light(BaseColor::Red), background);
+    Style::from(colors).combine(Effect::Underline).into()
+}
+
+/// The style of the delimiter matching the one under the cursor.
+fn match_style() -> StyleType {
+    let colors = ColorStyle::new(Color::Light(BaseColor::White), Color::Dark(BaseColor::Blue));
+    Style::from(colors).combine(Effect::Bold).into()
+}
+
+/// The style of an occurrence of the word the cursor rests on.
+fn occurrence_style(foreground: ColorType, background: ColorType) -> StyleType {
+    let colors = ColorStyle::new(foreground, background);
+    Style::from(colors)
+        .combine(Effect::Bold)
+        .combine(Effect::Underline)
+        .into()
+}
+
+/// Columns taken by the line numbers in panes showing `ed`: enough for
+/// the last line's, at least three, and a space; none when they're off.
+fn numbers_width(ws: &Workspace, ed: &Editor) -> usize {
+    if ws.settings().line_numbers == LineNumbers::Off {
+        return 0;
+    }
+    ed.buffer().line_count().to_string().len().max(3) + 1
+}
+
+/// Columns taken by the gutter in panes showing `ed`: the line numbers,
+/// then one for each kind of mark and a space after them. Hex dumps have
+/// none.
+fn gutter_width(ws: &Workspace, ed: &Editor) -> usize {
+    if ed.hex().is_some() {
+        return 0;
+    }
+    let marks = usize::from(ws.lsp().is_attached(ed)) + usize::from(ed.git_changes().is_some());
+    let numbers = numbers_width(ws, ed);
+    if marks == 0 {
+        numbers
+    } else {
+        numbers + marks + 1
+    }
+}
+
+/// Draws the gutter beside `lines`, the line starting on each row if one
+/// does: their numbers, then diagnostics, then the changes since the last
+/// commit.
+fn draw_gutter(printer: &Printer, ws: &Workspace, ed: &Editor, lines: &[Option<usize>]) {
+    for x in 0..gutter_width(ws, ed) {
+        printer.print_vline((x, 0), printer.size.y, " ");
+    }
+    let mut x = numbers_width(ws, ed);
+    if x > 0 {
+        draw_numbers(printer, ws, ed, lines);
+    }
+    if ws.lsp().is_attached(ed) {
+        draw_diagnostics(&printer.offset((x, 0)), ws, ed, lines);
+        x += 1;
+    }
+    if let Some(changes) = ed.git_changes() {
+        let printer = printer.offset((x, 0));
+        for (y, line) in lines.iter().enumerate() {
+            let Some(line) = *line else {
+                continue;
+            };
+            let (mark, color) = match changes.sign(line) {
+                Some(Sign::Added) => ("+", BaseColor::Green),
+                Some(Sign::Modified) => ("~", BaseColor::Yellow),
+                Some(Sign::Removed) => ("_", BaseColor::Red),
+                None => continue,
+            };
+            printer.with_color(ColorStyle::front(Color::Light(color)), |printer| {
+                printer.print((0, y), mark);
+            });
+        }
+    }
+}
+
+/// Draws the numbers of `lines`, right-aligned, with the cursor's line
+/// brighter than the rest.
+fn draw_numbers(printer: &Printer, ws: &Workspace, ed: &Editor, lines: &[Option<usize>]) {
+    let buffer = ed.buffer();
+    let current = buffer.line_of(ed.cursor());
+    let width = numbers_width(ws, ed) - 1;
+    for (y, line) in lines.iter().enumerate() {
+        let Some(line) = line.filter(|&line| line < buffer.line_count()) else {
+            continue;
+        };
+        let number = match ws.settings().line_numbers {
+            LineNumbers::Relative if line != current => line.abs_diff(current),
+            _ => line + 1,
+        };
+        let color = if line == current {
+            Color::Light(BaseColor::Yellow)
+        } else {
+            Color::Dark(BaseColor::White)
+        };
+        printer.with_color(ColorStyle::front(color), |printer| {
+            printer.print((0, y), &format!("{number:>width$}"));
+        });
+    }
+}
+
+/// Draws the column of the gutter flagging lines with diagnostics.
+fn draw_diagnostics(printer: &Printer, ws: &Workspace, ed: &Editor, lines: &[Option<usize>]) {
+    let diagnostics = ed.path().map_or(&[][..], |path| ws.lsp().diagnostics(path));
+    for (y, line) in lines.iter().enumerate() {
+        let Some(line) = *line else {
+            continue;
+        };
+        // The most serious diagnostic on a line decides its mark.
+        let worst = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.line == line)
+            .map(|diagnostic| diagnostic.severity)
+            .min_by_key(|&severity| severity as u8);
+        let (mark, color) = match worst {
+            Some(Severity::Error) => ("E", BaseColor::Red),
+            Some(Severity::Warning) => ("W", BaseColor::Yellow),
+            Some(Severity::Information | Severity::Hint) => ("I", BaseColor::Cyan),
+            None => continue,
+        };
+        printer.with_color(ColorStyle::front(Color::Light(color)), |printer| {
+            printer.print((0, y), mark);
+        });
+    }
+}
+
+/// Draws pane `index` of the workspace, filling `printer`.
+fn draw_pane(printer: &Printer, ws: &Workspace, index: usize) {
+    let pane = ws.panes()[index];
+    let ed = &ws.editors()[pane.editor];
+    if let Some(hex) = ed.hex() {
+        hex.draw(printer, Vec2::from(pane.scroll));
+        return;
+    }
+    let printer = &if has_scrollbar(ed, printer.size.y) && printer.size.x > 1 {
+        let lines = ed.buffer().line_count();
+        draw_scrollbar(
+            &printer.offset((printer.size.x - 1, 0)),
+            lines,
+            pane.scroll.1,
+        );
+        printer.cropped((printer.size.x - 1, printer.size.y))
+    } else {
+        printer.clone()
+    };
+    let gutter = gutter_width(ws, ed);
+    let rows = screen_rows(
+        ws,
+        ed,
+        pane.scroll.1,
+        printer.size.saturating_sub((gutter, 0)),
+    );
+    if gutter > 0 {
+        // Rows a line wraps onto get no marks; those past the last line are
+        // counted on from it.
+        let next = rows.last().map_or(pane.scroll.1, |(line, _)| line + 1);
+        let lines: Vec<Option<usize>> = rows
+            .iter()
+            .map(|(line, row)| (row.start == 0).then_some(*line))
+            .chain((next..).map(Some))
+            .take(printer.size.y)
+            .collect();
+        draw_gutter(printer, ws, ed, &lines);
+    }
+    let printer = &printer.offset((gutter, 0));
+    let buffer = ed.buffer();
+    // A pane showing the buffer another has focus in has cursors of its own.
+    let carets = ed.parked(index).map_or_else(|| ed.carets(), <[_]>::to_vec);
+    let is_cursor = |byte: usize| carets.iter().any(|caret| caret.cursor == byte);
+    let is_selected = |byte: usize| {
+        carets
+            .iter()
+            .any(|caret| caret.selection.is_some_and(|sel| sel.contains(byte)))
+    };
+    let query = ws.search_query();
+    let offset = Vec2::from(pane.scroll);
+    let matching = printer.focused.then(|| ed.matching_delimiter()).flatten();
+    let dictionary = ws.dictionary().filter(|_| ed.is_spell_checked());
+    let code = ed.highlighter().filetype() != Filetype::Plain;
+    let settings = ws.settings();
+    let whitespace = settings.whitespace;
+    // The word the cursor has rested on, and where it is.
+    let resting = printer
+        .focused
+        .then(|| ed.word_at_cursor())
+        .flatten()
+        .filter(|_| ws.idle_time() >= REST)
+        .map(|word| (buffer.slice(word.clone()), word));
+    // The column marking the width lines should keep to, counted from 0.
+    let column = settings.color_column.map(|column| column - 1);
+    // The cursor's line stands out only where the cursor is drawn.
+    let current_line = settings
+        .current_line
+        .filter(|_| printer.focused)
+        .map(|color| (buffer.line_of(ed.cursor()), color));
+    let selection_style = |foreground: ColorType| -> StyleType {
+        match settings.selection {
+            Some(background) => ColorStyle::new(foreground, background).into(),
+            None => PaletteStyle::Highlight.into(),
+        }
+    };
+
+    printer.with_style(PaletteStyle::EditableText, |printer| {
+        for y in 0..printer.size.y {
+            printer.print_hline((0, y), printer.size.x, " ");
+        }
+    });
+
+    let mut y = 0;
+    for line_rows in rows.chunk_by(|a, b| a.0 == b.0) {
+        let i = line_rows[0].0;
+        let line_start = buffer.line_start(i);
+        let line = buffer.line(i);
+        let line_end = line_start + line.len();
+        let current = current_line.filter(|&(line, _)| line == i);
+        let background: ColorType = match current {
+            Some((_, color)) => color.into(),
+            None => PaletteColor::Secondary.into(),
+        };
+        let spans = ed.highlighter().spans(i);
+        // Where the spaces and tabs at the end of the line start, and
+        // where those at its start end.
+        let trailing = line.trim_end_matches([' ', '\t']).len();
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let misspelled = dictionary.map_or_else(Vec::new, |dictionary| {
+            spell::misspelled(dictionary, &line, spans, code)
+        });
+
+        // Search matches never span lines, since the prompt is a single line.
+        let matches: Vec<(usize, usize)> = query
+            .map(|query| {
+                line.match_indices(query)
+                    .map(|(pos, m)| (line_start + pos, line_start + pos + m.len()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let occurrences: Vec<Range<usize>> = resting
+            .as_ref()
+            .map(|(text, word)| {
+                line.match_indices(text.as_str())
+                    .map(|(pos, _)| line_start + pos..line_start + pos + text.len())
+                    .filter(|range| range != word && motion::is_whole_word(buffer, range.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (_, row) in line_rows {
+            if current.is_some() {
+                printer.with_color(ColorStyle::new(PaletteColor::View, background), |printer| {
+                    printer.print_hline((0, y), printer.size.x, " ");
+                });
+            }
+            let mut x = 0;
+            for (idx, ch) in line[row.clone()].char_indices() {
+                let idx = row.start + idx;
+                let byte = line_start + idx;
+                let width = char_width(ch, x);
+                if x < offset.x {
+                    x += width;
+                    continue;
+                }
+                let screen_x = x - offset.x;
+                if screen_x >= printer.size.x {
+                    break;
+                }
+
+                let token = spans
+                    .iter()
+                    .find(|(range, _)| range.contains(&idx))
+                    .map(|&(_, token)| token);
+                // Indentation gets a guide at the start of every level.
+                let guide = settings.indent_guides && idx < indent && x % TAB_WIDTH == 0;
+                let glyph = whitespace
+                    .then(|| whitespace_glyph(ch, idx >= trailing))
+                    .flatten()
+                    .or(guide.then_some("│"));
+                let foreground: ColorType =
+                    token.map_or(PaletteColor::View.into(), |token| token_color(token).into());
+                let on_column = column.is_some_and(|column| (x..x + width).contains(&column));
+                let background = if on_column { COLUMN.into() } else { background };
+                let style = if printer.focused && is_cursor(byte) {
+                    PaletteStyle::EditableTextCursor.into()
+                } else if matching == Some(byte) {
+                    match_style()
+                } else if is_selected(byte) {
+                    selection_style(foreground)
+                } else if matches
+                    .iter()
+                    .any(|&(start, end)| (start..end).contains(&byte))
+                {
+                    PaletteStyle::HighlightInactive.into()
+                } else if occurrences.iter().any(|range| range.contains(&byte)) {
+                    occurrence_style(foreground, background)
+                } else if misspelled.iter().any(|range| range.contains(&idx)) {
+                    misspelled_style(background)
+                } else if glyph.is_some() {
+                    ColorStyle::new(Color::Light(BaseColor::Black), background).into()
+                } else if current.is_some() || on_column {
+                    ColorStyle::new(foreground, background).into()
+                } else if let Some(token) = token {
+                    token_style(token)
+                } else {
+                    PaletteStyle::EditableText.into()
+                };
+                printer.with_style(style, |printer| {
+                    if let Some(glyph) = glyph {
+                        printer.print_hline((screen_x, y), width, " ");
+                        printer.print((screen_x, y), glyph);
+                    } else if ch == '\t' {
+                        printer.print_hline((screen_x, y), width, " ");
+                    } else {
+                        let mut buf = [0; 4];
+                        printer.print((screen_x, y), ch.encode_utf8(&mut buf));
+                    }
+                });
+                x += width;
+            }
+            // Blank lines carry on the guides of the next line that isn't.
+            if settings.indent_guides && line.trim().is_empty() {
+                let depth = (i + 1..buffer.line_count())
+                    .take(printer.size.y)
+                    .map(|next| buffer.line(next))
+                    .find(|next| !next.trim().is_empty())
+                    .map_or(0, |next| {
+                        display_width(&next[..next.len() - next.trim_start().len()])
+                    });
+                printer.with_color(
+                    ColorStyle::new(Color::Light(BaseColor::Black), background),
+                    |printer| {
+                        for guide in (x.next_multiple_of(TAB_WIDTH)..depth).step_by(TAB_WIDTH) {
+                            if guide >= offset.x {
+                                printer.print((guide - offset.x, y), "│");
+                            }
+                        }
+                    },
+                );
+            }
+            if let Some(column) = column.filter(|&column| column >= x.max(offset.x)) {
+                printer.with_color(ColorStyle::new(PaletteColor::View, COLUMN), |printer| {
+                    printer.print((column - offset.x, y), " ");
+                });
+            }
+
+            // The cell past the end of the line stands in for the newline,
+            // so a selected line break or a cursor at the end stays visible.
+            if row.end == line.len() && x >= offset.x && x - offset.x < printer.size.x {
+                let screen_x = x - offset.x;
+                if printer.focused && is_cursor(line_end) {
+                    printer.with_style(PaletteStyle::EditableTextCursor, |printer| {
+                        printer.print((screen_x, y), "_");
+                    });
+                } else if line_end < buffer.len() && is_selected(line_end) {
+                    printer.with_style(selection_style(PaletteColor::View.into()), |printer| {
+                        printer.print((screen_x, y), " ");
+                    });
+                }
+                // Blame goes a little way past the end of the line, dimmed.
+                if let Some(blame) = ed.blame(i) {
+                    printer.with_color(
+                        ColorStyle::front(Color::Dark(BaseColor::White)),
+                        |printer| {
+                            printer.print((screen_x + 3, y), blame);
+                        },
+                    );
+                }
+            }
+            y += 1;
+        }
+    }
+}
+
+impl View for EditorView {
+    fn draw(&self, printer: &Printer) {
+        let ws = self.workspace.lock().unwrap();
+        for region in &self.regions {
+            match *region {
+                Region::Pane(index, rect) => {
+                    let printer = printer.windowed(rect).focused(index == ws.focus());
+                    draw_pane(&printer, &ws, index);
+                }
+                Region::Separator(Orientation::Vertical, start, len) => {
+                    printer.print_vline(start, len, "│");
+                }
+                Region::Separator(Orientation::Horizontal, start, len) => {
+                    printer.print_hline(start, len, "─");
+                }
+            }
+        }
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        let mut ws = self.workspace.lock().unwrap();
+        self.regions = ws.layout().arrange(Rect::from_size((0, 0), size));
+        for region in &self.regions {
+            if let Region::Pane(index, rect) = *region {
+                let ed = &ws.editors()[ws.panes()[index].editor];
+                let margins = gutter_width(&ws, ed) + usize::from(has_scrollbar(ed, rect.height()));
+                let pane = ws.pane_mut(index);
+                pane.height = rect.height();
+                pane.width = rect.width().saturating_sub(margins);
+                if index == ws.focus() {
+                    scroll_to_cursor(&mut ws, rect.size().saturating_sub((margins, 0)));
+                }
+                // Only the lines down to the bottom of the pane need colors yet.
+                let pane = ws.panes()[index];
+                let ed = &mut ws.editors_mut()[pane.editor];
+                ed.highlight(pane.scroll.1 + rect.size().y);
+                ed.update_git();
+            }
+        }
+        // Every change to the text gets here before it's drawn.
+        ws.sync_lsp();
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        constraint
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        if let Event::Mouse {
+            offset,
+            position,
+            event,
+        } = event
+        {
+            return self.on_mouse(position.saturating_sub(offset), event);
+        }
+        let mut ws = self.workspace.lock().unwrap();
+        // Digits typed after `count` make up the count, rather than text.
+        if let Event::Char(ch) = event {
+            if ch
+                .to_digit(10)
+                .is_some_and(|digit| ws.type_count_digit(digit))
+            {
+                return EventResult::Consumed(None);
+            }
+        }
+        let ed = ws.editor_mut();
+        if ed.hex().is_some() {
+            // The keys that move through the text or delete it run their
+            // commands here too, which work on the bytes instead.
+            if matches!(event, Event::Key(key) if TEXT_KEYS.contains(&key)) {
+                drop(ws);
+                return hints::press(&self.workspace, &event).unwrap_or(EventResult::Ignored);
+            }
+            return on_hex_event(ws, event);
+        }
+        match ws.grammar().mode() {
+            Mode::Normal => return grammar::on_event(ws, &self.workspace, event),
+            Mode::Insert if event == Event::Key(Key::Esc) && !ws.editor().in_snippet() => {
+                ws.grammar_mut().leave_insert();
+                return EventResult::Consumed(None);
+            }
+            _ => {}
+        }
+        // The keys that move through the text or delete it run whatever
+        // command the keymap binds them to, like any other key.
+        if matches!(event, Event::Key(key) if TEXT_KEYS.contains(&key)) {
+            drop(ws);
+            return hints::press(&self.workspace, &event).unwrap_or(EventResult::Ignored);
+        }
+        let options = ws.filetype_options();
+        let ed = ws.editor_mut();
+        // Keys that type text do nothing in a read-only buffer.
+        let typing = matches!(
+            event,
+            Event::Char(_) | Event::Key(Key::Enter | Key::Tab) | Event::Shift(Key::Tab)
+        );
+        if typing && ed.is_read_only() {
+            return EventResult::Ignored;
+        }
+        // Edits and motions happen at every cursor.
+        match event {
+            Event::Char(ch) => ws.type_char(ch, false),
+            Event::Key(Key::Enter) => ws.type_newline(),
+            // While a snippet is being filled in, Tab moves between its placeholders.
+            Event::Key(Key::Tab) if ed.in_snippet() => ed.next_stop(),
+            Event::Shift(Key::Tab) if ed.in_snippet() => ed.previous_stop(),
+            Event::Key(Key::Esc) if ed.in_snippet() => ed.end_snippet(),
+            // Tab over a selection of several lines indents them all.
+            Event::Key(Key::Tab) if ed.selected_lines().is_some() => {
+                ed.for_each_cursor(|ed| ed.indent(&options))
+            }
+            Event::Key(Key::Tab) => ed.for_each_cursor(|ed| ed.insert_tab(&options)),
+            Event::Shift(Key::Tab) => ed.for_each_cursor(|ed| ed.dedent(&options)),
+            Event::Key(Key::Esc) if ed.carets().len() > 1 => ed.clear_carets(),
+            _ => return EventResult::Ignored,
+        }
+        // Counts are for commands; a key handled here drops one.
+        ws.take_count();
+        EventResult::Consumed(None)
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::consumed())
+    }
+
+    fn important_area(&self, view_size: Vec2) -> Rect {
+        Rect::from_size((0, 0), view_size)
+    }
+}
+
+/// Handles the keys that type in a pane showing a hex dump: hex digits or
+/// text type over bytes, depending on the side Tab last switched to, and
+/// Insert adds a byte. Motions come through their commands.
+fn on_hex_event(mut ws: MutexGuard<Workspace>, event: Event) -> EventResult {
+    let ed = ws.editor_mut();
+    let Some(hex) = ed.hex_mut() else {
+        return EventResult::Ignored;
+    };
+    match event {
+        Event::Key(Key::Tab) => hex.switch_side(),
+        Event::Char(ch) => ed.edit_hex(|hex| hex.type_char(ch)),
+        Event::Key(Key::Ins) => ed.edit_hex(Hex::insert),
+        _ => return EventResult::Ignored,
+    }
+    ws.take_count();
+    EventResult::Consumed(None)
+}
@@ -0,0 +1,1623 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Bytes in a page of linear memory.
+pub const PAGE: usize = 1 << 16;
+
+/// Most locals a function may declare, so a module can't ask for more
+/// memory than it's allowed through them.
+const MAX_LOCALS: usize = 50_000;
+
+/// Most calls deep a module may go before it's stopped, and the most
+/// locals all of them may hold between them.
+const MAX_DEPTH: usize = 10_000;
+const MAX_LOCAL_SLOTS: usize = 1 << 20;
+
+/// How many instructions run between looks at the clock.
+const CLOCK_EVERY: u32 = 1 << 14;
+
+/// A value a function takes or gives back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+/// The type of a `Value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+/// What a function takes and gives back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// How much a module may use while it runs.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The most bytes of linear memory it may have.
+    pub memory: usize,
+    /// How long it may run for, from being instantiated to its last call.
+    pub time: Duration,
+}
+
+/// Why a module couldn't be loaded or run.
+#[derive(Debug)]
+pub enum Error {
+    /// The module is malformed, or needs something this interpreter
+    /// doesn't have.
+    Invalid(String),
+    /// The module trapped, as WebAssembly does on errors at run time.
+    Trap(String),
+    /// The module ran longer than its limit.
+    OutOfTime(Duration),
+    /// The module wanted more memory than its limit, in bytes.
+    OutOfMemory(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Invalid(message) => f.write_str(message),
+            Error::Trap(message) => write!(f, "it trapped: {message}"),
+            Error::OutOfTime(time) => write!(f, "it ran for longer than {time:?}"),
+            Error::OutOfMemory(bytes) => {
+                write!(f, "it needed more than {} MiB of memory", bytes >> 20)
+            }
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Invalid(message.into())
+}
+
+fn trap(message: &str) -> Error {
+    Error::Trap(message.to_string())
+}
+
+/// A WebAssembly module, decoded and ready to be instantiated.
+///
+/// This is a small interpreter of the 1.0 instruction set, plus the
+/// sign-extension, saturating conversion and bulk memory instructions that
+/// compilers now emit by default. It's meant for running code the user
+/// didn't write without trusting it: a module imports nothing, so it can
+/// only compute with what it's given, and it runs within `Limits`. It
+/// doesn't type-check code ahead of time the way a full runtime does; a
+/// module that's wrong in a way that matters stops with an error when it
+/// gets there.
+#[derive(Default)]
+pub struct Module {
+    types: Vec<FuncType>,
+    funcs: Vec<Func>,
+    /// The table's initial size, for `call_indirect`.
+    table: Option<u32>,
+    /// The memory's initial and most pages.
+    memory: Option<(u32, Option<u32>)>,
+    globals: Vec<Const>,
+    /// The functions exported, by name.
+    exports: HashMap<String, u32>,
+    start: Option<u32>,
+    /// Where each element segment goes in the table, and what it holds.
+    elements: Vec<(Const, Vec<u32>)>,
+    data: Vec<Data>,
+}
+
+/// A function's code.
+struct Func {
+    ty: u32,
+    /// How many locals it declares besides its parameters.
+    locals: usize,
+    code: Vec<u8>,
+    /// Where each block, loop and `if` in the code has its `else` and its
+    /// `end`, by the offset of its opcode.
+    blocks: HashMap<usize, Block>,
+}
+
+#[derive(Clone, Copy)]
+struct Block {
+    else_at: Option<usize>,
+    end: usize,
+}
+
+/// A constant expression, as globals and segments are initialised with.
+#[derive(Clone, Copy)]
+enum Const {
+    Value(u64),
+    Global(u32),
+}
+
+/// A data segment: the bytes, and where in memory they go if they're put
+/// there on instantiation.
+struct Data {
+    bytes: Vec<u8>,
+    offset: Option<Const>,
+}
+
+/// A module instantiated: its memory, globals and table, and the machine
+/// running its functions.
+pub struct Instance<'m> {
+    module: &'m Module,
+    memory: Vec<u8>,
+    /// The most pages memory may grow to, and the most the module itself
+    /// allows, which the limits may make fewer.
+    max_pages: usize,
+    module_max_pages: usize,
+    /// The limits, and whether memory didn't grow because of them.
+    limits: Limits,
+    refused: bool,
+    deadline: Instant,
+    globals: Vec<u64>,
+    table: Vec<Option<u32>>,
+    dropped: Vec<bool>,
+    stack: Vec<u64>,
+    locals: Vec<u64>,
+    labels: Vec<Label>,
+    frames: Vec<Frame>,
+}
+
+/// A block being run: where the stack started below its parameters, how
+/// many values a branch to it carries, and where a branch goes.
+#[derive(Clone, Copy)]
+struct Label {
+    height: usize,
+    arity: usize,
+    target: usize,
+    is_loop: bool,
+}
+
+/// A function being run, and where it is.
+#[derive(Clone, Copy)]
+struct Frame {
+    func: usize,
+    pc: usize,
+    locals: usize,
+    labels: usize,
+    height: usize,
+}
+
+/// Reads the binary format.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+/// What a block takes and gives back.
+enum BlockType {
+    Empty,
+    Value,
+    Index(u32),
+}
+
+impl Value {
+    pub fn ty(self) -> ValType {
+        match self {
+            Value::I32(_) => ValType::I32,
+            Value::I64(_) => ValType::I64,
+            Value::F32(_) => ValType::F32,
+            Value::F64(_) => ValType::F64,
+        }
+    }
+
+    fn raw(self) -> u64 {
+        match self {
+            Value::I32(n) => n.into_raw(),
+            Value::I64(n) => n.into_raw(),
+            Value::F32(n) => n.into_raw(),
+            Value::F64(n) => n.into_raw(),
+        }
+    }
+
+    fn from_raw(ty: ValType, raw: u64) -> Self {
+        match ty {
+            ValType::I32 => Value::I32(Raw::from_raw(raw)),
+            ValType::I64 => Value::I64(Raw::from_raw(raw)),
+            ValType::F32 => Value::F32(Raw::from_raw(raw)),
+            ValType::F64 => Value::F64(Raw::from_raw(raw)),
+        }
+    }
+}
+
+/// How values are kept on the stack: as 64 bits, whatever their type.
+trait Raw: Copy {
+    fn from_raw(raw: u64) -> Self;
+    fn into_raw(self) -> u64;
+}
+
+impl Raw for u32 {
+    fn from_raw(raw: u64) -> Self {
+        raw as u32
+    }
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+}
+
+impl Raw for i32 {
+    fn from_raw(raw: u64) -> Self {
+        raw as u32 as i32
+    }
+    fn into_raw(self) -> u64 {
+        self as u32 as u64
+    }
+}
+
+impl Raw for u64 {
+    fn from_raw(raw: u64) -> Self {
+        raw
+    }
+    fn into_raw(self) -> u64 {
+        self
+    }
+}
+
+impl Raw for i64 {
+    fn from_raw(raw: u64) -> Self {
+        raw as i64
+    }
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+}
+
+impl Raw for f32 {
+    fn from_raw(raw: u64) -> Self {
+        f32::from_bits(raw as u32)
+    }
+    fn into_raw(self) -> u64 {
+        self.to_bits() as u64
+    }
+}
+
+impl Raw for f64 {
+    fn from_raw(raw: u64) -> Self {
+        f64::from_bits(raw)
+    }
+    fn into_raw(self) -> u64 {
+        self.to_bits()
+    }
+}
+
+impl Raw for bool {
+    fn from_raw(raw: u64) -> Self {
+        raw != 0
+    }
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| invalid("it ends too soon"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid("it ends too soon"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            result |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return u32::try_from(result).map_err(|_| invalid("an integer is too big"));
+            }
+            if shift >= 35 {
+                return Err(invalid("an integer is too long"));
+            }
+        }
+    }
+
+    /// Reads a signed integer of `bits` bits.
+    fn signed(&mut self, bits: u32) -> Result<i64, Error> {
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < 64 {
+                result |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+            if shift >= bits {
+                return Err(invalid("an integer is too long"));
+            }
+        }
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(self.signed(32)? as i32)
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        self.signed(64)
+    }
+
+    fn count(&mut self) -> Result<usize, Error> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let len = self.count()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("a name isn't UTF-8"))
+    }
+
+    fn val_type(&mut self) -> Result<ValType, Error> {
+        match self.byte()? {
+            0x7f => Ok(ValType::I32),
+            0x7e => Ok(ValType::I64),
+            0x7d => Ok(ValType::F32),
+            0x7c => Ok(ValType::F64),
+            byte => Err(invalid(format!("it uses an unsupported type {byte:#04x}"))),
+        }
+    }
+
+    fn block_type(&mut self) -> Result<BlockType, Error> {
+        match self.bytes.get(self.pos) {
+            Some(0x40) => {
+                self.pos += 1;
+                Ok(BlockType::Empty)
+            }
+            Some(0x7c..=0x7f) => {
+                self.pos += 1;
+                Ok(BlockType::Value)
+            }
+            _ => match u32::try_from(self.signed(33)?) {
+                Ok(index) => Ok(BlockType::Index(index)),
+                Err(_) => Err(invalid("a block has an unknown type")),
+            },
+        }
+    }
+
+    /// Reads memory or table limits: the initial size, and the most.
+    fn limits(&mut self) -> Result<(u32, Option<u32>), Error> {
+        match self.byte()? {
+            0 => Ok((self.u32()?, None)),
+            1 => Ok((self.u32()?, Some(self.u32()?))),
+            _ => Err(invalid("it uses shared or 64-bit memory")),
+        }
+    }
+
+    /// Reads a constant expression, up to its `end`.
+    fn constant(&mut self) -> Result<Const, Error> {
+        let value = match self.byte()? {
+            0x41 => Const::Value(self.i32()?.into_raw()),
+            0x42 => Const::Value(self.i64()?.into_raw()),
+            0x43 => Const::Value(u32::from_le_bytes(self.array()?).into_raw()),
+            0x44 => Const::Value(u64::from_le_bytes(self.array()?)),
+            0x23 => Const::Global(self.u32()?),
+            _ => return Err(invalid("an initialiser isn't a constant")),
+        };
+        match self.byte()? {
+            0x0b => Ok(value),
+            _ => Err(invalid("an initialiser isn't a constant")),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    /// Skips what follows `op` in code.
+    fn skip_immediates(&mut self, op: u8) -> Result<(), Error> {
+        match op {
+            0x00 | 0x01 | 0x0f | 0x1a | 0x1b | 0x45..=0xc4 => {}
+            0x0c | 0x0d | 0x10 | 0x20..=0x24 => {
+                self.u32()?;
+            }
+            0x0e => {
+                for _ in 0..=self.u32()? {
+                    self.u32()?;
+                }
+            }
+            0x11 => {
+                self.u32()?;
+                self.u32()?;
+            }
+            0x1c => {
+                let count = self.count()?;
+                self.take(count)?;
+            }
+            0x28..=0x3e => {
+                self.u32()?;
+                self.u32()?;
+            }
+            0x3f | 0x40 => {
+                self.byte()?;
+            }
+            0x41 => {
+                self.i32()?;
+            }
+            0x42 => {
+                self.i64()?;
+            }
+            0x43 => {
+                self.take(4)?;
+            }
+            0x44 => {
+                self.take(8)?;
+            }
+            0xfc => match self.u32()? {
+                0..=7 => {}
+                8 => {
+                    self.u32()?;
+                    self.byte()?;
+                }
+                9 => {
+                    self.u32()?;
+                }
+                10 => {
+                    self.take(2)?;
+                }
+                11 => {
+                    self.byte()?;
+                }
+                sub => return Err(invalid(format!("it uses instruction 0xfc {sub}"))),
+            },
+            _ => return Err(invalid(format!("it uses instruction {op:#04x}"))),
+        }
+        Ok(())
+    }
+}
+
+/// Finds where each block in `code` has its `else` and its `end`.
+fn blocks(code: &[u8]) -> Result<HashMap<usize, Block>, Error> {
+    let mut r = Reader::new(code);
+    let mut open: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut blocks = HashMap::new();
+    loop {
+        let at = r.pos;
+        match r.byte()? {
+            0x02..=0x04 => {
+                r.block_type()?;
+                open.push((at, None));
+            }
+            0x05 => match open.last_mut() {
+                Some((_, else_at)) => *else_at = Some(at),
+                None => return Err(invalid("an `else` is outside any `if`")),
+            },
+            0x0b => match open.pop() {
+                Some((start, else_at)) => {
+                    blocks.insert(start, Block { else_at, end: at });
+                }
+                None if r.done() => return Ok(blocks),
+                None => return Err(invalid("a function goes on after its end")),
+            },
+            op => r.skip_immediates(op)?,
+        }
+    }
+}
+
+impl Module {
+    /// Decodes the module in `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(bytes);
+        if r.take(4).ok() != Some(b"\0asm") {
+            return Err(invalid("it isn't WebAssembly"));
+        }
+        if r.take(4)? != [1, 0, 0, 0] {
+            return Err(invalid("it's for an unknown version of WebAssembly"));
+        }
+        let mut module = Module::default();
+        let mut func_types = Vec::new();
+        while !r.done() {
+            let id = r.byte()?;
+            let size = r.count()?;
+            let mut s = Reader::new(r.take(size)?);
+            match id {
+                // Custom sections, and the count of data segments.
+                0 | 12 => {}
+                1 => {
+                    for _ in 0..s.u32()? {
+                        if s.byte()? != 0x60 {
+                            return Err(invalid("a type isn't a function type"));
+                        }
+                        let params = (0..s.u32()?)
+                            .map(|_| s.val_type())
+                            .collect::<Result<_, _>>()?;
+                        let results = (0..s.u32()?)
+                            .map(|_| s.val_type())
+                            .collect::<Result<_, _>>()?;
+                        module.types.push(FuncType { params, results });
+                    }
+                }
+                2 => {
+                    if s.u32()? > 0 {
+                        let from = s.name()?;
+                        let name = s.name()?;
+                        return Err(invalid(format!(
+                            "it imports `{from}.{name}`, and nothing can be imported"
+                        )));
+                    }
+                }
+                3 => {
+                    for _ in 0..s.u32()? {
+                        func_types.push(s.u32()?);
+                    }
+                }
+                4 => {
+                    for _ in 0..s.u32()? {
+                        if s.byte()? != 0x70 || module.table.is_some() {
+                            return Err(invalid("it has tables other than one of functions"));
+                        }
+                        module.table = Some(s.limits()?.0);
+                    }
+                }
+                5 => {
+                    for _ in 0..s.u32()? {
+                        if module.memory.is_some() {
+                            return Err(invalid("it has more than one memory"));
+                        }
+                        module.memory = Some(s.limits()?);
+                    }
+                }
+                6 => {
+                    for _ in 0..s.u32()? {
+                        s.val_type()?;
+                        s.byte()?;
+                        module.globals.push(s.constant()?);
+                    }
+                }
+                7 => {
+                    for _ in 0..s.u32()? {
+                        let name = s.name()?;
+                        let kind = s.byte()?;
+                        let index = s.u32()?;
+                        if kind == 0 {
+                            module.exports.insert(name, index);
+                        }
+                    }
+                }
+                8 => module.start = Some(s.u32()?),
+                9 => {
+                    for _ in 0..s.u32()? {
+                        if s.u32()? != 0 {
+                            return Err(invalid("it has an unsupported kind of element segment"));
+                        }
+                        let offset = s.constant()?;
+                        let funcs = (0..s.u32()?).map(|_| s.u32()).collect::<Result<_, _>>()?;
+                        module.elements.push((offset, funcs));
+                    }
+                }
+                10 => {
+                    let count = s.count()?;
+                    if count != func_types.len() {
+                        return Err(invalid("it has code for a different number of functions"));
+                    }
+                    for &ty in &func_types {
+                        let size = s.count()?;
+                        let mut body = Reader::new(s.take(size)?);
+                        let mut locals = 0;
+                        for _ in 0..body.u32()? {
+                            locals += body.count()?;
+                            body.val_type()?;
+                            if locals > MAX_LOCALS {
+                                return Err(invalid("a function has too many locals"));
+                            }
+                        }
+                        let code = body.bytes[body.pos..].to_vec();
+                        module.funcs.push(Func {
+                            ty,
+                            locals,
+                            blocks: blocks(&code)?,
+                            code,
+                        });
+                    }
+                }
+                11 => {
+                    for _ in 0..s.u32()? {
+                        let offset = match s.u32()? {
+                            0 => Some(s.constant()?),
+                            1 => None,
+                            2 => {
+                                s.u32()?;
+                                Some(s.constant()?)
+                            }
+                            _ => return Err(invalid("it has an unknown kind of data segment")),
+                        };
+                        let len = s.count()?;
+                        let bytes = s.take(len)?.to_vec();
+                        module.data.push(Data { bytes, offset });
+                    }
+                }
+                _ => return Err(invalid(format!("it has an unknown section {id}"))),
+            }
+        }
+        if module.funcs.len() != func_types.len() {
+            return Err(invalid("it has functions without code"));
+        }
+        if module
+            .funcs
+            .iter()
+            .any(|f| f.ty as usize >= module.types.len())
+        {
+            return Err(invalid("a function has an unknown type"));
+        }
+        Ok(module)
+    }
+
+    /// The type of the function exported as `name`, if there is one.
+    pub fn export_type(&self, name: &str) -> Option<&FuncType> {
+        let &func = self.exports.get(name)?;
+        self.type_of(func as usize).ok()
+    }
+
+    fn type_of(&self, func: usize) -> Result<&FuncType, Error> {
+        let func = self
+            .funcs
+            .get(func)
+            .ok_or_else(|| invalid("it calls a function that isn't there"))?;
+        Ok(&self.types[func.ty as usize])
+    }
+
+    /// How many values a block takes and gives back.
+    fn arity(&self, ty: BlockType) -> Result<(usize, usize), Error> {
+        match ty {
+            BlockType::Empty => Ok((0, 0)),
+            BlockType::Value => Ok((0, 1)),
+            BlockType::Index(index) => {
+                let ty = self
+                    .types
+                    .get(index as usize)
+                    .ok_or_else(|| invalid("a block has an unknown type"))?;
+                Ok((ty.params.len(), ty.results.len()))
+            }
+        }
+    }
+}
+
+impl<'m> Instance<'m> {
+    /// Instantiates `module` within `limits`: sets up its memory, table and
+    /// globals, and runs its start function if it has one.
+    pub fn new(module: &'m Module, limits: Limits) -> Result<Self, Error> {
+        let (initial, most) = module.memory.unwrap_or((0, Some(0)));
+        let module_max_pages = most.map_or(PAGE, |most| most as usize);
+        let max_pages = module_max_pages.min(limits.memory / PAGE);
+        if initial as usize > max_pages {
+            return Err(Error::OutOfMemory(limits.memory));
+        }
+        let mut instance = Self {
+            module,
+            memory: vec![0; initial as usize * PAGE],
+            max_pages,
+            module_max_pages,
+            limits,
+            refused: false,
+            deadline: Instant::now() + limits.time,
+            globals: Vec::new(),
+            table: vec![None; module.table.unwrap_or(0) as usize],
+            dropped: vec![false; module.data.len()],
+            stack: Vec::new(),
+            locals: Vec::new(),
+            labels: Vec::new(),
+            frames: Vec::new(),
+        };
+        for &global in &module.globals {
+            let value = instance.constant(global)?;
+            instance.globals.push(value);
+        }
+        for (offset, funcs) in &module.elements {
+            let start = instance.constant(*offset)? as u32 as usize;
+            let slots = instance
+                .table
+                .get_mut(start..start + funcs.len())
+                .ok_or_else(|| trap("an element segment doesn't fit in the table"))?;
+            for (slot, &func) in slots.iter_mut().zip(funcs) {
+                *slot = Some(func);
+            }
+        }
+        for (index, data) in module.data.iter().enumerate() {
+            let Some(offset) = data.offset else {
+                continue;
+            };
+            let start = instance.constant(offset)? as u32 as usize;
+            instance
+                .memory
+                .get_mut(start..start + data.bytes.len())
+                .ok_or_else(|| trap("a data segment doesn't fit in memory"))?
+                .copy_from_slice(&data.bytes);
+            instance.dropped[index] = true;
+        }
+        if let Some(start) = module.start {
+            instance.invoke(start as usize, Vec::new())?;
+        }
+        Ok(instance)
+    }
+
+    /// Calls the function exported as `name` with `args`.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Vec<Value>, Error> {
+        let module = self.module;
+        let &func = module
+            .exports
+            .get(name)
+            .ok_or_else(|| invalid(format!("it doesn't export `{name}`")))?;
+        let ty = module.type_of(func as usize)?;
+        if !args
+            .iter()
+            .map(|arg| arg.ty())
+            .eq(ty.params.iter().copied())
+        {
+            return Err(invalid(format!("`{name}` takes other arguments")));
+        }
+        let results = self.invoke(func as usize, args.iter().map(|arg| arg.raw()).collect())?;
+        Ok(ty
+            .results
+            .iter()
+            .zip(results)
+            .map(|(&ty, raw)| Value::from_raw(ty, raw))
+            .collect())
+    }
+
+    /// The module's linear memory.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    fn constant(&self, value: Const) -> Result<u64, Error> {
+        match value {
+            Const::Value(value) => Ok(value),
+            Const::Global(index) => self
+                .globals
+                .get(index as usize)
+                .copied()
+                .ok_or_else(|| invalid("an initialiser reads a global that isn't there")),
+        }
+    }
+
+    /// Runs `func` on `args` to the end, a trap, or a limit. A trap after
+    /// memory couldn't grow because of the limits is put down to them.
+    fn invoke(&mut self, func: usize, args: Vec<u64>) -> Result<Vec<u64>, Error> {
+        self.stack = args;
+        self.locals.clear();
+        self.labels.clear();
+        self.frames.clear();
+        match self.run(func) {
+            Err(Error::Trap(_)) if self.refused => Err(Error::OutOfMemory(self.limits.memory)),
+            result => result,
+        }
+    }
+
+    fn run(&mut self, mut func: usize) -> Result<Vec<u64>, Error> {
+        let module = self.module;
+        let mut r = self.call_into(func, 0)?;
+        let mut steps: u32 = 0;
+        loop {
+            steps = steps.wrapping_add(1);
+            if steps.is_multiple_of(CLOCK_EVERY) && Instant::now() > self.deadline {
+                return Err(Error::OutOfTime(self.limits.time));
+            }
+            let at = r.pos;
+            let op = r.byte()?;
+            // Whether the function is done, by an `end`, `return` or branch.
+            let mut leave = false;
+            match op {
+                0x00 => return Err(trap("unreachable")),
+                0x01 => {}
+                0x02..=0x04 => {
+                    let (params, results) = module.arity(r.block_type()?)?;
+                    let block = *module.funcs[func]
+                        .blocks
+                        .get(&at)
+                        .ok_or_else(|| invalid("a block has no end"))?;
+                    let cond = if op == 0x04 { self.pop()? } else { 1 };
+                    let height = self
+                        .stack
+                        .len()
+                        .checked_sub(params)
+                        .ok_or_else(stack_ran_out)?;
+                    let label = match op {
+                        0x03 => Label {
+                            height,
+                            arity: params,
+                            target: r.pos,
+                            is_loop: true,
+                        },
+                        _ => Label {
+                            height,
+                            arity: results,
+                            target: block.end + 1,
+                            is_loop: false,
+                        },
+                    };
+                    match (cond, block.else_at) {
+                        (0, None) => r.pos = block.end + 1,
+                        (0, Some(else_at)) => {
+                            self.labels.push(label);
+                            r.pos = else_at + 1;
+                        }
+                        _ => self.labels.push(label),
+                    }
+                }
+                0x05 => {
+                    // The end of the branch taken: carry on after the `if`.
+                    let label = self.pop_label()?;
+                    r.pos = label.target;
+                }
+                0x0b => {
+                    if self.labels.len() > self.labels_base() {
+                        self.labels.pop();
+                    } else {
+                        leave = true;
+                    }
+                }
+                0x0c => {
+                    let depth = r.u32()?;
+                    leave = !self.branch(depth, &mut r)?;
+                }
+                0x0d => {
+                    let depth = r.u32()?;
+                    if self.pop()? as u32 != 0 {
+                        leave = !self.branch(depth, &mut r)?;
+                    }
+                }
+                0x0e => {
+                    let index = self.pop()? as u32;
+                    let mut depth = None;
+                    for i in 0..r.u32()? {
+                        let target = r.u32()?;
+                        if i == index {
+                            depth = Some(target);
+                        }
+                    }
+                    let default = r.u32()?;
+                    leave = !self.branch(depth.unwrap_or(default), &mut r)?;
+                }
+                0x0f => leave = true,
+                0x10 => {
+                    let callee = r.u32()? as usize;
+                    r = self.call_into(callee, r.pos)?;
+                    func = callee;
+                }
+                0x11 => {
+                    let ty = r.u32()? as usize;
+                    r.u32()?;
+                    let index = self.pop()? as u32 as usize;
+                    let callee = self
+                        .table
+                        .get(index)
+                        .ok_or_else(|| trap("undefined element"))?
+                        .ok_or_else(|| trap("uninitialized element"))?
+                        as usize;
+                    let expected = module
+                        .types
+                        .get(ty)
+                        .ok_or_else(|| invalid("a call has an unknown type"))?;
+                    if module.type_of(callee)? != expected {
+                        return Err(trap("indirect call type mismatch"));
+                    }
+                    r = self.call_into(callee, r.pos)?;
+                    func = callee;
+                }
+                0x1a => {
+                    self.pop()?;
+                }
+                0x1b | 0x1c => {
+                    if op == 0x1c {
+                        let count = r.count()?;
+                        r.take(count)?;
+                    }
+                    let cond = self.pop()? as u32;
+                    let second = self.pop()?;
+                    let first = self.pop()?;
+                    self.stack.push(if cond != 0 { first } else { second });
+                }
+                0x20 => {
+                    let index = self.local(r.u32()?)?;
+                    self.stack.push(self.locals[index]);
+                }
+                0x21 => {
+                    let index = self.local(r.u32()?)?;
+                    self.locals[index] = self.pop()?;
+                }
+                0x22 => {
+                    let index = self.local(r.u32()?)?;
+                    self.locals[index] = *self.stack.last().ok_or_else(stack_ran_out)?;
+                }
+                0x23 => {
+                    let index = r.u32()? as usize;
+                    let value = *self.globals.get(index).ok_or_else(no_global)?;
+                    self.stack.push(value);
+                }
+                0x24 => {
+                    let index = r.u32()? as usize;
+                    let value = self.pop()?;
+                    *self.globals.get_mut(index).ok_or_else(no_global)? = value;
+                }
+                0x28..=0x35 => {
+                    r.u32()?;
+                    let offset = r.u32()?;
+                    let width = match op {
+                        0x28 | 0x2a | 0x34 | 0x35 => 4,
+                        0x29 | 0x2b => 8,
+                        0x2c | 0x2d | 0x30 | 0x31 => 1,
+                        _ => 2,
+                    };
+                    let value = self.load(width, offset)?;
+                    self.stack.push(match op {
+                        0x2c => (value as i8 as i32).into_raw(),
+                        0x2e => (value as i16 as i32).into_raw(),
+                        0x30 => (value as i8 as i64).into_raw(),
+                        0x32 => (value as i16 as i64).into_raw(),
+                        0x34 => (value as i32 as i64).into_raw(),
+                        _ => value,
+                    });
+                }
+                0x36..=0x3e => {
+                    r.u32()?;
+                    let offset = r.u32()?;
+                    let width = match op {
+                        0x36 | 0x38 | 0x3e => 4,
+                        0x37 | 0x39 => 8,
+                        0x3a | 0x3c => 1,
+                        _ => 2,
+                    };
+                    self.store(width, offset)?;
+                }
+                0x3f => {
+                    r.byte()?;
+                    self.stack.push((self.memory.len() / PAGE) as u64);
+                }
+                0x40 => {
+                    r.byte()?;
+                    let pages = self.memory.len() / PAGE;
+                    let grown = pages + self.pop()? as u32 as usize;
+                    if grown <= self.max_pages {
+                        self.memory.resize(grown * PAGE, 0);
+                        self.stack.push(pages as u64);
+                    } else {
+                        self.refused |= grown <= self.module_max_pages;
+                        self.stack.push((-1i32).into_raw());
+                    }
+                }
+                0x41 => self.stack.push(r.i32()?.into_raw()),
+                0x42 => self.stack.push(r.i64()?.into_raw()),
+                0x43 => self.stack.push(u32::from_le_bytes(r.array()?).into_raw()),
+                0x44 => self.stack.push(u64::from_le_bytes(r.array()?)),
+                0xfc => self.misc(&mut r)?,
+                op => self.numeric(op)?,
+            }
+            if leave {
+                match self.ret()? {
+                    Some((caller, pc)) => {
+                        func = caller;
+                        r = Reader {
+                            bytes: &module.funcs[caller].code,
+                            pos: pc,
+                        };
+                    }
+                    None => return Ok(std::mem::take(&mut self.stack)),
+                }
+            }
+        }
+    }
+
+    /// Starts running `func`, on the arguments on the stack, once the
+    /// function running now has been left at `pc`.
+    fn call_into(&mut self, func: usize, pc: usize) -> Result<Reader<'m>, Error> {
+        let module = self.module;
+        let params = module.type_of(func)?.params.len();
+        if self.frames.len() >= MAX_DEPTH || self.locals.len() > MAX_LOCAL_SLOTS {
+            return Err(trap("call stack exhausted"));
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.pc = pc;
+        }
+        let height = self
+            .stack
+            .len()
+            .checked_sub(params)
+            .ok_or_else(stack_ran_out)?;
+        let locals = self.locals.len();
+        self.locals.extend(self.stack.drain(height..));
+        self.locals
+            .resize(self.locals.len() + module.funcs[func].locals, 0);
+        self.frames.push(Frame {
+            func,
+            pc: 0,
+            locals,
+            labels: self.labels.len(),
+            height,
+        });
+        Ok(Reader::new(&module.funcs[func].code))
+    }
+
+    /// Returns from the function running, leaving its results on the
+    /// stack: gives where to carry on in the caller, or `None` if that was
+    /// the function called from outside.
+    fn ret(&mut self) -> Result<Option<(usize, usize)>, Error> {
+        let frame = self.frames.pop().ok_or_else(stack_ran_out)?;
+        let results = self.module.type_of(frame.func)?.results.len();
+        let from = self
+            .stack
+            .len()
+            .checked_sub(results)
+            .filter(|&from| from >= frame.height)
+            .ok_or_else(stack_ran_out)?;
+        self.stack.drain(frame.height..from);
+        self.locals.truncate(frame.locals);
+        self.labels.truncate(frame.labels);
+        Ok(self.frames.last().map(|frame| (frame.func, frame.pc)))
+    }
+
+    /// Branches `depth` blocks out, carrying the values the block takes
+    /// along. Gives `false` for a branch out of the function itself, which
+    /// returns from it.
+    fn branch(&mut self, depth: u32, r: &mut Reader) -> Result<bool, Error> {
+        let base = self.labels_base();
+        let depth = depth as usize;
+        if depth == self.labels.len() - base {
+            return Ok(false);
+        }
+        let index = self
+            .labels
+            .len()
+            .checked_sub(depth + 1)
+            .filter(|&index| index >= base)
+            .ok_or_else(|| invalid("a branch goes nowhere"))?;
+        let label = self.labels[index];
+        let from = self
+            .stack
+            .len()
+            .checked_sub(label.arity)
+            .filter(|&from| from >= label.height)
+            .ok_or_else(stack_ran_out)?;
+        self.stack.drain(label.height..from);
+        self.labels
+            .truncate(if label.is_loop { index + 1 } else { index });
+        r.pos = label.target;
+        Ok(true)
+    }
+
+    fn labels_base(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.labels)
+    }
+
+    fn pop_label(&mut self) -> Result<Label, Error> {
+        if self.labels.len() <= self.labels_base() {
+            return Err(invalid("an `else` is outside any `if`"));
+        }
+        self.labels.pop().ok_or_else(stack_ran_out)
+    }
+
+    /// Where local `index` of the function running is kept.
+    fn local(&self, index: u32) -> Result<usize, Error> {
+        let base = self.frames.last().map_or(0, |frame| frame.locals);
+        Some(base + index as usize)
+            .filter(|&index| index < self.locals.len())
+            .ok_or_else(|| invalid("a function uses a local it doesn't have"))
+    }
+
+    fn pop(&mut self) -> Result<u64, Error> {
+        self.stack.pop().ok_or_else(stack_ran_out)
+    }
+
+    fn unary<A: Raw, R: Raw>(&mut self, f: impl FnOnce(A) -> R) -> Result<(), Error> {
+        self.try_unary(|a| Ok(f(a)))
+    }
+
+    fn try_unary<A: Raw, R: Raw>(
+        &mut self,
+        f: impl FnOnce(A) -> Result<R, Error>,
+    ) -> Result<(), Error> {
+        let a = A::from_raw(self.pop()?);
+        self.stack.push(f(a)?.into_raw());
+        Ok(())
+    }
+
+    fn binary<A: Raw, R: Raw>(&mut self, f: impl FnOnce(A, A) -> R) -> Result<(), Error> {
+        self.try_binary(|a, b| Ok(f(a, b)))
+    }
+
+    fn try_binary<A: Raw, R: Raw>(
+        &mut self,
+        f: impl FnOnce(A, A) -> Result<R, Error>,
+    ) -> Result<(), Error> {
+        let b = A::from_raw(self.pop()?);
+        let a = A::from_raw(self.pop()?);
+        self.stack.push(f(a, b)?.into_raw());
+        Ok(())
+    }
+
+    /// Where `len` bytes from `base + offset` start in memory.
+    fn address(&self, base: u32, offset: u32, len: usize) -> Result<usize, Error> {
+        let start = base as usize + offset as usize;
+        if start + len > self.memory.len() {
+            return Err(trap("out of bounds memory access"));
+        }
+        Ok(start)
+    }
+
+    /// Loads `width` bytes from the address on the stack, plus `offset`.
+    fn load(&mut self, width: usize, offset: u32) -> Result<u64, Error> {
+        let base = self.pop()? as u32;
+        let at = self.address(base, offset, width)?;
+        let mut bytes = [0; 8];
+        bytes[..width].copy_from_slice(&self.memory[at..at + width]);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Stores the low `width` bytes of the value on the stack at the
+    /// address under it, plus `offset`.
+    fn store(&mut self, width: usize, offset: u32) -> Result<(), Error> {
+        let value = self.pop()?;
+        let base = self.pop()? as u32;
+        let at = self.address(base, offset, width)?;
+        self.memory[at..at + width].copy_from_slice(&value.to_le_bytes()[..width]);
+        Ok(())
+    }
+
+    /// Runs an instruction after the `0xfc` prefix: the saturating
+    /// conversions and bulk memory.
+    fn misc(&mut self, r: &mut Reader) -> Result<(), Error> {
+        match r.u32()? {
+            0 => self.unary(|a: f32| a as i32),
+            1 => self.unary(|a: f32| a as u32),
+            2 => self.unary(|a: f64| a as i32),
+            3 => self.unary(|a: f64| a as u32),
+            4 => self.unary(|a: f32| a as i64),
+            5 => self.unary(|a: f32| a as u64),
+            6 => self.unary(|a: f64| a as i64),
+            7 => self.unary(|a: f64| a as u64),
+            8 => {
+                let segment = r.count()?;
+                r.byte()?;
+                let len = self.pop()? as u32 as usize;
+                let from = self.pop()? as u32 as usize;
+                let to = self.pop()? as u32;
+                let data = self
+                    .module
+                    .data
+                    .get(segment)
+                    .ok_or_else(|| invalid("it uses a data segment that isn't there"))?;
+                let bytes: &[u8] = if self.dropped[segment] {
+                    &[]
+                } else {
+                    &data.bytes
+                };
+                let bytes = bytes
+                    .get(from..from + len)
+                    .ok_or_else(|| trap("out of bounds memory access"))?;
+                let at = self.address(to, 0, len)?;
+                self.memory[at..at + len].copy_from_slice(bytes);
+                Ok(())
+            }
+            9 => {
+                let segment = r.count()?;
+                *self
+                    .dropped
+                    .get_mut(segment)
+                    .ok_or_else(|| invalid("it drops a data segment that isn't there"))? = true;
+                Ok(())
+            }
+            10 => {
+                r.take(2)?;
+                let len = self.pop()? as u32 as usize;
+                let from = self.pop()? as u32;
+                let to = self.pop()? as u32;
+                let from = self.address(from, 0, len)?;
+                let to = self.address(to, 0, len)?;
+                self.memory.copy_within(from..from + len, to);
+                Ok(())
+            }
+            11 => {
+                r.byte()?;
+                let len = self.pop()? as u32 as usize;
+                let value = self.pop()? as u8;
+                let to = self.pop()? as u32;
+                let to = self.address(to, 0, len)?;
+                self.memory[to..to + len].fill(value);
+                Ok(())
+            }
+            sub => Err(invalid(format!("it uses instruction 0xfc {sub}"))),
+        }
+    }
+
+    /// Runs a numeric instruction: a comparison, arithmetic or a
+    /// conversion.
+    fn numeric(&mut self, op: u8) -> Result<(), Error> {
+        match op {
+            0x45 => self.unary(|a: u32| a == 0),
+            0x46 => self.binary(|a: u32, b| a == b),
+            0x47 => self.binary(|a: u32, b| a != b),
+            0x48 => self.binary(|a: i32, b| a < b),
+            0x49 => self.binary(|a: u32, b| a < b),
+            0x4a => self.binary(|a: i32, b| a > b),
+            0x4b => self.binary(|a: u32, b| a > b),
+            0x4c => self.binary(|a: i32, b| a <= b),
+            0x4d => self.binary(|a: u32, b| a <= b),
+            0x4e => self.binary(|a: i32, b| a >= b),
+            0x4f => self.binary(|a: u32, b| a >= b),
+            0x50 => self.unary(|a: u64| a == 0),
+            0x51 => self.binary(|a: u64, b| a == b),
+            0x52 => self.binary(|a: u64, b| a != b),
+            0x53 => self.binary(|a: i64, b| a < b),
+            0x54 => self.binary(|a: u64, b| a < b),
+            0x55 => self.binary(|a: i64, b| a > b),
+            0x56 => self.binary(|a: u64, b| a > b),
+            0x57 => self.binary(|a: i64, b| a <= b),
+            0x58 => self.binary(|a: u64, b| a <= b),
+            0x59 => self.binary(|a: i64, b| a >= b),
+            0x5a => self.binary(|a: u64, b| a >= b),
+            0x5b => self.binary(|a: f32, b| a == b),
+            0x5c => self.binary(|a: f32, b| a != b),
+            0x5d => self.binary(|a: f32, b| a < b),
+            0x5e => self.binary(|a: f32, b| a > b),
+            0x5f => self.binary(|a: f32, b| a <= b),
+            0x60 => self.binary(|a: f32, b| a >= b),
+            0x61 => self.binary(|a: f64, b| a == b),
+            0x62 => self.binary(|a: f64, b| a != b),
+            0x63 => self.binary(|a: f64, b| a < b),
+            0x64 => self.binary(|a: f64, b| a > b),
+            0x65 => self.binary(|a: f64, b| a <= b),
+            0x66 => self.binary(|a: f64, b| a >= b),
+
+            0x67 => self.unary(|a: u32| a.leading_zeros()),
+            0x68 => self.unary(|a: u32| a.trailing_zeros()),
+            0x69 => self.unary(|a: u32| a.count_ones()),
+            0x6a => self.binary(|a: u32, b| a.wrapping_add(b)),
+            0x6b => self.binary(|a: u32, b| a.wrapping_sub(b)),
+            0x6c => self.binary(|a: u32, b| a.wrapping_mul(b)),
+            0x6d => self.try_binary(|a: i32, b| match (a, b) {
+                (_, 0) => Err(trap("integer divide by zero")),
+                (i32::MIN, -1) => Err(trap("integer overflow")),
+                _ => Ok(a / b),
+            }),
+            0x6e => self.try_binary(|a: u32, b| a.checked_div(b).ok_or_else(divide_by_zero)),
+            0x6f => self.try_binary(|a: i32, b| match b {
+                0 => Err(divide_by_zero()),
+                _ => Ok(a.wrapping_rem(b)),
+            }),
+            0x70 => self.try_binary(|a: u32, b| a.checked_rem(b).ok_or_else(divide_by_zero)),
+            0x71 => self.binary(|a: u32, b| a & b),
+            0x72 => self.binary(|a: u32, b| a | b),
+            0x73 => self.binary(|a: u32, b| a ^ b),
+            0x74 => self.binary(|a: u32, b| a.wrapping_shl(b)),
+            0x75 => self.binary(|a: i32, b| a.wrapping_shr(b as u32)),
+            0x76 => self.binary(|a: u32, b| a.wrapping_shr(b)),
+            0x77 => self.binary(|a: u32, b| a.rotate_left(b % 32)),
+            0x78 => self.binary(|a: u32, b| a.rotate_right(b % 32)),
+
+            0x79 => self.unary(|a: u64| a.leading_zeros() as u64),
+            0x7a => self.unary(|a: u64| a.trailing_zeros() as u64),
+            0x7b => self.unary(|a: u64| a.count_ones() as u64),
+            0x7c => self.binary(|a: u64, b| a.wrapping_add(b)),
+            0x7d => self.binary(|a: u64, b| a.wrapping_sub(b)),
+            0x7e => self.binary(|a: u64, b| a.wrapping_mul(b)),
+            0x7f => self.try_binary(|a: i64, b| match (a, b) {
+                (_, 0) => Err(divide_by_zero()),
+                (i64::MIN, -1) => Err(trap("integer overflow")),
+                _ => Ok(a / b),
+            }),
+            0x80 => self.try_binary(|a: u64, b| a.checked_div(b).ok_or_else(divide_by_zero)),
+            0x81 => self.try_binary(|a: i64, b| match b {
+                0 => Err(divide_by_zero()),
+                _ => Ok(a.wrapping_rem(b)),
+            }),
+            0x82 => self.try_binary(|a: u64, b| a.checked_rem(b).ok_or_else(divide_by_zero)),
+            0x83 => self.binary(|a: u64, b| a & b),
+            0x84 => self.binary(|a: u64, b| a | b),
+            0x85 => self.binary(|a: u64, b| a ^ b),
+            0x86 => self.binary(|a: u64, b| a.wrapping_shl(b as u32)),
+            0x87 => self.binary(|a: i64, b| a.wrapping_shr(b as u32)),
+            0x88 => self.binary(|a: u64, b| a.wrapping_shr(b as u32)),
+            0x89 => self.binary(|a: u64, b| a.rotate_left((b % 64) as u32)),
+            0x8a => self.binary(|a: u64, b| a.rotate_right((b % 64) as u32)),
+
+            0x8b => self.unary(|a: f32| a.abs()),
+            0x8c => self.unary(|a: f32| -a),
+            0x8d => self.unary(|a: f32| a.ceil()),
+            0x8e => self.unary(|a: f32| a.floor()),
+            0x8f => self.unary(|a: f32| a.trunc()),
+            0x90 => self.unary(|a: f32| a.round_ties_even()),
+            0x91 => self.unary(|a: f32| a.sqrt()),
+            0x92 => self.binary(|a: f32, b| a + b),
+            0x93 => self.binary(|a: f32, b| a - b),
+            0x94 => self.binary(|a: f32, b| a * b),
+            0x95 => self.binary(|a: f32, b| a / b),
+            0x96 => self.binary(|a: f32, b| min(a as f64, b as f64) as f32),
+            0x97 => self.binary(|a: f32, b| max(a as f64, b as f64) as f32),
+            0x98 => self.binary(|a: f32, b| a.copysign(b)),
+
+            0x99 => self.unary(|a: f64| a.abs()),
+            0x9a => self.unary(|a: f64| -a),
+            0x9b => self.unary(|a: f64| a.ceil()),
+            0x9c => self.unary(|a: f64| a.floor()),
+            0x9d => self.unary(|a: f64| a.trunc()),
+            0x9e => self.unary(|a: f64| a.round_ties_even()),
+            0x9f => self.unary(|a: f64| a.sqrt()),
+            0xa0 => self.binary(|a: f64, b| a + b),
+            0xa1 => self.binary(|a: f64, b| a - b),
+            0xa2 => self.binary(|a: f64, b| a * b),
+            0xa3 => self.binary(|a: f64, b| a / b),
+            0xa4 => self.binary(min),
+            0xa5 => self.binary(max),
+            0xa6 => self.binary(|a: f64, b| a.copysign(b)),
+
+            0xa7 => self.unary(|a: u64| a as u32),
+            0xa8 => {
+                self.try_unary(|a: f32| Ok(truncate(a as f64, -2147483648.0, 2147483648.0)? as i32))
+            }
+            0xa9 => self.try_unary(|a: f32| Ok(truncate(a as f64, 0.0, 4294967296.0)? as u32)),
+            0xaa => self.try_unary(|a: f64| Ok(truncate(a, -2147483648.0, 2147483648.0)? as i32)),
+            0xab => self.try_unary(|a: f64| Ok(truncate(a, 0.0, 4294967296.0)? as u32)),
+            0xac => self.unary(|a: i32| a as i64),
+            0xad => self.unary(|a: u32| a as u64),
+            0xae => self.try_unary(|a: f32| {
+                Ok(truncate(a as f64, -9223372036854775808.0, 9223372036854775808.0)? as i64)
+            }),
+            0xaf => {
+                self.try_unary(|a: f32| Ok(truncate(a as f64, 0.0, 18446744073709551616.0)? as u64))
+            }
+            0xb0 => self.try_unary(|a: f64| {
+                Ok(truncate(a, -9223372036854775808.0, 9223372036854775808.0)? as i64)
+            }),
+            0xb1 => self.try_unary(|a: f64| Ok(truncate(a, 0.0, 18446744073709551616.0)? as u64)),
+            0xb2 => self.unary(|a: i32| a as f32),
+            0xb3 => self.unary(|a: u32| a as f32),
+            0xb4 => self.unary(|a: i64| a as f32),
+            0xb5 => self.unary(|a: u64| a as f32),
+            0xb6 => self.unary(|a: f64| a as f32),
+            0xb7 => self.unary(|a: i32| a as f64),
+            0xb8 => self.unary(|a: u32| a as f64),
+            0xb9 => self.unary(|a: i64| a as f64),
+            0xba => self.unary(|a: u64| a as f64),
+            0xbb => self.unary(|a: f32| a as f64),
+            // Values are kept as their bits, so reinterpreting them is a
+            // no-op.
+            0xbc..=0xbf => Ok(()),
+            0xc0 => self.unary(|a: u32| a as i8 as i32),
+            0xc1 => self.unary(|a: u32| a as i16 as i32),
+            0xc2 => self.unary(|a: u64| a as i8 as i64),
+            0xc3 => self.unary(|a: u64| a as i16 as i64),
+            0xc4 => self.unary(|a: u64| a as i32 as i64),
+            op => Err(invalid(format!("it uses instruction {op:#04x}"))),
+        }
+    }
+}
+
+fn stack_ran_out() -> Error {
+    invalid("a function takes more values than are there")
+}
+
+fn no_global() -> Error {
+    invalid("a function uses a global that isn't there")
+}
+
+fn divide_by_zero() -> Error {
+    trap("integer divide by zero")
+}
+
+/// Truncates `value` towards zero if the result is at least `min` and
+/// less than `max`, for converting to an integer.
+fn truncate(value: f64, min: f64, max: f64) -> Result<f64, Error> {
+    if value.is_nan() {
+        return Err(trap("invalid conversion to integer"));
+    }
+    let value = value.trunc();
+    if value < min || value >= max {
+        return Err(trap("integer overflow"));
+    }
+    Ok(value)
+}
+
+/// The lesser of two floats, as WebAssembly has it: NaN if either is, and
+/// -0 below 0.
+fn min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_negative() {
+            a
+        } else {
+            b
+        }
+    } else {
+        a.min(b)
+    }
+}
+
+/// The greater of two floats, as WebAssembly has it.
+fn max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_positive() {
+            a
+        } else {
+            b
+        }
+    } else {
+        a.max(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        memory: 4 * PAGE,
+        time: Duration::from_secs(5),
+    };
+
+    /// Puts a module together from its sections, each under 128 bytes.
+    fn module(sections: &[(u8, &[u8])]) -> Result<Module, Error> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        for (id, body) in sections {
+            bytes.extend([*id, body.len() as u8]);
+            bytes.extend(*body);
+        }
+        Module::parse(&bytes)
+    }
+
+    /// A module of one function of type `ty`, exported as `f`, with `body`
+    /// as its locals and code.
+    fn function(ty: &[u8], body: &[u8], memory: &[u8]) -> Module {
+        let mut code = vec![1, body.len() as u8];
+        code.extend(body);
+        let mut types = vec![1, 0x60];
+        types.extend(ty);
+        module(&[
+            (1, &types),
+            (3, &[1, 0]),
+            (5, memory),
+            (7, &[1, 1, b'f', 0, 0]),
+            (10, &code),
+        ])
+        .unwrap()
+    }
+
+    fn call(module: &Module, args: &[Value]) -> Result<Vec<Value>, Error> {
+        Instance::new(module, LIMITS)?.call("f", args)
+    }
+
+    #[test]
+    fn recursion_and_blocks() {
+        // (i64) -> i64: if n == 0 { 1 } else { n * f(n - 1) }
+        let factorial = function(
+            &[1, 0x7e, 1, 0x7e],
+            &[
+                0, 0x20, 0, 0x50, 0x04, 0x7e, 0x42, 1, 0x05, 0x20, 0, 0x20, 0, 0x42, 1, 0x7d, 0x10,
+                0, 0x7e, 0x0b, 0x0b,
+            ],
+            &[0],
+        );
+        assert_eq!(
+            call(&factorial, &[Value::I64(20)]).unwrap(),
+            [Value::I64(2432902008176640000)]
+        );
+        assert!(matches!(
+            call(&factorial, &[Value::I32(20)]),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn loops_and_branches() {
+        // (i32) -> i32: the sum of 1 to n, counting n down to 0.
+        let sum = function(
+            &[1, 0x7f, 1, 0x7f],
+            &[
+                1, 1, 0x7f, 0x02, 0x40, 0x03, 0x40, 0x20, 0, 0x45, 0x0d, 1, 0x20, 1, 0x20, 0, 0x6a,
+                0x21, 1, 0x20, 0, 0x41, 1, 0x6b, 0x21, 0, 0x0c, 0, 0x0b, 0x0b, 0x20, 1, 0x0b,
+            ],
+            &[0],
+        );
+        assert_eq!(call(&sum, &[Value::I32(100)]).unwrap(), [Value::I32(5050)]);
+    }
+
+    #[test]
+    fn memory_and_data() {
+        // () -> i32: the byte at 17, after "hi" was put at 16.
+        let mut greeting = function(&[0, 1, 0x7f], &[0, 0x41, 17, 0x2d, 0, 0, 0x0b], &[1, 0, 1]);
+        greeting.data.push(Data {
+            bytes: b"hi".to_vec(),
+            offset: Some(Const::Value(16)),
+        });
+        assert_eq!(call(&greeting, &[]).unwrap(), [Value::I32(b'i' as i32)]);
+        // Reading past the end of memory traps.
+        let beyond = function(
+            &[0, 1, 0x7f],
+            &[0, 0x41, 0, 0x28, 0, 0x80, 0x80, 4, 0x0b],
+            &[1, 0, 1],
+        );
+        assert!(matches!(call(&beyond, &[]), Err(Error::Trap(_))));
+    }
+
+    #[test]
+    fn traps() {
+        // (i32, i32) -> i32: a / b
+        let divide = function(
+            &[2, 0x7f, 0x7f, 1, 0x7f],
+            &[0, 0x20, 0, 0x20, 1, 0x6d, 0x0b],
+            &[0],
+        );
+        assert_eq!(
+            call(&divide, &[Value::I32(-7), Value::I32(2)]).unwrap(),
+            [Value::I32(-3)]
+        );
+        assert!(matches!(
+            call(&divide, &[Value::I32(1), Value::I32(0)]),
+            Err(Error::Trap(_))
+        ));
+        assert!(matches!(
+            call(&divide, &[Value::I32(i32::MIN), Value::I32(-1)]),
+            Err(Error::Trap(_))
+        ));
+    }
+
+    #[test]
+    fn limits() {
+        // () -> (): loop forever.
+        let spin = function(&[0, 0], &[0, 0x03, 0x40, 0x0c, 0, 0x0b, 0x0b], &[0]);
+        let short = Limits {
+            time: Duration::from_millis(20),
+            ..LIMITS
+        };
+        let result = Instance::new(&spin, short).unwrap().call("f", &[]);
+        assert!(matches!(result, Err(Error::OutOfTime(_))));
+
+        // () -> i32: grow memory by 8 pages, which is more than allowed.
+        let grow = function(&[0, 1, 0x7f], &[0, 0x41, 8, 0x40, 0, 0x0b], &[1, 0, 1]);
+        assert_eq!(call(&grow, &[]).unwrap(), [Value::I32(-1)]);
+        // Memory the module starts with counts too.
+        let big = function(&[0, 0], &[0, 0x0b], &[1, 0, 8]);
+        assert!(matches!(
+            Instance::new(&big, LIMITS),
+            Err(Error::OutOfMemory(_))
+        ));
+    }
+
+    #[test]
+    fn nothing_can_be_imported() {
+        let imports = module(&[
+            (1, &[1, 0x60, 0, 0]),
+            (2, &[1, 3, b'e', b'n', b'v', 1, b'f', 0, 0]),
+        ]);
+        assert!(matches!(imports, Err(Error::Invalid(_))));
+        assert!(matches!(Module::parse(b"\0elf"), Err(Error::Invalid(_))));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::diff;
+use crate::workspace::Workspace;
+use cursive::views::{Dialog, ScrollView, TextView};
+use cursive::Cursive;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// What the file system says about a file, to tell when it changes: its
+/// modification time and length.
+pub type Stamp = (SystemTime, u64);
+
+/// The stamp of the file at `path` now, if there is one.
+pub fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Looks for open files that changed on disk since they were loaded or
+/// saved. Buffers without changes of their own are reloaded; for the
+/// others, the choice is offered between reloading, keeping the buffer, and
+/// looking at how the two differ first.
+pub fn tick(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>) {
+    let mut ws = workspace.lock().unwrap();
+    let mut conflicts = Vec::new();
+    for ed in ws.editors_mut() {
+        if !ed.changed_on_disk() {
+            continue;
+        }
+        if !ed.is_modified() {
+            // Failing to read it, the buffer is kept as the next best thing.
+            let _ = ed.reload();
+        } else if let Some(path) = ed.path() {
+            conflicts.push((path.to_path_buf(), ed.name()));
+        }
+    }
+    drop(ws);
+    for (path, name) in conflicts {
+        let reload = workspace.clone();
+        let compare = workspace.clone();
+        let path_for_reload = path.clone();
+        s.add_layer(
+            Dialog::text(format!(
+                "{name} changed on disk, and the buffer has unsaved changes."
+            ))
+            .title("Changed on disk")
+            .button("Reload", move |s| {
+                s.pop_layer();
+                reload_file(s, &reload, &path_for_reload);
+            })
+            .button("Keep", |s| {
+                s.pop_layer();
+            })
+            .button("Diff", move |s| show_diff(s, &compare, &path)),
+        );
+    }
+}
+
+/// Reloads the buffer holding the file at `path`, reporting any failure.
+fn reload_file(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, path: &Path) {
+    let mut ws = workspace.lock().unwrap();
+    let Some(ed) = ws
+        .editors_mut()
+        .iter_mut()
+        .find(|ed| ed.path() == Some(path))
+    else {
+        return;
+    };
+    if let Err(err) = ed.reload() {
+        drop(ws);
+        s.add_layer(Dialog::info(format!(
+            "Could not reload {}: {err}",
+            path.display()
+        )));
+    }
+}
+
+/// Shows how the buffer holding the file at `path` differs from the file,
+/// over the dialog asking what to do about it.
+fn show_diff(s: &mut Cursive, workspace: &Arc<Mutex<Workspace>>, path: &Path) {
+    let ws = workspace.lock().unwrap();
+    let Some(ed) = ws.editors().iter().find(|ed| ed.path() == Some(path)) else {
+        return;
+    };
+    let text = match ed.read_from_disk() {
+        Ok(disk) => {
+            let diff = diff::unified(&ed.buffer().to_string(), &disk, 3);
+            if diff.is_empty() {
+                "The file on disk holds the same text as the buffer.".to_string()
+            } else {
+                diff
+            }
+        }
+        Err(err) => format!("Could not read {}: {err}", path.display()),
+    };
+    let title = format!("{}: - buffer, + disk", ed.name());
+    drop(ws);
+    s.add_layer(
+        Dialog::around(ScrollView::new(TextView::new(text)))
+            .title(title)
+            .dismiss_button("Close"),
+    );
+}
@@ -0,0 +1,19 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Number of columns a tab advances to.
+pub const TAB_WIDTH: usize = 4;
+
+/// Returns the number of terminal columns `text` occupies, expanding tabs.
+pub fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |x, ch| x + char_width(ch, x))
+}
+
+/// Returns the number of terminal columns `ch` occupies when it starts at
+/// column `x`: a tab reaches the next tab stop.
+pub fn char_width(ch: char, x: usize) -> usize {
+    if ch == '\t' {
+        TAB_WIDTH - x % TAB_WIDTH
+    } else {
+        ch.width().unwrap_or(0)
+    }
+}
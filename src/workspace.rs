@@ -0,0 +1,859 @@
+use crate::abbreviations::Abbreviations;
+use crate::charset::Charset;
+use crate::clipboard::Clipboard;
+use crate::filetype;
+use crate::grammar::Grammar;
+use crate::hints::Pending;
+use crate::jobs::Jobs;
+use crate::keymap::Keymap;
+use crate::line_ending;
+use crate::lsp::Lsp;
+use crate::macros::{Macros, Recorder};
+use crate::motion;
+use crate::pane::{Layout, Pane};
+use crate::plugins::Plugins;
+use crate::quickfix::Quickfix;
+use crate::recent;
+use crate::registers::Registers;
+use crate::scripts::{Hook, Scripts};
+use crate::settings::Settings;
+use crate::snippet::Snippets;
+use crate::spell::Dictionary;
+use crate::transform::{Json, Registry};
+use crate::unicode::Names;
+use crate::wrap;
+use crate::Editor;
+use cursive::direction::Orientation;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Longest a notice stays in the status bar, if no key dismisses it first.
+pub const NOTICE: Duration = Duration::from_secs(5);
+
+/// Every open buffer, the panes showing them, plus the state shared between them:
+/// - `editors`: one `Editor` per buffer, each keeping its own cursor and selection
+/// - `panes`: the windows onto the buffers; several may show the same one
+/// - `layout`: how the panes share the screen
+/// - `focus`: index of the pane being edited in
+/// - `clipboard`: where copied and cut text goes, and where pasted text comes from
+/// - `registers`: text yanked under a letter, to be pasted apart from the clipboard
+/// - `search`: the active search query, whose matches are highlighted
+/// - `keymap`: which keys run which commands
+/// - `recorder`: the keyboard macro being recorded, and the last one recorded
+/// - `macros`: the macros saved under a name, and the keys that play them
+/// - `lsp`: the language servers for the open files, and what they reported
+/// - `snippets`: the snippets that can be expanded, by filetype
+/// - `abbreviations`: the words that expand as they're typed, by filetype
+/// - `scripts`: the user's own transforms, and the commands hooked to opening and saving files
+///   and to changes of selection
+/// - `reported_selection`: the file and range of the selection the selection-change hooks
+///   were last told about
+/// - `plugins`: the extensions loaded from the plugin directory
+/// - `transforms`: what the transform menu offers, built in, the user's or the plugins'
+/// - `count`: the count being typed for the next command, if any
+/// - `settings`: the user's preferences
+/// - `last_input`: when a key was last pressed, to tell when the user is idle
+/// - `jobs`: the long operations running in the background
+/// - `quickfix`: the places the last build complained about
+/// - `tag_stack`: where the cursor was before each jump to a definition from the
+///   tags file, the latest last
+/// - `dictionary`: the words spell checking knows, once it's been loaded
+/// - `character_names`: the names of Unicode characters, once they've been looked up
+/// - `grammar`: whether keys select and act as commands rather than type
+/// - `pending`: the start of a key sequence, waiting for the rest
+/// - `notice`: a message shown in the status bar until the next key, and
+///   when it was given
+pub struct Workspace {
+    editors: Vec<Editor>,
+    panes: Vec<Pane>,
+    layout: Layout,
+    focus: usize,
+    clipboard: Clipboard,
+    registers: Registers,
+    search: Option<String>,
+    keymap: Keymap,
+    recorder: Recorder,
+    macros: Macros,
+    lsp: Lsp,
+    snippets: Snippets,
+    abbreviations: Abbreviations,
+    scripts: Scripts,
+    reported_selection: Option<(PathBuf, Range<usize>)>,
+    plugins: Plugins,
+    transforms: Registry,
+    count: Option<usize>,
+    settings: Settings,
+    last_input: Instant,
+    jobs: Jobs,
+    quickfix: Quickfix,
+    tag_stack: Vec<(PathBuf, usize)>,
+    dictionary: Option<Dictionary>,
+    character_names: Option<Names>,
+    grammar: Grammar,
+    pending: Pending,
+    notice: Option<(String, Instant)>,
+}
+
+impl Workspace {
+    /// Creates a workspace over `editors`, starting with a scratch buffer if there are none.
+    pub fn new(mut editors: Vec<Editor>) -> Self {
+        if editors.is_empty() {
+            editors.push(Editor::new());
+        }
+        for path in editors.iter().filter_map(Editor::path) {
+            let _ = recent::note(path);
+        }
+        Self {
+            editors,
+            panes: vec![Pane {
+                editor: 0,
+                scroll: (0, 0),
+                height: 0,
+                width: 0,
+            }],
+            layout: Layout::Pane(0),
+            focus: 0,
+            clipboard: Clipboard::new(),
+            registers: Registers::default(),
+            search: None,
+            keymap: Keymap::default(),
+            recorder: Recorder::new(),
+            macros: Macros::default(),
+            lsp: Lsp::default(),
+            snippets: Snippets::default(),
+            abbreviations: Abbreviations::default(),
+            scripts: Scripts::default(),
+            reported_selection: None,
+            plugins: Plugins::default(),
+            transforms: Registry::default(),
+            count: None,
+            settings: Settings::default(),
+            last_input: Instant::now(),
+            jobs: Jobs::default(),
+            quickfix: Quickfix::default(),
+            tag_stack: Vec::new(),
+            dictionary: None,
+            character_names: None,
+            grammar: Grammar::default(),
+            pending: Pending::default(),
+            notice: None,
+        }
+    }
+
+    /// The buffer being edited.
+    pub fn editor(&self) -> &Editor {
+        &self.editors[self.current()]
+    }
+
+    /// The buffer being edited, mutably.
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        let current = self.current();
+        &mut self.editors[current]
+    }
+
+    /// All open buffers, in the order they were opened.
+    pub fn editors(&self) -> &[Editor] {
+        &self.editors
+    }
+
+    /// All open buffers, mutably.
+    pub fn editors_mut(&mut self) -> &mut [Editor] {
+        &mut self.editors
+    }
+
+    /// Index of the buffer being edited.
+    pub fn current(&self) -> usize {
+        self.panes[self.focus].editor
+    }
+
+    /// Shows buffer `index` in the focused pane. Its cursor and selection are
+    /// exactly as they were left, and so is its scroll position.
+    pub fn switch_to(&mut self, index: usize) {
+        if index >= self.editors.len() {
+            return;
+        }
+        let pane = &mut self.panes[self.focus];
+        self.editors[pane.editor].set_scroll(pane.scroll);
+        pane.editor = index;
+        pane.scroll = self.editors[index].scroll();
+    }
+
+    /// All panes; `layout` refers to them by index.
+    pub fn panes(&self) -> &[Pane] {
+        &self.panes
+    }
+
+    /// Mutable access to pane `index`, for scrolling it.
+    pub fn pane_mut(&mut self, index: usize) -> &mut Pane {
+        &mut self.panes[index]
+    }
+
+    /// How the panes are arranged on screen.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Index of the focused pane.
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    /// Number of lines the focused pane shows, at least one.
+    pub fn page_height(&self) -> usize {
+        self.panes[self.focus].height.max(1)
+    }
+
+    /// Moves every cursor `lines` lines down (or up, if negative), keeping
+    /// its column, and scrolls the focused pane along by as much.
+    pub fn scroll_lines(&mut self, lines: isize) {
+        self.editor_mut().for_each_cursor(|ed| ed.move_lines(lines));
+        let last = self.editor().buffer().line_count().saturating_sub(1);
+        let pane = &mut self.panes[self.focus];
+        pane.scroll.1 = pane.scroll.1.saturating_add_signed(lines).min(last);
+    }
+
+    /// Scrolls the focused pane so that the cursor's line, or the row of it
+    /// the cursor is in while lines are wrapped, is halfway down it.
+    pub fn center_view(&mut self) {
+        let pane = self.panes[self.focus];
+        let half = pane.height.saturating_sub(1) / 2;
+        let ed = self.editor();
+        if let Some(hex) = ed.hex() {
+            let row = hex.cursor_cell().1;
+            self.panes[self.focus].scroll.1 = row.saturating_sub(half);
+            return;
+        }
+        let buffer = ed.buffer();
+        let mut first = buffer.line_of(ed.cursor());
+        if self.settings.wrap {
+            let text = buffer.line(first);
+            let rows = wrap::rows(&text, pane.width);
+            let mut left =
+                half.saturating_sub(wrap::row_of(&rows, ed.cursor() - buffer.line_start(first)));
+            // Whole lines above fill the rest of the top half, as many as fit.
+            while first > 0 {
+                let height = wrap::rows(&buffer.line(first - 1), pane.width).len();
+                if height > left {
+                    break;
+                }
+                left -= height;
+                first -= 1;
+            }
+        } else {
+            first = first.saturating_sub(half);
+        }
+        self.panes[self.focus].scroll.1 = first;
+    }
+
+    /// Splits the focused pane in two, both showing the same buffer, and
+    /// focuses the new half.
+    pub fn split(&mut self, orientation: Orientation) {
+        let pane = self.panes[self.focus];
+        self.panes.push(pane);
+        let new_pane = self.panes.len() - 1;
+        self.layout.split(self.focus, new_pane, orientation);
+        self.set_focus(new_pane);
+    }
+
+    /// Closes the focused pane unless it's the only one; the buffer stays open.
+    pub fn close_pane(&mut self) -> bool {
+        let order = self.layout.panes();
+        if order.len() < 2 {
+            return false;
+        }
+        let position = order.iter().position(|&p| p == self.focus).unwrap_or(0);
+        let next = order[(position + 1) % order.len()];
+
+        let closed = self.panes.remove(self.focus);
+        self.editors[closed.editor].set_scroll(closed.scroll);
+        self.layout.remove(self.focus);
+        for ed in &mut self.editors {
+            ed.forget_pane(self.focus);
+        }
+        self.focus = if next > self.focus { next - 1 } else { next };
+        let pane = self.panes[self.focus];
+        self.editors[pane.editor].unpark(self.focus);
+        true
+    }
+
+    /// Moves focus to pane `index`.
+    pub fn focus_pane(&mut self, index: usize) {
+        if index < self.panes.len() {
+            self.set_focus(index);
+        }
+    }
+
+    /// Moves focus to the next pane in screen order, wrapping around.
+    pub fn focus_next(&mut self) {
+        let order = self.layout.panes();
+        let position = order.iter().position(|&p| p == self.focus).unwrap_or(0);
+        self.set_focus(order[(position + 1) % order.len()]);
+    }
+
+    /// Moves focus to pane `index`. Panes showing the same buffer each
+    /// have cursors of their own: those of the pane losing focus are put
+    /// aside, and those of the one getting it taken back.
+    fn set_focus(&mut self, index: usize) {
+        if index == self.focus {
+            return;
+        }
+        let leaving = self.panes[self.focus];
+        self.editors[leaving.editor].park(self.focus);
+        self.focus = index;
+        let entering = self.panes[index];
+        self.editors[entering.editor].unpark(index);
+    }
+
+    /// Opens the file at `path` in a new buffer and switches to it, or just
+    /// switches if the file is already open.
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        match self.position_of(path) {
+            Some(index) => self.switch_to(index),
+            None => self.add(Editor::open(path, None)?),
+        }
+        Ok(())
+    }
+
+    /// Index of the buffer holding the file at `path`, if it's open.
+    pub fn position_of(&self, path: &Path) -> Option<usize> {
+        let wanted = path.canonicalize().ok();
+        self.editors.iter().position(|ed| {
+            ed.path()
+                .is_some_and(|p| p == path || (wanted.is_some() && p.canonicalize().ok() == wanted))
+        })
+    }
+
+    /// Adds `editor`, just opened, as a new buffer and switches to it.
+    pub fn add(&mut self, editor: Editor) {
+        let path = editor.path().map(Path::to_path_buf);
+        self.editors.push(editor);
+        self.switch_to(self.editors.len() - 1);
+        if let Some(path) = path {
+            // The list of recent files is a convenience; failing to keep it is no error.
+            let _ = recent::note(&path);
+            self.run_hook(Hook::Open, &path);
+        }
+    }
+
+    /// Reads the focused buffer's file again, decoding it as `charset`. The
+    /// buffer, and its undo history, are replaced.
+    pub fn reopen(&mut self, charset: Charset) -> io::Result<()> {
+        let index = self.current();
+        let Some(path) = self.editors[index].path().map(Path::to_path_buf) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer has no file name",
+            ));
+        };
+        let mut editor = Editor::open(&path, Some(charset))?;
+        editor.set_read_only(self.editors[index].is_read_only());
+        self.editors[index].discard_journal();
+        self.editors[index] = editor;
+        Ok(())
+    }
+
+    /// The selected text; with several cursors, each selection on a line of
+    /// its own.
+    fn selected_texts(&self) -> Option<String> {
+        let ed = self.editor();
+        let selected: Vec<String> = ed
+            .carets()
+            .iter()
+            .filter_map(|caret| caret.selection)
+            .map(|sel| ed.buffer().slice(sel.range()))
+            .collect();
+        (!selected.is_empty()).then(|| selected.join("\n"))
+    }
+
+    /// Copies the selected text to the clipboard.
+    pub fn copy(&mut self) {
+        if let Some(text) = self.selected_texts() {
+            self.clipboard.set(&text);
+        }
+    }
+
+    /// Moves the selected text to the clipboard.
+    pub fn cut(&mut self) {
+        self.copy();
+        self.editor_mut().for_each_cursor(|ed| {
+            ed.delete_selection();
+        });
+    }
+
+    /// Copies the selected text to register `name`: a letter's own, or when
+    /// it's a digit the clipboard, whose earlier text the digits stand for.
+    pub fn copy_to(&mut self, name: char) {
+        if !name.is_ascii_alphabetic() {
+            return self.copy();
+        }
+        if let Some(text) = self.selected_texts() {
+            self.registers.set(name, &text);
+        }
+    }
+
+    /// Moves the selected text to register `name`, as `copy_to` copies it.
+    pub fn cut_to(&mut self, name: char) {
+        self.copy_to(name);
+        self.editor_mut().for_each_cursor(|ed| {
+            ed.delete_selection();
+        });
+    }
+
+    /// Inserts what register `name` holds at every cursor, replacing the
+    /// selections: a letter's own text, or for a digit the text copied that
+    /// many times before the latest. Returns `false` if it holds nothing.
+    pub fn paste_from(&mut self, name: char) -> bool {
+        let text = match name.to_digit(10) {
+            Some(back) => self.clipboard.ring().nth(back as usize),
+            None => self.registers.get(name),
+        };
+        let Some(text) = text.map(line_ending::normalize) else {
+            return false;
+        };
+        self.editor_mut().for_each_cursor(|ed| ed.insert_str(&text));
+        true
+    }
+
+    /// The registers yanked into by name.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Inserts the clipboard contents at every cursor, replacing the selections.
+    pub fn paste(&mut self) {
+        let text = line_ending::normalize(&self.clipboard.get());
+        self.editor_mut().for_each_cursor(|ed| ed.insert_str(&text));
+    }
+
+    /// The text copied or cut before, newest first.
+    pub fn clipboard_ring(&self) -> Vec<String> {
+        self.clipboard.ring().map(str::to_string).collect()
+    }
+
+    /// Puts `text`, copied some time before, back in the clipboard and
+    /// pastes it.
+    pub fn paste_again(&mut self, text: &str) {
+        self.clipboard.set(text);
+        self.paste();
+    }
+
+    /// Puts a cursor on every match of the search query, or of the selected
+    /// text when nothing is being searched for.
+    pub fn carets_at_matches(&mut self) -> bool {
+        let query = match self.search.clone() {
+            Some(query) => query,
+            None => match self.editor().selected_text() {
+                Some(text) if !text.is_empty() => text,
+                _ => return false,
+            },
+        };
+        self.editor_mut().carets_at_matches(&query)
+    }
+
+    /// The key bindings in use.
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// Replaces the key bindings in use.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// The language servers.
+    pub fn lsp(&self) -> &Lsp {
+        &self.lsp
+    }
+
+    /// The language servers, mutably.
+    pub fn lsp_mut(&mut self) -> &mut Lsp {
+        &mut self.lsp
+    }
+
+    /// Replaces the language server configuration.
+    pub fn set_lsp(&mut self, lsp: Lsp) {
+        self.lsp = lsp;
+    }
+
+    /// Sends the language servers every change made to the buffers.
+    pub fn sync_lsp(&mut self) {
+        self.lsp.sync(&self.editors);
+    }
+
+    /// Asks the buffer's language server where the symbol under the cursor
+    /// is defined; the answer comes in later. Returns `false` if the buffer
+    /// has no server.
+    pub fn goto_definition(&mut self) -> bool {
+        let current = self.current();
+        self.lsp.request_definition(&self.editors[current])
+    }
+
+    /// Asks the buffer's language server how to rename the symbol under the
+    /// cursor to `name`; the answer comes in later. Returns `false` if the
+    /// buffer has no server.
+    pub fn rename(&mut self, name: &str) -> bool {
+        let current = self.current();
+        self.lsp.request_rename(&self.editors[current], name)
+    }
+
+    /// Asks the buffer's language server about the symbol under the cursor;
+    /// the answer comes in later. Returns `false` if the buffer has no server.
+    pub fn hover(&mut self) -> bool {
+        let current = self.current();
+        self.lsp.request_hover(&self.editors[current])
+    }
+
+    /// The transforms the transform menu offers.
+    pub fn transforms(&self) -> &Registry {
+        &self.transforms
+    }
+
+    /// Replaces the user's hooks and adds their transforms to the menu,
+    /// running the open hook for the files open already.
+    pub fn set_scripts(&mut self, scripts: Scripts) {
+        scripts.register_transforms(&mut self.transforms);
+        self.scripts = scripts;
+        for path in self.editors.iter().filter_map(Editor::path) {
+            self.scripts.run_hook(Hook::Open, path);
+        }
+    }
+
+    /// The plugins loaded at startup.
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+
+    /// Replaces the plugins and adds their transforms to the menu, telling
+    /// them about the files open already.
+    pub fn set_plugins(&mut self, plugins: Plugins) {
+        plugins.register_transforms(&mut self.transforms);
+        self.plugins = plugins;
+        for path in self.editors.iter().filter_map(Editor::path) {
+            self.plugins.run_hook(Hook::Open, path);
+        }
+    }
+
+    /// Runs what the user's scripts and the plugins hooked to `hook`, for
+    /// the file at `path`.
+    pub fn run_hook(&self, hook: Hook, path: &Path) {
+        self.scripts.run_hook(hook, path);
+        self.plugins.run_hook(hook, path);
+    }
+
+    /// Runs the selection-change hooks if the focused buffer's selection
+    /// isn't the one they were last told about, with the text it selects,
+    /// or none once nothing is selected. It's called once the keys have
+    /// rested, so a selection being dragged or extended is reported where
+    /// it stops rather than at every step.
+    pub fn report_selection(&mut self) {
+        let ed = self.editor();
+        let selection = ed
+            .path()
+            .zip(ed.selection.filter(|sel| !sel.is_empty()))
+            .map(|(path, sel)| (path.to_path_buf(), sel.range()));
+        if selection == self.reported_selection {
+            return;
+        }
+        let (path, text) = match (&selection, &self.reported_selection) {
+            (Some((path, range)), _) => (path.clone(), ed.buffer().slice(range.clone())),
+            (None, Some((path, _))) => (path.clone(), String::new()),
+            (None, None) => return,
+        };
+        self.scripts.run_selection_hook(&path, &text);
+        self.plugins.run_selection_hook(&path, &text);
+        self.reported_selection = selection;
+    }
+
+    /// Applies the user's settings.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.transforms.register(Json {
+            indent: Some(settings.json_indent),
+        });
+        self.settings = settings;
+    }
+
+    /// The jobs running in the background.
+    pub fn jobs(&self) -> &Jobs {
+        &self.jobs
+    }
+
+    /// The jobs running in the background, mutably.
+    pub fn jobs_mut(&mut self) -> &mut Jobs {
+        &mut self.jobs
+    }
+
+    /// The places the last build complained about.
+    pub fn quickfix(&self) -> &Quickfix {
+        &self.quickfix
+    }
+
+    /// The places the last build complained about, mutably.
+    pub fn quickfix_mut(&mut self) -> &mut Quickfix {
+        &mut self.quickfix
+    }
+
+    /// Where the cursor was before each jump to a definition from the
+    /// tags file, by file and offset.
+    pub fn tag_stack_mut(&mut self) -> &mut Vec<(PathBuf, usize)> {
+        &mut self.tag_stack
+    }
+
+    /// The user's preferences.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// How the focused buffer is indented, commented and reflowed, as its
+    /// filetype is.
+    pub fn filetype_options(&self) -> filetype::Options {
+        let filetype = self.editor().highlighter().filetype();
+        self.settings.filetype(filetype).clone()
+    }
+
+    /// Wraps long lines to the width of their panes, or stops wrapping them.
+    pub fn toggle_wrap(&mut self) {
+        self.settings.wrap = !self.settings.wrap;
+    }
+
+    /// Moves every cursor `rows` rows down, or up if negative: lines while
+    /// they aren't wrapped, the rows they're wrapped into while they are.
+    pub fn move_rows(&mut self, rows: isize) {
+        if !self.settings.wrap {
+            return self.editor_mut().for_each_cursor(|ed| ed.move_lines(rows));
+        }
+        let width = self.panes[self.focus].width;
+        self.editor_mut()
+            .for_each_cursor(|ed| ed.move_rows(rows, width));
+    }
+
+    /// Shows white space, or stops showing it.
+    pub fn toggle_whitespace(&mut self) {
+        self.settings.whitespace = !self.settings.whitespace;
+    }
+
+    /// Switches the gutter to the next way of numbering lines.
+    pub fn cycle_line_numbers(&mut self) {
+        self.settings.line_numbers = self.settings.line_numbers.next();
+    }
+
+    /// The words spell checking knows, if they've been loaded.
+    pub fn dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_ref()
+    }
+
+    /// The words spell checking knows, to add to them.
+    pub fn dictionary_mut(&mut self) -> Option<&mut Dictionary> {
+        self.dictionary.as_mut()
+    }
+
+    /// Keeps the words spell checking knows, once they're loaded.
+    pub fn set_dictionary(&mut self, dictionary: Dictionary) {
+        self.dictionary = Some(dictionary);
+    }
+
+    /// Notes that a key was just pressed, which dismisses the notice.
+    pub fn note_input(&mut self) {
+        self.last_input = Instant::now();
+        self.notice = None;
+    }
+
+    /// Shows `message` in the status bar, rather than in a dialog to
+    /// dismiss, until the next key or for `NOTICE` at most.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.notice = Some((message.into(), Instant::now()));
+    }
+
+    /// The message the status bar shows, if there is one.
+    pub fn notice(&self) -> Option<&str> {
+        self.notice
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < NOTICE)
+            .map(|(message, _)| message.as_str())
+    }
+
+    /// When a key was last pressed.
+    pub fn last_input(&self) -> Instant {
+        self.last_input
+    }
+
+    /// How long it's been since a key was pressed.
+    pub fn idle_time(&self) -> Duration {
+        self.last_input.elapsed()
+    }
+
+    /// Replaces the snippets that can be expanded.
+    pub fn set_snippets(&mut self, snippets: Snippets) {
+        self.snippets = snippets;
+    }
+
+    /// Expands the snippet whose trigger is the word before the cursor.
+    /// Returns `false` if there's no such snippet.
+    pub fn expand_snippet(&mut self) -> bool {
+        let current = self.current();
+        let ed = &mut self.editors[current];
+        let trigger = ed.word_before_cursor();
+        if trigger.is_empty() {
+            return false;
+        }
+        let Some(body) = self.snippets.find(ed.highlighter().filetype(), &trigger) else {
+            return false;
+        };
+        ed.expand_snippet(trigger.len(), body);
+        true
+    }
+
+    /// The names of Unicode characters, read the first time they're needed.
+    pub fn character_names(&mut self) -> &Names {
+        self.character_names.get_or_insert_with(Names::load)
+    }
+
+    /// Replaces the words that expand as they're typed.
+    pub fn set_abbreviations(&mut self, abbreviations: Abbreviations) {
+        self.abbreviations = abbreviations;
+    }
+
+    /// Types `ch` at every cursor. A character that can't be part of a word
+    /// first expands the abbreviation before it, if there is one, unless
+    /// `literal` says to type it as it is.
+    pub fn type_char(&mut self, ch: char, literal: bool) {
+        let current = self.current();
+        let ed = &mut self.editors[current];
+        let abbreviations = &self.abbreviations;
+        let expand = !literal && !motion::is_word(ch);
+        ed.for_each_cursor(|ed| {
+            if expand {
+                ed.expand_abbreviation(abbreviations);
+            }
+            ed.insert(ch);
+        });
+    }
+
+    /// Starts a new line at every cursor, expanding the abbreviation before
+    /// each first.
+    pub fn type_newline(&mut self) {
+        let current = self.current();
+        let ed = &mut self.editors[current];
+        let abbreviations = &self.abbreviations;
+        let options = self.settings.filetype(ed.highlighter().filetype());
+        ed.for_each_cursor(|ed| {
+            ed.expand_abbreviation(abbreviations);
+            ed.newline(options);
+        });
+    }
+
+    /// Starts typing a count for the next command.
+    pub fn start_count(&mut self) {
+        self.count = Some(0);
+    }
+
+    /// The count typed so far, while one is being typed.
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    /// Adds `digit` to the end of the count being typed. Returns `false` if
+    /// no count is being typed.
+    pub fn type_count_digit(&mut self, digit: u32) -> bool {
+        let Some(count) = self.count.as_mut() else {
+            return false;
+        };
+        *count = count.saturating_mul(10).saturating_add(digit as usize);
+        true
+    }
+
+    /// Takes the count typed for the command about to run, if any.
+    pub fn take_count(&mut self) -> Option<usize> {
+        self.count.take().filter(|&count| count > 0)
+    }
+
+    /// The keyboard macro recorder.
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    /// The keyboard macro recorder, mutably.
+    pub fn recorder_mut(&mut self) -> &mut Recorder {
+        &mut self.recorder
+    }
+
+    /// The saved macros.
+    pub fn macros(&self) -> &Macros {
+        &self.macros
+    }
+
+    /// The saved macros, mutably.
+    pub fn macros_mut(&mut self) -> &mut Macros {
+        &mut self.macros
+    }
+
+    /// Replaces the saved macros.
+    pub fn set_macros(&mut self, macros: Macros) {
+        self.macros = macros;
+    }
+
+    /// The key sequence waiting for its next key.
+    pub fn pending(&self) -> &Pending {
+        &self.pending
+    }
+
+    /// The key sequence waiting for its next key, mutably.
+    pub fn pending_mut(&mut self) -> &mut Pending {
+        &mut self.pending
+    }
+
+    /// The selection-first grammar's mode and pending key.
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    /// The selection-first grammar's mode and pending key, mutably.
+    pub fn grammar_mut(&mut self) -> &mut Grammar {
+        &mut self.grammar
+    }
+
+    /// The active search query, if any.
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_deref()
+    }
+
+    /// Sets the search query; an empty query clears the search.
+    pub fn set_search(&mut self, query: &str) {
+        self.search = (!query.is_empty()).then(|| query.to_string());
+    }
+
+    /// Moves the cursor to the first match at or after `from`, wrapping around.
+    pub fn search_from(&mut self, from: usize) -> bool {
+        let Some(query) = self.search.clone() else {
+            return false;
+        };
+        self.editor_mut().search_from(&query, from)
+    }
+
+    /// Moves the cursor to the next match after the cursor.
+    pub fn search_next(&mut self) -> bool {
+        let Some(query) = self.search.clone() else {
+            return false;
+        };
+        let from = self.editor().cursor();
+        let found = self.editor_mut().search_next(&query);
+        if found {
+            self.editor_mut().record_jump(from);
+        }
+        found
+    }
+
+    /// Moves the cursor to the closest match before the cursor.
+    pub fn search_previous(&mut self) -> bool {
+        let Some(query) = self.search.clone() else {
+            return false;
+        };
+        let from = self.editor().cursor();
+        let found = self.editor_mut().search_previous(&query);
+        if found {
+            self.editor_mut().record_jump(from);
+        }
+        found
+    }
+}
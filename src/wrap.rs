@@ -0,0 +1,89 @@
+use crate::width::{char_width, display_width};
+use std::ops::Range;
+
+/// The rows `line` is shown in when wrapped to `width` columns, as byte
+/// ranges of it. Rows break after the last space or tab that fits, or
+/// inside a word too long for a row of its own; a line always has at least
+/// one row, even an empty one.
+pub fn rows(line: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut x = 0;
+    // Where the row may break: just after the last space or tab on it.
+    let mut space = None;
+    for (idx, ch) in line.char_indices() {
+        let mut w = char_width(ch, x);
+        if x + w > width && x > 0 {
+            let end = space.filter(|&end| end > start).unwrap_or(idx);
+            rows.push(start..end);
+            start = end;
+            space = None;
+            x = display_width(&line[start..idx]);
+            w = char_width(ch, x);
+            // What was left after the space still doesn't fit with `ch`.
+            if x + w > width && x > 0 {
+                rows.push(start..idx);
+                start = idx;
+                x = 0;
+                w = char_width(ch, 0);
+            }
+        }
+        x += w;
+        if ch == ' ' || ch == '\t' {
+            space = Some(idx + ch.len_utf8());
+        }
+    }
+    rows.push(start..line.len());
+    rows
+}
+
+/// Which of `rows` the byte at `index` is shown in; the end of the line is
+/// in the last.
+pub fn row_of(rows: &[Range<usize>], index: usize) -> usize {
+    rows.iter().rposition(|row| row.start <= index).unwrap_or(0)
+}
+
+/// The byte of `line` shown at column `col` of `row`: the nearest one
+/// before it when the row is shorter, which is the end of the line on its
+/// last row.
+pub fn index_at(line: &str, row: Range<usize>, col: usize) -> usize {
+    let mut x = 0;
+    let mut last = row.start;
+    for (idx, ch) in line[row.clone()].char_indices() {
+        let w = char_width(ch, x);
+        if col < x + w {
+            return row.start + idx;
+        }
+        last = row.start + idx;
+        x += w;
+    }
+    // The end of a row that wraps is the start of the next one, so stop on
+    // its last character instead.
+    if row.end == line.len() {
+        row.end
+    } else {
+        last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_break_after_the_last_space_that_fits() {
+        assert_eq!(rows("aaaa bbbb", 6), vec![0..5, 5..9]);
+        assert_eq!(rows("abcdefgh", 3), vec![0..3, 3..6, 6..8]);
+        assert_eq!(rows("", 4), vec![0..0]);
+    }
+
+    #[test]
+    fn index_at_stops_inside_rows_that_wrap() {
+        let line = "aaaa bbbb";
+        assert_eq!(index_at(line, 0..5, 9), 4);
+        assert_eq!(index_at(line, 5..9, 9), 9);
+        assert_eq!(index_at(line, 5..9, 1), 6);
+        assert_eq!(row_of(&rows(line, 6), 5), 1);
+    }
+}